@@ -0,0 +1,22 @@
+use std::env;
+use std::process::Command;
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(String::from(String::from_utf8_lossy(&output.stdout).trim()))
+}
+
+fn main() {
+    let git_hash = command_output("git", &["rev-parse", "--short", "HEAD"]).unwrap_or(String::from("unknown"));
+    let rustc = env::var("RUSTC").unwrap_or(String::from("rustc"));
+    let rustc_version = command_output(&rustc, &["--version"]).unwrap_or(String::from("unknown"));
+
+    println!("cargo:rustc-env=DRAIN_GIT_HASH={git_hash}");
+    println!("cargo:rustc-env=DRAIN_RUSTC_VERSION={rustc_version}");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+}
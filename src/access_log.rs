@@ -0,0 +1,168 @@
+use std::net::IpAddr;
+use std::io::{stdout, Write};
+use std::sync::LazyLock;
+use std::thread::spawn;
+use std::time::Instant;
+use chrono::Local;
+use serde_json::json;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tracing::{debug, error, warn, Level};
+use crate::config::{AccessLogFormat, CONFIG};
+use crate::log_output::Destination;
+use crate::util::{update_request_info, RequestInfo, REQUEST_INFO};
+
+const QUEUE_LENGTH: usize = 4096;
+
+#[derive(Clone, Default)]
+pub struct AccessRecord {
+    pub method: Option<String>,
+    pub remote_ip: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    pub referer: Option<String>,
+    pub status: Option<u16>,
+    pub bytes_sent: u64
+}
+
+static SENDER: LazyLock<Option<Sender<String>>> = LazyLock::new(|| {
+    let config = CONFIG.load();
+    let access_log = config.access_log.as_ref()?;
+    let destination = match access_log.output.as_str() {
+        "stdout" => None,
+        output => match Destination::open(output, &access_log.rotation) {
+            Ok(destination) => Some(destination),
+            Err(e) => {
+                warn!("An error occurred while opening the access log {output}.\n\
+                       Error information:\n{e}\n\
+                       Continuing without it...");
+                return None;
+            }
+        }
+    };
+
+    let (sender, receiver) = channel(QUEUE_LENGTH);
+    spawn(move || write_lines(destination, receiver));
+    Some(sender)
+});
+
+fn write_lines(mut destination: Option<Destination>, mut receiver: Receiver<String>) {
+    while let Some(line) = receiver.blocking_recv() {
+        let result = match destination.as_mut() {
+            Some(destination) => destination.write(Level::INFO, line.as_bytes()),
+            None => stdout().write_all(line.as_bytes())
+        };
+
+        if let Err(e) = result {
+            error!("An error occurred while writing to the access log.\n\
+                    Error information:\n{e}");
+        }
+    }
+}
+
+pub fn record_status(status: u16) {
+    update_request_info(|info| info.access.status = Some(status));
+}
+
+pub fn record_bytes_sent(length: u64) {
+    update_request_info(|info| info.access.bytes_sent += length);
+}
+
+fn quoted(value: &Option<String>) -> String {
+    value.as_ref().map_or(String::from("-"), |value| value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn format_line(format: AccessLogFormat, info: &RequestInfo, remote_ip: &IpAddr, duration_ms: u128) -> Option<String> {
+    let access = &info.access;
+    let status = access.status?;
+    let remote_ip = access.remote_ip.as_ref().unwrap_or(remote_ip);
+    let method = access.method.as_deref().unwrap_or("-");
+    let resource = info.resource.as_deref().unwrap_or("-");
+    let remote_user = info.remote_user.as_ref().map(|(_, user)| user.as_str());
+    let (ja3, ja4) = match &info.tls_fingerprint {
+        Some(tls_fingerprint) => (Some(tls_fingerprint.ja3.as_str()), Some(tls_fingerprint.ja4.as_str())),
+        None => (None, None)
+    };
+
+    if format == AccessLogFormat::Json {
+        let line = json!({
+            "time": Local::now().to_rfc3339(),
+            "remote_ip": remote_ip.to_string(),
+            "remote_user": remote_user,
+            "method": access.method,
+            "host": info.host,
+            "resource": info.resource,
+            "status": status,
+            "bytes_sent": access.bytes_sent,
+            "duration_ms": duration_ms,
+            "referer": access.referer,
+            "user_agent": access.user_agent,
+            "tls_ja3": ja3,
+            "tls_ja4": ja4,
+            "request_id": info.request_id
+        });
+        return Some(format!("{line}\n"));
+    }
+
+    let time = Local::now().format("%d/%b/%Y:%H:%M:%S %z");
+    let bytes_sent = if access.bytes_sent == 0 {String::from("-")} else {access.bytes_sent.to_string()};
+    let mut line = format!("{remote_ip} - {} [{time}] \"{method} {resource} HTTP/1.1\" {status} {bytes_sent}", remote_user.unwrap_or("-"));
+
+    if format == AccessLogFormat::Combined {
+        line.push_str(&format!(" \"{}\" \"{}\"", quoted(&access.referer), quoted(&access.user_agent)));
+    }
+
+    line.push_str(&format!(" {duration_ms}"));
+
+    if format == AccessLogFormat::Combined {
+        line.push_str(&format!(" {}", ja4.unwrap_or("-")));
+    }
+
+    line.push('\n');
+    Some(line)
+}
+
+pub fn log(remote_ip: &IpAddr, started: Instant) {
+    let config = CONFIG.load();
+    let (Some(access_log), Some(sender)) = (config.access_log.as_ref(), &*SENDER) else {
+        return;
+    };
+
+    let duration_ms = started.elapsed().as_millis();
+    let Ok(Some(line)) = REQUEST_INFO.try_with(|info| format_line(access_log.format, &info.borrow(), remote_ip, duration_ms)) else {
+        return;
+    };
+
+    if sender.try_send(line).is_err() {
+        debug!("The access log queue is full, dropping an entry.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use crate::tls_fingerprint::TlsFingerprint;
+    use super::*;
+
+    fn request_info(tls_fingerprint: Option<TlsFingerprint>) -> RequestInfo {
+        RequestInfo {
+            access: AccessRecord {status: Some(200), ..AccessRecord::default()},
+            tls_fingerprint,
+            ..RequestInfo::default()
+        }
+    }
+
+    #[test]
+    fn tls_fingerprint_is_logged() {
+        let remote_ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let info = request_info(Some(TlsFingerprint {ja3: String::from("ja3-hash"), ja4: String::from("t13d1516h2_8daaf6152771_02713d6af862")}));
+
+        let line = format_line(AccessLogFormat::Combined, &info, &remote_ip, 5).unwrap();
+        assert!(line.ends_with(" 5 t13d1516h2_8daaf6152771_02713d6af862\n"));
+
+        let line: serde_json::Value = serde_json::from_str(&format_line(AccessLogFormat::Json, &info, &remote_ip, 5).unwrap()).unwrap();
+        assert_eq!(line["tls_ja3"], "ja3-hash");
+        assert_eq!(line["tls_ja4"], "t13d1516h2_8daaf6152771_02713d6af862");
+
+        let line = format_line(AccessLogFormat::Combined, &request_info(None), &remote_ip, 5).unwrap();
+        assert!(line.ends_with(" 5 -\n"));
+    }
+}
@@ -0,0 +1,431 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::sync::{LazyLock, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+use openssl::asn1::Asn1Time;
+use openssl::base64;
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::ec::{EcGroup, EcKey};
+use openssl::ecdsa::EcdsaSig;
+use openssl::hash::{hash, MessageDigest};
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::ssl::{SslContext, SslFiletype, SslMethod};
+use openssl::stack::Stack;
+use openssl::x509::extension::SubjectAlternativeName;
+use openssl::x509::{X509, X509NameBuilder, X509ReqBuilder};
+use serde_json::{json, Value};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::time::{sleep, timeout};
+use tracing::{error, info};
+use crate::config::{Acme, CONFIG};
+use crate::proxy::{parse_response, send_to_server, UpstreamResponse, UpstreamServer};
+use crate::util::send_response;
+
+const CHALLENGE_LOCATION: &str = "/.well-known/acme-challenge/";
+
+const CHECK_INTERVAL: u64 = 43200;
+
+const RETRY_INTERVAL: u64 = 3600;
+
+const POLL_ATTEMPTS: u32 = 30;
+
+static CHALLENGES: LazyLock<Mutex<HashMap<String, String>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+pub static CERTIFICATE: LazyLock<RwLock<Option<(SystemTime, SslContext)>>> = LazyLock::new(|| {
+    RwLock::new(CONFIG.load().acme.as_ref().and_then(load_certificate))
+});
+
+struct Client {
+    key: EcKey<Private>,
+    jwk: Value,
+    kid: Option<String>,
+    nonce: Option<String>,
+    directory: Value
+}
+
+fn base64url(data: &[u8]) -> String {
+    base64::encode_block(data).replace('+', "-").replace('/', "_").trim_end_matches('=').to_string()
+}
+
+fn storage_path(acme: &Acme, file: &str) -> String {
+    format!("{}/{}/{file}", CONFIG.load().server_root, acme.storage.trim_end_matches('/'))
+}
+
+pub fn certificate_path(acme: &Acme) -> String {
+    storage_path(acme, "certificate.pem")
+}
+
+fn write_private(path: &String, content: &[u8]) -> io::Result<()> {
+    let temporary = format!("{path}.{}", std::process::id());
+    fs::write(&temporary, content)?;
+
+    #[cfg(target_family = "unix")] {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&temporary, fs::Permissions::from_mode(0o600))?;
+    }
+
+    fs::rename(&temporary, path)
+}
+
+fn account_key(acme: &Acme) -> Result<EcKey<Private>, Box<dyn Error + Send + Sync>> {
+    let path = storage_path(acme, "account.key");
+    if let Ok(pem) = fs::read(&path) {
+        return Ok(EcKey::private_key_from_pem(&pem)?);
+    }
+
+    let key = EcKey::generate(&*EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?)?;
+    fs::create_dir_all(storage_path(acme, ""))?;
+    write_private(&path, &key.private_key_to_pem()?)?;
+    Ok(key)
+}
+
+fn jwk(key: &EcKey<Private>) -> Result<Value, Box<dyn Error + Send + Sync>> {
+    let mut x = BigNum::new()?;
+    let mut y = BigNum::new()?;
+    let mut context = BigNumContext::new()?;
+    key.public_key().affine_coordinates_gfp(key.group(), &mut x, &mut y, &mut context)?;
+
+    Ok(json!({
+        "crv": "P-256",
+        "kty": "EC",
+        "x": base64url(&x.to_vec_padded(32)?),
+        "y": base64url(&y.to_vec_padded(32)?)
+    }))
+}
+
+fn thumbprint(jwk: &Value) -> Result<String, Box<dyn Error + Send + Sync>> {
+    let canonical = format!("{{\"crv\":{},\"kty\":{},\"x\":{},\"y\":{}}}", jwk["crv"], jwk["kty"], jwk["x"], jwk["y"]);
+    Ok(base64url(&hash(MessageDigest::sha256(), canonical.as_bytes())?))
+}
+
+impl Client {
+    async fn new(acme: &Acme) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let key = account_key(acme)?;
+        let jwk = jwk(&key)?;
+
+        let response = Self::send("GET", &acme.directory, None).await?;
+        if response.status != 200 {
+            return Err(Box::new(io::Error::other(format!("{} responded with {} status", acme.directory, response.status))));
+        }
+
+        let directory = serde_json::from_slice(&response.body)?;
+        Ok(Self {key, jwk, kid: None, nonce: None, directory})
+    }
+
+    fn endpoint(&self, name: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+        match self.directory.get(name).and_then(Value::as_str) {
+            Some(url) => Ok(String::from(url)),
+            None => Err(Box::new(io::Error::other(format!("the ACME directory doesn't contain {name}"))))
+        }
+    }
+
+    async fn send(method: &str, url: &str, body: Option<Vec<u8>>) -> io::Result<UpstreamResponse> {
+        let Some(server) = UpstreamServer::parse(url) else {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{url} isn't a valid URL")));
+        };
+
+        let path = if server.path.is_empty() {"/"} else {server.path.as_str()};
+        let mut request = format!("{method} {path} HTTP/1.1\r\n\
+                                   Host: {}\r\n\
+                                   User-Agent: Drain/{}\r\n\
+                                   Connection: close\r\n", server.host_header(), env!("CARGO_PKG_VERSION"));
+        if let Some(body) = &body {
+            request.push_str(&format!("Content-Type: application/jose+json\r\nContent-Length: {}\r\n", body.len()));
+        }
+        request.push_str("\r\n");
+
+        let mut request = request.into_bytes();
+        request.extend(body.unwrap_or_default());
+
+        let response = match timeout(Duration::from_secs(CONFIG.load().request_timeout), send_to_server(&server, &request)).await {
+            Ok(response) => response?,
+            Err(_) => return Err(io::Error::new(io::ErrorKind::TimedOut, format!("{} didn't respond in {} seconds", server.host, CONFIG.load().request_timeout)))
+        };
+
+        parse_response(&response, method.eq("HEAD"))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("{} sent a malformed response", server.host)))
+    }
+
+    async fn nonce(&mut self) -> Result<String, Box<dyn Error + Send + Sync>> {
+        if let Some(nonce) = self.nonce.take() {
+            return Ok(nonce);
+        }
+
+        let response = Self::send("HEAD", &self.endpoint("newNonce")?, None).await?;
+        match response.headers.get("replay-nonce") {
+            Some(nonce) => Ok(nonce.clone()),
+            None => Err(Box::new(io::Error::other("the ACME server didn't send a nonce")))
+        }
+    }
+
+    fn sign(&self, url: &str, payload: Option<&Value>, nonce: String) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        let mut protected = json!({"alg": "ES256", "nonce": nonce, "url": url});
+        match &self.kid {
+            Some(kid) => protected["kid"] = json!(kid),
+            None => protected["jwk"] = self.jwk.clone()
+        }
+
+        let protected = base64url(serde_json::to_string(&protected)?.as_bytes());
+        let payload = match payload {
+            Some(payload) => base64url(serde_json::to_string(payload)?.as_bytes()),
+            None => String::new()
+        };
+
+        let digest = hash(MessageDigest::sha256(), format!("{protected}.{payload}").as_bytes())?;
+        let signature = EcdsaSig::sign(&digest, &self.key)?;
+        let mut raw = signature.r().to_vec_padded(32)?;
+        raw.extend(signature.s().to_vec_padded(32)?);
+
+        Ok(serde_json::to_vec(&json!({"protected": protected, "payload": payload, "signature": base64url(&raw)}))?)
+    }
+
+    async fn post(&mut self, url: &str, payload: Option<&Value>) -> Result<UpstreamResponse, Box<dyn Error + Send + Sync>> {
+        let mut attempts = 0;
+        loop {
+            let nonce = self.nonce().await?;
+            let response = Self::send("POST", url, Some(self.sign(url, payload, nonce)?)).await?;
+            self.nonce = response.headers.get("replay-nonce").cloned();
+
+            if response.status < 400 {
+                return Ok(response);
+            }
+
+            let problem: Value = serde_json::from_slice(&response.body).unwrap_or_default();
+            if problem["type"].as_str().is_some_and(|t| t.ends_with(":badNonce")) && attempts < 3 {
+                attempts += 1;
+                continue;
+            }
+
+            return Err(Box::new(io::Error::other(format!("{url} responded with {} status: {}", response.status,
+                                                         problem["detail"].as_str().unwrap_or("no details")))));
+        }
+    }
+
+    async fn poll(&mut self, url: &str, pending: &[&str]) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        for _ in 0..POLL_ATTEMPTS {
+            let response = self.post(url, None).await?;
+            let resource: Value = serde_json::from_slice(&response.body)?;
+
+            if !resource["status"].as_str().is_some_and(|status| pending.contains(&status)) {
+                return Ok(resource);
+            }
+
+            let retry_after = response.headers.get("retry-after").and_then(|r| r.parse::<u64>().ok()).unwrap_or(2);
+            sleep(Duration::from_secs(retry_after.clamp(1, 30))).await;
+        }
+
+        Err(Box::new(io::Error::other(format!("{url} is still pending after {POLL_ATTEMPTS} attempts"))))
+    }
+
+    async fn register(&mut self, acme: &Acme) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut account = json!({"termsOfServiceAgreed": true});
+        if let Some(contact) = &acme.contact {
+            account["contact"] = json!([format!("mailto:{contact}")]);
+        }
+
+        let response = self.post(&self.endpoint("newAccount")?, Some(&account)).await?;
+        match response.headers.get("location") {
+            Some(kid) => {
+                self.kid = Some(kid.clone());
+                Ok(())
+            },
+            None => Err(Box::new(io::Error::other("the ACME server didn't send the account URL")))
+        }
+    }
+
+    async fn authorize(&mut self, url: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let authorization: Value = serde_json::from_slice(&self.post(url, None).await?.body)?;
+        if authorization["status"].as_str() == Some("valid") {
+            return Ok(());
+        }
+
+        let domain = authorization["identifier"]["value"].as_str().unwrap_or("unknown domain").to_string();
+        let challenge = authorization["challenges"].as_array().into_iter().flatten()
+            .find(|challenge| challenge["type"].as_str() == Some("http-01"));
+        let (Some(token), Some(challenge_url)) = (challenge.and_then(|c| c["token"].as_str()), challenge.and_then(|c| c["url"].as_str())) else {
+            return Err(Box::new(io::Error::other(format!("the ACME server didn't offer an HTTP-01 challenge for {domain}"))));
+        };
+        let (token, challenge_url) = (String::from(token), String::from(challenge_url));
+
+        let key_authorization = format!("{token}.{}", thumbprint(&self.jwk)?);
+        if let Ok(mut challenges) = CHALLENGES.lock() {
+            challenges.insert(token.clone(), key_authorization);
+        }
+
+        let result = match self.post(&challenge_url, Some(&json!({}))).await {
+            Ok(_) => self.poll(url, &["pending"]).await,
+            Err(e) => Err(e)
+        };
+
+        if let Ok(mut challenges) = CHALLENGES.lock() {
+            challenges.remove(&token);
+        }
+
+        let authorization = result?;
+        match authorization["status"].as_str() {
+            Some("valid") => Ok(()),
+            status => Err(Box::new(io::Error::other(format!("authorization of {domain} ended with {} status: {}", status.unwrap_or("unknown"),
+                                                            authorization["challenges"][0]["error"]["detail"].as_str().unwrap_or("no details")))))
+        }
+    }
+}
+
+fn certificate_request(domains: &[String], key: &PKey<Private>) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let mut name = X509NameBuilder::new()?;
+    name.append_entry_by_nid(Nid::COMMONNAME, &domains[0])?;
+
+    let mut request = X509ReqBuilder::new()?;
+    request.set_subject_name(&name.build())?;
+    request.set_pubkey(key)?;
+
+    let mut alternative_names = SubjectAlternativeName::new();
+    for domain in domains {
+        alternative_names.dns(domain);
+    }
+    let mut extensions = Stack::new()?;
+    extensions.push(alternative_names.build(&request.x509v3_context(None))?)?;
+    request.add_extensions(&extensions)?;
+
+    request.sign(key, MessageDigest::sha256())?;
+    Ok(request.build().to_der()?)
+}
+
+async fn obtain(acme: &Acme) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut client = Client::new(acme).await?;
+    client.register(acme).await?;
+
+    let identifiers: Vec<Value> = acme.domains.iter().map(|domain| json!({"type": "dns", "value": domain})).collect();
+    let response = client.post(&client.endpoint("newOrder")?, Some(&json!({"identifiers": identifiers}))).await?;
+    let Some(order_url) = response.headers.get("location").cloned() else {
+        return Err(Box::new(io::Error::other("the ACME server didn't send the order URL")));
+    };
+    let order: Value = serde_json::from_slice(&response.body)?;
+
+    for authorization in order["authorizations"].as_array().into_iter().flatten().filter_map(Value::as_str) {
+        client.authorize(authorization).await?;
+    }
+
+    let key = PKey::from_ec_key(EcKey::generate(&*EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?)?)?;
+    let Some(finalize_url) = order["finalize"].as_str() else {
+        return Err(Box::new(io::Error::other("the ACME server didn't send the finalize URL")));
+    };
+    client.post(finalize_url, Some(&json!({"csr": base64url(&certificate_request(&acme.domains, &key)?)}))).await?;
+
+    let order = client.poll(&order_url, &["pending", "ready", "processing"]).await?;
+    let (Some("valid"), Some(certificate_url)) = (order["status"].as_str(), order["certificate"].as_str()) else {
+        return Err(Box::new(io::Error::other(format!("the order ended with {} status", order["status"].as_str().unwrap_or("unknown")))));
+    };
+
+    let certificate = client.post(certificate_url, None).await?.body;
+    X509::stack_from_pem(&certificate)?;
+
+    write_private(&storage_path(acme, "private_key.pem"), &key.private_key_to_pem_pkcs8()?)?;
+    write_private(&storage_path(acme, "certificate.pem"), &certificate)?;
+    Ok(())
+}
+
+fn needs_renewal(acme: &Acme) -> bool {
+    let Ok(certificate) = fs::read(storage_path(acme, "certificate.pem")).map_err(|_| ()).and_then(|pem| X509::from_pem(&pem).map_err(|_| ())) else {
+        return true;
+    };
+
+    let Ok(renew_at) = Asn1Time::days_from_now(acme.renew_before_days as u32) else {
+        return true;
+    };
+
+    let names: Vec<String> = certificate.subject_alt_names().into_iter().flatten()
+        .filter_map(|name| name.dnsname().map(String::from))
+        .collect();
+
+    certificate.not_after() < renew_at || !acme.domains.iter().all(|domain| names.iter().any(|name| name.eq_ignore_ascii_case(domain)))
+}
+
+fn load_certificate(acme: &Acme) -> Option<(SystemTime, SslContext)> {
+    let certificate_file = storage_path(acme, "certificate.pem");
+    let modified = fs::metadata(&certificate_file).and_then(|metadata| metadata.modified()).ok()?;
+
+    let ssl_ctx = SslContext::builder(SslMethod::tls()).and_then(|mut ssl_ctx_builder| {
+        ssl_ctx_builder.set_private_key_file(storage_path(acme, "private_key.pem"), SslFiletype::PEM)?;
+        ssl_ctx_builder.set_certificate_chain_file(&certificate_file)?;
+        ssl_ctx_builder.check_private_key()?;
+        if let Some(https) = &CONFIG.load().https {
+            https.configure_shared(&mut ssl_ctx_builder)?;
+        }
+        Ok(ssl_ctx_builder.build())
+    });
+
+    match ssl_ctx {
+        Ok(ssl_ctx) => Some((modified, ssl_ctx)),
+        Err(e) => {
+            error!("An error occurred while loading the ACME certificate.\n\
+                    Error information:\n{e}");
+            None
+        }
+    }
+}
+
+pub async fn renew_certificates() {
+    loop {
+        let Some(acme) = &CONFIG.load().acme else {
+            return;
+        };
+
+        let mut interval = CHECK_INTERVAL;
+        if needs_renewal(acme) {
+            info!("Requesting a certificate for {} from {}...", acme.domains.join(", "), acme.directory);
+            match obtain(acme).await {
+                Ok(()) => info!("Certificate for {} obtained.", acme.domains.join(", ")),
+                Err(e) => {
+                    error!("An error occurred while obtaining a certificate.\n\
+                            Error information:\n{e}\n\
+                            Retrying in {RETRY_INTERVAL} seconds...");
+                    interval = RETRY_INTERVAL;
+                }
+            }
+        }
+
+        sleep(Duration::from_secs(interval)).await;
+    }
+}
+
+pub async fn watch_certificate() {
+    loop {
+        let Some(acme) = &CONFIG.load().acme else {
+            return;
+        };
+
+        let modified = fs::metadata(storage_path(acme, "certificate.pem")).and_then(|metadata| metadata.modified()).ok();
+        let loaded = CERTIFICATE.read().ok().and_then(|certificate| certificate.as_ref().map(|(loaded, _)| *loaded));
+
+        if modified.is_some() && modified != loaded {
+            if let Some(certificate) = load_certificate(acme) {
+                if let Ok(mut current) = CERTIFICATE.write() {
+                    *current = Some(certificate);
+                    info!("Certificate for {} loaded.", acme.domains.join(", "));
+                }
+            }
+        }
+
+        sleep(Duration::from_secs(60)).await;
+    }
+}
+
+pub async fn send_challenge<T>(stream: &mut T, resource: &String) -> Option<Result<(), Box<dyn Error + Send + Sync>>>
+where
+    T: AsyncRead + AsyncWrite + Unpin
+{
+    CONFIG.load().acme.as_ref()?;
+    let token = resource.strip_prefix(CHALLENGE_LOCATION)?;
+    let key_authorization = CHALLENGES.lock().ok()?.get(token).cloned();
+
+    Some(match key_authorization {
+        Some(key_authorization) => {
+            let headers = HashMap::from([(String::from("Content-Type"), String::from("application/octet-stream"))]);
+            send_response(stream, 200, Some(headers), Some(key_authorization.into_bytes()), None, None).await
+        },
+        None => send_response(stream, 404, None, None, None, None).await
+    })
+}
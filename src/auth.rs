@@ -0,0 +1,322 @@
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::SystemTime;
+use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use chrono::Utc;
+use openssl::base64;
+use openssl::hash::{hash, MessageDigest};
+use openssl::memcmp;
+use openssl::rand::rand_bytes;
+use tokio::task::block_in_place;
+use tracing::error;
+use crate::config::{AuthRule, AuthScheme};
+use crate::fs_index::off_worker;
+use crate::journal::hex;
+use crate::requests::Request;
+use crate::signing::hmac_sha256;
+
+pub const REMOTE_USER_HEADER: &str = "x-drain-remote-user";
+
+const NONCE_LIFETIME: i64 = 300;
+
+static CREDENTIALS: LazyLock<Mutex<HashMap<String, (Option<SystemTime>, Arc<HashMap<String, String>>)>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+static NONCE_COUNTS: LazyLock<Mutex<HashMap<String, (i64, u64)>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+static NONCE_SECRET: LazyLock<[u8; 32]> = LazyLock::new(|| {
+    let mut secret = [0u8; 32];
+    if let Err(e) = rand_bytes(&mut secret) {
+        error!("An error occurred while generating the secret for Digest nonces.\n\
+                Error information:\n{e}");
+    }
+    secret
+});
+
+impl AuthScheme {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AuthScheme::Basic => "Basic",
+            AuthScheme::Digest => "Digest"
+        }
+    }
+}
+
+async fn credentials(rule: &AuthRule) -> Option<Arc<HashMap<String, String>>> {
+    let file = rule.credentials_file.clone();
+    let modified = off_worker(move || fs::metadata(file).and_then(|metadata| metadata.modified()).ok()).await.flatten();
+
+    if let Some((loaded, credentials)) = CREDENTIALS.lock().ok()?.get(&rule.credentials_file) {
+        if loaded.is_some() && *loaded == modified {
+            return Some(credentials.clone());
+        }
+    }
+
+    let file = rule.credentials_file.clone();
+    let content = match off_worker(move || fs::read_to_string(file)).await? {
+        Ok(content) => content,
+        Err(e) => {
+            error!("An error occurred while reading {}.\n\
+                    Error information:\n{e}", rule.credentials_file);
+            return None;
+        }
+    };
+
+    let credentials: Arc<HashMap<String, String>> = Arc::new(content.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| match rule.scheme {
+            AuthScheme::Basic => line.split_once(':').map(|(user, hash)| (String::from(user), String::from(hash))),
+            AuthScheme::Digest => line.rsplit_once(':').map(|(user_realm, ha1)| (String::from(user_realm), ha1.to_lowercase()))
+        })
+        .collect());
+
+    CREDENTIALS.lock().ok()?.insert(rule.credentials_file.clone(), (modified, credentials.clone()));
+    Some(credentials)
+}
+
+fn md5_hex(data: &str) -> String {
+    hash(MessageDigest::md5(), data.as_bytes()).map(|digest| hex(&digest)).unwrap_or_default()
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len() && memcmp::eq(a.as_bytes(), b.as_bytes())
+}
+
+fn verify_password(password: &str, hash: &str) -> bool {
+    block_in_place(|| {
+        if hash.starts_with("$2") {
+            return bcrypt::verify(password, hash).unwrap_or(false);
+        }
+
+        if hash.starts_with("$argon2") {
+            return PasswordHash::new(hash).is_ok_and(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok());
+        }
+
+        false
+    })
+}
+
+fn nonce_signature(issued: &str) -> String {
+    hex(&hmac_sha256(&*NONCE_SECRET, issued.as_bytes()))
+}
+
+fn nonce() -> String {
+    let mut random = [0u8; 16];
+    if let Err(e) = rand_bytes(&mut random) {
+        error!("An error occurred while generating a Digest nonce.\n\
+                Error information:\n{e}");
+    }
+
+    let issued = format!("{}-{}", Utc::now().timestamp(), hex(&random));
+    format!("{issued}-{}", nonce_signature(&issued))
+}
+
+fn nonce_issued_at(nonce: &str) -> Option<i64> {
+    let (issued, signature) = nonce.rsplit_once('-')?;
+    let timestamp = issued.split('-').next()?.parse::<i64>().ok()?;
+
+    if !constant_time_eq(signature, &nonce_signature(issued)) {
+        return None;
+    }
+    Some(timestamp)
+}
+
+fn advance_nonce_count(nonce: &str, issued_at: i64, nc: &str) -> bool {
+    let (Ok(nc), Ok(mut counts)) = (u64::from_str_radix(nc, 16), NONCE_COUNTS.lock()) else {
+        return false;
+    };
+
+    let now = Utc::now().timestamp();
+    counts.retain(|_, (issued_at, _)| now - *issued_at <= NONCE_LIFETIME);
+
+    match counts.get(nonce) {
+        Some((_, last)) if nc <= *last => false,
+        _ => {
+            counts.insert(String::from(nonce), (issued_at, nc));
+            true
+        }
+    }
+}
+
+fn auth_params(params: &str) -> HashMap<String, String> {
+    let mut parsed = HashMap::new();
+    let mut rest = params.trim();
+
+    while let Some((name, value)) = rest.split_once('=') {
+        let name = name.trim().trim_start_matches(',').trim().to_lowercase();
+        let value = value.trim_start();
+
+        let (value, remaining) = match value.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"').unwrap_or(quoted.len());
+                (&quoted[..end], quoted.get(end + 1..).unwrap_or(""))
+            },
+            None => {
+                let end = value.find(',').unwrap_or(value.len());
+                (value[..end].trim(), &value[end..])
+            }
+        };
+
+        parsed.insert(name, String::from(value));
+        rest = remaining;
+    }
+
+    parsed
+}
+
+async fn basic(rule: &AuthRule, credentials: &str) -> Option<String> {
+    let decoded = String::from_utf8(base64::decode_block(credentials.trim()).ok()?).ok()?;
+    let (user, password) = decoded.split_once(':')?;
+    let credentials = self::credentials(rule).await?;
+
+    let Some(hash) = credentials.get(user) else {
+        if let Some(hash) = credentials.values().next() {
+            verify_password(password, hash);
+        }
+        return None;
+    };
+
+    verify_password(password, hash).then(|| String::from(user))
+}
+
+async fn digest(rule: &AuthRule, request: &Request, params: &str, stale: &mut bool) -> Option<String> {
+    let params = auth_params(params);
+    let (user, realm, nonce, uri, response) = (params.get("username")?, params.get("realm")?, params.get("nonce")?, params.get("uri")?, params.get("response")?);
+
+    if !realm.eq(&rule.realm) || params.get("algorithm").is_some_and(|algorithm| !algorithm.eq_ignore_ascii_case("MD5")) {
+        return None;
+    }
+
+    let path = urlencoding::decode(uri.split('?').next().unwrap_or_default()).ok()?;
+    if request.resource().is_none_or(|resource| !resource.eq(&*path)) {
+        return None;
+    }
+
+    let issued_at = nonce_issued_at(nonce)?;
+    if Utc::now().timestamp() - issued_at > NONCE_LIFETIME {
+        *stale = true;
+        return None;
+    }
+
+    let nc = match params.get("qop").map(String::as_str) {
+        Some("auth") => Some(params.get("nc")?),
+        Some(_) => return None,
+        None => None
+    };
+
+    let credentials = credentials(rule).await?;
+    let ha1 = credentials.get(&format!("{user}:{realm}"))?;
+    let ha2 = md5_hex(&format!("{}:{uri}", request.method()));
+
+    let expected = match nc {
+        Some(nc) => md5_hex(&format!("{ha1}:{nonce}:{nc}:{}:auth:{ha2}", params.get("cnonce")?)),
+        None => md5_hex(&format!("{ha1}:{nonce}:{ha2}"))
+    };
+
+    if !constant_time_eq(&expected, &response.to_lowercase()) {
+        return None;
+    }
+
+    if nc.is_some_and(|nc| !advance_nonce_count(nonce, issued_at, nc)) {
+        return None;
+    }
+    Some(user.clone())
+}
+
+fn challenge(rule: &AuthRule, stale: bool) -> HashMap<String, String> {
+    let value = match rule.scheme {
+        AuthScheme::Basic => format!("Basic realm=\"{}\", charset=\"UTF-8\"", rule.realm),
+        AuthScheme::Digest => format!("Digest realm=\"{}\", qop=\"auth\", algorithm=MD5, nonce=\"{}\"{}",
+                                      rule.realm, nonce(), if stale {", stale=true"} else {""})
+    };
+
+    HashMap::from([(String::from("WWW-Authenticate"), value)])
+}
+
+pub async fn authenticate(rule: &AuthRule, request: &Request) -> Result<String, HashMap<String, String>> {
+    let authorization = request.headers().and_then(|headers| headers.get("authorization"));
+    let mut stale = false;
+
+    let user = match authorization.and_then(|authorization| authorization.trim().split_once(' ')) {
+        Some((scheme, params)) if scheme.eq_ignore_ascii_case(rule.scheme.name()) => match rule.scheme {
+            AuthScheme::Basic => basic(rule, params).await,
+            AuthScheme::Digest => digest(rule, request, params, &mut stale).await
+        },
+        _ => None
+    };
+
+    user.ok_or_else(|| challenge(rule, stale))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::time::{Duration, SystemTime};
+    use super::*;
+
+    fn credentials_file(name: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(format!("drain-auth-{name}-{}", std::process::id()));
+        fs::write(&path, content).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn rule(scheme: AuthScheme, credentials_file: String) -> AuthRule {
+        AuthRule {pattern: String::from("/private"), realm: String::from("drain"), scheme, credentials_file}
+    }
+
+    fn request(authorization: &str) -> Request {
+        let request = format!("GET /private HTTP/1.1\r\nHost: localhost\r\nAuthorization: {authorization}\r\n\r\n");
+        Request::parse_from_string(&request, request.as_bytes(), &mut true).unwrap()
+    }
+
+    fn basic_request(user: &str, password: &str) -> Request {
+        request(&format!("Basic {}", base64::encode_block(format!("{user}:{password}").as_bytes())))
+    }
+
+    fn digest_request(nonce: &str, nc: &str) -> Request {
+        let ha1 = md5_hex("alice:drain:secret");
+        let ha2 = md5_hex("GET:/private");
+        let response = md5_hex(&format!("{ha1}:{nonce}:{nc}:abc:auth:{ha2}"));
+        request(&format!("Digest username=\"alice\", realm=\"drain\", nonce=\"{nonce}\", uri=\"/private\", qop=auth, nc={nc}, cnonce=\"abc\", response=\"{response}\""))
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn basic_verifies_passwords() {
+        let hash = bcrypt::hash("secret", 4).unwrap();
+        let rule = rule(AuthScheme::Basic, credentials_file("basic", &format!("alice:{hash}\n")));
+
+        assert_eq!(authenticate(&rule, &basic_request("alice", "secret")).await.ok().as_deref(), Some("alice"));
+        assert!(authenticate(&rule, &basic_request("alice", "wrong")).await.is_err());
+        assert!(authenticate(&rule, &basic_request("mallory", "secret")).await.is_err());
+
+        fs::remove_file(&rule.credentials_file).unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn credentials_are_reloaded_after_a_change() {
+        let rule = rule(AuthScheme::Basic, credentials_file("reload", &format!("alice:{}\n", bcrypt::hash("old", 4).unwrap())));
+        assert!(authenticate(&rule, &basic_request("alice", "old")).await.is_ok());
+
+        fs::write(&rule.credentials_file, format!("alice:{}\n", bcrypt::hash("new", 4).unwrap())).unwrap();
+        File::options().write(true).open(&rule.credentials_file).unwrap().set_modified(SystemTime::now() + Duration::from_secs(60)).unwrap();
+
+        assert!(authenticate(&rule, &basic_request("alice", "old")).await.is_err());
+        assert!(authenticate(&rule, &basic_request("alice", "new")).await.is_ok());
+
+        fs::remove_file(&rule.credentials_file).unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn digest_rejects_reused_nonce_counts() {
+        let rule = rule(AuthScheme::Digest, credentials_file("digest", &format!("alice:drain:{}\n", md5_hex("alice:drain:secret"))));
+        let nonce = nonce();
+
+        assert!(authenticate(&rule, &digest_request(&nonce, "00000001")).await.is_ok());
+        assert!(authenticate(&rule, &digest_request(&nonce, "00000001")).await.is_err());
+        assert!(authenticate(&rule, &digest_request(&nonce, "00000002")).await.is_ok());
+        assert!(authenticate(&rule, &digest_request(&super::nonce(), "00000001")).await.is_ok());
+
+        fs::remove_file(&rule.credentials_file).unwrap();
+    }
+}
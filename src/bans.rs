@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::warn;
+use crate::storage::{Store, STORAGE};
+
+static BANS: LazyLock<Mutex<HashMap<IpAddr, Instant>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn key(ip: &IpAddr) -> String {
+    format!("ban:{ip}")
+}
+
+fn remember(ip: IpAddr, duration: Duration) {
+    if let Ok(mut bans) = BANS.lock() {
+        let now = Instant::now();
+        bans.retain(|_, expiry| *expiry > now);
+        bans.insert(ip, now + duration);
+    }
+}
+
+fn is_banned_locally(ip: &IpAddr) -> bool {
+    BANS.lock().is_ok_and(|bans| bans.get(ip).is_some_and(|expiry| *expiry > Instant::now()))
+}
+
+pub async fn ban(ip: IpAddr, duration: Duration) {
+    remember(ip, duration);
+
+    let expiry = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_or(0, |d| d.as_secs()) + duration.as_secs();
+    if let Err(e) = STORAGE.set(&key(&ip), &expiry.to_string(), duration).await {
+        warn!("An error occurred while storing the ban of {ip}.\n\
+               Error information:\n{e}");
+    }
+}
+
+pub async fn is_banned(ip: &IpAddr) -> bool {
+    match STORAGE.get(&key(ip)).await {
+        Ok(Some(expiry)) => {
+            let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+            if !is_banned_locally(ip) {
+                remember(*ip, Duration::from_secs(expiry.parse::<u64>().unwrap_or(now).saturating_sub(now)));
+            }
+            true
+        },
+        Ok(None) => false,
+        Err(e) => {
+            warn!("An error occurred while looking up the ban of {ip}.\n\
+                   Error information:\n{e}");
+            is_banned_locally(ip)
+        }
+    }
+}
+
+pub fn bans() -> Vec<(IpAddr, Duration)> {
+    let Ok(bans) = BANS.lock() else {
+        return Vec::new();
+    };
+
+    let now = Instant::now();
+    bans.iter()
+        .filter(|(_, expiry)| **expiry > now)
+        .map(|(ip, expiry)| (*ip, *expiry - now))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+    use std::time::Duration;
+    use tokio::time::sleep;
+    use super::{ban, bans, is_banned};
+
+    #[tokio::test]
+    async fn bans_are_kept_in_storage() {
+        let ip = "192.0.2.7".parse::<IpAddr>().unwrap();
+        assert!(!is_banned(&ip).await);
+
+        ban(ip, Duration::from_millis(200)).await;
+        assert!(is_banned(&ip).await);
+        assert!(bans().iter().any(|(banned, _)| *banned == ip));
+
+        sleep(Duration::from_millis(300)).await;
+        assert!(!is_banned(&ip).await);
+    }
+}
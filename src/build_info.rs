@@ -0,0 +1,38 @@
+use serde_json::{json, Value};
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const GIT_HASH: &str = env!("DRAIN_GIT_HASH");
+pub const RUSTC_VERSION: &str = env!("DRAIN_RUSTC_VERSION");
+
+pub fn enabled_features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if cfg!(feature = "cgi") {
+        features.push("cgi");
+    }
+    if cfg!(feature = "json") {
+        features.push("json");
+    }
+    features
+}
+
+pub fn build_info() -> Value {
+    json!({
+        "version": VERSION,
+        "git_hash": GIT_HASH,
+        "rustc_version": RUSTC_VERSION,
+        "features": enabled_features(),
+        "target_family": if cfg!(target_family = "unix") {"unix"} else {"windows"}
+    })
+}
+
+pub fn print_version(as_json: bool) {
+    if as_json {
+        println!("{}", build_info());
+        return;
+    }
+
+    let features = enabled_features();
+    println!("Drain {VERSION} ({GIT_HASH})\n\
+              Compiled with {RUSTC_VERSION}\n\
+              Features: {}", if features.is_empty() {String::from("none")} else {features.join(", ")});
+}
@@ -1,25 +1,61 @@
 use std::collections::HashMap;
 use std::error::Error;
 use std::net::IpAddr;
-use std::path::Path;
+use std::io;
+use std::io::ErrorKind;
+use std::path::PathBuf;
 use std::process::Stdio;
+use std::time::{Duration, Instant};
+#[cfg(target_family = "unix")]
+use std::sync::Arc;
+#[cfg(target_family = "unix")]
+use std::sync::atomic::{AtomicBool, Ordering};
 use bstr::ByteSlice;
 use drain_common::RequestData::Default;
-use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
-use tokio::process::{Child, Command};
-use crate::config::CONFIG;
-use crate::endpoints::ENDPOINT_LIBRARY;
+use serde_json::Value;
+use tokio::fs::File;
+use tokio::io::{copy, AsyncRead, AsyncReadExt, AsyncWrite};
+use tokio::process::Command;
+#[cfg(target_family = "unix")]
+use tokio::task::spawn_blocking;
+use tokio::time::timeout;
+use tracing::{debug, error};
+use crate::config::{CGIBackend, CGIRuleOptions, CONFIG};
+use crate::endpoints::{endpoint_library, endpoint_names};
 use crate::error::ServerError;
-use crate::pages::forbidden::forbidden;
+use crate::fallback;
+use crate::fs_index;
+use crate::gateway;
+use crate::gateway::GatewayOutput;
+use crate::metrics::record_cgi_usage;
+use crate::pages::denied::denied;
 use crate::pages::index_of::index_of;
-use crate::pages::not_found::not_found;
 use crate::util::ResourceType::Dynamic;
-use crate::util::send_response;
-#[cfg(target_family = "unix")]
-use crate::util::CHROOT;
+use crate::util::{decode_bounded, document_root, is_inside_document_root, send_response, REQUEST_INFO};
+
+pub enum CGIBody {
+    Memory(Vec<u8>),
+    Spooled {path: PathBuf, length: u64}
+}
+
+impl CGIBody {
+    pub fn len(&self) -> u64 {
+        match self {
+            CGIBody::Memory(data) => data.len() as u64,
+            CGIBody::Spooled {length, ..} => *length
+        }
+    }
+
+    async fn reader(&self) -> io::Result<Box<dyn AsyncRead + Send + Unpin + '_>> {
+        match self {
+            CGIBody::Memory(data) => Ok(Box::new(data.as_slice())),
+            CGIBody::Spooled {path, length} => Ok(Box::new(File::open(path).await?.take(*length)))
+        }
+    }
+}
 
 pub struct CGIData {
-    pub data: Vec<u8>,
+    pub body: CGIBody,
     pub content_type: String
 }
 
@@ -30,6 +66,169 @@ pub enum CGIStatus {
     IndexOf
 }
 
+#[cfg(target_family = "unix")]
+struct Reaper {
+    pid: libc::pid_t,
+    reaped: Arc<AtomicBool>
+}
+
+#[cfg(target_family = "unix")]
+impl Drop for Reaper {
+    fn drop(&mut self) {
+        if self.reaped.load(Ordering::Acquire) {
+            return;
+        }
+
+        let pid = self.pid;
+        unsafe {libc::kill(pid, libc::SIGKILL)};
+        spawn_blocking(move || {
+            let mut status = 0;
+            unsafe {libc::waitpid(pid, &mut status, 0)};
+        });
+    }
+}
+
+#[cfg(target_family = "unix")]
+fn set_resource_limits(command: &mut Command, rule_options: Option<&CGIRuleOptions>) {
+    let Some(rule_options) = rule_options else {
+        return;
+    };
+
+    let limits: Vec<(libc::c_int, u64)> = [
+        (libc::RLIMIT_CPU as libc::c_int, rule_options.cpu_limit),
+        (libc::RLIMIT_AS as libc::c_int, rule_options.memory_limit)
+    ].into_iter().filter_map(|(resource, limit)| limit.map(|l| (resource, l))).collect();
+
+    if limits.is_empty() {
+        return;
+    }
+
+    unsafe {
+        command.pre_exec(move || {
+            for (resource, limit) in &limits {
+                let rlimit = libc::rlimit {rlim_cur: *limit as libc::rlim_t, rlim_max: *limit as libc::rlim_t};
+                if libc::setrlimit(*resource as _, &rlimit) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(target_family = "unix")]
+async fn run_cgi_process(cgi_server: &String,
+                         script_filename: &String,
+                         script: &str,
+                         envs: &HashMap<String, String>,
+                         data: Option<CGIBody>,
+                         rule_options: Option<&CGIRuleOptions>) -> io::Result<GatewayOutput> {
+    let mut cgi_command = Command::new(cgi_server);
+    cgi_command
+        .envs(envs)
+        .arg(script_filename)
+        .stdin(if data.is_some() {Stdio::piped()} else {Stdio::null()})
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    set_resource_limits(&mut cgi_command, rule_options);
+
+    let started = Instant::now();
+    let mut cgi_process = cgi_command.spawn()?;
+    let Some(pid) = cgi_process.id() else {
+        return Err(io::Error::other("CGI process exited before it could be tracked"));
+    };
+    let reaped = Arc::new(AtomicBool::new(false));
+    let _reaper = Reaper {pid: pid as libc::pid_t, reaped: reaped.clone()};
+
+    let stdin = cgi_process.stdin.take();
+    let mut stdout = cgi_process.stdout.take();
+    let mut stderr = cgi_process.stderr.take();
+
+    let write = async move {
+        if let (Some(data), Some(mut stdin)) = (data, stdin) {
+            copy(&mut data.reader().await?, &mut stdin).await?;
+        }
+        Ok::<(), io::Error>(())
+    };
+    let read_stdout = async {
+        let mut buffer = Vec::new();
+        if let Some(stdout) = &mut stdout {
+            stdout.read_to_end(&mut buffer).await?;
+        }
+        Ok::<Vec<u8>, io::Error>(buffer)
+    };
+    let read_stderr = async {
+        let mut buffer = Vec::new();
+        if let Some(stderr) = &mut stderr {
+            stderr.read_to_end(&mut buffer).await?;
+        }
+        Ok::<Vec<u8>, io::Error>(buffer)
+    };
+
+    let (written, stdout, stderr) = tokio::join!(write, read_stdout, read_stderr);
+    written?;
+    let (stdout, stderr) = (stdout?, stderr?);
+
+    let reaped_flag = reaped.clone();
+    let (status, usage) = spawn_blocking(move || {
+        let mut status = 0;
+        let mut usage: libc::rusage = unsafe {std::mem::zeroed()};
+        let result = unsafe {libc::wait4(pid as libc::pid_t, &mut status, 0, &mut usage)};
+        reaped_flag.store(true, Ordering::Release);
+
+        if result < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok((status, usage))
+        }
+    }).await.map_err(io::Error::other)??;
+    drop(cgi_process);
+
+    let cpu = Duration::new((usage.ru_utime.tv_sec + usage.ru_stime.tv_sec) as u64, 0)
+        + Duration::from_micros((usage.ru_utime.tv_usec + usage.ru_stime.tv_usec) as u64);
+    #[cfg(target_os = "macos")]
+    let max_rss_bytes = usage.ru_maxrss as u64;
+    #[cfg(not(target_os = "macos"))]
+    let max_rss_bytes = usage.ru_maxrss as u64 * 1024;
+    record_cgi_usage(script, started.elapsed(), cpu, max_rss_bytes);
+
+    if libc::WIFSIGNALED(status) && matches!(libc::WTERMSIG(status), libc::SIGXCPU | libc::SIGKILL) {
+        error!("{script_filename} was terminated after exceeding its resource limits.");
+    }
+
+    let success = libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0;
+    Ok(GatewayOutput {stdout, stderr, success})
+}
+
+#[cfg(not(target_family = "unix"))]
+async fn run_cgi_process(cgi_server: &String,
+                         script_filename: &String,
+                         script: &str,
+                         envs: &HashMap<String, String>,
+                         data: Option<CGIBody>,
+                         _rule_options: Option<&CGIRuleOptions>) -> io::Result<GatewayOutput> {
+    let mut cgi_command = Command::new(cgi_server);
+    cgi_command
+        .envs(envs)
+        .arg(script_filename)
+        .stdin(if data.is_some() {Stdio::piped()} else {Stdio::null()})
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let started = Instant::now();
+    let mut cgi_process = cgi_command.spawn()?;
+
+    if let (Some(data), Some(stdin)) = (data, &mut cgi_process.stdin) {
+        copy(&mut data.reader().await?, stdin).await?;
+    }
+    drop(cgi_process.stdin.take());
+
+    let output = cgi_process.wait_with_output().await?;
+    record_cgi_usage(script, started.elapsed(), Duration::ZERO, 0);
+    Ok(GatewayOutput {stdout: output.stdout, stderr: output.stderr, success: output.status.success()})
+}
+
 pub async fn handle_cgi<T>(stream: &mut T,
                            headers: &HashMap<String, String>,
                            resource: &String,
@@ -45,46 +244,28 @@ where
 {
     let resource_trimmed = String::from((&resource).trim_start_matches('/'));
     let mut response_headers: HashMap<String, String> = HashMap::new();
-    if let Some(access_control) = &CONFIG.access_control {
-        if !access_control.is_access_allowed(&resource_trimmed) {
-            let deny_action = access_control.deny_action;
-            if let Some(library) = &*ENDPOINT_LIBRARY {
-                if deny_action == 403u16 {
-                    if let Err(_) = forbidden(stream, Default, headers, response_headers, local_ip, remote_ip, remote_port, library).await {
-                        return Err(Box::new(ServerError::BadGateway));
-                    }
-                    return Ok(CGIStatus::Denied);
-                }
-                if let Err(_) = not_found(stream, Default, headers, response_headers, local_ip, remote_ip, remote_port, library).await {
-                    return Err(Box::new(ServerError::BadGateway));
-                }
-                return Ok(CGIStatus::Denied);
-            }
-            if let Err(_) = send_response(stream, deny_action, Some(response_headers), None, None, None).await {
-                return Err(Box::new(ServerError::BadGateway));
-            }
-            return Ok(CGIStatus::Denied);
+    if let Some(denial) = CONFIG.load().denial(&resource_trimmed, request_method, remote_ip) {
+        if let Err(_) = denied(stream, denial, Default, headers, response_headers, local_ip, remote_ip, remote_port).await {
+            return Err(Box::new(ServerError::BadGateway));
         }
+        return Ok(CGIStatus::Denied);
     }
 
-    #[cfg(target_family = "unix")]
-    let document_root = if *&*CHROOT {&String::from("")} else {&CONFIG.document_root};
-    #[cfg(not(target_family = "unix"))]
-    let document_root = &CONFIG.document_root;
+    let document_root = &document_root();
     let mut res_validated = resource;
     let mut res_tmp: String;
 
-    if Path::new(&format!("{document_root}/{resource_trimmed}")).is_dir() {
+    if fs_index::is_dir(&format!("{document_root}/{resource_trimmed}")).await {
         res_tmp = String::from("");
-        for index in &CONFIG.indices {
-            if Path::new(&format!("{document_root}/{resource_trimmed}/{index}")).is_file() {
+        for index in &CONFIG.load().indices {
+            if fs_index::is_file(&format!("{document_root}/{resource_trimmed}/{index}")).await {
                 res_tmp = format!("{}/{index}", resource.trim_end_matches('/'));
                 break;
             }
         }
 
-        if Path::new(&format!("{document_root}/{res_tmp}")).is_dir() {
-            if CONFIG.should_display_index_of(&resource_trimmed) {
+        if fs_index::is_dir(&format!("{document_root}/{res_tmp}")).await {
+            if CONFIG.load().should_display_index_of(&resource_trimmed) {
                 if let Err(e) = index_of(stream, &resource_trimmed, if request_method.eq("HEAD") {true} else {false}, headers).await {
                     return Err(e);
                 }
@@ -95,12 +276,12 @@ where
 
         let res_tmp_trim = String::from(res_tmp.trim_start_matches("/"));
 
-        if !Path::new(&format!("{document_root}/{res_tmp}")).is_file() {
-            return match &CONFIG.endpoints {
-                Some(endpoints) if (&ENDPOINT_LIBRARY).is_some() && endpoints.contains(&res_tmp_trim) =>
+        if !fs_index::is_file(&format!("{document_root}/{res_tmp}")).await {
+            return match endpoint_names() {
+                Some(endpoints) if endpoint_library().is_some() && endpoints.contains(&res_tmp_trim) =>
                     Ok(CGIStatus::Unavailable {not_found_guaranteed: false, resource_present_in_endpoints: true}),
                 _ => {
-                    if CONFIG.should_display_index_of(&resource_trimmed) {
+                    if CONFIG.load().should_display_index_of(&resource_trimmed) {
                         if let Err(e) = index_of(stream, &resource_trimmed, if request_method.eq("HEAD") {true} else {false}, headers).await {
                             return Err(e);
                         }
@@ -116,21 +297,24 @@ where
 
     let gateway_interface: String = String::from("CGI/1.1");
     let server_addr = local_ip.to_string();
-    let server_name = &CONFIG.bind_host;
-    let server_port = CONFIG.bind_port.to_string();
+    let server_name = &CONFIG.load().bind_host;
+    let server_port = CONFIG.load().bind_port.to_string();
     let server_protocol = String::from("HTTP/1.1");
     let server_software = format!("Drain {}", env!("CARGO_PKG_VERSION"));
-    let content_length: String;
     let request_uri = res_validated;
     let path_split: Vec<&str> = res_validated.split("/").collect();
     let mut script_filename = String::from(document_root);
     let mut file_pos = 1;
 
-    while !Path::is_file(Path::new(&script_filename)) && file_pos < path_split.len() {
+    while !fs_index::is_file(&script_filename).await && file_pos < path_split.len() {
         script_filename.push_str(&*format!("/{}", path_split[file_pos]));
         file_pos += 1;
     }
 
+    if !is_inside_document_root(&script_filename).await {
+        return Ok(CGIStatus::Unavailable {not_found_guaranteed: true, resource_present_in_endpoints: false});
+    }
+
     let script_name = &path_split[file_pos - 1];
     let mut path_info = String::from("");
     for i in file_pos..path_split.len() {
@@ -161,66 +345,154 @@ where
     ]);
 
     envs.extend(headers.iter().map(|(k, v)| (format!("HTTP_{}", k.replace('-', "_")).to_uppercase(), v.clone())));
+
+    if let Ok(Some(tls_fingerprint)) = REQUEST_INFO.try_with(|info| info.borrow().tls_fingerprint.clone()) {
+        envs.insert(String::from("SSL_CLIENT_JA3"), tls_fingerprint.ja3);
+        envs.insert(String::from("SSL_CLIENT_JA4"), tls_fingerprint.ja4);
+    }
+
+    if let Ok(Some((scheme, user))) = REQUEST_INFO.try_with(|info| info.borrow().remote_user.clone()) {
+        envs.insert(String::from("AUTH_TYPE"), String::from(scheme.name()));
+        envs.insert(String::from("REMOTE_USER"), user);
+    }
+
+    if let Ok(Some(claims)) = REQUEST_INFO.try_with(|info| info.borrow().jwt_claims.clone()) {
+        if !envs.contains_key("REMOTE_USER") {
+            if let Some(subject) = claims.get("sub").and_then(|subject| subject.as_str()) {
+                envs.insert(String::from("AUTH_TYPE"), String::from("Bearer"));
+                envs.insert(String::from("REMOTE_USER"), String::from(subject));
+            }
+        }
+
+        for (name, value) in &claims {
+            let value = match value {
+                Value::String(value) => value.clone(),
+                Value::Number(_) | Value::Bool(_) => value.to_string(),
+                _ => continue
+            };
+            let name: String = name.chars().map(|c| if c.is_ascii_alphanumeric() {c.to_ascii_uppercase()} else {'_'}).collect();
+            envs.insert(format!("JWT_CLAIM_{name}"), value);
+        }
+
+        envs.insert(String::from("JWT_CLAIMS"), Value::Object(claims).to_string());
+    }
+
+    match REQUEST_INFO.try_with(|info| info.borrow().client_certificate.clone()) {
+        Ok(Some(client_certificate)) => {
+            envs.insert(String::from("SSL_CLIENT_VERIFY"), String::from("SUCCESS"));
+            envs.insert(String::from("SSL_CLIENT_S_DN"), client_certificate.subject);
+            envs.insert(String::from("SSL_CLIENT_I_DN"), client_certificate.issuer);
+            envs.insert(String::from("SSL_CLIENT_M_SERIAL"), client_certificate.serial);
+            let mut counts: HashMap<String, usize> = HashMap::new();
+            for name in &client_certificate.subject_alt_names {
+                if let Some((kind, value)) = name.split_once(':') {
+                    let count = counts.entry(kind.to_uppercase()).or_default();
+                    envs.insert(format!("SSL_CLIENT_SAN_{}_{count}", kind.to_uppercase()), String::from(value));
+                    *count += 1;
+                }
+            }
+        },
+        Ok(None) => {
+            envs.insert(String::from("SSL_CLIENT_VERIFY"), String::from("NONE"));
+        },
+        Err(_) => {}
+    }
     envs.remove("HTTP_CONTENT_TYPE");
     envs.remove("HTTP_CONTENT_LENGTH");
 
-    let Some(cgi) = &CONFIG.cgi else {
+    let Some(cgi) = &CONFIG.load().cgi else {
         return Err(Box::new(ServerError::BadGateway));
     };
 
-    let mut cgi_command = Command::new(&cgi.cgi_server);
-    let mut cgi_process: Child;
-
-    if let Some(cgi_data) = cgi_data {
-        content_length = cgi_data.data.len().to_string();
-        let data = cgi_data.data;
-        let content_type = cgi_data.content_type;
-
-        envs.insert(String::from("CONTENT_TYPE"), content_type);
-        envs.insert(String::from("CONTENT_LENGTH"), content_length);
+    let rule_options = cgi.rule_options(&resource_trimmed);
+    let cgi_server = rule_options.and_then(|o| o.interpreter.as_ref()).unwrap_or(&cgi.cgi_server);
+    let data = match cgi_data {
+        Some(cgi_data) => {
+            envs.insert(String::from("CONTENT_TYPE"), cgi_data.content_type);
+            envs.insert(String::from("CONTENT_LENGTH"), cgi_data.body.len().to_string());
+            Some(cgi_data.body)
+        },
+        None => None
+    };
 
-        cgi_process = cgi_command
-            .envs(&envs)
-            .arg(&script_filename)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()?;
+    if let Some(rule_options) = rule_options {
+        envs.extend(rule_options.env.clone());
+    }
 
-        let Some(ref mut stdin) = cgi_process.stdin else {
+    let head = request_method.eq("HEAD");
+    if head && rule_options.is_some_and(|o| !o.execute_on_head) {
+        if let Err(_) = send_response(stream, 200, Some(response_headers), None, None, None).await {
             return Err(Box::new(ServerError::BadGateway));
-        };
+        }
+        return Ok(CGIStatus::Available);
+    }
 
-        stdin.write_all(&*data).await?;
-    } else {
-        cgi_process = cgi_command
-            .envs(&envs)
-            .arg(&script_filename)
-            .stdin(Stdio::null())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()?;
+    let gateway_address = match rule_options {
+        Some(CGIRuleOptions {backend: CGIBackend::FastCGI | CGIBackend::SCGI, address: Some(address), ..}) => Some(address),
+        _ => None
+    };
+    let fallback = rule_options.and_then(|o| o.fallback.as_ref().map(|fallback| (fallback, o.fallback_retry)));
+
+    if let (Some(address), Some((fallback, fallback_retry))) = (gateway_address, fallback) {
+        if !fallback::is_healthy(address, fallback_retry).await {
+            return serve_fallback(stream, fallback, &resource_trimmed, head, headers).await;
+        }
     }
 
-    let output = cgi_process.wait_with_output().await?;
+    let execution = async {
+        match rule_options {
+            Some(CGIRuleOptions {backend: CGIBackend::FastCGI, address: Some(address), ..}) => {
+                let body = data.unwrap_or(CGIBody::Memory(Vec::new()));
+                gateway::fastcgi(address, &envs, body.reader().await?, body.len()).await
+            },
+            Some(CGIRuleOptions {backend: CGIBackend::SCGI, address: Some(address), ..}) => {
+                let body = data.unwrap_or(CGIBody::Memory(Vec::new()));
+                gateway::scgi(address, &envs, body.reader().await?, body.len()).await
+            },
+            _ => run_cgi_process(cgi_server, &script_filename, script_filename.trim_start_matches(document_root.as_str()).trim_start_matches('/'), &envs, data, rule_options).await
+        }
+    };
 
-    match (output.stderr.is_empty(), output.status.success()) {
+    let output = match rule_options.and_then(|o| o.timeout) {
+        Some(t) => match timeout(Duration::from_secs(t), execution).await {
+            Ok(output) => output,
+            Err(_) => {
+                error!("{script_filename} didn't finish in {t} seconds.");
+                Err(io::Error::from(ErrorKind::TimedOut))
+            }
+        },
+        None => execution.await
+    };
+
+    let output = match (output, gateway_address, fallback) {
+        (Ok(output), Some(address), _) => {
+            fallback::mark_healthy(address).await;
+            output
+        },
+        (Ok(output), None, _) => output,
+        (Err(_), Some(address), Some((fallback, _))) => {
+            fallback::mark_unhealthy(address).await;
+            return serve_fallback(stream, fallback, &resource_trimmed, head, headers).await;
+        },
+        (Err(e), _, _) if e.kind() == ErrorKind::TimedOut => return Err(Box::new(ServerError::BadGateway)),
+        (Err(e), _, _) => return Err(Box::new(e))
+    };
+
+    match (output.stderr.is_empty(), output.success) {
         (true, false) => {
-            if let Some(endpoints) = &CONFIG.endpoints {
-                if (&ENDPOINT_LIBRARY).is_some() && endpoints.contains(&resource_trimmed) {
+            if let Some(endpoints) = endpoint_names() {
+                if endpoint_library().is_some() && endpoints.contains(&resource_trimmed) {
                     return Ok(CGIStatus::Unavailable {not_found_guaranteed: false, resource_present_in_endpoints: true})
                 }
             }
             return Ok(CGIStatus::Unavailable {not_found_guaranteed: true, resource_present_in_endpoints: false})
         },
         (false, false) => {
-            if CONFIG.be_verbose {
-                eprintln!("[handle_cgi():{}] Standard error message received while executing {script_filename}:\n{}", line!(), String::from_utf8_lossy(&*output.stderr));
-            }
+            debug!("Standard error message received while executing {script_filename}:\n{}", String::from_utf8_lossy(&*output.stderr));
             return Err(Box::new(ServerError::BadGateway));
         },
-        (false, true) if CONFIG.be_verbose =>
-            eprintln!("[handle_cgi():{}] Standard error message received while executing {script_filename}:\n{}", line!(), String::from_utf8_lossy(&*output.stderr)),
+        (false, true) =>
+            debug!("Standard error message received while executing {script_filename}:\n{}", String::from_utf8_lossy(&*output.stderr)),
         _ => {}
     }
 
@@ -237,6 +509,17 @@ where
         response_headers.insert(String::from_utf8_lossy(name).trim().to_lowercase(), String::from(String::from_utf8_lossy(value).trim()));
     }
 
+    if let Some(content_encoding) = response_headers.get("content-encoding") {
+        match decode_bounded(content_encoding.trim(), content, &mut io::sink()) {
+            Ok(_) | Err(ServerError::UnsupportedEncoding) => {},
+            Err(e) => {
+                error!("{script_filename} sent a response body, which couldn't be safely decoded.\n\
+                        Error information:\n{e}");
+                return Err(Box::new(ServerError::BadGateway));
+            }
+        }
+    }
+
     let status = match response_headers.get("status") {
         Some(status_raw) => {
             if let Ok(s) = (&status_raw.as_str()[0..3]).parse::<u16>() {
@@ -249,6 +532,23 @@ where
     };
     response_headers.remove("status");
 
+    if head {
+        if !response_headers.contains_key("content-length") {
+            response_headers.insert(String::from("content-length"), content.len().to_string());
+        }
+
+        if let Err(_) = send_response(stream,
+                                      if response_headers.contains_key("location") {302} else {status},
+                                      Some(response_headers),
+                                      None,
+                                      None,
+                                      None).await {
+            return Err(Box::new(ServerError::BadGateway));
+        }
+
+        return Ok(CGIStatus::Available);
+    }
+
     if let Err(_) = send_response(stream,
                                   if response_headers.contains_key("location") {302} else {status},
                                   Some(response_headers),
@@ -259,4 +559,19 @@ where
     }
 
     Ok(CGIStatus::Available)
-}
\ No newline at end of file
+}
+
+async fn serve_fallback<T>(stream: &mut T,
+                           fallback: &String,
+                           resource: &String,
+                           head: bool,
+                           headers: &HashMap<String, String>) -> Result<CGIStatus, Box<dyn Error + Send + Sync>>
+where
+    T: AsyncRead + AsyncWrite + Unpin
+{
+    if !fallback::serve_fallback(stream, fallback, resource, head, headers).await? {
+        return Ok(CGIStatus::Unavailable {not_found_guaranteed: true, resource_present_in_endpoints: false});
+    }
+
+    Ok(CGIStatus::Available)
+}
@@ -0,0 +1,94 @@
+use std::sync::LazyLock;
+use clap::{Parser, Subcommand, ValueEnum};
+use crate::config::Config;
+
+#[derive(Parser)]
+#[command(name = "drain", about = "A WIP web server/framework.", disable_version_flag = true)]
+pub struct Cli {
+    #[arg(long, global = true, value_name = "PATH", help = "Path of config.json, takes precedence over DRAIN_CONFIG")]
+    pub config: Option<String>,
+    #[arg(long, value_name = "ADDRESS", help = "Address to bind to, overrides bind_host")]
+    pub bind: Option<String>,
+    #[arg(long, help = "Port to bind to, overrides bind_port")]
+    pub port: Option<u16>,
+    #[arg(long, value_name = "PATH", help = "Overrides document_root")]
+    pub document_root: Option<String>,
+    #[arg(long, help = "Validate the config and exit")]
+    pub check_config: bool,
+    #[arg(short = 'V', long, help = "Print version and build information")]
+    pub version: bool,
+    #[arg(long, requires = "version", help = "Print build information as JSON")]
+    pub json: bool,
+    #[cfg(target_family = "unix")]
+    #[arg(long, help = "Detach from the terminal and run in the background")]
+    pub daemon: bool,
+    #[cfg(target_family = "unix")]
+    #[arg(long, value_name = "ACTION", help = "Signal the server running with the configured pidfile")]
+    pub signal: Option<SignalAction>,
+    #[command(subcommand)]
+    pub command: Option<Command>
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    #[command(about = "Print the resolved listeners, locations and limits, and the handlers of given paths")]
+    Routes {
+        #[arg(long)]
+        host: Option<String>,
+        paths: Vec<String>
+    },
+    #[command(about = "Translate an nginx config, an .htaccess or an Apache httpd.conf into a config.json skeleton")]
+    Import {
+        #[arg(long, value_name = "FILE")]
+        from: String
+    },
+    #[command(about = "Serve a directory for local development, reloading the browser when its files change")]
+    Dev {
+        #[arg(default_value = ".")]
+        dir: String
+    },
+    #[command(about = "Verify or export the request journal")]
+    Journal {
+        #[command(subcommand)]
+        command: JournalCommand
+    }
+}
+
+#[derive(Subcommand)]
+pub enum JournalCommand {
+    #[command(about = "Check that no entry was modified, removed or reordered")]
+    Verify,
+    #[command(about = "Verify the journal and print its entries as JSON lines")]
+    Export {
+        #[arg(long, value_name = "SEQ")]
+        since: Option<u64>
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum SignalAction {
+    Stop,
+    Reload
+}
+
+impl Cli {
+    pub fn apply_overrides(&self, config: &mut Config) {
+        if let Some(bind) = &self.bind {
+            config.bind_host = bind.clone();
+            config.bind_host_v6 = None;
+        }
+
+        if let Some(port) = self.port {
+            config.bind_port = port;
+        }
+
+        if let Some(document_root) = &self.document_root {
+            config.document_root = document_root.clone();
+        }
+    }
+}
+
+#[cfg(not(test))]
+pub static CLI: LazyLock<Cli> = LazyLock::new(Cli::parse);
+#[cfg(test)]
+pub static CLI: LazyLock<Cli> = LazyLock::new(|| Cli::parse_from(["drain", "dev", env!("CARGO_MANIFEST_DIR")]));
@@ -0,0 +1,83 @@
+use std::net::IpAddr;
+use openssl::ssl::SslRef;
+use openssl::x509::{X509NameRef, X509VerifyResult};
+
+pub const SUBJECT_DN_HEADER: &str = "x-drain-client-s-dn";
+pub const ISSUER_DN_HEADER: &str = "x-drain-client-i-dn";
+pub const SAN_HEADER: &str = "x-drain-client-san";
+pub const SERIAL_HEADER: &str = "x-drain-client-serial";
+
+pub const CLIENT_CERTIFICATE_HEADERS: [&str; 4] = [SUBJECT_DN_HEADER, ISSUER_DN_HEADER, SAN_HEADER, SERIAL_HEADER];
+
+#[derive(Clone)]
+pub struct ClientCertificate {
+    pub subject: String,
+    pub issuer: String,
+    pub subject_alt_names: Vec<String>,
+    pub serial: String
+}
+
+impl ClientCertificate {
+    pub fn headers(&self) -> Vec<(&'static str, String)> {
+        vec![
+            (SUBJECT_DN_HEADER, self.subject.clone()),
+            (ISSUER_DN_HEADER, self.issuer.clone()),
+            (SAN_HEADER, self.subject_alt_names.join(", ")),
+            (SERIAL_HEADER, self.serial.clone())
+        ]
+    }
+}
+
+fn distinguished_name(name: &X509NameRef) -> String {
+    let mut attributes: Vec<String> = name.entries()
+        .map(|entry| {
+            let key = entry.object().nid().short_name().unwrap_or("UNDEF");
+            let value = entry.data().to_string().unwrap_or_default();
+            format!("{key}={}", value.replace('\\', "\\\\").replace(',', "\\,").replace('\0', "\\00"))
+        })
+        .collect();
+
+    attributes.reverse();
+    attributes.join(",")
+}
+
+fn ip_address(bytes: &[u8]) -> Option<IpAddr> {
+    match bytes.len() {
+        4 => Some(IpAddr::from(<[u8; 4]>::try_from(bytes).ok()?)),
+        16 => Some(IpAddr::from(<[u8; 16]>::try_from(bytes).ok()?)),
+        _ => None
+    }
+}
+
+pub fn client_certificate(ssl: &SslRef) -> Option<ClientCertificate> {
+    let certificate = ssl.peer_certificate()?;
+    if ssl.verify_result() != X509VerifyResult::OK {
+        return None;
+    }
+
+    let subject_alt_names = certificate.subject_alt_names()
+        .map(|names| names.iter().filter_map(|name| {
+            if let Some(dns) = name.dnsname() {
+                return Some(format!("DNS:{dns}"));
+            }
+            if let Some(email) = name.email() {
+                return Some(format!("email:{email}"));
+            }
+            if let Some(uri) = name.uri() {
+                return Some(format!("URI:{uri}"));
+            }
+            name.ipaddress().and_then(ip_address).map(|ip| format!("IP:{ip}"))
+        }).collect())
+        .unwrap_or_default();
+
+    let serial = certificate.serial_number().to_bn().ok()
+        .and_then(|serial| serial.to_hex_str().ok().map(|serial| serial.to_string()))
+        .unwrap_or_default();
+
+    Some(ClientCertificate {
+        subject: distinguished_name(certificate.subject_name()),
+        issuer: distinguished_name(certificate.issuer_name()),
+        subject_alt_names,
+        serial
+    })
+}
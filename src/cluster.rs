@@ -0,0 +1,175 @@
+use std::iter::once;
+use std::net::IpAddr;
+use std::sync::LazyLock;
+use std::time::{Duration, SystemTime};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+use crate::bans;
+use crate::config::CONFIG;
+use crate::file_cache;
+use crate::noise;
+use crate::reload::reload;
+use crate::storage::RedisStore;
+use crate::util::purge_etags;
+#[cfg(target_family = "unix")]
+use crate::util::CHROOT;
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum ClusterEvent {
+    Reload,
+    Purge {prefix: Option<String>},
+    Ban {ip: IpAddr, duration: u64}
+}
+
+#[derive(Serialize, Deserialize)]
+struct ClusterMessage {
+    node: String,
+    #[serde(flatten)]
+    event: ClusterEvent
+}
+
+struct ClusterChannel {
+    store: RedisStore,
+    channel: String,
+    retry_interval: Duration
+}
+
+static CLUSTER: LazyLock<Option<ClusterChannel>> = LazyLock::new(|| {
+    CONFIG.load().cluster.as_ref().map(|cluster| ClusterChannel {
+        store: RedisStore::new(&cluster.address, &cluster.password, 0),
+        channel: cluster.channel.clone(),
+        retry_interval: Duration::from_secs(cluster.retry_interval)
+    })
+});
+
+static NODE: LazyLock<String> = LazyLock::new(|| {
+    let started = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_or(0, |d| d.as_nanos());
+    format!("{}-{started:x}", std::process::id())
+});
+
+pub async fn publish(event: ClusterEvent) {
+    let Some(cluster) = &*CLUSTER else {
+        return;
+    };
+
+    let message = match serde_json::to_string(&ClusterMessage {node: NODE.clone(), event}) {
+        Ok(message) => message,
+        Err(e) => {
+            warn!("An error occurred while serializing a cluster message.\n\
+                   Error information:\n{e}");
+            return;
+        }
+    };
+
+    if let Err(e) = cluster.store.publish(&cluster.channel, &message).await {
+        warn!("An error occurred while publishing a cluster message, peers won't receive it.\n\
+               Error information:\n{e}");
+    }
+}
+
+fn document_roots() -> Vec<String> {
+    let config = CONFIG.load();
+    let roots = once(&config.document_root)
+        .chain(config.virtual_hosts.iter().flatten().filter_map(|virtual_host| virtual_host.document_root.as_ref()))
+        .chain(config.listeners.iter().flatten().filter_map(|listener| listener.document_root.as_ref()));
+
+    roots.map(|root| {
+        #[cfg(target_family = "unix")]
+        if *CHROOT {
+            return String::from(root.strip_prefix(config.document_root.trim_end_matches('/')).unwrap_or(root.as_str()).trim_end_matches('/'));
+        }
+
+        String::from(root.trim_end_matches('/'))
+    }).collect()
+}
+
+async fn purge_locally(prefix: Option<&str>) {
+    let Some(prefix) = prefix else {
+        file_cache::purge(|_| true);
+        noise::purge(|_| true);
+        purge_etags(|_| true).await;
+        return;
+    };
+
+    let prefix = format!("/{}", prefix.trim_start_matches('/'));
+    let roots = document_roots();
+    let matches = |path: &str| roots.iter().any(|root| path.strip_prefix(root.as_str()).is_some_and(|rest| rest.starts_with(&prefix)));
+
+    file_cache::purge(matches);
+    noise::purge(matches);
+    purge_etags(matches).await;
+}
+
+pub async fn purge(prefix: Option<String>) {
+    purge_locally(prefix.as_deref()).await;
+    publish(ClusterEvent::Purge {prefix}).await;
+}
+
+pub async fn ban(ip: IpAddr, duration: Duration) {
+    bans::ban(ip, duration).await;
+    publish(ClusterEvent::Ban {ip, duration: duration.as_secs()}).await;
+}
+
+async fn receive(message: &str) {
+    let message = match serde_json::from_str::<ClusterMessage>(message) {
+        Ok(message) => message,
+        Err(e) => {
+            warn!("Received an invalid cluster message.\n\
+                   Error information:\n{e}");
+            return;
+        }
+    };
+
+    if message.node.eq(&*NODE) {
+        return;
+    }
+
+    match message.event {
+        ClusterEvent::Reload => {
+            info!("Reloading the config on request of cluster node {}.", message.node);
+            reload().await;
+        },
+        ClusterEvent::Purge {prefix} => {
+            debug!("Purging {} on request of cluster node {}.", prefix.as_deref().unwrap_or("all caches"), message.node);
+            purge_locally(prefix.as_deref()).await;
+        },
+        ClusterEvent::Ban {ip, duration} => {
+            debug!("Banning {ip} for {duration} seconds on request of cluster node {}.", message.node);
+            bans::ban(ip, Duration::from_secs(duration)).await;
+        }
+    }
+}
+
+pub async fn listen() {
+    let Some(cluster) = &*CLUSTER else {
+        return;
+    };
+
+    loop {
+        match cluster.store.subscribe(&cluster.channel).await {
+            Ok(mut subscription) => {
+                info!("Joined cluster channel {} as node {}.", cluster.channel, *NODE);
+
+                loop {
+                    match subscription.next_message().await {
+                        Ok(message) => receive(&message).await,
+                        Err(e) => {
+                            warn!("Lost the connection to the cluster channel.\n\
+                                   Error information:\n{e}\n\
+                                   Reconnecting in {} seconds...", cluster.retry_interval.as_secs());
+                            break;
+                        }
+                    }
+                }
+            },
+            Err(e) => {
+                warn!("An error occurred while subscribing to the cluster channel.\n\
+                       Error information:\n{e}\n\
+                       Retrying in {} seconds...", cluster.retry_interval.as_secs());
+            }
+        }
+
+        tokio::time::sleep(cluster.retry_interval).await;
+    }
+}
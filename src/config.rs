@@ -1,26 +1,98 @@
 use std::collections::HashMap;
 use std::env;
-use std::sync::LazyLock;
-use glob::glob;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, OnceLock, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::SystemTime;
+use chrono::{DateTime, Local, Utc};
+use chrono::format::{Item, StrftimeItems};
+use glob::{glob, MatchOptions, Pattern};
+use regex::Regex;
 use openssl::error::ErrorStack;
-use openssl::ssl::{select_next_proto, AlpnError, SslContext, SslFiletype, SslMethod, SslOptions, SslSessionCacheMode, SslVerifyMode, SslVersion};
+use openssl::ssl::{select_next_proto, AlpnError, ClientHelloResponse, NameType, SniError, SslContext, SslContextBuilder, SslFiletype, SslAlert, SslMethod, SslOptions, SslSessionCacheMode, SslVerifyMode, SslVersion};
+use openssl::x509::X509Name;
 use serde::Deserialize;
-use std::fs::File;
+use tracing::{error, info};
+use tracing::level_filters::LevelFilter;
+use std::fs::{read, File};
 use std::io::Read;
-#[cfg(target_family = "unix")]
-use crate::util::CHROOT;
+use crate::dictionary::{negotiate_dictionary, DCB_ENCODING};
+use crate::tls_fingerprint::{compute, FINGERPRINT_INDEX};
+use crate::util::REQUEST_INFO;
+use crate::forwarded::Cidr;
+use crate::prefilter::is_server_name_allowed;
+use crate::ocsp::configure_stapling;
+use crate::proxy::UpstreamServer;
+use crate::acme::CERTIFICATE;
+use crate::cli::{Command, CLI};
+use crate::dev::RELOAD_SCRIPT;
+use crate::logging::configure_logging;
 
 #[derive(Deserialize)]
 pub struct AccessControl {
-    pub deny_action: u16,
-    list: HashMap<String, String>
+    pub deny_action: DenyAction,
+    pub list: HashMap<String, String>,
+    #[serde(default)]
+    pub rules: Vec<AccessRule>,
+    #[serde(default = "AccessControl::default_policy")]
+    pub default_policy: String
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+pub enum DenyAction {
+    Status(u16),
+    Redirect {
+        redirect: String,
+        #[serde(default = "DenyAction::default_redirect_status")]
+        status: u16
+    },
+    Page {
+        page: String,
+        #[serde(default = "DenyAction::default_page_status")]
+        status: u16
+    }
+}
+
+pub struct Denial {
+    pub rule: String,
+    pub action: DenyAction
+}
+
+#[derive(Deserialize)]
+pub struct AccessRule {
+    pub name: Option<String>,
+    pub action: String,
+    pub deny_action: Option<DenyAction>,
+    pub ips: Option<Vec<String>>,
+    pub pattern: Option<String>,
+    pub regex: Option<String>,
+    pub methods: Option<Vec<String>>,
+    pub tls_fingerprints: Option<Vec<String>>
 }
 
 #[derive(Deserialize)]
 pub struct Encoding {
     pub use_encoding: String,
     pub supported_encodings: Vec<String>,
-    pub encoding_applicable_mime_types: Option<Vec<String>>
+    pub encoding_applicable_mime_types: Option<Vec<String>>,
+    #[serde(default = "Encoding::default_min_length")]
+    pub min_length: usize,
+    #[serde(default = "Encoding::default_skipped_mime_types")]
+    pub skipped_mime_types: Vec<String>,
+    pub dictionaries: Option<Vec<CompressionDictionary>>,
+    #[serde(default)]
+    pub precompressed: bool,
+    #[serde(default)]
+    pub precompressed_only: bool
+}
+
+#[derive(Deserialize)]
+pub struct CompressionDictionary {
+    pub path: String,
+    #[serde(rename = "match")]
+    pub match_pattern: String
 }
 
 #[derive(Deserialize)]
@@ -30,7 +102,594 @@ pub struct Https {
     pub min_protocol_version: Option<String>,
     pub cipher_list: String,
     pub ssl_private_key_file: String,
-    pub ssl_certificate_file: String
+    pub ssl_certificate_file: String,
+    #[serde(default)]
+    pub blocked_tls_fingerprints: Vec<String>,
+    #[serde(default)]
+    pub redirect_http: bool,
+    pub hsts: Option<Hsts>,
+    #[serde(default = "Https::default_certificate_reload_interval")]
+    pub certificate_reload_interval: u64,
+    pub client_certificates: Option<ClientCertificates>,
+    #[serde(default)]
+    pub ocsp_stapling: bool,
+    #[serde(default)]
+    pub session_tickets: bool,
+    #[serde(default)]
+    pub session_cache_size: u32
+}
+
+#[derive(Deserialize)]
+pub struct ClientCertificates {
+    pub ca_file: String,
+    #[serde(default)]
+    pub paths: HashMap<String, ClientCertificatePolicy>
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientCertificatePolicy {
+    Required,
+    Optional
+}
+
+#[derive(Deserialize)]
+pub struct Acme {
+    #[serde(default = "Acme::default_directory")]
+    pub directory: String,
+    pub contact: Option<String>,
+    pub domains: Vec<String>,
+    #[serde(default = "Acme::default_storage")]
+    pub storage: String,
+    #[serde(default = "Acme::default_renew_before_days")]
+    pub renew_before_days: u64
+}
+
+#[derive(Deserialize)]
+pub struct Hsts {
+    #[serde(default = "Hsts::default_max_age")]
+    pub max_age: u64,
+    #[serde(default)]
+    pub include_subdomains: bool,
+    #[serde(default)]
+    pub preload: bool
+}
+
+#[derive(Deserialize)]
+pub struct ConnectTunnel {
+    pub enabled: bool,
+    pub allowed_targets: Vec<String>
+}
+
+#[derive(Deserialize)]
+pub struct EndpointLibrary {
+    pub host: Option<String>,
+    pub location: Option<String>,
+    pub endpoints_library: String,
+    pub endpoints: Option<Vec<String>>
+}
+
+#[derive(Deserialize)]
+pub struct VirtualHost {
+    #[serde(default)]
+    pub hosts: Vec<String>,
+    #[serde(default)]
+    pub default: bool,
+    pub document_root: Option<String>,
+    pub endpoints_library: Option<String>,
+    pub endpoints: Option<Vec<String>>,
+    pub access_control: Option<AccessControl>,
+    pub headers: Option<HashMap<String, String>>,
+    pub ssl_certificate_file: Option<String>,
+    pub ssl_private_key_file: Option<String>
+}
+
+#[derive(Deserialize)]
+pub struct CacheControlRule {
+    pub pattern: String,
+    pub max_age: Option<u64>,
+    #[serde(default)]
+    pub immutable: bool,
+    #[serde(default)]
+    pub no_store: bool,
+    #[serde(default)]
+    pub private: bool
+}
+
+#[derive(Deserialize)]
+pub struct SensitivePaths {
+    #[serde(default = "SensitivePaths::default_block_dotfiles")]
+    pub block_dotfiles: bool,
+    #[serde(default = "SensitivePaths::default_blocked_names")]
+    pub blocked_names: Vec<String>,
+    #[serde(default = "SensitivePaths::default_allowed")]
+    pub allowed: Vec<String>
+}
+
+#[derive(Deserialize)]
+pub struct SecurityHeaders {
+    pub pattern: String,
+    pub content_security_policy: Option<String>,
+    #[serde(default)]
+    pub content_security_policy_report_only: bool,
+    #[serde(default = "SecurityHeaders::default_content_type_options")]
+    pub content_type_options: Option<String>,
+    #[serde(default = "SecurityHeaders::default_frame_options")]
+    pub frame_options: Option<String>,
+    #[serde(default = "SecurityHeaders::default_referrer_policy")]
+    pub referrer_policy: Option<String>,
+    pub permissions_policy: Option<String>
+}
+
+#[derive(Deserialize)]
+pub struct Prefilters {
+    pub deny_ips: Option<Vec<String>>,
+    pub allow_ips: Option<Vec<String>>,
+    #[serde(default)]
+    pub blocked_server_names: Vec<String>,
+    #[serde(default)]
+    pub require_server_name: bool,
+    #[serde(default = "Prefilters::default_sniff_protocol")]
+    pub sniff_protocol: bool
+}
+
+#[derive(Deserialize)]
+pub struct Listener {
+    pub address: String,
+    pub port: u16,
+    #[serde(default)]
+    pub tls: bool,
+    pub document_root: Option<String>
+}
+
+#[derive(Deserialize)]
+pub struct Keepalive {
+    pub time: u64,
+    pub interval: Option<u64>,
+    pub retries: Option<u32>
+}
+
+#[derive(Deserialize)]
+pub struct SocketOptions {
+    #[serde(default)]
+    pub reuseport: bool,
+    #[serde(default)]
+    pub nodelay: bool,
+    pub keepalive: Option<Keepalive>,
+    pub interface: Option<String>,
+    pub ipv6_only: Option<bool>,
+    #[serde(default = "SocketOptions::default_backlog")]
+    pub backlog: i32,
+    #[serde(default = "SocketOptions::default_acceptors")]
+    pub acceptors: usize
+}
+
+#[derive(Deserialize)]
+pub struct BandwidthRule {
+    pub pattern: String,
+    pub limit: u64
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum RequestSigning {
+    Hmac {
+        header: String,
+        secret: String
+    },
+    SigV4 {
+        access_key: String,
+        secret_key: String,
+        region: String,
+        #[serde(default = "RequestSigning::default_service")]
+        service: String
+    }
+}
+
+impl RequestSigning {
+    fn default_service() -> String {
+        String::from("s3")
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Upstream {
+    pub servers: Vec<String>,
+    pub signing: Option<RequestSigning>,
+    pub fallback_dir: Option<String>,
+    #[serde(default = "Upstream::default_fallback_retry")]
+    pub fallback_retry: u64
+}
+
+impl Upstream {
+    const fn default_fallback_retry() -> u64 {
+        5
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AuthRule {
+    pub pattern: String,
+    pub realm: String,
+    #[serde(default)]
+    pub scheme: AuthScheme,
+    pub credentials_file: String
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthScheme {
+    #[default]
+    Basic,
+    Digest
+}
+
+#[derive(Deserialize)]
+pub struct Jwt {
+    pub paths: Vec<String>,
+    pub issuer: Option<String>,
+    pub audience: Option<String>,
+    pub jwks_url: Option<String>,
+    pub key_file: Option<String>,
+    pub secret_file: Option<String>,
+    #[serde(default = "Jwt::default_leeway")]
+    pub leeway: u64,
+    #[serde(default = "Jwt::default_jwks_refresh_interval")]
+    pub jwks_refresh_interval: u64
+}
+
+#[derive(Deserialize)]
+pub struct ProxyRule {
+    pub pattern: String,
+    pub upstream: String,
+    #[serde(default)]
+    pub compression: ProxyCompression
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ProxyCompression {
+    #[default]
+    Passthrough,
+    Identity,
+    Recompress
+}
+
+#[derive(Deserialize)]
+pub struct S3Origin {
+    pub location: String,
+    pub endpoint: String,
+    pub bucket: String,
+    #[serde(default)]
+    pub prefix: String,
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+    #[serde(default)]
+    pub path_style: bool,
+    #[serde(default)]
+    pub allow_put: bool
+}
+
+#[derive(Deserialize)]
+pub struct Quarantine {
+    pub location: String,
+    pub quarantine_dir: String,
+    pub target_dir: String,
+    pub scanner: Option<String>,
+    pub admin_endpoint: Option<String>,
+    pub quota: Option<u64>,
+    pub user_quota: Option<u64>,
+    #[serde(default = "Quarantine::default_retention")]
+    pub retention: u64
+}
+
+#[derive(Deserialize)]
+pub struct NoisePaths {
+    #[serde(default = "NoisePaths::default_patterns")]
+    pub patterns: Vec<String>,
+    #[serde(default = "NoisePaths::default_max_size")]
+    pub max_size: u64,
+    #[serde(default = "NoisePaths::default_max_age")]
+    pub max_age: u64
+}
+
+#[derive(Deserialize)]
+pub struct Fairness {
+    #[serde(default)]
+    pub weights: HashMap<String, u32>,
+    #[serde(default = "Fairness::default_busy_threshold")]
+    pub busy_threshold: f64
+}
+
+#[derive(Deserialize)]
+pub struct Journal {
+    pub directory: String,
+    #[serde(default = "Journal::default_methods")]
+    pub methods: Vec<String>,
+    #[serde(default = "Journal::default_max_size")]
+    pub max_size: u64
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessLogFormat {
+    Common,
+    #[default]
+    Combined,
+    Json
+}
+
+#[derive(Deserialize)]
+pub struct AccessLog {
+    #[serde(default)]
+    pub format: AccessLogFormat,
+    #[serde(default = "AccessLog::default_output")]
+    pub output: String,
+    #[serde(default)]
+    pub rotation: Rotation
+}
+
+#[derive(Deserialize)]
+pub struct SubFilter {
+    location: Option<String>,
+    pub search: String,
+    pub replace: String,
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default)]
+    pub once: bool,
+    #[serde(default = "SubFilter::default_types")]
+    types: Vec<String>,
+    #[serde(skip)]
+    compiled: OnceLock<Option<regex::bytes::Regex>>
+}
+
+#[derive(Deserialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RedirectPolicyAction {
+    #[default]
+    Log,
+    Block
+}
+
+#[derive(Deserialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json
+}
+
+#[derive(Deserialize)]
+pub struct Logging {
+    pub level: Option<String>,
+    #[serde(default)]
+    pub format: LogFormat,
+    #[serde(default)]
+    pub filters: HashMap<String, String>,
+    #[serde(default = "Logging::default_output")]
+    pub output: String,
+    #[serde(default)]
+    pub rotation: Rotation
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RotationInterval {
+    Hourly,
+    Daily,
+    Weekly
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub struct Rotation {
+    pub max_size: Option<u64>,
+    pub interval: Option<RotationInterval>,
+    pub keep: Option<usize>
+}
+
+#[derive(Deserialize)]
+pub struct Debug {
+    #[serde(default)]
+    pub header: bool
+}
+
+#[derive(Deserialize)]
+pub struct MethodOverride {
+    pub paths: Vec<String>
+}
+
+#[derive(Deserialize)]
+pub struct RedirectPolicy {
+    #[serde(default)]
+    pub action: RedirectPolicyAction,
+    #[serde(default)]
+    pub allowed_hosts: Vec<String>
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CookieSameSite {
+    Strict,
+    Lax,
+    None
+}
+
+#[derive(Deserialize)]
+pub struct CookiePolicy {
+    #[serde(default)]
+    pub force_secure: bool,
+    pub default_samesite: Option<CookieSameSite>,
+    pub max_length: Option<usize>,
+    #[serde(default)]
+    pub exemptions: Vec<String>
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Normalization {
+    pub merge_slashes: bool,
+    pub lowercase_host: bool,
+    pub strip_default_port: bool,
+    pub redirect: bool
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum EtagMode {
+    #[default]
+    Content,
+    Metadata
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionOverflow {
+    Wait,
+    #[default]
+    Reject,
+    Close
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Timezone {
+    #[default]
+    Utc,
+    Local
+}
+
+#[derive(Deserialize)]
+pub struct TimeFormat {
+    #[serde(default = "TimeFormat::default_format")]
+    pub format: String,
+    #[serde(default)]
+    pub timezone: Timezone
+}
+
+#[derive(Deserialize)]
+pub struct Dns {
+    #[serde(default)]
+    pub overrides: HashMap<String, Vec<IpAddr>>,
+    #[serde(default = "Dns::default_refresh_interval")]
+    pub refresh_interval: u64,
+    #[serde(default = "Dns::default_negative_ttl")]
+    pub negative_ttl: u64,
+    #[serde(default = "Dns::default_happy_eyeballs_delay")]
+    pub happy_eyeballs_delay: u64,
+    #[serde(default)]
+    pub prefer_ipv4: bool
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum UnexpectedBodyPolicy {
+    #[default]
+    Discard,
+    Reject
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RateLimitKey {
+    Ip,
+    Header(String),
+    Cookie(String),
+    #[serde(rename = "jwt_subject")]
+    JwtSubject
+}
+
+#[derive(Deserialize, Default)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum StorageBackend {
+    #[default]
+    Memory,
+    File {
+        path: String
+    },
+    Redis {
+        address: String,
+        password: Option<String>,
+        #[serde(default)]
+        database: u8
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Cluster {
+    pub address: String,
+    pub password: Option<String>,
+    #[serde(default = "Cluster::default_channel")]
+    pub channel: String,
+    #[serde(default = "Cluster::default_retry_interval")]
+    pub retry_interval: u64
+}
+
+#[derive(Deserialize)]
+pub struct RateLimit {
+    pub key: RateLimitKey,
+    pub capacity: u32,
+    pub refill_per_second: f64
+}
+
+#[cfg(feature = "cgi")]
+#[derive(Deserialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CGIBackend {
+    #[default]
+    CGI,
+    FastCGI,
+    SCGI
+}
+
+#[cfg(feature = "cgi")]
+#[derive(Deserialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CGIRuleAction {
+    #[default]
+    Pass,
+    Deny
+}
+
+#[cfg(feature = "cgi")]
+#[derive(Deserialize)]
+pub struct CGIRuleOptions {
+    #[serde(default)]
+    pub action: CGIRuleAction,
+    pub interpreter: Option<String>,
+    #[serde(default)]
+    pub backend: CGIBackend,
+    pub address: Option<String>,
+    pub timeout: Option<u64>,
+    pub cpu_limit: Option<u64>,
+    pub memory_limit: Option<u64>,
+    #[serde(default = "CGIRuleOptions::default_execute_on_head")]
+    pub execute_on_head: bool,
+    pub fallback: Option<String>,
+    #[serde(default = "CGIRuleOptions::default_fallback_retry")]
+    pub fallback_retry: u64,
+    #[serde(default)]
+    pub env: HashMap<String, String>
+}
+
+#[cfg(feature = "cgi")]
+impl CGIRuleOptions {
+    const fn default_execute_on_head() -> bool {
+        true
+    }
+
+    const fn default_fallback_retry() -> u64 {
+        5
+    }
+}
+
+#[cfg(feature = "cgi")]
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum CGIRule {
+    Enabled(bool),
+    Options(CGIRuleOptions)
 }
 
 #[cfg(feature = "cgi")]
@@ -38,239 +697,1814 @@ pub struct Https {
 pub struct CGI {
     pub enabled: bool,
     pub cgi_server: String,
-    cgi_rules: HashMap<String, bool>
+    pub cgi_rules: HashMap<String, CGIRule>
 }
 
 #[derive(Deserialize)]
 pub struct Config {
     #[serde(default = "Config::default_max_content_length")]
     pub max_content_length: usize,
+    #[serde(default = "Config::default_max_decompression_ratio")]
+    pub max_decompression_ratio: u64,
+    pub accepted_media_types: Option<Vec<String>>,
+    #[serde(default)]
+    pub unexpected_body_policy: UnexpectedBodyPolicy,
+    pub upload_spool_dir: Option<String>,
+    #[serde(default = "Config::default_upload_spool_threshold")]
+    pub upload_spool_threshold: usize,
+    pub upload_spool_quota: Option<u64>,
+    pub quarantine: Option<Quarantine>,
+    pub journal: Option<Journal>,
+    pub access_log: Option<AccessLog>,
     pub global_response_headers: Option<HashMap<String, String>>,
+    pub cookie_policy: Option<CookiePolicy>,
+    pub redirect_policy: Option<RedirectPolicy>,
+    pub method_override: Option<MethodOverride>,
+    pub trusted_proxies: Option<Vec<String>>,
+    pub prefilters: Option<Prefilters>,
+    pub upstreams: Option<HashMap<String, Upstream>>,
+    pub proxy_rules: Option<Vec<ProxyRule>>,
+    pub auth: Option<Vec<AuthRule>>,
+    pub jwt: Option<Jwt>,
+    pub s3_origins: Option<Vec<S3Origin>>,
     pub access_control: Option<AccessControl>,
+    #[serde(default)]
+    pub sensitive_paths: SensitivePaths,
     pub bind_host: String,
+    pub bind_host_v6: Option<String>,
     pub bind_port: u16,
+    #[serde(default)]
+    pub socket: SocketOptions,
+    pub listeners: Option<Vec<Listener>>,
     pub endpoints: Option<Vec<String>>,
+    pub streaming_endpoints: Option<Vec<String>>,
+    #[cfg(feature = "json")]
+    pub json_endpoints: Option<Vec<String>>,
     pub endpoints_library: Option<String>,
+    pub endpoint_libraries: Option<Vec<EndpointLibrary>>,
+    pub virtual_hosts: Option<Vec<VirtualHost>>,
+    #[serde(default)]
+    pub problem_details: bool,
     #[serde(default = "Config::default_cache_max_age")]
     pub cache_max_age: u64,
+    pub cache_control_rules: Option<Vec<CacheControlRule>>,
+    pub security_headers: Option<Vec<SecurityHeaders>>,
+    #[serde(default = "Config::default_file_handle_limit")]
+    pub file_handle_limit: usize,
+    #[serde(default = "Config::default_file_cache_size")]
+    pub file_cache_size: usize,
+    #[serde(default = "Config::default_zero_copy_threshold")]
+    pub zero_copy_threshold: u64,
+    #[serde(default)]
+    pub etag_mode: EtagMode,
+    #[serde(default = "Config::default_etag_cache_size")]
+    pub etag_cache_size: usize,
+    pub etag_store: Option<String>,
+    #[serde(default = "Config::default_etag_store_interval")]
+    pub etag_store_interval: u64,
     pub encoding: Option<Encoding>,
+    pub sub_filters: Option<Vec<SubFilter>>,
+    #[serde(default)]
+    pub normalization: Normalization,
+    #[serde(default)]
+    pub time_format: TimeFormat,
     pub document_root: String,
+    #[serde(default)]
+    pub watch_document_root: bool,
+    #[serde(default)]
+    pub follow_external_symlinks: bool,
     pub server_root: String,
-    index_of_page_rules: Option<HashMap<String, bool>>,
+    pub index_of_page_rules: Option<HashMap<String, bool>>,
+    #[serde(default = "Config::default_index_of_page_size")]
+    pub index_of_page_size: usize,
     pub indices: Vec<String>,
     pub https: Option<Https>,
+    pub acme: Option<Acme>,
     #[cfg(target_family = "unix")]
     #[serde(default)]
     pub chroot: bool,
+    pub user: Option<String>,
+    pub group: Option<String>,
+    #[serde(default)]
+    pub daemon: bool,
+    pub pidfile: Option<String>,
+    pub error_log: Option<String>,
     #[serde(default)]
     pub enable_trace: bool,
+    #[serde(default = "Config::default_trace_redacted_headers")]
+    pub trace_redacted_headers: Vec<String>,
+    #[serde(default = "Config::default_trace_max_length")]
+    pub trace_max_length: usize,
     #[serde(default = "Config::default_server_header_state")]
     pub enable_server_header: bool,
     #[serde(default = "Config::default_request_timeout")]
     pub request_timeout: u64,
+    #[serde(default = "Config::default_shutdown_grace_period")]
+    pub shutdown_grace_period: u64,
+    pub bandwidth_limit: Option<u64>,
+    pub bandwidth_rules: Option<Vec<BandwidthRule>>,
+    pub max_connections: Option<usize>,
+    pub max_connections_per_ip: Option<usize>,
+    pub fairness: Option<Fairness>,
+    #[serde(default)]
+    pub connection_overflow: ConnectionOverflow,
+    #[serde(default = "Config::default_connection_retry_after")]
+    pub connection_retry_after: u64,
     #[serde(default)]
     pub be_verbose: bool,
+    #[serde(default)]
+    pub logging: Logging,
+    pub debug: Option<Debug>,
+    pub connect: Option<ConnectTunnel>,
+    pub dns: Option<Dns>,
+    pub rate_limits: Option<Vec<RateLimit>>,
+    #[serde(default)]
+    pub storage: StorageBackend,
+    pub cluster: Option<Cluster>,
+    #[serde(default = "Config::default_ban_duration")]
+    pub ban_duration: u64,
+    pub endpoint_concurrency: Option<HashMap<String, usize>>,
+    #[serde(default = "Config::default_endpoint_queue_timeout")]
+    pub endpoint_queue_timeout: u64,
+    #[serde(default = "Config::default_endpoint_pool_size")]
+    pub endpoint_pool_size: usize,
+    pub noise_paths: Option<NoisePaths>,
+    pub metrics_endpoint: Option<String>,
+    pub metrics_locations: Option<Vec<String>>,
+    pub reload_endpoint: Option<String>,
+    pub admin_endpoint: Option<String>,
+    pub integrity_endpoint: Option<String>,
     #[cfg(feature = "cgi")]
     pub cgi: Option<CGI>
 }
 
 impl Config {
-    pub fn new() -> Self {
-        let config_path = env::var("DRAIN_CONFIG");
+    pub fn new() -> Option<Self> {
+        if let Some(Command::Dev {dir}) = &CLI.command {
+            return Config::dev(dir);
+        }
+
+        let config_path = match &CLI.config {
+            Some(config) => Ok(config.clone()),
+            None => env::var("DRAIN_CONFIG")
+        };
         let config_file;
 
         match &config_path {
             Ok(c_f) => {
                 config_file = File::open(c_f);
-                println!("Config path: {c_f}");
+                info!("Config path: {c_f}");
+            }
+            Err(e) => {
+                error!("A critical server config file wasn't found.\n\
+                        Error information:\n{}", e);
+                return None;
+            }
+        }
+
+        let mut content: Vec<u8> = Vec::new();
+        match config_file {
+            Ok(mut f) => {
+                if let Err(e) = f.read_to_end(&mut content) {
+                    error!("An error occurred after an attempt to read from a file: {:?}.\n\
+                            Error information:\n\
+                            {e}\n", f);
+                    return None;
+                }
+            },
+            Err(e) => {
+                error!("A critical server config file wasn't found.\n\
+                        Error information:\n\
+                        {e}");
+                return None;
+            }
+        }
+
+        let parsed = match &config_path {
+            Ok(c_f) => parse_config_value(Path::new(c_f), content)
+                .and_then(|value| resolve_config_value(Path::new(c_f), value, &mut Vec::new()))
+                .and_then(|value| serde_path_to_error::deserialize::<_, Config>(value).map_err(|e| match e.path().to_string().as_str() {
+                    "." => e.inner().to_string(),
+                    path => format!("{path}: {}", e.inner())
+                })),
+            Err(e) => Err(e.to_string())
+        };
+
+        let mut config: Config = match parsed {
+            Ok(config) => config,
+            Err(e) => {
+                error!("A critical server config file is malformed.\n\
+                        Error information:\n\
+                        {e}");
+                return None;
+            }
+        };
+
+        CLI.apply_overrides(&mut config);
+
+        let problems = config.validate();
+        if !problems.is_empty() {
+            error!("A critical server config file is malformed.\n\
+                    Error information:\n\
+                    {} problem(s) found:\n{}", problems.len(),
+                      problems.iter().map(|problem| format!("  - {problem}")).collect::<Vec<String>>().join("\n"));
+            return None;
+        }
+
+        Some(config)
+    }
+
+    fn dev(dir: &String) -> Option<Self> {
+        let document_root = match std::fs::canonicalize(dir) {
+            Ok(document_root) => document_root.to_string_lossy().into_owned(),
+            Err(e) => {
+                error!("The directory to serve wasn't found.\n\
+                        Error information:\n{e}");
+                return None;
+            }
+        };
+
+        let value = serde_json::json!({
+            "bind_host": "127.0.0.1",
+            "bind_port": 8080,
+            "document_root": document_root,
+            "server_root": document_root,
+            "indices": ["index.html", "index.htm"],
+            "index_of_page_rules": {"**": true},
+            "cache_control_rules": [{"pattern": "**", "no_store": true}],
+            "etag_mode": "metadata",
+            "sub_filters": [{"search": "</body>", "replace": format!("{RELOAD_SCRIPT}</body>"), "once": true}]
+        });
+
+        let mut config: Config = match serde_json::from_value(value) {
+            Ok(config) => config,
+            Err(e) => {
+                error!("The development config couldn't be built.\n\
+                        Error information:\n{e}");
+                return None;
+            }
+        };
+
+        CLI.apply_overrides(&mut config);
+        Some(config)
+    }
+
+    fn validate(&self) -> Vec<String> {
+        let mut problems: Vec<String> = Vec::new();
+
+        if let Some(access_control) = &self.access_control {
+
+            for (k, v) in &access_control.list {
+                if !v.eq("allow") && !v.eq("deny") {
+                    problems.push(format!("access_control.list.\"{k}\": invalid word {v}, should be either \"allow\" or \"deny\""));
+                }
+            }
+
+            problems.extend(access_control.rule_problems("access_control"));
+        }
+
+        let https_enabled = matches!(&self.https, Some(https) if https.enabled);
+        for (i, virtual_host) in self.virtual_hosts.iter().flatten().enumerate() {
+            if virtual_host.hosts.is_empty() && !virtual_host.default {
+                problems.push(format!("virtual_hosts[{i}].hosts: must list at least one host unless the entry is the default"));
+            }
+
+            for (j, host) in virtual_host.hosts.iter().enumerate() {
+                if let Err(e) = Pattern::new(host) {
+                    problems.push(format!("virtual_hosts[{i}].hosts[{j}]: invalid pattern {host}: {e}"));
+                }
+            }
+
+            if let Some(access_control) = &virtual_host.access_control {
+
+                for (k, v) in &access_control.list {
+                    if !v.eq("allow") && !v.eq("deny") {
+                        problems.push(format!("virtual_hosts[{i}].access_control.list.\"{k}\": invalid word {v}, should be either \"allow\" or \"deny\""));
+                    }
+                }
+
+                problems.extend(access_control.rule_problems(&format!("virtual_hosts[{i}].access_control")));
+            }
+
+            match (&virtual_host.ssl_certificate_file, &virtual_host.ssl_private_key_file) {
+                (Some(_), Some(_)) if !https_enabled => {
+                    problems.push(format!("virtual_hosts[{i}].ssl_certificate_file: requires https to be enabled"));
+                },
+                (Some(_), Some(_)) if virtual_host.hosts.is_empty() => {
+                    problems.push(format!("virtual_hosts[{i}].ssl_certificate_file: requires hosts to select the certificate by"));
+                },
+                (Some(ssl_certificate_file), Some(ssl_private_key_file)) if !INITIAL_CONFIG_LOADED.load(Ordering::Relaxed) => {
+                    for (field, file) in [("ssl_certificate_file", ssl_certificate_file), ("ssl_private_key_file", ssl_private_key_file)] {
+                        if !Path::new(file).is_file() {
+                            problems.push(format!("virtual_hosts[{i}].{field}: {file} doesn't exist or isn't a file"));
+                        }
+                    }
+                },
+                (Some(_), None) => problems.push(format!("virtual_hosts[{i}].ssl_private_key_file: has to be set together with ssl_certificate_file")),
+                (None, Some(_)) => problems.push(format!("virtual_hosts[{i}].ssl_certificate_file: has to be set together with ssl_private_key_file")),
+                _ => {}
+            }
+
+            #[cfg(target_family = "unix")]
+            if self.chroot && virtual_host.document_root.as_ref().is_some_and(|root| !root.starts_with(self.document_root.trim_end_matches('/'))) {
+                problems.push(format!("virtual_hosts[{i}].document_root: must be inside document_root, when chroot is enabled"));
+            }
+
+            if virtual_host.endpoints.is_some() && virtual_host.endpoints_library.is_none() {
+                problems.push(format!("virtual_hosts[{i}].endpoints: requires endpoints_library to be set"));
+            }
+        }
+
+        if self.virtual_hosts.iter().flatten().filter(|virtual_host| virtual_host.default).count() > 1 {
+            problems.push(String::from("virtual_hosts: only one entry can be the default"));
+        }
+
+        for (i, trusted_proxy) in self.trusted_proxies.iter().flatten().enumerate() {
+            if Cidr::parse(trusted_proxy).is_none() {
+                problems.push(format!("trusted_proxies[{i}]: invalid address or CIDR range {trusted_proxy}"));
+            }
+        }
+
+        if self.index_of_page_size == 0 {
+            problems.push(String::from("index_of_page_size: must be greater than 0"));
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "fuchsia", target_os = "macos", target_os = "ios")))]
+        if self.socket.interface.is_some() {
+            problems.push(String::from("socket.interface: isn't supported on this OS"));
+        }
+
+        #[cfg(not(target_family = "unix"))]
+        for (field, set) in [("user", self.user.is_some()), ("group", self.group.is_some()), ("daemon", self.daemon), ("pidfile", self.pidfile.is_some())] {
+            if set {
+                problems.push(format!("{field}: isn't supported on this OS"));
+            }
+        }
+
+        if self.endpoints.is_some() && self.endpoints_library.is_none() {
+            problems.push(String::from("endpoints: endpoints_library has to be set in order to serve endpoints"));
+        }
+
+        if self.streaming_endpoints.is_some() && self.endpoints_library.is_none() {
+            problems.push(String::from("streaming_endpoints: endpoints_library has to be set in order to serve endpoints"));
+        }
+
+        #[cfg(feature = "json")]
+        if self.json_endpoints.is_some() && self.endpoints_library.is_none() {
+            problems.push(String::from("json_endpoints: endpoints_library has to be set in order to serve endpoints"));
+        }
+
+        if let Some(https) = self.https.as_ref().filter(|https| https.enabled) {
+            if let Some(hsts) = https.hsts.as_ref().filter(|hsts| hsts.preload) {
+                if !hsts.include_subdomains || hsts.max_age < 31536000 {
+                    problems.push(String::from("https.hsts.preload: requires include_subdomains and max_age of at least 31536000"));
+                }
+            }
+
+            if !INITIAL_CONFIG_LOADED.load(Ordering::Relaxed) {
+                for (field, file) in [("ssl_certificate_file", &https.ssl_certificate_file), ("ssl_private_key_file", &https.ssl_private_key_file)] {
+                    if !Path::new(file).is_file() {
+                        problems.push(format!("https.{field}: {file} doesn't exist or isn't a file"));
+                    }
+                }
+
+                if let Some(client_certificates) = &https.client_certificates {
+                    if !Path::new(&client_certificates.ca_file).is_file() {
+                        problems.push(format!("https.client_certificates.ca_file: {} doesn't exist or isn't a file", client_certificates.ca_file));
+                    }
+                }
+            }
+
+            if https.ocsp_stapling && self.chroot {
+                problems.push(String::from("https.ocsp_stapling: isn't supported together with chroot, as the certificates are read again to refresh the responses"));
+            }
+
+            if https.session_cache_size > i32::MAX as u32 {
+                problems.push(format!("https.session_cache_size: must be at most {}", i32::MAX));
+            }
+
+            for path in https.client_certificates.iter().flat_map(|client_certificates| client_certificates.paths.keys()) {
+                if !path.starts_with('/') {
+                    problems.push(format!("https.client_certificates.paths.{path}: must start with /"));
+                }
+            }
+        }
+
+        if let Some(acme) = &self.acme {
+            if !https_enabled {
+                problems.push(String::from("acme: requires https to be enabled"));
+            }
+
+            if acme.domains.is_empty() {
+                problems.push(String::from("acme.domains: must list at least one domain"));
+            }
+
+            for (i, domain) in acme.domains.iter().enumerate() {
+                if domain.contains('*') {
+                    problems.push(format!("acme.domains[{i}]: wildcard domains can't be validated with HTTP-01 challenges"));
+                }
+            }
+
+            if acme.renew_before_days == 0 || acme.renew_before_days > 365 {
+                problems.push(format!("acme.renew_before_days: {} isn't between 1 and 365", acme.renew_before_days));
+            }
+
+            if UpstreamServer::parse(&acme.directory).is_none() {
+                problems.push(format!("acme.directory: {} isn't a valid URL", acme.directory));
+            }
+
+            #[cfg(target_family = "unix")]
+            if self.chroot {
+                problems.push(String::from("acme: isn't supported together with chroot, as the certificates are stored under server_root"));
+            }
+        }
+
+        for (i, listener) in self.listeners.iter().flatten().enumerate() {
+            if listener.tls && !https_enabled {
+                problems.push(format!("listeners[{i}].tls: requires https to be enabled"));
+            }
+
+            #[cfg(target_family = "unix")]
+            if self.chroot && listener.document_root.as_ref().is_some_and(|root| !root.starts_with(self.document_root.trim_end_matches('/'))) {
+                problems.push(format!("listeners[{i}].document_root: must be inside document_root, when chroot is enabled"));
+            }
+        }
+
+        let mut bound: Vec<(String, &String, u16)> = Vec::new();
+        for address in [Some(&self.bind_host), self.bind_host_v6.as_ref()].into_iter().flatten() {
+            bound.push((String::from("bind_host"), address, self.bind_port));
+            if let Some(https) = self.https.as_ref().filter(|https| https.enabled) {
+                bound.push((String::from("https"), address, https.bind_port));
+            }
+        }
+        for (i, listener) in self.listeners.iter().flatten().enumerate() {
+            bound.push((format!("listeners[{i}]"), &listener.address, listener.port));
+        }
+
+        for (i, (field, address, port)) in bound.iter().enumerate() {
+            let overlapping = bound[..i].iter().find(|(_, other_address, other_port)| {
+                if port != other_port {
+                    return false;
+                }
+
+                match (address.parse::<IpAddr>(), other_address.parse::<IpAddr>()) {
+                    (Ok(a), Ok(b)) => a == b || (!self.socket.reuseport && a.is_ipv4() == b.is_ipv4() && (a.is_unspecified() || b.is_unspecified())),
+                    _ => address.eq_ignore_ascii_case(other_address)
+                }
+            });
+
+            if let Some((other_field, other_address, _)) = overlapping {
+                problems.push(format!("{field}: {address}:{port} overlaps with {other_address}:{port} of {other_field}"));
+            }
+        }
+
+        if let Some(cluster) = &self.cluster {
+            if cluster.channel.is_empty() {
+                problems.push(String::from("cluster.channel: can't be empty"));
+            }
+
+            if cluster.retry_interval == 0 {
+                problems.push(String::from("cluster.retry_interval: must be greater than 0"));
+            }
+        }
+
+        if self.ban_duration == 0 {
+            problems.push(String::from("ban_duration: must be greater than 0"));
+        }
+
+        if self.socket.acceptors != 1 && !self.socket.reuseport {
+            problems.push(String::from("socket.acceptors: values other than 1 require socket.reuseport to be enabled"));
+        }
+
+        if self.bandwidth_limit == Some(0) {
+            problems.push(String::from("bandwidth_limit: must be greater than 0"));
+        }
+
+        for (i, rule) in self.bandwidth_rules.iter().flatten().enumerate() {
+            if rule.limit == 0 {
+                problems.push(format!("bandwidth_rules[{i}].limit: must be greater than 0"));
+            }
+        }
+
+        for (name, upstream) in self.upstreams.iter().flatten() {
+            if upstream.servers.is_empty() {
+                problems.push(format!("upstreams.{name}.servers: can't be empty"));
+            }
+
+            for (i, server) in upstream.servers.iter().enumerate() {
+                if UpstreamServer::parse(server).is_none() {
+                    problems.push(format!("upstreams.{name}.servers[{i}]: {server} isn't an http:// or https:// URL"));
+                }
+            }
+        }
+
+        if let Some(journal) = &self.journal {
+            if journal.max_size == 0 {
+                problems.push(String::from("journal.max_size: must be greater than 0"));
+            }
+
+            for (i, method) in journal.methods.iter().enumerate() {
+                if !["POST", "PUT", "DELETE", "PATCH"].contains(&&*method.to_uppercase()) {
+                    problems.push(format!("journal.methods[{i}]: {method} isn't one of POST, PUT, DELETE and PATCH"));
+                }
+            }
+        }
+
+        if let Some(level) = &self.logging.level {
+            if level.parse::<LevelFilter>().is_err() {
+                problems.push(format!("logging.level: {level} isn't one of off, error, warn, info, debug and trace"));
+            }
+        }
+
+        for (module, level) in &self.logging.filters {
+            if level.parse::<LevelFilter>().is_err() {
+                problems.push(format!("logging.filters.\"{module}\": {level} isn't one of off, error, warn, info, debug and trace"));
+            }
+        }
+
+        if let Some(access_log) = &self.access_log {
+            if access_log.output.is_empty() {
+                problems.push(String::from("access_log.output: must be \"stdout\", \"syslog\", \"journald\" or a file path"));
+            }
+            #[cfg(not(target_family = "unix"))]
+            if ["syslog", "journald"].contains(&&*access_log.output) {
+                problems.push(format!("access_log.output: {} is available only in UNIX-like operating systems", access_log.output));
+            }
+            problems.extend(access_log.rotation.problems("access_log.rotation"));
+        }
+
+        if self.logging.output.is_empty() {
+            problems.push(String::from("logging.output: must be \"console\", \"syslog\", \"journald\" or a file path"));
+        }
+        #[cfg(not(target_family = "unix"))]
+        if ["syslog", "journald"].contains(&&*self.logging.output) {
+            problems.push(format!("logging.output: {} is available only in UNIX-like operating systems", self.logging.output));
+        }
+        problems.extend(self.logging.rotation.problems("logging.rotation"));
+
+        for (i, s3_origin) in self.s3_origins.iter().flatten().enumerate() {
+            if UpstreamServer::parse(&s3_origin.endpoint).is_none() {
+                problems.push(format!("s3_origins[{i}].endpoint: {} isn't an http:// or https:// URL", s3_origin.endpoint));
+            }
+
+            if s3_origin.bucket.is_empty() {
+                problems.push(format!("s3_origins[{i}].bucket: can't be empty"));
+            }
+        }
+
+        for (i, proxy_rule) in self.proxy_rules.iter().flatten().enumerate() {
+            if !self.upstreams.as_ref().is_some_and(|upstreams| upstreams.contains_key(&proxy_rule.upstream)) {
+                problems.push(format!("proxy_rules[{i}].upstream: {} isn't defined in upstreams", proxy_rule.upstream));
+            }
+        }
+
+        for (i, auth_rule) in self.auth.iter().flatten().enumerate() {
+            if let Err(e) = Pattern::new(&auth_rule.pattern) {
+                problems.push(format!("auth[{i}].pattern: invalid pattern {}: {e}", auth_rule.pattern));
+            }
+
+            if auth_rule.realm.contains('"') || auth_rule.realm.contains(':') {
+                problems.push(format!("auth[{i}].realm: can't contain quotes or colons"));
+            }
+
+            let credentials_file = if self.chroot {format!("{}/{}", self.document_root.trim_end_matches('/'), auth_rule.credentials_file.trim_start_matches('/'))} else {auth_rule.credentials_file.clone()};
+            if !INITIAL_CONFIG_LOADED.load(Ordering::Relaxed) && !Path::new(&credentials_file).is_file() {
+                problems.push(format!("auth[{i}].credentials_file: {} doesn't exist or isn't a file", auth_rule.credentials_file));
+            }
+        }
+
+        if let Some(jwt) = &self.jwt {
+            if jwt.paths.is_empty() {
+                problems.push(String::from("jwt.paths: must list at least one path"));
+            }
+
+            for (i, path) in jwt.paths.iter().enumerate() {
+                if !path.starts_with('/') {
+                    problems.push(format!("jwt.paths[{i}]: must start with /"));
+                }
+            }
+
+            if [jwt.jwks_url.is_some(), jwt.key_file.is_some(), jwt.secret_file.is_some()].iter().filter(|set| **set).count() != 1 {
+                problems.push(String::from("jwt: exactly one of jwks_url, key_file and secret_file must be set"));
+            }
+
+            if let Some(jwks_url) = &jwt.jwks_url {
+                if UpstreamServer::parse(jwks_url).is_none() {
+                    problems.push(format!("jwt.jwks_url: {jwks_url} isn't an http:// or https:// URL"));
+                }
+            }
+
+            for (field, file) in [("key_file", &jwt.key_file), ("secret_file", &jwt.secret_file)] {
+                let Some(file) = file else {
+                    continue;
+                };
+
+                let path = if self.chroot {format!("{}/{}", self.document_root.trim_end_matches('/'), file.trim_start_matches('/'))} else {file.clone()};
+                if !INITIAL_CONFIG_LOADED.load(Ordering::Relaxed) && !Path::new(&path).is_file() {
+                    problems.push(format!("jwt.{field}: {file} doesn't exist or isn't a file"));
+                }
+            }
+        }
+
+        if self.max_connections == Some(0) {
+            problems.push(String::from("max_connections: must be greater than 0"));
+        }
+
+        if self.max_connections_per_ip == Some(0) {
+            problems.push(String::from("max_connections_per_ip: must be greater than 0"));
+        }
+
+        if let Some(prefilters) = &self.prefilters {
+            for (field, ranges) in [("deny_ips", &prefilters.deny_ips), ("allow_ips", &prefilters.allow_ips)] {
+                for (i, cidr) in ranges.iter().flatten().enumerate() {
+                    if Cidr::parse(cidr).is_none() {
+                        problems.push(format!("prefilters.{field}[{i}]: invalid address or CIDR range {cidr}"));
+                    }
+                }
+            }
+
+            for (i, pattern) in prefilters.blocked_server_names.iter().enumerate() {
+                if let Err(e) = Pattern::new(pattern) {
+                    problems.push(format!("prefilters.blocked_server_names[{i}]: invalid pattern {pattern}: {e}"));
+                }
+            }
+        }
+
+        if let Some(fairness) = &self.fairness {
+            if !(fairness.busy_threshold > 0f64 && fairness.busy_threshold <= 1f64) {
+                problems.push(format!("fairness.busy_threshold: {} has to be greater than 0 and at most 1", fairness.busy_threshold));
+            }
+
+            for (cidr, weight) in &fairness.weights {
+                if Cidr::parse(cidr).is_none() {
+                    problems.push(format!("fairness.weights.\"{cidr}\": invalid address or CIDR range"));
+                }
+
+                if *weight == 0 {
+                    problems.push(format!("fairness.weights.\"{cidr}\": weight must be greater than 0"));
+                }
+            }
+        }
+
+        for (i, rate_limit) in self.rate_limits.iter().flatten().enumerate() {
+            if rate_limit.capacity == 0 {
+                problems.push(format!("rate_limits[{i}].capacity: must be greater than 0"));
+            }
+
+            if !(rate_limit.refill_per_second > 0f64) {
+                problems.push(format!("rate_limits[{i}].refill_per_second: must be greater than 0"));
+            }
+        }
+
+        if StrftimeItems::new(&self.time_format.format).any(|item| matches!(item, Item::Error)) {
+            problems.push(format!("time_format.format: invalid format {}", self.time_format.format));
+        }
+
+        for (field, patterns) in [("blocked_names", &self.sensitive_paths.blocked_names), ("allowed", &self.sensitive_paths.allowed)] {
+            for (i, pattern) in patterns.iter().enumerate() {
+                if let Err(e) = Pattern::new(pattern) {
+                    problems.push(format!("sensitive_paths.{field}[{i}]: invalid pattern {pattern}: {e}"));
+                }
+            }
+        }
+
+        for (i, rule) in self.security_headers.iter().flatten().enumerate() {
+            if let Err(e) = Pattern::new(&rule.pattern) {
+                problems.push(format!("security_headers[{i}].pattern: invalid pattern {}: {e}", rule.pattern));
+            }
+
+            if let Some(frame_options) = &rule.frame_options {
+                if !frame_options.eq_ignore_ascii_case("DENY") && !frame_options.eq_ignore_ascii_case("SAMEORIGIN") {
+                    problems.push(format!("security_headers[{i}].frame_options: invalid value {frame_options}, should be either \"DENY\" or \"SAMEORIGIN\""));
+                }
+            }
+
+            if rule.content_security_policy_report_only && rule.content_security_policy.is_none() {
+                problems.push(format!("security_headers[{i}].content_security_policy_report_only: requires content_security_policy"));
+            }
+
+            for (field, value) in [("content_security_policy", &rule.content_security_policy), ("referrer_policy", &rule.referrer_policy), ("permissions_policy", &rule.permissions_policy)] {
+                if value.as_ref().is_some_and(|value| value.contains(['\r', '\n'])) {
+                    problems.push(format!("security_headers[{i}].{field}: can't contain line breaks"));
+                }
+            }
+        }
+
+        for (i, rule) in self.cache_control_rules.iter().flatten().enumerate() {
+            if let Err(e) = Pattern::new(&rule.pattern) {
+                problems.push(format!("cache_control_rules[{i}].pattern: invalid pattern {}: {e}", rule.pattern));
+            }
+        }
+
+        for (i, pattern) in self.noise_paths.iter().flat_map(|noise_paths| noise_paths.patterns.iter()).enumerate() {
+            if let Err(e) = Pattern::new(pattern) {
+                problems.push(format!("noise_paths.patterns[{i}]: invalid pattern {pattern}: {e}"));
+            }
+        }
+
+        for (i, location) in self.metrics_locations.iter().flatten().enumerate() {
+            if let Err(e) = Pattern::new(location) {
+                problems.push(format!("metrics_locations[{i}]: invalid pattern {location}: {e}"));
+            }
+        }
+
+        for (i, sub_filter) in self.sub_filters.iter().flatten().enumerate() {
+            if sub_filter.search.is_empty() {
+                problems.push(format!("sub_filters[{i}].search: can't be empty"));
+            } else if sub_filter.regex {
+                if let Err(e) = regex::bytes::Regex::new(&sub_filter.search) {
+                    problems.push(format!("sub_filters[{i}].search: invalid regular expression {}: {e}", sub_filter.search));
+                }
+            }
+        }
+
+        #[cfg(feature = "cgi")]
+        if let Some(cgi) = &self.cgi {
+            for (k, v) in &cgi.cgi_rules {
+                if let CGIRule::Options(CGIRuleOptions {backend: CGIBackend::FastCGI | CGIBackend::SCGI, address: None, ..}) = v {
+                    problems.push(format!("cgi.cgi_rules.\"{k}\".address: FastCGI and SCGI backends require an address"));
+                }
+            }
+        }
+
+        if let Some(encoding) = &self.encoding {
+            for (i, supported_encoding) in encoding.supported_encodings.iter().enumerate() {
+                if !supported_encoding.eq("gzip") && !supported_encoding.eq("br") {
+                    problems.push(format!("encoding.supported_encodings[{i}]: unknown encoding {supported_encoding}, should be either \"gzip\" or \"br\""));
+                }
+            }
+
+            if !encoding.supported_encodings.contains(&encoding.use_encoding) {
+                problems.push(format!("encoding.use_encoding: {} has to be present in supported_encodings", encoding.use_encoding));
+            }
+        }
+
+        problems
+    }
+
+    const fn default_max_content_length() -> usize {
+        1073741824
+    }
+
+    const fn default_max_decompression_ratio() -> u64 {
+        100
+    }
+
+    const fn default_upload_spool_threshold() -> usize {
+        1048576
+    }
+
+    const fn default_server_header_state() -> bool {
+        true
+    }
+
+    const fn default_file_handle_limit() -> usize {
+        if cfg!(target_os = "linux") { 1023 }
+        else if cfg!(target_os = "windows") { 16777215 }
+        else if cfg!(target_os = "macos") { 10239 }
+        else { 255 }
+    }
+
+    const fn default_file_cache_size() -> usize {
+        256
+    }
+
+    const fn default_zero_copy_threshold() -> u64 {
+        1048576
+    }
+
+    const fn default_etag_cache_size() -> usize {
+        4096
+    }
+
+    const fn default_etag_store_interval() -> u64 {
+        60
+    }
+
+    const fn default_cache_max_age() -> u64 {
+        3600
+    }
+
+    const fn default_request_timeout() -> u64 {
+        10
+    }
+
+    const fn default_shutdown_grace_period() -> u64 {
+        30
+    }
+
+    const fn default_endpoint_queue_timeout() -> u64 {
+        1000
+    }
+
+    const fn default_endpoint_pool_size() -> usize {
+        32
+    }
+
+    const fn default_index_of_page_size() -> usize {
+        1000
+    }
+
+    const fn default_connection_retry_after() -> u64 {
+        1
+    }
+
+    const fn default_ban_duration() -> u64 {
+        3600
+    }
+
+    const fn default_trace_max_length() -> usize {
+        8192
+    }
+
+    fn default_trace_redacted_headers() -> Vec<String> {
+        vec![String::from("cookie"), String::from("authorization"), String::from("proxy-authorization")]
+    }
+
+    pub fn get_supported_encodings(&self) -> Option<&Vec<String>> {
+        if let Some(encoding) = &self.encoding {
+            let supported_encodings = &encoding.supported_encodings;
+
+            if !supported_encodings.is_empty() {
+                return Some(supported_encodings);
+            }
+        }
+        None
+    }
+
+    pub fn cache_control(&self) -> String {
+        let resource = REQUEST_INFO.try_with(|info| info.borrow().resource.clone()).ok().flatten().unwrap_or_default();
+        let resource = resource.trim_start_matches('/');
+
+        self.cache_control_rules.iter().flatten()
+            .find(|rule| Pattern::new(&rule.pattern).is_ok_and(|p| p.matches(resource)))
+            .map(CacheControlRule::directives)
+            .unwrap_or_else(|| format!("max-age={}", self.cache_max_age))
+    }
+
+    pub fn security_headers(&self) -> Vec<(&'static str, String)> {
+        let resource = REQUEST_INFO.try_with(|info| info.borrow().resource.clone()).ok().flatten().unwrap_or_default();
+        let resource = resource.trim_start_matches('/');
+
+        self.security_headers.iter().flatten()
+            .find(|rule| Pattern::new(&rule.pattern).is_ok_and(|p| p.matches(resource)))
+            .map(SecurityHeaders::headers)
+            .unwrap_or_default()
+    }
+
+    pub fn metrics_location(&self, resource: &str) -> &str {
+        self.metrics_locations.iter().flatten()
+            .find(|p| Pattern::new(p).is_ok_and(|p| p.matches(resource)))
+            .map_or("other", |p| p.as_str())
+    }
+
+    pub fn auth_rule(&self, resource: &str) -> Option<&AuthRule> {
+        let resource = resource.trim_start_matches('/');
+
+        self.auth.iter().flatten()
+            .find(|rule| Pattern::new(&rule.pattern).is_ok_and(|p| p.matches(resource)))
+    }
+
+    pub fn upstream(&self, resource: &str) -> Option<(&String, &Upstream, ProxyCompression)> {
+        let resource = resource.trim_start_matches('/');
+
+        self.proxy_rules.iter().flatten()
+            .find(|rule| Pattern::new(&rule.pattern).is_ok_and(|p| p.matches(resource)))
+            .and_then(|rule| Some((&rule.upstream, self.upstreams.as_ref()?.get(&rule.upstream)?, rule.compression)))
+    }
+
+    pub fn s3_origin(&self, resource: &str) -> Option<(&S3Origin, String)> {
+        self.s3_origins.iter().flatten().find_map(|origin| {
+            let object = resource.strip_prefix(origin.location.trim_end_matches('/'))?;
+            if !object.is_empty() && !object.starts_with('/') {
+                return None;
+            }
+
+            Some((origin, String::from(object.trim_start_matches('/'))))
+        })
+    }
+
+    pub fn bandwidth_limit(&self) -> Option<u64> {
+        let resource = REQUEST_INFO.try_with(|info| info.borrow().resource.clone()).ok().flatten().unwrap_or_default();
+        let resource = resource.trim_start_matches('/');
+
+        self.bandwidth_rules.iter().flatten()
+            .find(|rule| Pattern::new(&rule.pattern).is_ok_and(|p| p.matches(resource)))
+            .map(|rule| rule.limit)
+            .or(self.bandwidth_limit)
+    }
+
+    pub fn get_response_encoding(&self, content: &Vec<u8>, type_guess: &String, type_: &String, headers: &HashMap<String, String>) -> Option<&String> {
+        self.response_encoding_for_length(content.len(), type_guess, type_, headers)
+    }
+
+    pub fn response_encoding_for_length(&self, content_length: usize, type_guess: &String, type_: &String, headers: &HashMap<String, String>) -> Option<&String> {
+        if let Some(encoding) = &self.encoding {
+            if content_length < encoding.min_length || encoding.is_skipped_mime_type(type_guess) {
+                return None;
+            }
+
+            if let Some(content_encoding) = headers.get("accept-encoding") {
+                let content_empty = content_length == 0;
+                let type_equals_text = type_.eq("text");
+                if !content_empty && type_equals_text {
+                    let encoding = &encoding.use_encoding;
+                    let accepted_encodings: Vec<String> = content_encoding.split(',').map(|x| String::from(x.trim())).collect();
+
+                    if encoding.eq("br") && accepted_encodings.contains(&*DCB_ENCODING) && negotiate_dictionary().is_some() {
+                        return Some(&*DCB_ENCODING);
+                    }
+
+                    if accepted_encodings.contains(&encoding) {
+                        return Some(encoding);
+                    }
+                    return None;
+                }
+                if !content_empty && !type_equals_text {
+                    if let Some(encoding_applicable_mime_types) = &encoding.encoding_applicable_mime_types {
+                        if encoding_applicable_mime_types.contains(type_guess) {
+                            let encoding = &encoding.use_encoding;
+                            let accepted_encodings: Vec<String> = content_encoding.split(',').map(|x| String::from(x.trim())).collect();
+
+                            if encoding.eq("br") && accepted_encodings.contains(&*DCB_ENCODING) && negotiate_dictionary().is_some() {
+                                return Some(&*DCB_ENCODING);
+                            }
+
+                            if accepted_encodings.contains(&encoding) {
+                                return Some(encoding);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    pub fn accepted_media_type(&self, content_type: &String) -> Option<String> {
+        let accepted_media_types = self.accepted_media_types.as_ref()?;
+
+        let media_type = content_type.split(';').next().unwrap_or("").trim().to_lowercase();
+        let general_type = media_type.split('/').next().unwrap_or("");
+
+        accepted_media_types.iter()
+            .any(|t| t.eq_ignore_ascii_case(&media_type) || t.eq_ignore_ascii_case(&format!("{general_type}/*")))
+            .then_some(media_type)
+    }
+
+    pub fn precompressed_encodings(&self, headers: &HashMap<String, String>) -> Vec<&String> {
+        let (Some(encoding), Some(accept_encoding)) = (&self.encoding, headers.get("accept-encoding")) else {
+            return Vec::new();
+        };
+
+        if !encoding.precompressed {
+            return Vec::new();
+        }
+
+        let accepted_encodings: Vec<&str> = accept_encoding.split(',').map(|x| x.split(';').next().unwrap_or("").trim()).collect();
+
+        std::iter::once(&encoding.use_encoding)
+            .chain(encoding.supported_encodings.iter().filter(|e| **e != encoding.use_encoding))
+            .filter(|e| matches!(e.as_str(), "gzip" | "br") && accepted_encodings.contains(&e.as_str()))
+            .collect()
+    }
+
+    pub fn is_precompressed_only(&self) -> bool {
+        matches!(&self.encoding, Some(encoding) if encoding.precompressed && encoding.precompressed_only)
+    }
+
+    pub fn is_streaming_endpoint(&self, resource: &String) -> bool {
+        matches!(&self.streaming_endpoints, Some(streaming_endpoints) if streaming_endpoints.contains(resource))
+    }
+
+    #[cfg(feature = "json")]
+    pub fn is_json_endpoint(&self, resource: &String) -> bool {
+        matches!(&self.json_endpoints, Some(json_endpoints) if json_endpoints.contains(resource))
+    }
+
+    pub fn is_connect_enabled(&self) -> bool {
+        matches!(&self.connect, Some(connect) if connect.enabled)
+    }
+
+    pub fn resolve_virtual_host(&self, host: Option<&String>) -> Option<usize> {
+        let virtual_hosts = self.virtual_hosts.as_ref()?;
+
+        host.and_then(|host| virtual_hosts.iter().position(|virtual_host| virtual_host.matches(host)))
+            .or_else(|| virtual_hosts.iter().position(|virtual_host| virtual_host.default))
+    }
+
+    pub fn virtual_host(&self) -> Option<&VirtualHost> {
+        let i = REQUEST_INFO.try_with(|info| info.borrow().virtual_host).ok().flatten()?;
+        self.virtual_hosts.as_ref()?.get(i)
+    }
+
+    pub fn access_control(&self) -> Option<&AccessControl> {
+        match self.virtual_host() {
+            Some(VirtualHost {access_control: Some(access_control), ..}) => Some(access_control),
+            _ => self.access_control.as_ref()
+        }
+    }
+
+    pub fn denial(&self, resource: &String, method: &str, remote_ip: &IpAddr) -> Option<Denial> {
+        let access_control = self.access_control();
+
+        if self.sensitive_paths.is_sensitive(resource) {
+            return Some(Denial {
+                rule: String::from("sensitive_paths"),
+                action: access_control.map_or(DenyAction::Status(404), |access_control| access_control.deny_action.clone())
+            });
+        }
+
+        access_control?.denial(resource, method, remote_ip)
+    }
+
+    pub fn should_display_index_of(&self, resource: &String) -> bool {
+        if let Some(index_of_rules) = &self.index_of_page_rules {
+            for (k, v) in index_of_rules {
+                if glob_matches(k, resource) {
+                    return *v;
+                }
+            }
+        }
+        false
+    }
+}
+
+fn glob_matches(pattern: &str, resource: &str) -> bool {
+    let options = MatchOptions {require_literal_separator: true, ..MatchOptions::new()};
+
+    Pattern::new(pattern.trim_start_matches('/'))
+        .is_ok_and(|pattern| pattern.matches_with(resource.trim_start_matches('/'), options))
+}
+
+fn parse_config_value(path: &Path, content: Vec<u8>) -> Result<serde_json::Value, String> {
+    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+
+    let value = match extension.as_deref() {
+        Some("toml") => String::from_utf8(content)
+            .map_err(|e| e.to_string())
+            .and_then(|content| toml::from_str(&content).map_err(|e| e.to_string())),
+        Some("yaml" | "yml") => serde_yaml::from_slice(&*content).map_err(|e| e.to_string()),
+        _ => serde_json::from_slice(&*content).map_err(|e| e.to_string())
+    };
+
+    value.map_err(|e| format!("{}: {e}", path.display()))
+}
+
+fn expand_env_vars(input: &str, path: &Path) -> Result<String, String> {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            output.push_str(&rest[..start - 1]);
+            output.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        output.push_str(&rest[..start]);
+        let Some(length) = rest[start..].find('}') else {
+            return Err(format!("{}: unterminated ${{ in \"{input}\"", path.display()));
+        };
+
+        let expression = &rest[start + 2..start + length];
+        let (name, default) = match expression.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (expression, None)
+        };
+
+        match (env::var(name), default) {
+            (Ok(value), _) => output.push_str(&value),
+            (Err(env::VarError::NotPresent), Some(default)) => output.push_str(default),
+            (Err(env::VarError::NotPresent), None) => {
+                return Err(format!("{}: environment variable {name} isn't set", path.display()));
+            },
+            (Err(e), _) => {
+                return Err(format!("{}: environment variable {name} can't be used: {e}", path.display()));
+            }
+        }
+        rest = &rest[start + length + 1..];
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+fn expand_env_vars_in_value(value: &mut serde_json::Value, path: &Path) -> Result<(), String> {
+    match value {
+        serde_json::Value::String(string) => *string = expand_env_vars(string, path)?,
+        serde_json::Value::Array(array) => {
+            for value in array {
+                expand_env_vars_in_value(value, path)?;
+            }
+        },
+        serde_json::Value::Object(object) => {
+            for value in object.values_mut() {
+                expand_env_vars_in_value(value, path)?;
+            }
+        },
+        _ => {}
+    }
+    Ok(())
+}
+
+fn merge_config_values(base: &mut serde_json::Value, overlay: serde_json::Value) {
+    match (base, overlay) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(base) => merge_config_values(base, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        },
+        (serde_json::Value::Array(base), serde_json::Value::Array(overlay)) => base.extend(overlay),
+        (base, overlay) => *base = overlay
+    }
+}
+
+fn resolve_config_value(path: &Path, mut value: serde_json::Value, including: &mut Vec<PathBuf>) -> Result<serde_json::Value, String> {
+    let canonical = path.canonicalize().map_err(|e| format!("{}: {e}", path.display()))?;
+    if including.contains(&canonical) {
+        return Err(format!("{} includes itself", path.display()));
+    }
+
+    expand_env_vars_in_value(&mut value, path)?;
+
+    let patterns = match value.as_object_mut().and_then(|object| object.remove("include")) {
+        None => Vec::new(),
+        Some(serde_json::Value::String(pattern)) => vec![pattern],
+        Some(serde_json::Value::Array(patterns)) => patterns.into_iter()
+            .map(|pattern| pattern.as_str().map(String::from))
+            .collect::<Option<Vec<String>>>()
+            .ok_or_else(|| format!("{}: include must be a string or a list of strings", path.display()))?,
+        Some(_) => return Err(format!("{}: include must be a string or a list of strings", path.display()))
+    };
+
+    including.push(canonical);
+    let directory = path.parent().unwrap_or(Path::new(""));
+
+    for pattern in patterns {
+        let pattern = directory.join(&pattern).to_string_lossy().into_owned();
+        let mut included: Vec<PathBuf> = glob(&pattern)
+            .map_err(|e| format!("{}: invalid include pattern {pattern}: {e}", path.display()))?
+            .filter_map(Result::ok)
+            .collect();
+        included.sort();
+
+        if included.is_empty() && Pattern::escape(&pattern).eq(&pattern) {
+            return Err(format!("{}: included file {pattern} doesn't exist", path.display()));
+        }
+
+        for included in included {
+            let content = read(&included).map_err(|e| format!("{}: {e}", included.display()))?;
+            let included_value = resolve_config_value(&included, parse_config_value(&included, content)?, including)?;
+            merge_config_values(&mut value, included_value);
+        }
+    }
+
+    including.pop();
+    Ok(value)
+}
+
+static INITIAL_CONFIG_LOADED: AtomicBool = AtomicBool::new(false);
+
+static CURRENT_CONFIG: LazyLock<RwLock<Arc<Config>>> = LazyLock::new(|| {
+    match Config::new() {
+        Some(config) => {
+            INITIAL_CONFIG_LOADED.store(true, Ordering::Relaxed);
+            RwLock::new(Arc::new(config))
+        },
+        None => panic!("Unrecoverable error occurred while trying to set up connection.")
+    }
+});
+
+pub struct CurrentConfig;
+
+impl CurrentConfig {
+    pub fn load(&self) -> Arc<Config> {
+        CURRENT_CONFIG.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+pub static CONFIG: CurrentConfig = CurrentConfig;
+
+pub fn reload_config() -> bool {
+    match Config::new() {
+        Some(config) => {
+            *CURRENT_CONFIG.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(config);
+            configure_logging();
+            info!("Config reloaded.");
+            true
+        },
+        None => {
+            error!("The new config couldn't be loaded, the previous one is still in use.");
+            false
+        }
+    }
+}
+
+impl Default for Logging {
+    fn default() -> Self {
+        Self {
+            level: None,
+            format: LogFormat::default(),
+            filters: HashMap::new(),
+            output: Self::default_output(),
+            rotation: Rotation::default()
+        }
+    }
+}
+
+impl Default for SensitivePaths {
+    fn default() -> Self {
+        Self {
+            block_dotfiles: Self::default_block_dotfiles(),
+            blocked_names: Self::default_blocked_names(),
+            allowed: Self::default_allowed()
+        }
+    }
+}
+
+impl SensitivePaths {
+    const fn default_block_dotfiles() -> bool {
+        true
+    }
+
+    fn default_blocked_names() -> Vec<String> {
+        vec![String::from("*~"), String::from("*.bak"), String::from("*.swp"), String::from("*.orig")]
+    }
+
+    fn default_allowed() -> Vec<String> {
+        vec![String::from(".well-known/**")]
+    }
+
+    pub fn is_sensitive(&self, resource: &str) -> bool {
+        let resource = urlencoding::decode(resource).map_or_else(|_| String::from(resource), |decoded| decoded.into_owned());
+        let resource = resource.trim_start_matches(['/', '\\']);
+        let names: Vec<&str> = resource.split(['/', '\\']).filter(|name| !name.is_empty()).collect();
+
+        if !names.contains(&"..") && self.allowed.iter().any(|pattern| Pattern::new(pattern).is_ok_and(|p| p.matches(resource))) {
+            return false;
+        }
+
+        names.iter().any(|name| {
+            let name = name.to_lowercase();
+            (self.block_dotfiles && name.starts_with('.'))
+                || self.blocked_names.iter().any(|pattern| Pattern::new(&pattern.to_lowercase()).is_ok_and(|p| p.matches(&name)))
+        })
+    }
+}
+
+impl SecurityHeaders {
+    fn default_content_type_options() -> Option<String> {
+        Some(String::from("nosniff"))
+    }
+
+    fn default_frame_options() -> Option<String> {
+        Some(String::from("DENY"))
+    }
+
+    fn default_referrer_policy() -> Option<String> {
+        Some(String::from("strict-origin-when-cross-origin"))
+    }
+
+    fn headers(&self) -> Vec<(&'static str, String)> {
+        let content_security_policy = if self.content_security_policy_report_only {"Content-Security-Policy-Report-Only"} else {"Content-Security-Policy"};
+
+        [
+            (content_security_policy, &self.content_security_policy),
+            ("X-Content-Type-Options", &self.content_type_options),
+            ("X-Frame-Options", &self.frame_options),
+            ("Referrer-Policy", &self.referrer_policy),
+            ("Permissions-Policy", &self.permissions_policy)
+        ].into_iter()
+            .filter_map(|(name, value)| Some((name, value.clone()?)))
+            .collect()
+    }
+}
+
+impl DenyAction {
+    const fn default_redirect_status() -> u16 {
+        302
+    }
+
+    const fn default_page_status() -> u16 {
+        403
+    }
+
+    pub fn status(&self) -> u16 {
+        match self {
+            DenyAction::Status(status) | DenyAction::Redirect {status, ..} | DenyAction::Page {status, ..} => *status
+        }
+    }
+
+    fn problems(&self, field: &str) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        match self {
+            DenyAction::Status(status) if !(400..600).contains(status) => {
+                problems.push(format!("{field}: invalid deny action {status}, should be a 4xx or 5xx status"));
+            },
+            DenyAction::Redirect {status, ..} if ![301, 302, 303, 307, 308].contains(status) => {
+                problems.push(format!("{field}.status: invalid redirect status {status}, should be one of 301, 302, 303, 307 or 308"));
+            },
+            DenyAction::Page {status, ..} if !(400..600).contains(status) => {
+                problems.push(format!("{field}.status: invalid status {status}, should be a 4xx or 5xx status"));
+            },
+            _ => {}
+        }
+
+        if let DenyAction::Page {page, ..} = self {
+            if page.split(['/', '\\']).any(|segment| segment.eq("..")) {
+                problems.push(format!("{field}.page: {page} can't point outside document_root"));
+            }
+        }
+
+        problems
+    }
+}
+
+impl AccessControl {
+    pub fn is_access_allowed(&self, resource: &String) -> bool {
+        self.denying_rule(resource).is_none()
+    }
+
+    fn default_policy() -> String {
+        String::from("allow")
+    }
+
+    pub fn denial(&self, resource: &String, method: &str, remote_ip: &IpAddr) -> Option<Denial> {
+        if let Some(pattern) = self.denying_rule(resource) {
+            return Some(Denial {rule: pattern.clone(), action: self.deny_action.clone()});
+        }
+
+        let matching_rule = self.rules.iter().enumerate().find(|(_, rule)| {
+            rule.pattern.as_ref().is_none_or(|pattern| Pattern::new(pattern).is_ok_and(|p| p.matches(resource)))
+                && rule.regex.as_ref().is_none_or(|regex| Regex::new(regex).is_ok_and(|r| r.is_match(resource)))
+                && rule.methods.as_ref().is_none_or(|methods| methods.iter().any(|m| m.eq_ignore_ascii_case(method) || (method.eq("HEAD") && m.eq_ignore_ascii_case("GET"))))
+                && rule.ips.as_ref().is_none_or(|ips| ips.iter().filter_map(|cidr| Cidr::parse(cidr)).any(|cidr| cidr.contains(remote_ip)))
+                && rule.tls_fingerprints.as_ref().is_none_or(|fingerprints| {
+                    REQUEST_INFO.try_with(|info| info.borrow().tls_fingerprint.as_ref().is_some_and(|f| f.matches(fingerprints))).unwrap_or(false)
+                })
+        });
+
+        match matching_rule {
+            Some((i, rule)) if rule.action.eq("deny") => Some(Denial {
+                rule: rule.name.clone().unwrap_or_else(|| format!("rules[{i}]")),
+                action: rule.deny_action.clone().unwrap_or_else(|| self.deny_action.clone())
+            }),
+            Some(_) => None,
+            None => self.default_policy.eq("deny").then(|| Denial {rule: String::from("default_policy"), action: self.deny_action.clone()})
+        }
+    }
+
+    fn rule_problems(&self, field: &str) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if !self.default_policy.eq("allow") && !self.default_policy.eq("deny") {
+            problems.push(format!("{field}.default_policy: invalid word {}, should be either \"allow\" or \"deny\"", self.default_policy));
+        }
+
+        problems.extend(self.deny_action.problems(&format!("{field}.deny_action")));
+
+        for (i, rule) in self.rules.iter().enumerate() {
+            if let Some(deny_action) = &rule.deny_action {
+                problems.extend(deny_action.problems(&format!("{field}.rules[{i}].deny_action")));
+            }
+
+            if !rule.action.eq("allow") && !rule.action.eq("deny") {
+                problems.push(format!("{field}.rules[{i}].action: invalid word {}, should be either \"allow\" or \"deny\"", rule.action));
+            }
+
+            for (j, ip) in rule.ips.iter().flatten().enumerate() {
+                if Cidr::parse(ip).is_none() {
+                    problems.push(format!("{field}.rules[{i}].ips[{j}]: {ip} isn't an IP address or a CIDR range"));
+                }
+            }
+
+            if let Some(pattern) = &rule.pattern {
+                if let Err(e) = Pattern::new(pattern) {
+                    problems.push(format!("{field}.rules[{i}].pattern: invalid pattern {pattern}: {e}"));
+                }
+            }
+
+            if let Some(regex) = &rule.regex {
+                if let Err(e) = Regex::new(regex) {
+                    problems.push(format!("{field}.rules[{i}].regex: invalid regular expression {regex}: {e}"));
+                }
             }
-            Err(e) => {
-                eprintln!("[Config::new():{}] A critical server config file wasn't found.\n\
-                                              Error information:\n{}", line!(), e);
-                panic!("Unrecoverable error occurred while trying to set up connection.");
+
+            if rule.pattern.is_some() && rule.regex.is_some() {
+                problems.push(format!("{field}.rules[{i}]: pattern and regex can't be used together"));
             }
         }
 
-        let mut json: Vec<u8> = Vec::new();
-        match config_file {
-            Ok(mut f) => {
-                if let Err(e) = f.read_to_end(&mut json) {
-                    eprintln!("[Config::new():{}] An error occurred after an attempt to read from a file: {:?}.\n\
-                               Error information:\n\
-                               {e}\n", line!(), f);
-                    panic!("Unrecoverable error occurred while trying to set up connection.");
-                }
-            },
-            Err(e) => {
-                eprintln!("[Config::new():{}] A critical server config file wasn't found.\n\
-                            Error information:\n\
-                            {e}", line!());
-                panic!("Unrecoverable error occurred while trying to set up connection.");
+        problems
+    }
+
+    pub fn denying_rule(&self, resource: &String) -> Option<&String> {
+        for (k, v) in &self.list {
+            if v.eq("deny") && glob_matches(k, resource) {
+                return Some(k);
             }
         }
+        None
+    }
+}
 
-        let config: Config = match serde_json::from_slice(&*json) {
-            Ok(json) => json,
-            Err(e) => {
-                eprintln!("[Config::new():{}] A critical server config file is malformed.\n\
-                           Error information:\n\
-                           {e}", line!());
-                panic!("Unrecoverable error occurred while trying to set up connection.");
-            }
-        };
+impl ConnectTunnel {
+    pub fn is_target_allowed(&self, host: &String, port: u16) -> bool {
+        for target in &self.allowed_targets {
+            let Some((target_host, target_port)) = target.rsplit_once(':') else {
+                continue;
+            };
 
-        if let Some(access_control) = &config.access_control {
-            if access_control.deny_action != 404 && access_control.deny_action != 403 {
-                eprintln!("[Config::new():{}]   A critical server config file is malformed.\n\
-                                                Error information:\n\
-                                                invalid deny action in config.json access_control, should be either 404 or 403", line!());
-                panic!("Unrecoverable error occurred while trying to set up connection.")
+            if !target_port.eq("*") && !target_port.eq(&*port.to_string()) {
+                continue;
             }
 
-            for (_, v) in &access_control.list {
-                if !v.eq("allow") && !v.eq("deny") {
-                    eprintln!("[Config::new():{}]   A critical server config file is malformed.\n\
-                                                    Error information:\n\
-                                                    invalid word in config.json access_control, should be either \"allow\" or \"deny\"", line!());
-
-                    panic!("Unrecoverable error occurred while trying to set up connection.");
+            let target_host = target_host.trim_matches(|c| c == '[' || c == ']');
+            if let Ok(pattern) = Pattern::new(target_host) {
+                if pattern.matches(&*host.to_lowercase()) {
+                    return true;
                 }
             }
         }
+        false
+    }
+}
 
-        if let Some(encoding) = &config.encoding {
-            if !encoding.supported_encodings.contains(&encoding.use_encoding) {
-                eprintln!("[Config::new():{}]   A critical server config file is malformed.\n\
-                                                Error information:\n\
-                                                invalid word in config.json use_encoding, should be either \"gzip\" or \"br\"\n\
-                                                if you specified either \"gzip\" or \"br\" and still got this error, make sure it's specified in supported_encodings", line!());
+impl Encoding {
+    const fn default_min_length() -> usize {
+        256
+    }
 
-                panic!("Unrecoverable error occurred while trying to set up connection.");
-            }
+    fn default_skipped_mime_types() -> Vec<String> {
+        ["image/png", "image/jpeg", "image/gif", "image/webp", "image/avif", "video/*", "audio/*", "font/woff", "font/woff2",
+         "application/zip", "application/gzip", "application/x-bzip2", "application/x-xz", "application/zstd", "application/x-7z-compressed", "application/x-rar-compressed", "application/pdf"]
+            .iter()
+            .map(|t| String::from(*t))
+            .collect()
+    }
+
+    fn is_skipped_mime_type(&self, mime_type: &String) -> bool {
+        let mime_type = mime_type.split(';').next().unwrap_or("").trim().to_lowercase();
+        let general_type = mime_type.split('/').next().unwrap_or("");
+
+        self.skipped_mime_types.iter().any(|t| t.eq_ignore_ascii_case(&mime_type) || t.eq_ignore_ascii_case(&format!("{general_type}/*")))
+    }
+}
+
+impl Default for Normalization {
+    fn default() -> Self {
+        Self {
+            merge_slashes: true,
+            lowercase_host: true,
+            strip_default_port: true,
+            redirect: false
+        }
+    }
+}
+
+impl Default for SocketOptions {
+    fn default() -> Self {
+        Self {
+            reuseport: false,
+            nodelay: false,
+            keepalive: None,
+            interface: None,
+            ipv6_only: None,
+            backlog: Self::default_backlog(),
+            acceptors: Self::default_acceptors()
         }
+    }
+}
 
-        config
+impl SocketOptions {
+    const fn default_backlog() -> i32 {
+        1024
     }
 
-    const fn default_max_content_length() -> usize {
-        1073741824
+    const fn default_acceptors() -> usize {
+        1
     }
+}
 
-    const fn default_server_header_state() -> bool {
-        true
+impl Default for TimeFormat {
+    fn default() -> Self {
+        Self {
+            format: Self::default_format(),
+            timezone: Timezone::default()
+        }
     }
+}
 
-    const fn default_cache_max_age() -> u64 {
-        3600
+impl TimeFormat {
+    fn default_format() -> String {
+        String::from("%Y-%m-%d %H:%M:%S %Z")
     }
 
-    const fn default_request_timeout() -> u64 {
-        10
+    pub fn format(&self, time: SystemTime) -> String {
+        match self.timezone {
+            Timezone::Utc => DateTime::<Utc>::from(time).format(&self.format).to_string(),
+            Timezone::Local => DateTime::<Local>::from(time).format(&self.format).to_string()
+        }
     }
 
-    pub fn get_supported_encodings(&self) -> Option<&Vec<String>> {
-        if let Some(encoding) = &self.encoding {
-            let supported_encodings = &encoding.supported_encodings;
+    pub fn now(&self) -> String {
+        self.format(SystemTime::now())
+    }
+}
 
-            if !supported_encodings.is_empty() {
-                return Some(supported_encodings);
-            }
+impl CacheControlRule {
+    fn directives(&self) -> String {
+        let mut directives: Vec<String> = Vec::new();
+
+        if self.private {
+            directives.push(String::from("private"));
         }
-        None
+        if self.no_store {
+            directives.push(String::from("no-store"));
+        }
+        if let Some(max_age) = self.max_age {
+            directives.push(format!("max-age={max_age}"));
+        }
+        if self.immutable {
+            directives.push(String::from("immutable"));
+        }
+
+        directives.join(", ")
+    }
+}
+
+impl SubFilter {
+    fn default_types() -> Vec<String> {
+        vec![String::from("text/html")]
     }
 
-    pub fn get_response_encoding(&self, content: &Vec<u8>, type_guess: &String, type_: &String, headers: &HashMap<String, String>) -> Option<&String> {
-        if let Some(encoding) = &self.encoding {
-            if let Some(content_encoding) = headers.get("accept-encoding") {
-                let content_empty = content.is_empty();
-                let type_equals_text = type_.eq("text");
-                if !content_empty && type_equals_text {
-                    let encoding = &encoding.use_encoding;
-                    let accepted_encodings: Vec<String> = content_encoding.split(',').map(|x| String::from(x.trim())).collect();
+    pub fn compiled_regex(&self) -> Option<&regex::bytes::Regex> {
+        self.compiled.get_or_init(|| self.regex.then(|| regex::bytes::Regex::new(&self.search).ok()).flatten()).as_ref()
+    }
 
-                    if accepted_encodings.contains(&encoding) {
-                        return Some(encoding);
-                    }
-                    return None;
-                }
-                if !content_empty && !type_equals_text {
-                    if let Some(encoding_applicable_mime_types) = &encoding.encoding_applicable_mime_types {
-                        if encoding_applicable_mime_types.contains(type_guess) {
-                            let encoding = &encoding.use_encoding;
-                            let accepted_encodings: Vec<String> = content_encoding.split(',').map(|x| String::from(x.trim())).collect();
+    pub fn applies_to(&self, resource: Option<&String>, content_type: &str) -> bool {
+        if let Some(location) = &self.location {
+            let Some(resource) = resource else {
+                return false;
+            };
 
-                            if accepted_encodings.contains(&encoding) {
-                                return Some(encoding);
-                            }
-                        }
-                    }
-                }
+            if !resource.trim_start_matches('/').starts_with(location.trim_start_matches('/')) {
+                return false;
             }
         }
-        None
+
+        let media_type = content_type.split(';').next().unwrap_or("").trim();
+        self.types.iter().any(|t| t.eq("*") || t.eq_ignore_ascii_case(media_type))
     }
+}
 
-    pub fn should_display_index_of(&self, resource: &String) -> bool {
-        #[cfg(target_family = "unix")]
-        let document_root = if *&*CHROOT {&String::from("")} else {&CONFIG.document_root};
-        #[cfg(not(target_family = "unix"))]
-        let document_root = &CONFIG.document_root;
+impl EndpointLibrary {
+    pub fn matches(&self, host: Option<&String>, resource: Option<&String>) -> bool {
+        if let Some(pattern) = &self.host {
+            let Some(host) = host else {
+                return false;
+            };
 
-        if let Some(index_of_rules) = &self.index_of_page_rules {
-            for (k, v) in index_of_rules {
-                if let Ok(paths) = glob(&*format!("{document_root}/{k}")) {
-                    for entry in paths.filter_map(Result::ok) {
-                        #[cfg(target_family = "unix")]
-                        if entry.to_string_lossy().eq(&*format!("{document_root}/{resource}")) {
-                            return *v;
-                        }
-                        #[cfg(not(target_family = "unix"))]
-                        if entry.to_string_lossy().eq(&*format!("{document_root}\\{resource}")) {
-                            return *v;
-                        }
-                    }
-                }
+            let host = match host.rsplit_once(':') {
+                Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) => name,
+                _ => host.as_str()
+            };
+
+            if !matches!(Pattern::new(pattern), Ok(pattern) if pattern.matches(&*host.to_lowercase())) {
+                return false;
             }
         }
-        false
+
+        if let Some(location) = &self.location {
+            let Some(resource) = resource else {
+                return false;
+            };
+
+            if !resource.trim_start_matches('/').starts_with(location.trim_start_matches('/')) {
+                return false;
+            }
+        }
+        true
     }
 }
 
-pub static CONFIG: LazyLock<Config> = LazyLock::new(|| {
-    Config::new()
-});
+impl Acme {
+    fn default_directory() -> String {
+        String::from("https://acme-v02.api.letsencrypt.org/directory")
+    }
 
-impl AccessControl {
-    pub fn is_access_allowed(&self, resource: &String) -> bool {
-        #[cfg(target_family = "unix")]
-        let document_root = if *&*CHROOT {&String::from("")} else {&CONFIG.document_root};
-        #[cfg(not(target_family = "unix"))]
-        let document_root = &CONFIG.document_root;
+    fn default_storage() -> String {
+        String::from("acme")
+    }
 
-        for (k, v) in &self.list {
-            if let Ok(paths) = glob(&*format!("{document_root}/{k}")) {
-                for entry in paths.filter_map(Result::ok) {
-                    #[cfg(target_family = "unix")]
-                    if entry.to_string_lossy().eq(&*format!("{document_root}/{resource}")) {
-                        if v.eq("deny") {
-                            return false;
-                        }
-                    }
-                    #[cfg(not(target_family = "unix"))]
-                    if entry.to_string_lossy().eq(&*format!("{document_root}\\{resource}")) {
-                        if v.eq("deny") {
-                            return false;
-                        }
-                    }
-                }
-            }
+    const fn default_renew_before_days() -> u64 {
+        30
+    }
+}
+
+impl Hsts {
+    const fn default_max_age() -> u64 {
+        31536000
+    }
+
+    pub fn header_value(&self) -> String {
+        format!("max-age={}{}{}",
+                self.max_age,
+                if self.include_subdomains {"; includeSubDomains"} else {""},
+                if self.preload {"; preload"} else {""})
+    }
+}
+
+impl VirtualHost {
+    pub fn matches(&self, host: &str) -> bool {
+        let host = match host.rsplit_once(':') {
+            Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) => name,
+            _ => host
+        };
+
+        hosts_match(&self.hosts, host)
+    }
+
+    fn configure_ssl(&self, https: &Https) -> Result<Option<SslContext>, ErrorStack> {
+        let (Some(ssl_certificate_file), Some(ssl_private_key_file)) = (&self.ssl_certificate_file, &self.ssl_private_key_file) else {
+            return Ok(None);
+        };
+
+        let mut ssl_ctx_builder = SslContext::builder(SslMethod::tls())?;
+        ssl_ctx_builder.set_private_key_file(ssl_private_key_file, SslFiletype::PEM)?;
+        ssl_ctx_builder.set_certificate_chain_file(ssl_certificate_file)?;
+        ssl_ctx_builder.check_private_key()?;
+
+        https.configure_shared(&mut ssl_ctx_builder)?;
+
+        Ok(Some(ssl_ctx_builder.build()))
+    }
+}
+
+fn hosts_match(patterns: &[String], host: &str) -> bool {
+    let host = host.trim_end_matches('.').to_lowercase();
+    patterns.iter().any(|pattern| Pattern::new(&pattern.to_lowercase()).is_ok_and(|pattern| pattern.matches(&host)))
+}
+
+impl NoisePaths {
+    fn default_patterns() -> Vec<String> {
+        vec![String::from("/favicon.ico"), String::from("/apple-touch-icon*.png"), String::from("/browserconfig.xml")]
+    }
+
+    const fn default_max_size() -> u64 {
+        65536
+    }
+
+    const fn default_max_age() -> u64 {
+        86400
+    }
+}
+
+impl Quarantine {
+    const fn default_retention() -> u64 {
+        86400
+    }
+}
+
+impl Fairness {
+    const fn default_busy_threshold() -> f64 {
+        0.75
+    }
+}
+
+impl Journal {
+    fn default_methods() -> Vec<String> {
+        vec![String::from("POST"), String::from("PUT"), String::from("DELETE"), String::from("PATCH")]
+    }
+
+    const fn default_max_size() -> u64 {
+        67108864
+    }
+}
+
+impl AccessLog {
+    fn default_output() -> String {
+        String::from("stdout")
+    }
+}
+
+impl Logging {
+    fn default_output() -> String {
+        String::from("console")
+    }
+}
+
+impl Rotation {
+    fn problems(&self, field: &str) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if self.max_size == Some(0) {
+            problems.push(format!("{field}.max_size: must be greater than 0"));
+        }
+
+        if self.keep == Some(0) {
+            problems.push(format!("{field}.keep: must be greater than 0"));
         }
+
+        problems
+    }
+}
+
+impl Cluster {
+    fn default_channel() -> String {
+        String::from("drain")
+    }
+
+    const fn default_retry_interval() -> u64 {
+        5
+    }
+}
+
+impl Dns {
+    const fn default_refresh_interval() -> u64 {
+        60
+    }
+
+    const fn default_negative_ttl() -> u64 {
+        5
+    }
+
+    const fn default_happy_eyeballs_delay() -> u64 {
+        250
+    }
+}
+
+impl Prefilters {
+    const fn default_sniff_protocol() -> bool {
         true
     }
 }
 
+impl ClientCertificates {
+    fn configure_ssl(&self, ssl_ctx_builder: &mut SslContextBuilder) -> Result<(), ErrorStack> {
+        ssl_ctx_builder.set_ca_file(&self.ca_file)?;
+        ssl_ctx_builder.set_client_ca_list(X509Name::load_client_ca_file(&self.ca_file)?);
+        ssl_ctx_builder.set_verify(SslVerifyMode::PEER);
+        Ok(())
+    }
+
+    pub fn policy(&self, resource: &str) -> ClientCertificatePolicy {
+        self.paths.iter()
+            .filter(|(path, _)| {
+                let path = path.trim_end_matches('/');
+                resource.strip_prefix(path).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+            .max_by_key(|(path, _)| path.trim_end_matches('/').len())
+            .map_or(ClientCertificatePolicy::Optional, |(_, policy)| *policy)
+    }
+}
+
+impl Jwt {
+    const fn default_leeway() -> u64 {
+        60
+    }
+
+    const fn default_jwks_refresh_interval() -> u64 {
+        3600
+    }
+
+    pub fn protects(&self, resource: &str) -> bool {
+        self.paths.iter().any(|path| {
+            let path = path.trim_end_matches('/');
+            resource.strip_prefix(path).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+    }
+}
+
 impl Https {
-    pub fn configure_ssl(&self) -> Result<SslContext, ErrorStack> {
+    const fn default_certificate_reload_interval() -> u64 {
+        60
+    }
+
+    pub fn configure_shared(&self, ssl_ctx_builder: &mut SslContextBuilder) -> Result<(), ErrorStack> {
+        if let Some(client_certificates) = &self.client_certificates {
+            client_certificates.configure_ssl(ssl_ctx_builder)?;
+        }
+
+        if self.ocsp_stapling {
+            configure_stapling(ssl_ctx_builder)?;
+        }
+
+        if self.session_tickets || self.session_cache_size != 0 {
+            ssl_ctx_builder.set_session_id_context(b"drain")?;
+        }
+        Ok(())
+    }
+
+    pub fn configure_ssl(&self, virtual_hosts: &[VirtualHost], acme: Option<&Acme>) -> Result<SslContext, ErrorStack> {
         let mut ssl_ctx_builder = SslContext::builder(SslMethod::tls())?;
 
         ssl_ctx_builder.set_private_key_file(format!("{}", &self.ssl_private_key_file), SslFiletype::PEM)?;
@@ -299,7 +2533,11 @@ impl Https {
             }
         }
 
-        ssl_ctx_builder.set_verify(SslVerifyMode::PEER);
+        if self.client_certificates.is_none() {
+            ssl_ctx_builder.set_verify(SslVerifyMode::NONE);
+        }
+        self.configure_shared(&mut ssl_ctx_builder)?;
+
         ssl_ctx_builder.set_alpn_select_callback(|_ssl, client_protocols| {
             if let Some(p) = select_next_proto(b"\x08http/1.1", client_protocols) {
                 Ok(p)
@@ -308,8 +2546,55 @@ impl Https {
             }
         });
 
-        ssl_ctx_builder.set_options(SslOptions::NO_TICKET);
-        ssl_ctx_builder.set_session_cache_mode(SslSessionCacheMode::OFF);
+        ssl_ctx_builder.set_client_hello_callback(|ssl, alert| {
+            if !is_server_name_allowed(ssl) {
+                *alert = SslAlert::UNRECOGNIZED_NAME;
+                return Err(ErrorStack::get());
+            }
+
+            if let Some(index) = *FINGERPRINT_INDEX {
+                ssl.set_ex_data(index, compute(ssl));
+            }
+            Ok(ClientHelloResponse::SUCCESS)
+        });
+
+        let mut certificates: Vec<(Vec<String>, SslContext)> = Vec::new();
+        for virtual_host in virtual_hosts {
+            if let Some(ssl_ctx) = virtual_host.configure_ssl(self)? {
+                certificates.push((virtual_host.hosts.clone(), ssl_ctx));
+            }
+        }
+
+        let acme_domains = acme.map(|acme| acme.domains.clone()).unwrap_or_default();
+        if !certificates.is_empty() || !acme_domains.is_empty() {
+            ssl_ctx_builder.set_servername_callback(move |ssl, _alert| {
+                let Some(name) = ssl.servername(NameType::HOST_NAME).map(String::from) else {
+                    return Ok(());
+                };
+
+                if let Some((_, ssl_ctx)) = certificates.iter().find(|(hosts, _)| hosts_match(hosts, &name)) {
+                    return ssl.set_ssl_context(ssl_ctx).map_err(|_| SniError::ALERT_FATAL);
+                }
+
+                if hosts_match(&acme_domains, &name) {
+                    if let Ok(Some((_, ssl_ctx))) = CERTIFICATE.read().as_deref() {
+                        return ssl.set_ssl_context(ssl_ctx).map_err(|_| SniError::ALERT_FATAL);
+                    }
+                }
+                Ok(())
+            });
+        }
+
+        if !self.session_tickets {
+            ssl_ctx_builder.set_options(SslOptions::NO_TICKET);
+        }
+
+        if self.session_cache_size == 0 {
+            ssl_ctx_builder.set_session_cache_mode(SslSessionCacheMode::OFF);
+        } else {
+            ssl_ctx_builder.set_session_cache_mode(SslSessionCacheMode::SERVER);
+            ssl_ctx_builder.set_session_cache_size(self.session_cache_size as i32);
+        }
 
         Ok(ssl_ctx_builder.build())
     }
@@ -318,25 +2603,85 @@ impl Https {
 #[cfg(feature = "cgi")]
 impl CGI {
     pub fn should_attempt_cgi(&self, resource: &String) -> bool {
-        #[cfg(target_family = "unix")]
-        let document_root = if *&*CHROOT {&String::from("")} else {&CONFIG.document_root};
-        #[cfg(not(target_family = "unix"))]
-        let document_root = &CONFIG.document_root;
+        match self.matching_rule(resource) {
+            Some(CGIRule::Enabled(enabled)) => *enabled,
+            Some(CGIRule::Options(options)) => options.action == CGIRuleAction::Pass,
+            None => false
+        }
+    }
+
+    pub fn rule_options(&self, resource: &String) -> Option<&CGIRuleOptions> {
+        match self.matching_rule(resource) {
+            Some(CGIRule::Options(options)) => Some(options),
+            _ => None
+        }
+    }
 
+    fn matching_rule(&self, resource: &String) -> Option<&CGIRule> {
         for (k, v) in &self.cgi_rules {
-            if let Ok(paths) = glob(&*format!("{document_root}/{k}")) {
-                for entry in paths.filter_map(Result::ok) {
-                    #[cfg(target_family = "unix")]
-                    if entry.to_string_lossy().eq(&*format!("{document_root}/{resource}")) {
-                        return *v;
-                    }
-                    #[cfg(not(target_family = "unix"))]
-                    if entry.to_string_lossy().eq(&*format!("{document_root}\\{resource}")) {
-                        return *v;
-                    }
-                }
+            if glob_matches(k, resource) {
+                return Some(v);
             }
         }
-        false
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dotfiles_are_sensitive() {
+        let sensitive_paths = SensitivePaths::default();
+        assert!(sensitive_paths.is_sensitive("/.git/config"));
+        assert!(sensitive_paths.is_sensitive("/.env"));
+        assert!(sensitive_paths.is_sensitive("/app/.htpasswd"));
+        assert!(sensitive_paths.is_sensitive("\\.git\\config"));
+        assert!(sensitive_paths.is_sensitive("/index.html.bak"));
+        assert!(!sensitive_paths.is_sensitive("/index.html"));
+        assert!(!sensitive_paths.is_sensitive("/assets/app.min.js"));
+        assert!(!sensitive_paths.is_sensitive("/.well-known/acme-challenge/token"));
+    }
+
+    #[test]
+    fn traversal_is_sensitive() {
+        let sensitive_paths = SensitivePaths::default();
+        assert!(sensitive_paths.is_sensitive("/a/../.env"));
+        assert!(sensitive_paths.is_sensitive("/../etc/passwd"));
+        assert!(sensitive_paths.is_sensitive("/.well-known/../.git/config"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn encoded_dots_are_sensitive() {
+        let sensitive_paths = SensitivePaths::default();
+        assert!(sensitive_paths.is_sensitive("/%2e%2e/"));
+        assert!(sensitive_paths.is_sensitive("/%2E%2E/etc/passwd"));
+        assert!(sensitive_paths.is_sensitive("/%2egit/config"));
+        assert!(sensitive_paths.is_sensitive("/a/%2e%2e/%2eenv"));
+        assert!(!sensitive_paths.is_sensitive("/%252egit/config"));
+        assert!(sensitive_paths.is_sensitive("/%2e%2e%2f.env"));
+        assert!(sensitive_paths.is_sensitive("/.well-known/%2e%2e/.git/config"));
+        assert!(!sensitive_paths.is_sensitive("/%69ndex.html"));
+    }
+
+    #[test]
+    fn accepted_media_type_is_normalized() {
+        let mut config = serde_json::from_str::<serde_json::Value>(include_str!("../config.json")).unwrap();
+        config["accepted_media_types"] = serde_json::json!(["application/xml", "image/*"]);
+        let config = serde_json::from_value::<Config>(config).unwrap();
+
+        assert_eq!(config.accepted_media_type(&String::from("Application/XML; charset=utf-8")).as_deref(), Some("application/xml"));
+        assert_eq!(config.accepted_media_type(&String::from("image/PNG")).as_deref(), Some("image/png"));
+        assert_eq!(config.accepted_media_type(&String::from("text/csv")), None);
+    }
+
+    #[test]
+    fn glob_rules_match_paths_that_do_not_exist() {
+        assert!(glob_matches("api/*", "/api/users"));
+        assert!(!glob_matches("api/*", "/api/v1/users"));
+        assert!(glob_matches("**/*.php", "missing/dir/index.php"));
+        assert!(glob_matches("/example2.html", "example2.html"));
+        assert!(!glob_matches("example2.html", "example1.html"));
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::net::IpAddr;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use crate::config::{ConnectionOverflow, CONFIG};
+use crate::fairness::exceeds_fair_share;
+use crate::util::send_response;
+
+static CONNECTIONS: LazyLock<Option<Arc<Semaphore>>> = LazyLock::new(|| {
+    CONFIG.load().max_connections.map(|max_connections| Arc::new(Semaphore::new(max_connections.min(Semaphore::MAX_PERMITS))))
+});
+
+static CONNECTIONS_PER_IP: LazyLock<Mutex<HashMap<IpAddr, usize>>> = LazyLock::new(|| {
+    Mutex::new(HashMap::new())
+});
+
+static ACTIVE_CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+pub struct ConnectionGuard {
+    _permit: Option<OwnedSemaphorePermit>,
+    remote_ip: Option<IpAddr>
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        ACTIVE_CONNECTIONS.fetch_sub(1, Ordering::Relaxed);
+
+        let Some(remote_ip) = self.remote_ip else {
+            return;
+        };
+
+        if let Ok(mut connections) = CONNECTIONS_PER_IP.lock() {
+            if let Some(count) = connections.get_mut(&remote_ip) {
+                *count = count.saturating_sub(1);
+                if *count == 0 {
+                    connections.remove(&remote_ip);
+                }
+            }
+        }
+    }
+}
+
+pub async fn reserve_connection() -> Option<OwnedSemaphorePermit> {
+    match (&*CONNECTIONS, &CONFIG.load().connection_overflow) {
+        (Some(connections), ConnectionOverflow::Wait) => connections.clone().acquire_owned().await.ok(),
+        _ => None
+    }
+}
+
+pub fn admit_connection(remote_ip: &IpAddr, reserved: Option<OwnedSemaphorePermit>) -> Option<ConnectionGuard> {
+    let permit = match (reserved, &*CONNECTIONS) {
+        (Some(permit), _) => Some(permit),
+        (None, Some(connections)) => Some(connections.clone().try_acquire_owned().ok()?),
+        (None, None) => None
+    };
+
+    if CONFIG.load().max_connections_per_ip.is_none() && CONFIG.load().fairness.is_none() {
+        ACTIVE_CONNECTIONS.fetch_add(1, Ordering::Relaxed);
+        return Some(ConnectionGuard {_permit: permit, remote_ip: None});
+    }
+
+    let mut connections = CONNECTIONS_PER_IP.lock().ok()?;
+    if CONFIG.load().max_connections_per_ip.is_some_and(|max| connections.get(remote_ip).is_some_and(|count| *count >= max)) {
+        return None;
+    }
+
+    if let (Some(connections_semaphore), Some(max_connections)) = (&*CONNECTIONS, CONFIG.load().max_connections) {
+        if exceeds_fair_share(&connections, remote_ip, max_connections, connections_semaphore.available_permits()) {
+            return None;
+        }
+    }
+    *connections.entry(*remote_ip).or_insert(0) += 1;
+
+    ACTIVE_CONNECTIONS.fetch_add(1, Ordering::Relaxed);
+    Some(ConnectionGuard {_permit: permit, remote_ip: Some(*remote_ip)})
+}
+
+pub fn active_connections() -> usize {
+    ACTIVE_CONNECTIONS.load(Ordering::Relaxed)
+}
+
+pub fn should_respond_when_rejected() -> bool {
+    !matches!(CONFIG.load().connection_overflow, ConnectionOverflow::Close)
+}
+
+pub async fn reject_connection<T>(stream: &mut T) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+    T: AsyncRead + AsyncWrite + Unpin
+{
+    let response_headers = HashMap::from([
+        (String::from("Retry-After"), CONFIG.load().connection_retry_after.to_string()),
+        (String::from("Connection"), String::from("close"))
+    ]);
+
+    send_response(stream, 503, Some(response_headers), None, None, None).await
+}
@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+use drain_common::cookies::{SameSite, SetCookie};
+use glob::Pattern;
+use tracing::debug;
+use crate::config::{CookieSameSite, CONFIG};
+use crate::util::REQUEST_INFO;
+
+fn is_exempt(exemptions: &Vec<String>) -> bool {
+    let Ok(Some(resource)) = REQUEST_INFO.try_with(|info| info.borrow().resource.clone()) else {
+        return false;
+    };
+
+    let resource = format!("/{}", resource.trim_start_matches('/'));
+    exemptions.iter().any(|e| matches!(Pattern::new(e), Ok(pattern) if pattern.matches(&resource)))
+}
+
+fn is_https() -> bool {
+    REQUEST_INFO.try_with(|info| info.borrow().https).unwrap_or(false)
+}
+
+pub fn apply_cookie_policy(set_cookie: &mut HashMap<String, SetCookie>) {
+    let Some(cookie_policy) = &CONFIG.load().cookie_policy else {
+        return;
+    };
+
+    if set_cookie.is_empty() || is_exempt(&cookie_policy.exemptions) {
+        return;
+    }
+
+    if let Some(max_length) = cookie_policy.max_length {
+        set_cookie.retain(|name, cookie| {
+            let allowed = name.len() + cookie.value.len() + 1 <= max_length;
+            if !allowed {
+                debug!("Cookie {name} wasn't sent, because it's longer than {max_length} bytes.");
+            }
+            allowed
+        });
+    }
+
+    let https = is_https();
+    for cookie in set_cookie.values_mut() {
+        if cookie_policy.force_secure && https {
+            cookie.secure = true;
+        }
+
+        if cookie.samesite.is_none() {
+            cookie.samesite = match cookie_policy.default_samesite {
+                Some(CookieSameSite::Strict) => Some(SameSite::Strict),
+                Some(CookieSameSite::Lax) => Some(SameSite::Lax),
+                Some(CookieSameSite::None) => Some(SameSite::None),
+                None => None
+            };
+        }
+    }
+}
+
+pub fn apply_cookie_policy_to_headers(headers: &mut HashMap<String, String>) {
+    let Some(cookie_policy) = &CONFIG.load().cookie_policy else {
+        return;
+    };
+
+    let Some(name) = headers.keys().find(|k| k.eq_ignore_ascii_case("set-cookie")).cloned() else {
+        return;
+    };
+
+    if is_exempt(&cookie_policy.exemptions) {
+        return;
+    }
+
+    let Some(value) = headers.get_mut(&name) else {
+        return;
+    };
+
+    let mut attributes = value.split(';');
+    let cookie = attributes.next().unwrap_or("").trim();
+
+    if let Some(max_length) = cookie_policy.max_length {
+        if cookie.len() > max_length {
+            debug!("Cookie {} wasn't sent, because it's longer than {max_length} bytes.", cookie.split('=').next().unwrap_or(""));
+            headers.remove(&name);
+            return;
+        }
+    }
+
+    let attribute_names: Vec<String> = attributes.map(|a| a.split('=').next().unwrap_or("").trim().to_lowercase()).collect();
+
+    if cookie_policy.force_secure && is_https() && !attribute_names.iter().any(|a| a.eq("secure")) {
+        value.push_str("; Secure");
+    }
+
+    if !attribute_names.iter().any(|a| a.eq("samesite")) {
+        match cookie_policy.default_samesite {
+            Some(CookieSameSite::Strict) => value.push_str("; SameSite=Strict"),
+            Some(CookieSameSite::Lax) => value.push_str("; SameSite=Lax"),
+            Some(CookieSameSite::None) => value.push_str("; SameSite=None"),
+            None => {}
+        }
+    }
+}
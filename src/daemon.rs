@@ -0,0 +1,75 @@
+use std::fs::{read_to_string, remove_file, write, OpenOptions};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::process::{exit, id};
+use std::sync::atomic::{AtomicU32, Ordering};
+use fork::{daemon, Fork};
+use crate::config::CONFIG;
+use crate::cli::SignalAction;
+
+static PIDFILE_OWNER: AtomicU32 = AtomicU32::new(0);
+
+pub fn daemonize() -> io::Result<()> {
+    match daemon(true, true) {
+        Ok(Fork::Child) => {},
+        Ok(Fork::Parent(_)) => exit(0),
+        Err(e) => return Err(io::Error::other(format!("daemonizing failed with {e} status code"))),
+    }
+
+    unsafe {libc::setpgid(0, 0)};
+
+    let dev_null = OpenOptions::new().read(true).write(true).open("/dev/null")?;
+    let output = match &CONFIG.load().error_log {
+        Some(error_log) => OpenOptions::new().create(true).append(true).open(error_log)?,
+        None => dev_null.try_clone()?
+    };
+
+    unsafe {
+        if libc::dup2(dev_null.as_raw_fd(), libc::STDIN_FILENO) < 0 ||
+           libc::dup2(output.as_raw_fd(), libc::STDOUT_FILENO) < 0 ||
+           libc::dup2(output.as_raw_fd(), libc::STDERR_FILENO) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+pub fn write_pidfile() -> io::Result<()> {
+    let Some(pidfile) = &CONFIG.load().pidfile else {
+        return Ok(());
+    };
+
+    write(pidfile, format!("{}\n", id()))?;
+    PIDFILE_OWNER.store(id(), Ordering::Relaxed);
+    Ok(())
+}
+
+pub fn remove_pidfile() {
+    if let Some(pidfile) = &CONFIG.load().pidfile {
+        if PIDFILE_OWNER.load(Ordering::Relaxed) == id() {
+            let _ = remove_file(pidfile);
+        }
+    }
+}
+
+pub fn send_signal(action: SignalAction) -> io::Result<()> {
+    let signal = match action {
+        SignalAction::Stop => libc::SIGTERM,
+        SignalAction::Reload => libc::SIGHUP
+    };
+
+    let Some(pidfile) = &CONFIG.load().pidfile else {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "pidfile isn't set in config.json"));
+    };
+
+    let pid = read_to_string(pidfile)?.trim().parse::<libc::pid_t>()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("{pidfile} doesn't contain a valid PID")))?;
+
+    let target = if unsafe {libc::getpgid(pid)} == pid {-pid} else {pid};
+    if unsafe {libc::kill(target, signal)} != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+use chrono::Local;
+use notify::{recommended_watcher, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::sync::broadcast;
+use tokio::time::interval;
+use tracing::warn;
+use crate::cli::{Command, CLI};
+use crate::config::CONFIG;
+use crate::shutdown::shutdown_requested;
+use crate::util::REQUEST_INFO;
+
+pub const RELOAD_EVENTS: &str = "/__drain/reload";
+
+pub const RELOAD_SCRIPT: &str = "<script>new EventSource(\"/__drain/reload\").addEventListener(\"reload\", () => location.reload());</script>";
+
+static RELOADS: LazyLock<broadcast::Sender<()>> = LazyLock::new(|| broadcast::channel(16).0);
+
+pub static DEV_WATCHER: LazyLock<Option<Mutex<RecommendedWatcher>>> = LazyLock::new(|| {
+    if !is_dev() {
+        return None;
+    }
+
+    let mut watcher = match recommended_watcher(|event: notify::Result<Event>| {
+        if event.is_ok_and(|event| !matches!(event.kind, EventKind::Access(_))) {
+            let _ = RELOADS.send(());
+        }
+    }) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            warn!("An error occurred while setting up a watch on the document root.\n\
+                   Error information:\n{e}\n\
+                   Continuing without live reload...");
+            return None;
+        }
+    };
+
+    if let Err(e) = watcher.watch(&PathBuf::from(&CONFIG.load().document_root), RecursiveMode::Recursive) {
+        warn!("An error occurred while setting up a watch on the document root.\n\
+               Error information:\n{e}\n\
+               Continuing without live reload...");
+        return None;
+    }
+
+    Some(Mutex::new(watcher))
+});
+
+pub fn is_dev() -> bool {
+    matches!(CLI.command, Some(Command::Dev {..}))
+}
+
+pub fn log_response(status: u16) {
+    if !is_dev() {
+        return;
+    }
+
+    let Ok(Some(resource)) = REQUEST_INFO.try_with(|info| info.borrow().resource.clone()) else {
+        return;
+    };
+
+    if resource.eq(RELOAD_EVENTS) {
+        return;
+    }
+
+    let color = match status {
+        200..=299 => "32",
+        300..=399 => "36",
+        400..=499 => "33",
+        _ => "31"
+    };
+
+    println!("\x1b[2m{}\x1b[0m \x1b[{color}m{status}\x1b[0m {resource}", Local::now().format("%H:%M:%S"));
+}
+
+pub async fn send_reload_events<T>(stream: &mut T, keep_alive: &mut bool) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+    T: AsyncRead + AsyncWrite + Unpin
+{
+    let mut reloads = RELOADS.subscribe();
+    let mut heartbeat = interval(Duration::from_secs(15));
+    *keep_alive = false;
+
+    stream.write_all(b"HTTP/1.1 200 OK\r\n\
+                       Content-Type: text/event-stream\r\n\
+                       Cache-Control: no-store\r\n\
+                       Connection: close\r\n\r\n").await?;
+    stream.flush().await?;
+
+    loop {
+        tokio::select! {
+            reload = reloads.recv() => {
+                if let Err(broadcast::error::RecvError::Closed) = reload {
+                    return Ok(());
+                }
+                stream.write_all(b"event: reload\ndata: \n\n").await?;
+            },
+            _ = heartbeat.tick() => {
+                stream.write_all(b": heartbeat\n\n").await?;
+            },
+            _ = shutdown_requested() => {
+                return Ok(());
+            }
+        }
+        stream.flush().await?;
+    }
+}
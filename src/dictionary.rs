@@ -0,0 +1,132 @@
+use std::fs::read;
+use std::io::{Error, ErrorKind};
+use std::sync::LazyLock;
+use brotli::enc::{BrotliCompressCustomIoCustomDict, BrotliEncoderParams, StandardAlloc};
+use brotli::{IoReaderWrapper, IoWriterWrapper};
+use glob::Pattern;
+use openssl::base64;
+use openssl::hash::{hash, MessageDigest};
+use tracing::{debug, error, warn};
+use crate::config::CONFIG;
+use crate::util::REQUEST_INFO;
+#[cfg(target_family = "unix")]
+use crate::util::CHROOT;
+
+const DCB_MAGIC: [u8; 4] = [0xff, 0x44, 0x43, 0x42];
+const MAX_DICTIONARY_LENGTH: usize = 4194304;
+
+pub static DCB_ENCODING: LazyLock<String> = LazyLock::new(|| {
+    String::from("dcb")
+});
+
+pub struct Dictionary {
+    resource: String,
+    match_pattern: String,
+    hash: Vec<u8>,
+    content: Vec<u8>
+}
+
+pub static DICTIONARIES: LazyLock<Vec<Dictionary>> = LazyLock::new(|| {
+    let config = CONFIG.load();
+    let Some(dictionaries) = config.encoding.as_ref().and_then(|encoding| encoding.dictionaries.as_ref()) else {
+        return Vec::new();
+    };
+
+    #[cfg(target_family = "unix")]
+    let document_root = if *&*CHROOT {&String::from("")} else {&config.document_root};
+    #[cfg(not(target_family = "unix"))]
+    let document_root = &config.document_root;
+
+    let mut loaded = Vec::new();
+    for dictionary in dictionaries {
+        let resource = String::from(dictionary.path.trim_start_matches('/'));
+        let content = match read(format!("{document_root}/{resource}")) {
+            Ok(content) if content.len() <= MAX_DICTIONARY_LENGTH => content,
+            Ok(_) => {
+                warn!("Compression dictionary {resource} is larger than {MAX_DICTIONARY_LENGTH} bytes.\n\
+                       Continuing without it...");
+                continue;
+            },
+            Err(e) => {
+                warn!("An error occurred while reading compression dictionary {resource}.\n\
+                       Error information:\n{e}\n\
+                       Continuing without it...");
+                continue;
+            }
+        };
+
+        let hash = match hash(MessageDigest::sha256(), &content) {
+            Ok(hash) => hash.to_vec(),
+            Err(e) => {
+                warn!("An error occurred while hashing compression dictionary {resource}.\n\
+                       Error information:\n{e}\n\
+                       Continuing without it...");
+                continue;
+            }
+        };
+
+        loaded.push(Dictionary {resource, match_pattern: dictionary.match_pattern.clone(), hash, content});
+    }
+
+    debug!("{} compression dictionaries loaded.", loaded.len());
+
+    loaded
+});
+
+fn request_info() -> (Option<String>, Option<String>) {
+    REQUEST_INFO.try_with(|info| {
+        let info = info.borrow();
+        (info.resource.clone(), info.available_dictionary.clone())
+    }).unwrap_or((None, None))
+}
+
+pub fn use_as_dictionary() -> Option<String> {
+    let (Some(resource), _) = request_info() else {
+        return None;
+    };
+
+    DICTIONARIES.iter()
+        .find(|d| d.resource.eq(resource.trim_start_matches('/')))
+        .map(|d| format!("match=\"{}\"", d.match_pattern.replace('\\', "\\\\").replace('"', "\\\"")))
+}
+
+pub fn negotiate_dictionary() -> Option<&'static Dictionary> {
+    let (Some(resource), Some(available_dictionary)) = request_info() else {
+        return None;
+    };
+
+    let encoded = available_dictionary.trim().strip_prefix(':')?.strip_suffix(':')?;
+    let hash = base64::decode_block(encoded).ok()?;
+    let resource = format!("/{}", resource.trim_start_matches('/'));
+
+    DICTIONARIES.iter().find(|d| {
+        d.hash.eq(&hash) && matches!(Pattern::new(&d.match_pattern), Ok(pattern) if pattern.matches(&resource))
+    })
+}
+
+pub fn dictionary_compress(content: &[u8]) -> Option<Vec<u8>> {
+    let dictionary = negotiate_dictionary()?;
+    let mut compressed = Vec::from(DCB_MAGIC);
+    compressed.extend_from_slice(&dictionary.hash);
+
+    let mut reader = content;
+    let result = BrotliCompressCustomIoCustomDict(
+        &mut IoReaderWrapper(&mut reader),
+        &mut IoWriterWrapper(&mut compressed),
+        &mut [0u8; 4096],
+        &mut [0u8; 4096],
+        &BrotliEncoderParams::default(),
+        StandardAlloc::default(),
+        &mut |_, _, _, _| (),
+        &dictionary.content,
+        Error::new(ErrorKind::UnexpectedEof, "Unexpected EOF")
+    );
+
+    if let Err(e) = result {
+        error!("An error occurred while compressing the content of a response using a shared dictionary:\n{e}\n\
+                Falling back to Brotli without a dictionary...");
+        return None;
+    }
+
+    Some(compressed)
+}
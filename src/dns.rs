@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::io;
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+use tokio::net::{lookup_host, TcpStream};
+use tokio::sync::Mutex;
+use tokio::task::JoinSet;
+use tokio::time::timeout;
+use tracing::debug;
+use crate::config::CONFIG;
+
+const MAX_CACHED_HOSTS: usize = 4096;
+
+enum Resolution {
+    Resolved(Vec<IpAddr>, Instant),
+    Failed(Instant)
+}
+
+static CACHE: LazyLock<Mutex<HashMap<String, Resolution>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+pub async fn resolve(host: &String) -> io::Result<Vec<IpAddr>> {
+    if let Ok(address) = host.trim_matches(|c| c == '[' || c == ']').parse::<IpAddr>() {
+        return Ok(vec![address]);
+    }
+
+    let Some(dns) = &CONFIG.load().dns else {
+        return lookup(host).await;
+    };
+
+    let host = host.to_lowercase();
+    if let Some(addresses) = dns.overrides.get(&host) {
+        return Ok(addresses.clone());
+    }
+
+    let refresh_interval = Duration::from_secs(dns.refresh_interval);
+    let negative_ttl = Duration::from_secs(dns.negative_ttl);
+
+    let stale = match CACHE.lock().await.get(&host) {
+        Some(Resolution::Resolved(addresses, resolved_at)) if resolved_at.elapsed() < refresh_interval => return Ok(addresses.clone()),
+        Some(Resolution::Resolved(addresses, _)) => Some(addresses.clone()),
+        Some(Resolution::Failed(failed_at)) if failed_at.elapsed() < negative_ttl => {
+            return Err(io::Error::new(ErrorKind::NotFound, format!("resolution of {host} failed recently")));
+        },
+        _ => None
+    };
+
+    let result = lookup(&host).await;
+    let mut cache = CACHE.lock().await;
+
+    if cache.len() >= MAX_CACHED_HOSTS {
+        cache.retain(|_, r| match r {
+            Resolution::Resolved(_, resolved_at) => resolved_at.elapsed() < refresh_interval,
+            Resolution::Failed(failed_at) => failed_at.elapsed() < negative_ttl
+        });
+    }
+
+    match result {
+        Ok(addresses) => {
+            cache.insert(host, Resolution::Resolved(addresses.clone(), Instant::now()));
+            Ok(addresses)
+        },
+        Err(e) => {
+            if let Some(addresses) = stale {
+                debug!("Resolution of {host} failed, using previously resolved addresses.");
+                return Ok(addresses);
+            }
+
+            cache.insert(host, Resolution::Failed(Instant::now()));
+            Err(e)
+        }
+    }
+}
+
+pub async fn forget(hosts: Option<Vec<String>>) {
+    let mut cache = CACHE.lock().await;
+
+    match hosts {
+        Some(hosts) => hosts.iter().for_each(|host| {
+            cache.remove(&host.to_lowercase());
+        }),
+        None => cache.clear()
+    }
+}
+
+async fn lookup(host: &String) -> io::Result<Vec<IpAddr>> {
+    let addresses: Vec<IpAddr> = match timeout(Duration::from_secs(CONFIG.load().request_timeout), lookup_host((host.as_str(), 0))).await {
+        Ok(addresses) => addresses?.map(|a| a.ip()).collect(),
+        Err(_) => return Err(io::Error::new(ErrorKind::TimedOut, format!("resolution of {host} timed out")))
+    };
+
+    if addresses.is_empty() {
+        return Err(io::Error::new(ErrorKind::NotFound, format!("{host} has no addresses")));
+    }
+    Ok(addresses)
+}
+
+fn interleave(addresses: Vec<IpAddr>, prefer_ipv4: bool) -> Vec<IpAddr> {
+    let (mut preferred, mut other): (Vec<IpAddr>, Vec<IpAddr>) = addresses.into_iter().partition(|a| a.is_ipv4() == prefer_ipv4);
+    let mut interleaved = Vec::with_capacity(preferred.len() + other.len());
+    preferred.reverse();
+    other.reverse();
+
+    loop {
+        match (preferred.pop(), other.pop()) {
+            (None, None) => break,
+            (p, o) => interleaved.extend(p.into_iter().chain(o))
+        }
+    }
+    interleaved
+}
+
+pub async fn connect(host: &String, port: u16) -> io::Result<TcpStream> {
+    let (happy_eyeballs_delay, prefer_ipv4) = match &CONFIG.load().dns {
+        Some(dns) => (dns.happy_eyeballs_delay, dns.prefer_ipv4),
+        None => (250, false)
+    };
+
+    let mut addresses = interleave(resolve(host).await?, prefer_ipv4).into_iter().peekable();
+    let mut attempts: JoinSet<io::Result<TcpStream>> = JoinSet::new();
+    let mut last_error: Option<io::Error> = None;
+
+    loop {
+        match addresses.next() {
+            Some(address) => {
+                attempts.spawn(TcpStream::connect(SocketAddr::new(address, port)));
+            },
+            None if attempts.is_empty() => break,
+            None => {}
+        }
+
+        let finished = if addresses.peek().is_some() {
+            match timeout(Duration::from_millis(happy_eyeballs_delay), attempts.join_next()).await {
+                Ok(finished) => finished,
+                Err(_) => continue
+            }
+        } else {
+            attempts.join_next().await
+        };
+
+        match finished {
+            Some(Ok(Ok(upstream))) => return Ok(upstream),
+            Some(Ok(Err(e))) => last_error = Some(e),
+            Some(Err(e)) => last_error = Some(io::Error::other(e)),
+            None => {}
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| io::Error::new(ErrorKind::NotFound, format!("{host} has no addresses"))))
+}
@@ -2,12 +2,23 @@ use std::any::Any;
 use std::collections::HashMap;
 use std::net::IpAddr;
 use std::sync::LazyLock;
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender};
+use std::time::{Duration, Instant};
 use drain_common::cookies::SetCookie;
 use drain_common::RequestData;
-use libloading::{Library, Error as LibError};
+use libloading::Library;
+#[cfg(feature = "json")]
+use serde_json::Value;
 use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::sync::{mpsc, Semaphore, SemaphorePermit};
+use tokio::task::{block_in_place, spawn_blocking};
+use tokio::time::timeout;
+use tracing::{debug, error, info};
 use crate::config::CONFIG;
+use crate::fairness::FairPool;
+use crate::metrics::{record_endpoint_rejection, record_endpoint_usage, thread_cpu_time, InFlightGuard};
 use crate::pages::internal_server_error::internal_server_error;
+use crate::util::REQUEST_INFO;
 
 type Endpoint = fn(RequestData,
                    &HashMap<String, String>,
@@ -20,32 +31,236 @@ type Endpoint = fn(RequestData,
                    &IpAddr,
                    &u16) -> Result<Option<Vec<u8>>, Box<dyn Any + Send>>;
 
-pub static ENDPOINT_LIBRARY: LazyLock<Option<Library>> = LazyLock::new(|| {
-    if let Some(endpoints_library) = &CONFIG.endpoints_library {
-        println!("Initializing the library...");
+type StreamingEndpoint = fn(RequestData,
+                            &HashMap<String, String>,
+                            &mut HashMap<String, String>,
+                            &mut HashMap<String, SetCookie>,
+                            &mut u16,
+                            &String,
+                            &IpAddr,
+                            &u16,
+                            &IpAddr,
+                            &u16,
+                            SyncSender<Vec<u8>>) -> Result<Option<Vec<u8>>, Box<dyn Any + Send>>;
+
+#[cfg(feature = "json")]
+type JsonEndpoint = fn(RequestData,
+                       &HashMap<String, String>,
+                       &mut HashMap<String, String>,
+                       &mut HashMap<String, SetCookie>,
+                       &mut u16,
+                       &String,
+                       &IpAddr,
+                       &u16,
+                       &IpAddr,
+                       &u16,
+                       Option<&Value>) -> Result<Option<Vec<u8>>, Box<dyn Any + Send>>;
+
+pub struct LoadedLibrary {
+    _library: Library,
+    endpoints: HashMap<String, Endpoint>,
+    streaming_endpoints: HashMap<String, StreamingEndpoint>,
+    #[cfg(feature = "json")]
+    json_endpoints: HashMap<String, JsonEndpoint>
+}
+
+pub struct MissingEndpoint;
+
+const ERROR_PAGES: [&str; 2] = ["not_found", "forbidden"];
+
+impl LoadedLibrary {
+    fn new(library: Library, path: &String, names: &Option<Vec<String>>) -> Self {
+        let mut endpoints = HashMap::new();
+        let mut streaming_endpoints = HashMap::new();
+        #[cfg(feature = "json")]
+        let mut json_endpoints = HashMap::new();
+        let mut missing = Vec::new();
+
+        for name in names.iter().flatten() {
+            let symbol = name.replace(|x| x == '/' || x == '\\', "::");
+            #[cfg(feature = "json")]
+            if CONFIG.load().is_json_endpoint(name) {
+                if unsafe {library.get::<JsonEndpoint>(symbol.as_bytes()).map(|e| json_endpoints.insert(name.clone(), *e)).is_err()} {
+                    missing.push(name.as_str());
+                }
+                continue;
+            }
+
+            let resolved = unsafe {
+                if CONFIG.load().is_streaming_endpoint(name) {
+                    library.get::<StreamingEndpoint>(symbol.as_bytes()).map(|e| streaming_endpoints.insert(name.clone(), *e)).is_ok()
+                } else {
+                    library.get::<Endpoint>(symbol.as_bytes()).map(|e| endpoints.insert(name.clone(), *e)).is_ok()
+                }
+            };
+
+            if !resolved {
+                missing.push(name.as_str());
+            }
+        }
+
+        for name in ERROR_PAGES {
+            if let Ok(e) = unsafe {library.get::<Endpoint>(name.as_bytes())} {
+                endpoints.insert(String::from(name), *e);
+            }
+        }
+
+        if !missing.is_empty() {
+            error!("{path} doesn't export {} listed in endpoints. \
+                    Check for typos in config.json, requests for them will be served as if they weren't endpoints.", missing.join(", "));
+        }
+
+        Self {
+            _library: library,
+            endpoints,
+            streaming_endpoints,
+            #[cfg(feature = "json")]
+            json_endpoints
+        }
+    }
+}
+
+pub struct EventStream {
+    pub receiver: mpsc::Receiver<Vec<u8>>,
+    _permit: Option<SemaphorePermit<'static>>
+}
+
+fn forward_events(receiver: Receiver<Vec<u8>>, events: mpsc::Sender<Vec<u8>>) {
+    while !events.is_closed() {
+        match receiver.recv_timeout(Duration::from_secs(1)) {
+            Ok(chunk) => {
+                if events.blocking_send(chunk).is_err() {
+                    break;
+                }
+            },
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break
+        }
+    }
+}
+
+pub static ENDPOINT_LIBRARY: LazyLock<Option<LoadedLibrary>> = LazyLock::new(|| {
+    if let Some(endpoints_library) = &CONFIG.load().endpoints_library {
+        info!("Initializing the library...");
         unsafe {
-            return match Library::new(format!("{}/{}", &CONFIG.server_root, endpoints_library)) {
+            return match Library::new(format!("{}/{}", &CONFIG.load().server_root, endpoints_library)) {
                 Ok(lib) => {
-                    println!("Success.{}", if CONFIG.be_verbose {"\r\nPUT, DELETE and PATCH are available."} else {""});
-                    Some(lib)
+                    info!("Success.");
+                    debug!("PUT, DELETE and PATCH are available.");
+                    Some(LoadedLibrary::new(lib, endpoints_library, &CONFIG.load().endpoints))
                 },
                 Err(e) => {
-                    eprintln!("[ENDPOINT_LIBRARY:{}] An error occurred while opening a dynamic library file. \
-                                                     Check if dynamic_pages_library field in config.json is correct. Proceeding without it...\n\
-                                                     Error information:\n{e}\n", line!());
-                    if CONFIG.be_verbose {
-                        println!("PUT, DELETE and PATCH are disabled.");
-                    }
+                    error!("An error occurred while opening a dynamic library file. \
+                            Check if dynamic_pages_library field in config.json is correct. Proceeding without it...\n\
+                            Error information:\n{e}\n");
+                    debug!("PUT, DELETE and PATCH are disabled.");
                     None
                 }
             }
         }
     }
 
-    println!("Library not provided, skipping...{}", if CONFIG.be_verbose {"\r\nPUT, DELETE and PATCH are disabled."} else {""});
+    info!("Library not provided, skipping...");
+    debug!("PUT, DELETE and PATCH are disabled.");
     None
 });
 
+pub static ENDPOINT_LIBRARIES: LazyLock<Vec<Option<LoadedLibrary>>> = LazyLock::new(|| {
+    let Some(endpoint_libraries) = &CONFIG.load().endpoint_libraries else {
+        return Vec::new();
+    };
+
+    endpoint_libraries.iter().map(|endpoint_library| {
+        info!("Initializing the library {}...", endpoint_library.endpoints_library);
+        unsafe {
+            match Library::new(format!("{}/{}", &CONFIG.load().server_root, endpoint_library.endpoints_library)) {
+                Ok(lib) => {
+                    info!("Success.");
+                    Some(LoadedLibrary::new(lib, &endpoint_library.endpoints_library, &endpoint_library.endpoints))
+                },
+                Err(e) => {
+                    error!("An error occurred while opening a dynamic library file. \
+                            Check if endpoints_library field in config.json endpoint_libraries is correct. Proceeding without it...\n\
+                            Error information:\n{e}\n");
+                    None
+                }
+            }
+        }
+    }).collect()
+});
+
+pub static VIRTUAL_HOST_LIBRARIES: LazyLock<Vec<Option<LoadedLibrary>>> = LazyLock::new(|| {
+    let Some(virtual_hosts) = &CONFIG.load().virtual_hosts else {
+        return Vec::new();
+    };
+
+    virtual_hosts.iter().map(|virtual_host| {
+        let endpoints_library = virtual_host.endpoints_library.as_ref()?;
+        info!("Initializing the library {endpoints_library}...");
+        unsafe {
+            match Library::new(format!("{}/{}", &CONFIG.load().server_root, endpoints_library)) {
+                Ok(lib) => {
+                    info!("Success.");
+                    Some(LoadedLibrary::new(lib, endpoints_library, &virtual_host.endpoints))
+                },
+                Err(e) => {
+                    error!("An error occurred while opening a dynamic library file. \
+                            Check if endpoints_library field in config.json virtual_hosts is correct. Proceeding without it...\n\
+                            Error information:\n{e}\n");
+                    None
+                }
+            }
+        }
+    }).collect()
+});
+
+fn resolve_endpoint_library() -> (Option<Vec<String>>, &'static Option<LoadedLibrary>) {
+    let config = CONFIG.load();
+    let virtual_host = REQUEST_INFO.try_with(|info| info.borrow().virtual_host).ok().flatten();
+    if let (Some(i), Some(virtual_hosts)) = (virtual_host, &config.virtual_hosts) {
+        if virtual_hosts.get(i).is_some_and(|virtual_host| virtual_host.endpoints_library.is_some()) {
+            return (virtual_hosts[i].endpoints.clone(), &VIRTUAL_HOST_LIBRARIES[i]);
+        }
+    }
+
+    if let Some(endpoint_libraries) = &config.endpoint_libraries {
+        let resolved = REQUEST_INFO.try_with(|info| {
+            let info = info.borrow();
+            endpoint_libraries
+                .iter()
+                .position(|endpoint_library| endpoint_library.matches(info.host.as_ref(), info.resource.as_ref()))
+        });
+
+        if let Ok(Some(i)) = resolved {
+            return (endpoint_libraries[i].endpoints.clone(), &ENDPOINT_LIBRARIES[i]);
+        }
+    }
+
+    (config.endpoints.clone(), &*ENDPOINT_LIBRARY)
+}
+
+pub fn endpoint_library() -> &'static Option<LoadedLibrary> {
+    resolve_endpoint_library().1
+}
+
+pub fn endpoint_names() -> Option<Vec<String>> {
+    resolve_endpoint_library().0
+}
+
+static ENDPOINT_PERMITS: LazyLock<HashMap<String, Semaphore>> = LazyLock::new(|| {
+    match &CONFIG.load().endpoint_concurrency {
+        Some(endpoint_concurrency) => endpoint_concurrency
+            .iter()
+            .map(|(k, v)| (String::from(k.trim_start_matches('/')), Semaphore::new(*v)))
+            .collect(),
+        None => HashMap::new()
+    }
+});
+
+static ENDPOINT_POOL: LazyLock<FairPool> = LazyLock::new(|| {
+    FairPool::new(CONFIG.load().endpoint_pool_size)
+});
+
 pub async fn endpoint<'a, T>(endpoint: &str,
                              stream: &mut T,
                              request_data: RequestData<'a>,
@@ -56,35 +271,138 @@ pub async fn endpoint<'a, T>(endpoint: &str,
                              local_ip: &IpAddr,
                              remote_ip: &IpAddr,
                              remote_port: &u16,
-                             library: &Library) -> Result<Option<Vec<u8>>, LibError>
+                             library: &LoadedLibrary) -> Result<Option<Vec<u8>>, MissingEndpoint>
 where
     T: AsyncRead + AsyncWrite + Unpin
 {
-    match unsafe {
-        let endpoint_symbol = String::from(endpoint).replace(|x| x == '/' || x == '\\', "::");
-        let e = library.get::<Endpoint>(endpoint_symbol.as_bytes())?;
+    #[cfg(feature = "json")]
+    if let Some(e) = library.json_endpoints.get(endpoint).copied() {
+        let json_body = REQUEST_INFO.try_with(|info| info.borrow().json_body.clone()).ok().flatten();
+        return run_endpoint(endpoint, stream, response_headers, status, remote_ip, |response_headers, status| {
+            e(request_data, request_headers, response_headers, set_cookie, status, &CONFIG.load().bind_host, local_ip, &CONFIG.load().bind_port, remote_ip, remote_port, json_body.as_deref())
+        }).await;
+    }
+
+    let Some(e) = library.endpoints.get(endpoint).copied() else {
+        return Err(MissingEndpoint);
+    };
+
+    run_endpoint(endpoint, stream, response_headers, status, remote_ip, |response_headers, status| {
+        e(request_data, request_headers, response_headers, set_cookie, status, &CONFIG.load().bind_host, local_ip, &CONFIG.load().bind_port, remote_ip, remote_port)
+    }).await
+}
+
+async fn run_endpoint<T, F>(endpoint: &str,
+                            stream: &mut T,
+                            response_headers: &mut HashMap<String, String>,
+                            status: &mut u16,
+                            remote_ip: &IpAddr,
+                            handler: F) -> Result<Option<Vec<u8>>, MissingEndpoint>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+    F: FnOnce(&mut HashMap<String, String>, &mut u16) -> Result<Option<Vec<u8>>, Box<dyn Any + Send>>
+{
+    let Ok(_permit) = acquire_endpoint_permit(endpoint, response_headers, status).await else {
+        return Ok(None);
+    };
+    let _in_flight = InFlightGuard::new(endpoint);
+    let _pool_permit = ENDPOINT_POOL.acquire(remote_ip).await;
 
-        e(request_data, &request_headers, response_headers, set_cookie, status, &CONFIG.bind_host, local_ip, &CONFIG.bind_port, remote_ip, remote_port)
-    } {
+    match block_in_place(|| {
+        let (started, cpu_started) = (Instant::now(), thread_cpu_time());
+        let result = handler(response_headers, status);
+        record_endpoint_usage(endpoint, started.elapsed(), thread_cpu_time().saturating_sub(cpu_started));
+        result
+    }) {
         Ok(content) => Ok(content),
         Err(e) => {
-            if let Some(e) = e.downcast_ref::<&str>() {
-                eprintln!("[endpoint():{}] A panic occurred inside the dynamic endpoint. Error information:\n{e}", line!());
-            } else if let Some(e) = e.downcast_ref::<String>() {
-                eprintln!("[endpoint():{}] A panic occurred inside the dynamic endpoint. Error information:\n{e}", line!());
-            } else {
-                eprintln!("[endpoint():{}] A panic occurred inside the dynamic endpoint. No information about the error.", line!());
-            }
+            report_endpoint_panic(stream, e).await;
+            panic!("Unrecoverable error occurred while handling connection.");
+        }
+    }
+}
 
-            eprintln!("Attempting to send Internal Server Error page to the client...");
-            if let Err(e) = internal_server_error(stream).await {
-                eprintln!("[endpoint():{}] FAILED. Error information:\n{e}", line!());
-            }
-            eprintln!("Attempting to close connection...");
-            if let Err(e) = stream.shutdown().await {
-                eprintln!("[endpoint():{}] FAILED. Error information:\n{e}", line!());
-            }
+pub async fn streaming_endpoint<'a, T>(endpoint: &str,
+                                       stream: &mut T,
+                                       request_data: RequestData<'a>,
+                                       request_headers: &HashMap<String, String>,
+                                       response_headers: &mut HashMap<String, String>,
+                                       set_cookie: &mut HashMap<String, SetCookie>,
+                                       status: &mut u16,
+                                       local_ip: &IpAddr,
+                                       remote_ip: &IpAddr,
+                                       remote_port: &u16,
+                                       library: &LoadedLibrary) -> Result<Option<(Option<Vec<u8>>, EventStream)>, MissingEndpoint>
+where
+    T: AsyncRead + AsyncWrite + Unpin
+{
+    let Some(e) = library.streaming_endpoints.get(endpoint).copied() else {
+        return Err(MissingEndpoint);
+    };
+
+    let Ok(permit) = acquire_endpoint_permit(endpoint, response_headers, status).await else {
+        return Ok(None);
+    };
+    let _in_flight = InFlightGuard::new(endpoint);
+    let _pool_permit = ENDPOINT_POOL.acquire(remote_ip).await;
+    let (sender, receiver) = sync_channel::<Vec<u8>>(64);
+
+    match block_in_place(|| {
+        let (started, cpu_started) = (Instant::now(), thread_cpu_time());
+        let result = e(request_data, &request_headers, response_headers, set_cookie, status, &CONFIG.load().bind_host, local_ip, &CONFIG.load().bind_port, remote_ip, remote_port, sender);
+        record_endpoint_usage(endpoint, started.elapsed(), thread_cpu_time().saturating_sub(cpu_started));
+        result
+    }) {
+        Ok(content) => {
+            let (events, event_receiver) = mpsc::channel::<Vec<u8>>(64);
+            spawn_blocking(move || forward_events(receiver, events));
+            Ok(Some((content, EventStream {receiver: event_receiver, _permit: permit})))
+        },
+        Err(e) => {
+            report_endpoint_panic(stream, e).await;
             panic!("Unrecoverable error occurred while handling connection.");
         }
     }
+}
+
+async fn acquire_endpoint_permit(endpoint: &str,
+                                 response_headers: &mut HashMap<String, String>,
+                                 status: &mut u16) -> Result<Option<SemaphorePermit<'static>>, ()> {
+    match ENDPOINT_PERMITS.get(endpoint) {
+        Some(permits) => {
+            match timeout(Duration::from_millis(CONFIG.load().endpoint_queue_timeout), permits.acquire()).await {
+                Ok(Ok(permit)) => Ok(Some(permit)),
+                _ => {
+                    debug!("Concurrency limit of the endpoint {endpoint} was reached. Sending 503 status to the client...");
+                    record_endpoint_rejection(endpoint);
+                    response_headers.insert(String::from("Retry-After"), String::from("1"));
+                    *status = 503;
+                    Err(())
+                }
+            }
+        },
+        None => Ok(None)
+    }
+}
+
+async fn report_endpoint_panic<T>(stream: &mut T, e: Box<dyn Any + Send>)
+where
+    T: AsyncRead + AsyncWrite + Unpin
+{
+    if let Some(e) = e.downcast_ref::<&str>() {
+        error!("A panic occurred inside the dynamic endpoint. Error information:\n{e}");
+    } else if let Some(e) = e.downcast_ref::<String>() {
+        error!("A panic occurred inside the dynamic endpoint. Error information:\n{e}");
+    } else {
+        error!("A panic occurred inside the dynamic endpoint. No information about the error.");
+    }
+
+    error!("Attempting to send Internal Server Error page to the client...");
+    if let Err(e) = internal_server_error(stream).await {
+        error!("FAILED. Error information:\n{e}");
+    }
+    error!("Attempting to close connection...");
+    if let Err(e) = stream.shutdown().await {
+        error!("FAILED. Error information:\n{e}");
+    }
 }
\ No newline at end of file
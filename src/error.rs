@@ -6,12 +6,18 @@ use std::fmt::Display;
 pub enum ServerError {
     InvalidStatusCode(u16),
     DecompressionError(IoError),
+    DecompressionLimitExceeded,
     UnsupportedEncoding,
     UnsupportedMediaType,
     MalformedPayload,
     InvalidRequest,
     BodyTooLarge,
+    HeadersTooLarge,
+    UnexpectedBody,
+    RequestTimeout,
     VersionNotSupported,
+    NotImplemented,
+    InsufficientStorage,
     #[cfg(feature = "cgi")]
     BadGateway
 }
@@ -21,12 +27,18 @@ impl Display for ServerError {
         match self {
             ServerError::InvalidStatusCode(status) => write!(f, "Invalid HTTP error status was provided to send_response(): {status}."),
             ServerError::DecompressionError(io_error) => write!(f, "An error occurred while decoding the payload: {io_error}."),
+            ServerError::DecompressionLimitExceeded => write!(f, "Decompressed payload exceeded the allowed size or compression ratio."),
             ServerError::UnsupportedEncoding => write!(f, "Payload was encoded in an unsupported encoding."),
             ServerError::UnsupportedMediaType => write!(f, "Payload contained data in an unsupported media type."),
             ServerError::MalformedPayload => write!(f, "Payload contained malformed data."),
             ServerError::InvalidRequest => write!(f, "A request was malformed."),
             ServerError::BodyTooLarge => write!(f, "Content sent by the client was too large."),
+            ServerError::HeadersTooLarge => write!(f, "Header section of a request was too large."),
+            ServerError::UnexpectedBody => write!(f, "A request carried a body, which isn't accepted for its method."),
+            ServerError::RequestTimeout => write!(f, "The client took too long to send the request body."),
             ServerError::VersionNotSupported => write!(f, "HTTP version not supported."),
+            ServerError::NotImplemented => write!(f, "A request used a transfer coding, which isn't supported."),
+            ServerError::InsufficientStorage => write!(f, "An uploaded file exceeded the space left for uploads."),
             #[cfg(feature = "cgi")]
             ServerError::BadGateway => write!(f, "A problem occurred within the gateway."),
         }
@@ -0,0 +1,147 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::{LazyLock, Mutex};
+use tokio::sync::oneshot;
+use crate::config::CONFIG;
+use crate::forwarded::Cidr;
+
+static WEIGHTS: LazyLock<Vec<(Cidr, u32)>> = LazyLock::new(|| {
+    CONFIG.load().fairness.iter()
+        .flat_map(|fairness| fairness.weights.iter())
+        .filter_map(|(cidr, weight)| Some((Cidr::parse(cidr)?, *weight)))
+        .collect()
+});
+
+fn weight(ip: &IpAddr) -> f64 {
+    WEIGHTS.iter().find(|(cidr, _)| cidr.contains(ip)).map_or(1f64, |(_, weight)| *weight as f64)
+}
+
+fn client_key(ip: &IpAddr) -> IpAddr {
+    if CONFIG.load().fairness.is_some() {*ip} else {IpAddr::V4(Ipv4Addr::UNSPECIFIED)}
+}
+
+pub fn exceeds_fair_share(connections: &HashMap<IpAddr, usize>, remote_ip: &IpAddr, max_connections: usize, available: usize) -> bool {
+    let Some(fairness) = &CONFIG.load().fairness else {
+        return false;
+    };
+
+    let in_use = max_connections.saturating_sub(available);
+    if (in_use as f64) < fairness.busy_threshold * max_connections as f64 {
+        return false;
+    }
+
+    let total_weight = connections.keys().filter(|ip| *ip != remote_ip).map(weight).sum::<f64>() + weight(remote_ip);
+    let share = (max_connections as f64 * weight(remote_ip) / total_weight).ceil() as usize;
+    connections.get(remote_ip).copied().unwrap_or(0) >= share.max(1)
+}
+
+#[derive(Default)]
+struct PoolState {
+    available: usize,
+    in_use: HashMap<IpAddr, usize>,
+    waiting: HashMap<IpAddr, VecDeque<oneshot::Sender<FairPermit>>>,
+    order: VecDeque<IpAddr>
+}
+
+pub struct FairPool {
+    state: Mutex<PoolState>
+}
+
+pub struct FairPermit {
+    pool: &'static FairPool,
+    client: IpAddr
+}
+
+impl Drop for FairPermit {
+    fn drop(&mut self) {
+        self.pool.release(self.client);
+    }
+}
+
+impl FairPool {
+    pub fn new(size: usize) -> Self {
+        Self {state: Mutex::new(PoolState {available: size, ..PoolState::default()})}
+    }
+
+    pub async fn acquire(&'static self, ip: &IpAddr) -> Option<FairPermit> {
+        let client = client_key(ip);
+
+        let receiver = {
+            let mut state = self.state.lock().ok()?;
+            if state.available > 0 && state.order.is_empty() {
+                state.available -= 1;
+                *state.in_use.entry(client).or_insert(0) += 1;
+                return Some(FairPermit {pool: self, client});
+            }
+
+            let (sender, receiver) = oneshot::channel();
+            state.waiting.entry(client).or_default().push_back(sender);
+            if !state.order.contains(&client) {
+                state.order.push_back(client);
+            }
+            receiver
+        };
+
+        receiver.await.ok()
+    }
+
+    fn next_waiter(state: &mut PoolState) -> Option<(IpAddr, oneshot::Sender<FairPermit>)> {
+        loop {
+            let position = state.order.iter()
+                .enumerate()
+                .min_by(|(i, a), (j, b)| {
+                    let a = state.in_use.get(*a).copied().unwrap_or(0) as f64 / weight(a);
+                    let b = state.in_use.get(*b).copied().unwrap_or(0) as f64 / weight(b);
+                    a.total_cmp(&b).then(i.cmp(j))
+                })
+                .map(|(position, _)| position)?;
+
+            let client = state.order.remove(position)?;
+            let Some(queue) = state.waiting.get_mut(&client) else {
+                continue;
+            };
+
+            let sender = queue.pop_front();
+            if queue.is_empty() {
+                state.waiting.remove(&client);
+            } else {
+                state.order.push_back(client);
+            }
+
+            match sender {
+                Some(sender) if !sender.is_closed() => return Some((client, sender)),
+                _ => continue
+            }
+        }
+    }
+
+    fn release(&'static self, client: IpAddr) {
+        let waiter = {
+            let Ok(mut state) = self.state.lock() else {
+                return;
+            };
+
+            if let Some(count) = state.in_use.get_mut(&client) {
+                *count = count.saturating_sub(1);
+                if *count == 0 {
+                    state.in_use.remove(&client);
+                }
+            }
+
+            match Self::next_waiter(&mut state) {
+                Some((next, sender)) => {
+                    *state.in_use.entry(next).or_insert(0) += 1;
+                    Some((next, sender))
+                },
+                None => {
+                    state.available += 1;
+                    None
+                }
+            }
+        };
+
+        if let Some((client, sender)) = waiter {
+            let _ = sender.send(FairPermit {pool: self, client});
+        }
+    }
+}
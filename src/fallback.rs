@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+use bstr::ByteSlice;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::Mutex;
+use tracing::{error, info};
+use crate::config::CONFIG;
+use crate::fs_index;
+use crate::util::ResourceType::Dynamic;
+use crate::util::{is_inside_root, send_response};
+
+static UNHEALTHY: LazyLock<Mutex<HashMap<String, Instant>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+pub async fn is_healthy(address: &String, retry_interval: u64) -> bool {
+    match UNHEALTHY.lock().await.get(address) {
+        Some(failed_at) => failed_at.elapsed() >= Duration::from_secs(retry_interval),
+        None => true
+    }
+}
+
+pub async fn mark_unhealthy(address: &String) {
+    if UNHEALTHY.lock().await.insert(address.clone(), Instant::now()).is_none() {
+        error!("{address} is down, switching to its fallback.");
+    }
+}
+
+pub async fn mark_healthy(address: &String) {
+    if UNHEALTHY.lock().await.remove(address).is_some() {
+        info!("{address} has recovered, switching back from its fallback.");
+    }
+}
+
+pub async fn serve_fallback<T>(stream: &mut T,
+                               fallback: &String,
+                               resource: &str,
+                               head: bool,
+                               headers: &HashMap<String, String>) -> Result<bool, Box<dyn Error + Send + Sync>>
+where
+    T: AsyncRead + AsyncWrite + Unpin
+{
+    let mut path = format!("{}/{}", fallback.trim_end_matches('/'), resource.trim_start_matches('/'));
+    if fs_index::is_dir(&path).await {
+        let mut index_path = None;
+        for index in &CONFIG.load().indices {
+            let candidate = format!("{}/{index}", path.trim_end_matches('/'));
+            if fs_index::is_file(&candidate).await {
+                index_path = Some(candidate);
+                break;
+            }
+        }
+
+        let Some(index_path) = index_path else {
+            return Ok(false);
+        };
+        path = index_path;
+    }
+
+    if !is_inside_root(fallback, &path).await {
+        return Ok(false);
+    }
+
+    let Ok(content) = tokio::fs::read(&path).await else {
+        return Ok(false);
+    };
+
+    let (guess, general_type) = match mime_guess::from_path(&path).first() {
+        Some(guess) => (guess.to_string(), guess.type_().to_string()),
+        None if content.is_utf8() => (String::from("text/plain"), String::from("text")),
+        None => (String::from("application/octet-stream"), String::from("application"))
+    };
+
+    let mut response_headers = HashMap::from([
+        (String::from("Cache-Control"), String::from("no-store"))
+    ]);
+
+    if head {
+        response_headers.insert(String::from("Content-Length"), content.len().to_string());
+        response_headers.insert(String::from("Content-Type"), guess);
+        send_response(stream, 200, Some(response_headers), None, None, None).await?;
+        return Ok(true);
+    }
+
+    if let Some(encoding) = CONFIG.load().get_response_encoding(&content, &guess, &general_type, headers) {
+        response_headers.insert(String::from("Content-Encoding"), String::from(encoding));
+        response_headers.insert(String::from("Vary"), String::from("Accept-Encoding"));
+    }
+    response_headers.insert(String::from("Content-Type"), guess);
+
+    send_response(stream, 200, Some(response_headers), Some(content), None, Some(Dynamic)).await?;
+    Ok(true)
+}
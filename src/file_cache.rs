@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::io;
+use std::io::{Seek, SeekFrom};
+use std::ops::{Deref, DerefMut};
+#[cfg(target_family = "unix")]
+use std::os::unix::fs::MetadataExt;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+use tokio::fs::File;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use crate::config::CONFIG;
+
+#[derive(Clone, Copy, PartialEq)]
+struct FileVersion {
+    modified: Option<SystemTime>,
+    size: u64,
+    inode: u64
+}
+
+impl FileVersion {
+    fn new(meta: &Metadata) -> Self {
+        Self {
+            modified: meta.modified().ok(),
+            size: meta.len(),
+            #[cfg(target_family = "unix")]
+            inode: meta.ino(),
+            #[cfg(not(target_family = "unix"))]
+            inode: 0
+        }
+    }
+}
+
+struct CachedFile {
+    file: std::fs::File,
+    version: FileVersion,
+    permit: OwnedSemaphorePermit,
+    last_used: u64
+}
+
+static FILE_HANDLE_LIMIT: LazyLock<Arc<Semaphore>> = LazyLock::new(|| {
+    Arc::new(Semaphore::new(CONFIG.load().file_handle_limit.min(Semaphore::MAX_PERMITS)))
+});
+
+static FILE_CACHE: LazyLock<Mutex<HashMap<String, CachedFile>>> = LazyLock::new(|| {
+    Mutex::new(HashMap::new())
+});
+
+static FILE_CACHE_CLOCK: AtomicU64 = AtomicU64::new(0);
+
+pub struct OpenFile {
+    file: Option<File>,
+    path: String,
+    version: FileVersion,
+    permit: Option<OwnedSemaphorePermit>
+}
+
+impl Deref for OpenFile {
+    type Target = File;
+
+    fn deref(&self) -> &File {
+        self.file.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for OpenFile {
+    fn deref_mut(&mut self) -> &mut File {
+        self.file.as_mut().unwrap()
+    }
+}
+
+impl Drop for OpenFile {
+    fn drop(&mut self) {
+        let (Some(file), Some(permit)) = (self.file.take(), self.permit.take()) else {
+            return;
+        };
+
+        if CONFIG.load().file_cache_size == 0 {
+            return;
+        }
+
+        let Ok(mut file) = file.try_into_std() else {
+            return;
+        };
+
+        if file.seek(SeekFrom::Start(0)).is_err() {
+            return;
+        }
+
+        let Ok(mut cache) = FILE_CACHE.lock() else {
+            return;
+        };
+
+        if !cache.contains_key(&self.path) && cache.len() >= CONFIG.load().file_cache_size {
+            evict_least_recently_used(&mut cache);
+        }
+
+        cache.insert(self.path.clone(), CachedFile {
+            file,
+            version: self.version,
+            permit,
+            last_used: FILE_CACHE_CLOCK.fetch_add(1, Ordering::Relaxed)
+        });
+    }
+}
+
+fn evict_least_recently_used(cache: &mut HashMap<String, CachedFile>) -> bool {
+    let least_recently_used = cache.iter()
+        .min_by_key(|(_, cached)| cached.last_used)
+        .map(|(path, _)| path.clone());
+
+    match least_recently_used {
+        Some(path) => cache.remove(&path).is_some(),
+        None => false
+    }
+}
+
+async fn acquire_permit() -> io::Result<OwnedSemaphorePermit> {
+    loop {
+        if let Ok(permit) = FILE_HANDLE_LIMIT.clone().try_acquire_owned() {
+            return Ok(permit);
+        }
+
+        let evicted = match FILE_CACHE.lock() {
+            Ok(mut cache) => evict_least_recently_used(&mut cache),
+            Err(_) => false
+        };
+
+        if !evicted {
+            return FILE_HANDLE_LIMIT.clone().acquire_owned().await
+                .map_err(io::Error::other);
+        }
+    }
+}
+
+pub async fn open_file(path: &String) -> io::Result<OpenFile> {
+    let version = FileVersion::new(&tokio::fs::metadata(path).await?);
+
+    let cached = match FILE_CACHE.lock() {
+        Ok(mut cache) => cache.remove(path),
+        Err(_) => None
+    };
+
+    if let Some(cached) = cached {
+        if cached.version == version {
+            return Ok(OpenFile {
+                file: Some(File::from_std(cached.file)),
+                path: path.clone(),
+                version,
+                permit: Some(cached.permit)
+            });
+        }
+    }
+
+    let permit = acquire_permit().await?;
+    let file = File::open(path).await?;
+
+    Ok(OpenFile {
+        file: Some(file),
+        path: path.clone(),
+        version,
+        permit: Some(permit)
+    })
+}
+
+pub fn purge<F>(matches: F)
+where
+    F: Fn(&str) -> bool
+{
+    if let Ok(mut cache) = FILE_CACHE.lock() {
+        cache.retain(|path, _| !matches(path));
+    }
+}
@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+use bstr::ByteSlice;
+use crate::config::CONFIG;
+use crate::util::REQUEST_INFO;
+
+pub fn sub_filters_apply(content_type: &str) -> bool {
+    let Some(sub_filters) = &CONFIG.load().sub_filters else {
+        return false;
+    };
+
+    let resource = REQUEST_INFO.try_with(|info| info.borrow().resource.clone()).ok().flatten();
+    sub_filters.iter().any(|sub_filter| sub_filter.applies_to(resource.as_ref(), content_type))
+}
+
+pub fn apply_sub_filters(headers: &mut HashMap<String, String>, content: &mut Vec<u8>) -> bool {
+    let Some(sub_filters) = &CONFIG.load().sub_filters else {
+        return false;
+    };
+
+    let Some(content_type) = headers.iter().find(|(k, _)| k.eq_ignore_ascii_case("content-type")).map(|(_, v)| v) else {
+        return false;
+    };
+
+    let resource = REQUEST_INFO.try_with(|info| info.borrow().resource.clone()).ok().flatten();
+    let mut modified = false;
+
+    for sub_filter in sub_filters {
+        if !sub_filter.applies_to(resource.as_ref(), content_type) {
+            continue;
+        }
+
+        let filtered = match sub_filter.compiled_regex() {
+            Some(regex) if sub_filter.once => regex.replace(content, sub_filter.replace.as_bytes()).into_owned(),
+            Some(regex) => regex.replace_all(content, sub_filter.replace.as_bytes()).into_owned(),
+            None if sub_filter.regex => continue,
+            None if sub_filter.once => content.replacen(sub_filter.search.as_bytes(), sub_filter.replace.as_bytes(), 1),
+            None => content.replace(sub_filter.search.as_bytes(), sub_filter.replace.as_bytes())
+        };
+
+        if filtered != *content {
+            *content = filtered;
+            modified = true;
+        }
+    }
+
+    if modified {
+        if let Some((_, etag)) = headers.iter_mut().find(|(k, _)| k.eq_ignore_ascii_case("etag")) {
+            if !etag.starts_with("W/") {
+                etag.insert_str(0, "W/");
+            }
+        }
+    }
+
+    modified
+}
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::LazyLock;
+use crate::config::CONFIG;
+
+pub const PEER_ADDRESS_HEADER: &str = "x-drain-peer-addr";
+
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u32
+}
+
+impl Cidr {
+    pub fn parse(cidr: &str) -> Option<Self> {
+        let (network, prefix) = match cidr.split_once('/') {
+            Some((network, prefix)) => (network.parse::<IpAddr>().ok()?, Some(prefix.parse::<u32>().ok()?)),
+            None => (cidr.parse::<IpAddr>().ok()?, None)
+        };
+
+        let max_prefix = if network.is_ipv4() {32} else {128};
+        let prefix = prefix.unwrap_or(max_prefix);
+        if prefix > max_prefix {
+            return None;
+        }
+
+        Some(Self {network, prefix})
+    }
+
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            },
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            },
+            _ => false
+        }
+    }
+}
+
+static TRUSTED_PROXIES: LazyLock<Vec<Cidr>> = LazyLock::new(|| {
+    CONFIG.load().trusted_proxies.iter().flatten().filter_map(|cidr| Cidr::parse(cidr)).collect()
+});
+
+fn is_trusted(ip: &IpAddr) -> bool {
+    TRUSTED_PROXIES.iter().any(|cidr| cidr.contains(ip))
+}
+
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+
+    node.parse::<IpAddr>().ok()
+        .or_else(|| node.parse::<SocketAddr>().ok().map(|address| address.ip()))
+        .or_else(|| node.trim_start_matches('[').split_once(']').and_then(|(ip, _)| ip.parse::<IpAddr>().ok()))
+}
+
+fn forwarded_chain(headers: &HashMap<String, String>) -> Vec<&str> {
+    if let Some(forwarded) = headers.get("forwarded") {
+        return forwarded.split(',')
+            .flat_map(|element| element.split(';'))
+            .filter_map(|pair| pair.trim().split_once('='))
+            .filter(|(name, _)| name.eq_ignore_ascii_case("for"))
+            .map(|(_, value)| value)
+            .collect();
+    }
+
+    match headers.get("x-forwarded-for") {
+        Some(x_forwarded_for) => x_forwarded_for.split(',').collect(),
+        None => Vec::new()
+    }
+}
+
+pub fn client_ip(peer: &IpAddr, headers: Option<&HashMap<String, String>>) -> IpAddr {
+    let Some(headers) = headers else {
+        return *peer;
+    };
+
+    if !is_trusted(peer) {
+        return *peer;
+    }
+
+    let mut client = *peer;
+    for node in forwarded_chain(headers).into_iter().rev() {
+        let Some(ip) = parse_node(node) else {
+            break;
+        };
+
+        client = ip;
+        if !is_trusted(&ip) {
+            break;
+        }
+    }
+
+    client
+}
@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::fs::{metadata, read_dir};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{LazyLock, Mutex, RwLock};
+use tokio::task::spawn_blocking;
+use notify::{recommended_watcher, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tracing::{debug, warn};
+use crate::config::CONFIG;
+use crate::util::{remove_etags, ETAGS};
+#[cfg(target_family = "unix")]
+use crate::util::CHROOT;
+
+#[derive(Clone, Copy, PartialEq)]
+enum EntryKind {
+    File,
+    Dir
+}
+
+pub struct FsIndex {
+    entries: RwLock<HashMap<PathBuf, EntryKind>>,
+    root: PathBuf,
+    _watcher: Mutex<RecommendedWatcher>
+}
+
+pub static FS_INDEX: LazyLock<Option<FsIndex>> = LazyLock::new(|| {
+    if !CONFIG.load().watch_document_root {
+        return None;
+    }
+
+    #[cfg(target_family = "unix")]
+    let root = if *&*CHROOT {PathBuf::from("/")} else {PathBuf::from(&CONFIG.load().document_root)};
+    #[cfg(not(target_family = "unix"))]
+    let root = PathBuf::from(&CONFIG.load().document_root);
+
+    let mut watcher = match recommended_watcher(|event| {
+        if let Some(fs_index) = &*FS_INDEX {
+            fs_index.handle_event(event);
+        }
+    }) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            warn!("An error occurred while setting up a watch on the document root.\n\
+                   Error information:\n{e}\n\
+                   Continuing without it...");
+            return None;
+        }
+    };
+
+    if let Err(e) = watcher.watch(&root, RecursiveMode::Recursive) {
+        warn!("An error occurred while setting up a watch on the document root.\n\
+               Error information:\n{e}\n\
+               Continuing without it...");
+        return None;
+    }
+
+    let mut entries = HashMap::new();
+    index_tree(&root, &mut entries);
+
+    debug!("Watching the document root, {} entries indexed.", entries.len());
+
+    Some(FsIndex {entries: RwLock::new(entries), root, _watcher: Mutex::new(watcher)})
+});
+
+fn index_tree(path: &Path, entries: &mut HashMap<PathBuf, EntryKind>) {
+    let Ok(meta) = metadata(path) else {
+        return;
+    };
+
+    if meta.is_file() {
+        entries.insert(path.to_path_buf(), EntryKind::File);
+        return;
+    }
+
+    if !meta.is_dir() {
+        return;
+    }
+
+    entries.insert(path.to_path_buf(), EntryKind::Dir);
+    if let Ok(dir) = read_dir(path) {
+        for entry in dir.filter_map(Result::ok) {
+            index_tree(&entry.path(), entries);
+        }
+    }
+}
+
+impl FsIndex {
+    fn handle_event(&self, event: notify::Result<Event>) {
+        let Ok(mut entries) = self.entries.write() else {
+            return;
+        };
+
+        let event = match event {
+            Ok(event) if !event.need_rescan() => event,
+            _ => {
+                entries.clear();
+                index_tree(&self.root, &mut entries);
+                ETAGS.blocking_lock().clear();
+                return;
+            }
+        };
+
+        if matches!(event.kind, EventKind::Access(_)) {
+            return;
+        }
+
+        let mut etags = ETAGS.blocking_lock();
+        for path in event.paths {
+            entries.retain(|p, _| !p.starts_with(&path));
+            remove_etags(&mut etags, |p| Path::new(p).starts_with(&path));
+            index_tree(&path, &mut entries);
+        }
+    }
+
+    fn lookup(&self, path: &Path) -> Option<Option<EntryKind>> {
+        if path.components().any(|c| c == Component::ParentDir) || !path.starts_with(&self.root) {
+            return None;
+        }
+
+        let entries = self.entries.read().ok()?;
+        Some(entries.get(path).copied())
+    }
+}
+
+pub async fn off_worker<F, T>(f: F) -> Option<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static
+{
+    spawn_blocking(f).await.ok()
+}
+
+fn stat(path: PathBuf) -> Option<EntryKind> {
+    let meta = metadata(path).ok()?;
+    if meta.is_file() {
+        Some(EntryKind::File)
+    } else if meta.is_dir() {
+        Some(EntryKind::Dir)
+    } else {
+        None
+    }
+}
+
+async fn entry_kind(path: &String, stat: fn(PathBuf) -> Option<EntryKind>) -> Option<EntryKind> {
+    match FS_INDEX.as_ref().and_then(|fs_index| fs_index.lookup(Path::new(path))) {
+        Some(kind) => kind,
+        None => {
+            let path = PathBuf::from(path);
+            off_worker(move || stat(path)).await.flatten()
+        }
+    }
+}
+
+pub async fn is_file(path: &String) -> bool {
+    entry_kind(path, stat).await == Some(EntryKind::File)
+}
+
+pub async fn is_dir(path: &String) -> bool {
+    entry_kind(path, stat).await == Some(EntryKind::Dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+    use tokio::spawn;
+    use tokio::time::sleep;
+    use super::{entry_kind, stat, EntryKind};
+
+    fn slow_stat(path: PathBuf) -> Option<EntryKind> {
+        thread::sleep(Duration::from_millis(300));
+        stat(path)
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn slow_filesystem_does_not_stall_other_tasks() {
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let ticker = spawn({
+            let ticks = ticks.clone();
+            async move {
+                loop {
+                    sleep(Duration::from_millis(10)).await;
+                    ticks.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        });
+
+        let manifest = format!("{}/Cargo.toml", env!("CARGO_MANIFEST_DIR"));
+        let file = entry_kind(&manifest, slow_stat).await;
+        let dir = entry_kind(&String::from(env!("CARGO_MANIFEST_DIR")), slow_stat).await;
+        let missing = entry_kind(&format!("{manifest}.missing"), slow_stat).await;
+        ticker.abort();
+
+        assert!(file == Some(EntryKind::File));
+        assert!(dir == Some(EntryKind::Dir));
+        assert!(missing.is_none());
+        assert!(ticks.load(Ordering::Relaxed) >= 20);
+    }
+}
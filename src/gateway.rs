@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::io;
+use std::io::ErrorKind;
+use tokio::io::{copy, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+#[cfg(target_family = "unix")]
+use tokio::net::UnixStream;
+
+const FCGI_VERSION_1: u8 = 1;
+const FCGI_BEGIN_REQUEST: u8 = 1;
+const FCGI_END_REQUEST: u8 = 3;
+const FCGI_PARAMS: u8 = 4;
+const FCGI_STDIN: u8 = 5;
+const FCGI_STDOUT: u8 = 6;
+const FCGI_STDERR: u8 = 7;
+const FCGI_RESPONDER: u16 = 1;
+const FCGI_REQUEST_ID: u16 = 1;
+const FCGI_MAX_RECORD_LENGTH: usize = 65535;
+
+pub struct GatewayOutput {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub success: bool
+}
+
+pub async fn fastcgi<R>(address: &String, envs: &HashMap<String, String>, data: R, length: u64) -> io::Result<GatewayOutput>
+where
+    R: AsyncRead + Unpin
+{
+    #[cfg(target_family = "unix")]
+    if let Some(path) = address.strip_prefix("unix:") {
+        return fastcgi_exchange(UnixStream::connect(path).await?, envs, data, length).await;
+    }
+
+    fastcgi_exchange(TcpStream::connect(address.as_str()).await?, envs, data, length).await
+}
+
+pub async fn scgi<R>(address: &String, envs: &HashMap<String, String>, data: R, length: u64) -> io::Result<GatewayOutput>
+where
+    R: AsyncRead + Unpin
+{
+    #[cfg(target_family = "unix")]
+    if let Some(path) = address.strip_prefix("unix:") {
+        return scgi_exchange(UnixStream::connect(path).await?, envs, data, length).await;
+    }
+
+    scgi_exchange(TcpStream::connect(address.as_str()).await?, envs, data, length).await
+}
+
+fn fastcgi_record(record_type: u8, content: &[u8]) -> Vec<u8> {
+    let mut record = Vec::with_capacity(8 + content.len());
+    record.push(FCGI_VERSION_1);
+    record.push(record_type);
+    record.extend_from_slice(&FCGI_REQUEST_ID.to_be_bytes());
+    record.extend_from_slice(&(content.len() as u16).to_be_bytes());
+    record.extend_from_slice(&[0, 0]);
+    record.extend_from_slice(content);
+    record
+}
+
+fn fastcgi_length(length: usize, buffer: &mut Vec<u8>) {
+    if length < 128 {
+        buffer.push(length as u8);
+    } else {
+        buffer.extend_from_slice(&((length as u32) | 0x80000000).to_be_bytes());
+    }
+}
+
+async fn fastcgi_exchange<S, R>(mut stream: S, envs: &HashMap<String, String>, data: R, length: u64) -> io::Result<GatewayOutput>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    R: AsyncRead + Unpin
+{
+    let mut begin_request = FCGI_RESPONDER.to_be_bytes().to_vec();
+    begin_request.extend_from_slice(&[0; 6]);
+    let mut request = fastcgi_record(FCGI_BEGIN_REQUEST, &begin_request);
+
+    let mut params = Vec::new();
+    for (name, value) in envs {
+        fastcgi_length(name.len(), &mut params);
+        fastcgi_length(value.len(), &mut params);
+        params.extend_from_slice(name.as_bytes());
+        params.extend_from_slice(value.as_bytes());
+    }
+
+    for chunk in params.chunks(FCGI_MAX_RECORD_LENGTH) {
+        request.extend(fastcgi_record(FCGI_PARAMS, chunk));
+    }
+    request.extend(fastcgi_record(FCGI_PARAMS, &[]));
+    stream.write_all(&request).await?;
+
+    let mut data = data.take(length);
+    let mut chunk = vec![0u8; FCGI_MAX_RECORD_LENGTH.min(length as usize)];
+    loop {
+        let read = data.read(&mut chunk).await?;
+        if read == 0 {
+            break;
+        }
+        stream.write_all(&fastcgi_record(FCGI_STDIN, &chunk[..read])).await?;
+    }
+    stream.write_all(&fastcgi_record(FCGI_STDIN, &[])).await?;
+    stream.flush().await?;
+
+    let mut output = GatewayOutput {stdout: Vec::new(), stderr: Vec::new(), success: false};
+    let mut header = [0u8; 8];
+
+    loop {
+        stream.read_exact(&mut header).await?;
+        let content_length = u16::from_be_bytes([header[4], header[5]]) as usize;
+        let padding_length = header[6] as usize;
+
+        let mut content = vec![0u8; content_length + padding_length];
+        stream.read_exact(&mut content).await?;
+        content.truncate(content_length);
+
+        match header[1] {
+            FCGI_STDOUT => output.stdout.extend(content),
+            FCGI_STDERR => output.stderr.extend(content),
+            FCGI_END_REQUEST => {
+                if content.len() < 5 {
+                    return Err(io::Error::new(ErrorKind::InvalidData, "malformed FastCGI end request record"));
+                }
+
+                output.success = content[0..4] == [0, 0, 0, 0] && content[4] == 0;
+                return Ok(output);
+            },
+            _ => {}
+        }
+    }
+}
+
+async fn scgi_exchange<S, R>(mut stream: S, envs: &HashMap<String, String>, data: R, length: u64) -> io::Result<GatewayOutput>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    R: AsyncRead + Unpin
+{
+    let mut headers = Vec::new();
+    for (name, value) in [("CONTENT_LENGTH", length.to_string()), ("SCGI", String::from("1"))] {
+        headers.extend_from_slice(format!("{name}\0{value}\0").as_bytes());
+    }
+
+    for (name, value) in envs.iter().filter(|(k, _)| k.as_str() != "CONTENT_LENGTH" && k.as_str() != "SCGI") {
+        headers.extend_from_slice(format!("{name}\0{value}\0").as_bytes());
+    }
+
+    let mut request = format!("{}:", headers.len()).into_bytes();
+    request.extend(headers);
+    request.push(b',');
+
+    stream.write_all(&request).await?;
+    copy(&mut data.take(length), &mut stream).await?;
+    stream.flush().await?;
+
+    let mut stdout = Vec::new();
+    stream.read_to_end(&mut stdout).await?;
+
+    Ok(GatewayOutput {stdout, stderr: Vec::new(), success: true})
+}
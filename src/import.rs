@@ -0,0 +1,436 @@
+use std::fs::read_to_string;
+use std::path::Path;
+use serde_json::{json, Map, Value};
+use tracing::error;
+
+struct Directive {
+    line: usize,
+    name: String,
+    args: Vec<String>,
+    context: Option<String>
+}
+
+struct Import {
+    config: Map<String, Value>,
+    warnings: Vec<String>
+}
+
+impl Import {
+    fn new() -> Self {
+        let mut config = Map::new();
+        config.insert(String::from("bind_host"), json!("0.0.0.0"));
+        config.insert(String::from("bind_port"), json!(80));
+        config.insert(String::from("document_root"), json!("htdocs"));
+        config.insert(String::from("server_root"), json!("server"));
+        config.insert(String::from("indices"), json!(["index.html"]));
+
+        Self {config, warnings: Vec::new()}
+    }
+
+    fn warn(&mut self, directive: &Directive, reason: &str) {
+        self.warnings.push(format!("line {}: {} {} - {reason}", directive.line, directive.name, directive.args.join(" ")));
+    }
+
+    fn object(&mut self, key: &str) -> &mut Map<String, Value> {
+        let value = self.config.entry(key).or_insert_with(|| json!({}));
+        if !value.is_object() {
+            *value = json!({});
+        }
+        value.as_object_mut().unwrap()
+    }
+
+    fn deny(&mut self, directive: &Directive, status: u16) {
+        let Some(patterns) = directive.context.as_deref().map(location_patterns) else {
+            self.warn(directive, "denying access to the whole site isn't supported, use access_control for specific paths");
+            return;
+        };
+
+        let Some(patterns) = patterns else {
+            self.warn(directive, "this location can't be translated into a glob pattern");
+            return;
+        };
+
+        let access_control = self.object("access_control");
+        access_control.entry("deny_action").or_insert(json!(status));
+        let list = access_control.entry("list").or_insert_with(|| json!({}));
+        for pattern in patterns {
+            list[pattern] = json!("deny");
+        }
+    }
+
+    fn index_of(&mut self, directive: &Directive, enabled: bool) {
+        let patterns = match directive.context.as_deref().map(location_patterns) {
+            None => Some(vec![String::from("**")]),
+            Some(patterns) => patterns
+        };
+
+        let Some(patterns) = patterns else {
+            self.warn(directive, "this location can't be translated into a glob pattern");
+            return;
+        };
+
+        let index_of_page_rules = self.object("index_of_page_rules");
+        for pattern in patterns {
+            index_of_page_rules.insert(pattern, json!(enabled));
+        }
+    }
+
+    fn header(&mut self, directive: &Directive, name: Option<&String>, value: Option<&String>) {
+        match (name, value) {
+            (Some(name), Some(value)) if directive.context.is_none() => {
+                self.object("global_response_headers").insert(name.clone(), json!(value));
+            },
+            (Some(_), Some(_)) => self.warn(directive, "only server-wide response headers are supported"),
+            _ => self.warn(directive, "malformed directive")
+        }
+    }
+
+    fn listen(&mut self, directive: &Directive) {
+        let Some(address) = directive.args.first() else {
+            return self.warn(directive, "malformed directive");
+        };
+
+        let (host, port) = match address.rsplit_once(':') {
+            Some((host, port)) => (Some(host.trim_matches(|c: char| c == '[' || c == ']')), port),
+            None if address.chars().all(|c| c.is_ascii_digit()) => (None, address.as_str()),
+            None => (Some(address.as_str()), "80")
+        };
+
+        let Ok(port) = port.parse::<u16>() else {
+            return self.warn(directive, "malformed port");
+        };
+
+        if directive.args.iter().any(|a| a.eq("ssl")) {
+            let https = self.object("https");
+            https.insert(String::from("enabled"), json!(true));
+            https.insert(String::from("bind_port"), json!(port));
+        } else {
+            self.config.insert(String::from("bind_port"), json!(port));
+        }
+
+        if let Some(host) = host.filter(|h| !h.eq(&"*")) {
+            self.config.insert(String::from("bind_host"), json!(host));
+        }
+    }
+
+    fn certificate(&mut self, directive: &Directive, key: &str) {
+        let Some(path) = directive.args.first() else {
+            return self.warn(directive, "malformed directive");
+        };
+
+        self.object("https").insert(String::from(key), json!(path));
+    }
+
+    fn body_limit(&mut self, directive: &Directive) {
+        match directive.args.first().and_then(|a| parse_size(a)) {
+            Some(0) => {},
+            Some(size) => {
+                self.config.insert(String::from("max_content_length"), json!(size));
+            },
+            None => self.warn(directive, "malformed size")
+        }
+    }
+
+    fn print(self) {
+        println!("{}", serde_json::to_string_pretty(&Value::Object(self.config)).unwrap_or_default());
+
+        if !self.warnings.is_empty() {
+            error!("The following directives couldn't be translated and have to be migrated by hand:");
+            for warning in self.warnings {
+                error!("  {warning}");
+            }
+        }
+    }
+}
+
+fn location_patterns(location: &str) -> Option<Vec<String>> {
+    let mut parts = location.split_whitespace();
+    let (modifier, path) = match (parts.next(), parts.next()) {
+        (Some(modifier), Some(path)) => (modifier, path),
+        (Some(path), None) => ("", path),
+        _ => return None
+    };
+
+    match modifier {
+        "~" | "~*" => None,
+        _ if path.trim_matches('/').is_empty() => Some(vec![String::from("**")]),
+        "=" => Some(vec![String::from(path.trim_matches('/'))]),
+        "files" => Some(vec![format!("**/{}", path.trim_matches('/'))]),
+        _ if path.ends_with('/') => Some(vec![format!("{}/**", path.trim_matches('/'))]),
+        _ => {
+            let path = path.trim_matches('/');
+            Some(vec![String::from(path), format!("{path}/**")])
+        }
+    }
+}
+
+fn parse_size(size: &str) -> Option<u64> {
+    let size = size.to_lowercase();
+    let (number, multiplier) = match size.chars().last()? {
+        'k' => (&size[..size.len() - 1], 1024),
+        'm' => (&size[..size.len() - 1], 1048576),
+        'g' => (&size[..size.len() - 1], 1073741824),
+        _ => (&size[..], 1)
+    };
+
+    number.parse::<u64>().ok().map(|n| n * multiplier)
+}
+
+fn parse_nginx(content: &str) -> Vec<Directive> {
+    let mut directives = Vec::new();
+    let mut blocks: Vec<Option<String>> = Vec::new();
+    let mut tokens: Vec<String> = Vec::new();
+    let mut token = String::new();
+    let mut quote: Option<char> = None;
+    let mut line = 1;
+    let mut start_line = 1;
+
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\n' {
+            line += 1;
+        }
+
+        if let Some(q) = quote {
+            if c == q {
+                quote = None;
+            } else {
+                token.push(c);
+            }
+            continue;
+        }
+
+        match c {
+            '#' => {
+                while chars.peek().is_some_and(|c| *c != '\n') {
+                    chars.next();
+                }
+            },
+            '"' | '\'' => {
+                quote = Some(c);
+            },
+            ';' | '{' | '}' => {
+                if !token.is_empty() {
+                    tokens.push(token.clone());
+                    token.clear();
+                }
+
+                let context = blocks.iter().rev().flatten().next().cloned();
+                match c {
+                    ';' if !tokens.is_empty() => {
+                        directives.push(Directive {line: start_line, name: tokens.remove(0), args: tokens.clone(), context});
+                    },
+                    '{' => {
+                        let block = if tokens.first().is_some_and(|t| t.eq("location")) {Some(tokens[1..].join(" "))} else {None};
+                        blocks.push(block);
+                    },
+                    '}' => {
+                        blocks.pop();
+                    },
+                    _ => {}
+                }
+                tokens.clear();
+            },
+            c if c.is_whitespace() => {
+                if !token.is_empty() {
+                    tokens.push(token.clone());
+                    token.clear();
+                }
+            },
+            c => {
+                if tokens.is_empty() && token.is_empty() {
+                    start_line = line;
+                }
+                token.push(c);
+            }
+        }
+    }
+
+    directives
+}
+
+fn parse_apache(content: &str) -> Vec<Directive> {
+    let mut directives = Vec::new();
+    let mut blocks: Vec<Option<String>> = Vec::new();
+
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line.starts_with("</") {
+            blocks.pop();
+            continue;
+        }
+
+        if let Some(block) = line.strip_prefix('<') {
+            let block = block.trim_end_matches('>');
+            let (kind, path) = block.split_once(char::is_whitespace).unwrap_or((block, ""));
+            let path = path.trim().trim_matches('"');
+            let context = match kind.to_lowercase().as_str() {
+                "location" => Some(String::from(path)),
+                "directory" => Some(format!("directory {path}")),
+                "files" => Some(format!("files {path}")),
+                "locationmatch" | "directorymatch" | "filesmatch" => Some(format!("~ {path}")),
+                _ => None
+            };
+            blocks.push(context);
+            continue;
+        }
+
+        let mut args = Vec::new();
+        let mut arg = String::new();
+        let mut quoted = false;
+        for c in line.chars() {
+            match c {
+                '"' => quoted = !quoted,
+                c if c.is_whitespace() && !quoted => {
+                    if !arg.is_empty() {
+                        args.push(arg.clone());
+                        arg.clear();
+                    }
+                },
+                c => arg.push(c)
+            }
+        }
+        if !arg.is_empty() {
+            args.push(arg);
+        }
+
+        let name = args.remove(0).to_lowercase();
+        let context = blocks.iter().rev().flatten().next().cloned();
+        directives.push(Directive {line: i + 1, name, args, context});
+    }
+
+    directives
+}
+
+fn import_nginx(directives: Vec<Directive>) -> Import {
+    let mut import = Import::new();
+
+    for directive in directives {
+        match directive.name.as_str() {
+            "root" if directive.context.is_none() => {
+                if let Some(root) = directive.args.first() {
+                    import.config.insert(String::from("document_root"), json!(root));
+                }
+            },
+            "index" => {
+                import.config.insert(String::from("indices"), json!(directive.args));
+            },
+            "listen" => import.listen(&directive),
+            "autoindex" => import.index_of(&directive, directive.args.first().is_some_and(|a| a.eq("on"))),
+            "deny" if directive.args.first().is_some_and(|a| a.eq("all")) => import.deny(&directive, 403),
+            "allow" => {},
+            "add_header" => import.header(&directive, directive.args.first(), directive.args.get(1)),
+            "client_max_body_size" => import.body_limit(&directive),
+            "ssl_certificate" => import.certificate(&directive, "ssl_certificate_file"),
+            "ssl_certificate_key" => import.certificate(&directive, "ssl_private_key_file"),
+            "server_tokens" => {
+                import.config.insert(String::from("enable_server_header"), json!(!directive.args.first().is_some_and(|a| a.eq("off"))));
+            },
+            "gzip" if directive.args.first().is_some_and(|a| a.eq("on")) => {
+                import.config.insert(String::from("encoding"), json!({
+                    "use_encoding": "gzip",
+                    "supported_encodings": ["gzip", "br"]
+                }));
+            },
+            "server_name" | "location" | "server" | "http" | "events" | "worker_processes" | "worker_connections" | "sendfile" | "include" => {},
+            "return" | "rewrite" => import.warn(&directive, "redirects have to be implemented in an endpoint"),
+            "types" | "default_type" => import.warn(&directive, "media types are guessed from file extensions"),
+            "auth_basic" | "auth_basic_user_file" => import.warn(&directive, "basic authentication has to be implemented in an endpoint"),
+            "root" => import.warn(&directive, "per-location document roots aren't supported"),
+            _ => import.warn(&directive, "no equivalent setting")
+        }
+    }
+
+    import
+}
+
+fn import_apache(directives: Vec<Directive>) -> Import {
+    let mut import = Import::new();
+
+    for mut directive in directives {
+        if let Some(directory) = directive.context.as_ref().and_then(|c| c.strip_prefix("directory ")) {
+            let document_root = import.config["document_root"].as_str().unwrap_or_default().trim_end_matches('/');
+            directive.context = match directory.strip_prefix(document_root) {
+                Some(path) if !document_root.is_empty() => Some(format!("{path}/")),
+                _ => Some(String::from("~ outside of document root"))
+            };
+        }
+
+        let first = directive.args.first().map(|a| a.to_lowercase());
+        match directive.name.as_str() {
+            "documentroot" => {
+                if let Some(root) = directive.args.first() {
+                    import.config.insert(String::from("document_root"), json!(root));
+                }
+            },
+            "directoryindex" => {
+                import.config.insert(String::from("indices"), json!(directive.args));
+            },
+            "listen" => import.listen(&directive),
+            "options" => {
+                if directive.args.iter().any(|a| a.eq_ignore_ascii_case("+indexes") || a.eq_ignore_ascii_case("indexes")) {
+                    import.index_of(&directive, true);
+                } else if directive.args.iter().any(|a| a.eq_ignore_ascii_case("-indexes")) {
+                    import.index_of(&directive, false);
+                }
+            },
+            "deny" if directive.args.get(1).is_some_and(|a| a.eq_ignore_ascii_case("all")) => import.deny(&directive, 403),
+            "require" if first.as_deref() == Some("all") && directive.args.get(1).is_some_and(|a| a.eq_ignore_ascii_case("denied")) => {
+                import.deny(&directive, 403)
+            },
+            "require" if first.as_deref() == Some("all") => {},
+            "order" | "allow" => {},
+            "header" if matches!(first.as_deref(), Some("set") | Some("always")) => {
+                let offset = if first.as_deref() == Some("always") {2} else {1};
+                import.header(&directive, directive.args.get(offset), directive.args.get(offset + 1))
+            },
+            "limitrequestbody" => import.body_limit(&directive),
+            "sslcertificatefile" => import.certificate(&directive, "ssl_certificate_file"),
+            "sslcertificatekeyfile" => import.certificate(&directive, "ssl_private_key_file"),
+            "sslengine" => {
+                import.object("https").insert(String::from("enabled"), json!(first.as_deref() == Some("on")));
+            },
+            "servertokens" | "serversignature" => {
+                if first.as_deref().is_some_and(|a| a.eq("prod") || a.eq("off")) {
+                    import.config.insert(String::from("enable_server_header"), json!(false));
+                }
+            },
+            "servername" | "serveralias" => {},
+            "redirect" | "redirectmatch" | "redirectpermanent" | "rewriteengine" | "rewriterule" | "rewritecond" => {
+                import.warn(&directive, "redirects have to be implemented in an endpoint")
+            },
+            "addtype" | "forcetype" | "defaulttype" => import.warn(&directive, "media types are guessed from file extensions"),
+            "authtype" | "authname" | "authuserfile" | "authgroupfile" | "require" => {
+                import.warn(&directive, "basic authentication has to be implemented in an endpoint")
+            },
+            _ => import.warn(&directive, "no equivalent setting")
+        }
+    }
+
+    import
+}
+
+pub fn import(path: &str) {
+
+    let content = match read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            error!("An error occurred while reading {path}.\n\
+                    Error information:\n{e}");
+            return;
+        }
+    };
+
+    let file_name = Path::new(path).file_name().map(|f| f.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let import = if file_name.ends_with(".htaccess") || file_name.starts_with("httpd") || file_name.starts_with("apache") {
+        import_apache(parse_apache(&content))
+    } else {
+        import_nginx(parse_nginx(&content))
+    };
+
+    import.print();
+}
@@ -0,0 +1,49 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::net::IpAddr;
+use tokio::io::{AsyncRead, AsyncWrite};
+use crate::config::{DenyAction, CONFIG};
+use crate::fs_index;
+use crate::util::ResourceType::Dynamic;
+use crate::util::{document_root, file_integrity, is_inside_document_root, send_response};
+
+pub fn integrity_resource(resource: &String) -> Option<&str> {
+    let config = CONFIG.load();
+    let integrity_endpoint = config.integrity_endpoint.as_ref()?;
+    let prefix = integrity_endpoint.trim_end_matches('/');
+
+    resource.strip_prefix(prefix)?.strip_prefix('/')
+}
+
+pub async fn send_integrity<T>(stream: &mut T, asset: &str, remote_ip: &IpAddr) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+    T: AsyncRead + AsyncWrite + Unpin
+{
+    let document_root = &document_root();
+
+    let asset = String::from(asset.trim_start_matches('/'));
+
+    if let Some(denial) = CONFIG.load().denial(&asset, "GET", remote_ip) {
+        let status = match denial.action {
+            DenyAction::Redirect {..} => 404,
+            action => action.status()
+        };
+        return send_response(stream, status, None, None, None, None).await;
+    }
+
+    let path = format!("{document_root}/{asset}");
+    if asset.is_empty() || !fs_index::is_file(&path).await || !is_inside_document_root(&path).await {
+        return send_response(stream, 404, None, None, None, None).await;
+    }
+
+    let Some(integrity) = file_integrity(&path).await else {
+        return send_response(stream, 500, None, None, None, None).await;
+    };
+
+    let response_headers = HashMap::from([
+        (String::from("Content-Type"), String::from("text/plain; charset=utf-8")),
+        (String::from("Cache-Control"), String::from("no-cache"))
+    ]);
+
+    send_response(stream, 200, Some(response_headers), Some(Vec::from(integrity)), None, Some(Dynamic)).await
+}
@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+use std::fs::{create_dir_all, read_dir, read_to_string, rename, File, OpenOptions};
+use std::io::{self, Write};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use chrono::Utc;
+use openssl::sha::sha256;
+use serde::{Deserialize, Serialize};
+use tokio::task::block_in_place;
+use crate::config::{Journal, CONFIG};
+use crate::pages::request_id;
+use crate::requests::Request;
+use crate::subsystems::is_available;
+use crate::util::REQUEST_INFO;
+
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub seq: u64,
+    pub time: String,
+    pub request_id: Option<String>,
+    pub remote_ip: String,
+    pub method: String,
+    pub host: Option<String>,
+    pub resource: String,
+    pub content_type: Option<String>,
+    pub content_length: Option<String>,
+    pub body_sha256: Option<String>,
+    pub prev: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>
+}
+
+impl Entry {
+    fn compute_hash(&self) -> io::Result<String> {
+        let unsealed = Entry {hash: None, ..self.clone()};
+        let serialized = serde_json::to_string(&unsealed)?;
+        Ok(hex(&sha256(format!("{}\n{serialized}", self.prev).as_bytes())))
+    }
+}
+
+struct Chain {
+    file: File,
+    size: u64,
+    seq: u64,
+    last_hash: String
+}
+
+static CHAINS: LazyLock<Mutex<HashMap<&'static str, Chain>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn current_path(journal: &Journal, chain: &str) -> PathBuf {
+    Path::new(&journal.directory).join(format!("{chain}.log"))
+}
+
+fn segments(directory: &str, chain: &str) -> io::Result<Vec<PathBuf>> {
+    let prefix = format!("{chain}-");
+    let mut segments: Vec<PathBuf> = read_dir(directory)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with(&prefix) && name.ends_with(".log")))
+        .collect();
+    segments.sort();
+
+    let current = Path::new(directory).join(format!("{chain}.log"));
+    if current.exists() {
+        segments.push(current);
+    }
+    Ok(segments)
+}
+
+fn last_entry(directory: &str, chain: &str) -> io::Result<Option<Entry>> {
+    for segment in segments(directory, chain)?.iter().rev() {
+        let content = read_to_string(segment)?;
+        if let Some(line) = content.lines().rev().find(|line| !line.is_empty()) {
+            return serde_json::from_str(line).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+        }
+    }
+    Ok(None)
+}
+
+fn open_chain(journal: &Journal, chain: &str) -> io::Result<Chain> {
+    create_dir_all(&journal.directory)?;
+    let (seq, last_hash) = match last_entry(&journal.directory, chain)? {
+        Some(entry) => (entry.seq, entry.hash.unwrap_or_default()),
+        None => (0, String::from(GENESIS))
+    };
+
+    let file = OpenOptions::new().create(true).append(true).open(current_path(journal, chain))?;
+    let size = file.metadata()?.len();
+    Ok(Chain {file, size, seq, last_hash})
+}
+
+fn rotate(journal: &Journal, chain_name: &str, chain: &mut Chain) -> io::Result<()> {
+    let current = current_path(journal, chain_name);
+    rename(&current, Path::new(&journal.directory).join(format!("{chain_name}-{:020}.log", chain.seq)))?;
+    chain.file = OpenOptions::new().create(true).append(true).open(current)?;
+    chain.size = 0;
+    Ok(())
+}
+
+fn append(journal: &Journal, chain_name: &'static str, mut entry: Entry) -> io::Result<()> {
+    let mut chains = CHAINS.lock().unwrap_or_else(|e| e.into_inner());
+    if !chains.contains_key(chain_name) {
+        chains.insert(chain_name, open_chain(journal, chain_name)?);
+    }
+    let Some(chain) = chains.get_mut(chain_name) else {
+        return Err(io::Error::other("journal chain is missing"));
+    };
+
+    entry.seq = chain.seq + 1;
+    entry.prev = chain.last_hash.clone();
+    let hash = entry.compute_hash()?;
+    entry.hash = Some(hash.clone());
+    let line = format!("{}\n", serde_json::to_string(&entry)?);
+
+    if chain.size > 0 && chain.size + line.len() as u64 > journal.max_size {
+        rotate(journal, chain_name, chain)?;
+    }
+
+    chain.file.write_all(line.as_bytes())?;
+    chain.file.sync_data()?;
+    chain.size += line.len() as u64;
+    chain.seq = entry.seq;
+    chain.last_hash = hash;
+    Ok(())
+}
+
+pub fn close() {
+    CHAINS.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+pub fn record(request: &Request, remote_ip: &IpAddr) -> io::Result<()> {
+    let Some(journal) = &CONFIG.load().journal else {
+        return Ok(());
+    };
+
+    if !is_available("journal") {
+        return Ok(());
+    }
+
+    let (method, resource, headers) = match request {
+        Request::Post {resource, headers, ..} => ("POST", resource, headers),
+        Request::Put {resource, headers, ..} => ("PUT", resource, headers),
+        Request::Delete {resource, headers, ..} => ("DELETE", resource, headers),
+        Request::Patch {resource, headers, ..} => ("PATCH", resource, headers),
+        _ => return Ok(())
+    };
+
+    if !journal.methods.iter().any(|m| m.eq_ignore_ascii_case(method)) {
+        return Ok(());
+    }
+
+    let (https, body_sha256) = REQUEST_INFO.try_with(|info| {
+        let info = info.borrow();
+        (info.https, info.body_sha256.clone())
+    }).unwrap_or((false, None));
+
+    let entry = Entry {
+        seq: 0,
+        time: Utc::now().to_rfc3339(),
+        request_id: request_id(),
+        remote_ip: remote_ip.to_string(),
+        method: String::from(method),
+        host: headers.get("host").cloned(),
+        resource: resource.clone(),
+        content_type: headers.get("content-type").cloned(),
+        content_length: headers.get("content-length").cloned(),
+        body_sha256,
+        prev: String::new(),
+        hash: None
+    };
+
+    block_in_place(|| append(journal, if https {"https"} else {"http"}, entry))
+}
+
+fn verified_entries(journal: &Journal, chain: &str) -> io::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    let mut last_hash = String::from(GENESIS);
+    let mut last_seq = 0;
+
+    for segment in segments(&journal.directory, chain)? {
+        for (line_number, line) in read_to_string(&segment)?.lines().enumerate().filter(|(_, line)| !line.is_empty()) {
+            let tampered = |reason: &str| io::Error::new(io::ErrorKind::InvalidData,
+                format!("{}:{}: {reason}", segment.display(), line_number + 1));
+
+            let entry: Entry = serde_json::from_str(line).map_err(|_| tampered("malformed entry"))?;
+            if entry.seq != last_seq + 1 {
+                return Err(tampered(&format!("expected sequence number {}, found {}", last_seq + 1, entry.seq)));
+            }
+            if entry.prev != last_hash {
+                return Err(tampered("the chain is broken, prev doesn't match the hash of the previous entry"));
+            }
+
+            let hash = entry.compute_hash()?;
+            if entry.hash.as_ref() != Some(&hash) {
+                return Err(tampered("the entry was modified, its hash doesn't match"));
+            }
+
+            last_hash = hash;
+            last_seq = entry.seq;
+            entries.push(entry);
+        }
+    }
+
+    Ok(entries)
+}
+
+pub fn verify() -> io::Result<()> {
+    let Some(journal) = &CONFIG.load().journal else {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "journal isn't set in config.json"));
+    };
+
+    for chain in ["http", "https"] {
+        let entries = verified_entries(journal, chain)?;
+        println!("{chain}: {} entries, the chain is intact.", entries.len());
+    }
+    Ok(())
+}
+
+pub fn export(since: Option<u64>) -> io::Result<()> {
+    let Some(journal) = &CONFIG.load().journal else {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "journal isn't set in config.json"));
+    };
+
+    let mut stdout = io::stdout().lock();
+    for chain in ["http", "https"] {
+        for entry in verified_entries(journal, chain)? {
+            if since.is_some_and(|since| entry.seq < since) {
+                continue;
+            }
+            let mut value = serde_json::to_value(&entry)?;
+            value["chain"] = serde_json::json!(chain);
+            writeln!(stdout, "{value}")?;
+        }
+    }
+    Ok(())
+}
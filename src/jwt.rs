@@ -0,0 +1,392 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+use chrono::Utc;
+use openssl::base64;
+use openssl::bn::BigNum;
+use openssl::ec::{EcGroup, EcKey};
+use openssl::ecdsa::EcdsaSig;
+use openssl::error::ErrorStack;
+use openssl::hash::MessageDigest;
+use openssl::memcmp;
+use openssl::nid::Nid;
+use openssl::pkey::{Id, PKey, Public};
+use openssl::rsa::{Padding, Rsa};
+use openssl::sign::{RsaPssSaltlen, Signer, Verifier};
+use openssl::x509::X509;
+use serde_json::{Map, Value};
+use tokio::time::timeout;
+use tracing::{error, warn};
+use crate::config::{Jwt, CONFIG};
+use crate::fs_index::off_worker;
+use crate::proxy::{parse_response, send_to_server, UpstreamServer};
+use crate::requests::Request;
+
+pub const SUBJECT_HEADER: &str = "x-drain-jwt-sub";
+pub const CLAIMS_HEADER: &str = "x-drain-jwt-claims";
+
+pub const JWT_HEADERS: [&str; 2] = [SUBJECT_HEADER, CLAIMS_HEADER];
+
+const MIN_JWKS_REFETCH_INTERVAL: u64 = 60;
+
+#[derive(Clone)]
+enum Key {
+    Public(PKey<Public>),
+    Secret(Vec<u8>)
+}
+
+struct JwkSet {
+    fetched: Option<Instant>,
+    keys: Vec<(Option<String>, Option<String>, PKey<Public>)>
+}
+
+static JWKS: LazyLock<RwLock<JwkSet>> = LazyLock::new(|| RwLock::new(JwkSet {fetched: None, keys: Vec::new()}));
+
+static STATIC_KEY: LazyLock<Mutex<Option<(String, Option<SystemTime>, Arc<Key>)>>> = LazyLock::new(|| Mutex::new(None));
+
+fn base64url_decode(data: &str) -> Option<Vec<u8>> {
+    let mut data = data.replace('-', "+").replace('_', "/");
+    while data.len() % 4 != 0 {
+        data.push('=');
+    }
+    base64::decode_block(&data).ok()
+}
+
+fn digest(alg: &str) -> Option<MessageDigest> {
+    match alg.get(2..)? {
+        "256" => Some(MessageDigest::sha256()),
+        "384" => Some(MessageDigest::sha384()),
+        "512" => Some(MessageDigest::sha512()),
+        _ => None
+    }
+}
+
+fn jwk_key(jwk: &Value) -> Option<PKey<Public>> {
+    let component = |name: &str| base64url_decode(jwk.get(name)?.as_str()?);
+
+    match (jwk.get("kty")?.as_str()?, jwk.get("crv").and_then(Value::as_str)) {
+        ("RSA", _) => {
+            let rsa = Rsa::from_public_components(BigNum::from_slice(&component("n")?).ok()?, BigNum::from_slice(&component("e")?).ok()?).ok()?;
+            PKey::from_rsa(rsa).ok()
+        },
+        ("EC", Some(crv)) => {
+            let nid = match crv {
+                "P-256" => Nid::X9_62_PRIME256V1,
+                "P-384" => Nid::SECP384R1,
+                "P-521" => Nid::SECP521R1,
+                _ => return None
+            };
+            let group = EcGroup::from_curve_name(nid).ok()?;
+            let (x, y) = (BigNum::from_slice(&component("x")?).ok()?, BigNum::from_slice(&component("y")?).ok()?);
+            PKey::from_ec_key(EcKey::from_public_key_affine_coordinates(&group, &x, &y).ok()?).ok()
+        },
+        ("OKP", Some("Ed25519")) => PKey::public_key_from_raw_bytes(&component("x")?, Id::ED25519).ok(),
+        _ => None
+    }
+}
+
+async fn fetch_jwks(url: &str) -> Result<Vec<(Option<String>, Option<String>, PKey<Public>)>, Box<dyn Error + Send + Sync>> {
+    let Some(server) = UpstreamServer::parse(url) else {
+        return Err(Box::new(io::Error::new(io::ErrorKind::InvalidInput, format!("{url} isn't a valid URL"))));
+    };
+
+    let path = if server.path.is_empty() {"/"} else {server.path.as_str()};
+    let message = format!("GET {path} HTTP/1.1\r\n\
+                           Host: {}\r\n\
+                           User-Agent: Drain/{}\r\n\
+                           Accept: application/json\r\n\
+                           Connection: close\r\n\r\n", server.host_header(), env!("CARGO_PKG_VERSION"));
+
+    let response = match timeout(Duration::from_secs(CONFIG.load().request_timeout), send_to_server(&server, message.as_bytes())).await {
+        Ok(response) => response?,
+        Err(_) => return Err(Box::new(io::Error::new(io::ErrorKind::TimedOut, format!("{} didn't respond in {} seconds", server.host, CONFIG.load().request_timeout))))
+    };
+
+    let body = match parse_response(&response, false) {
+        Some(response) if response.status == 200 => response.body,
+        Some(response) => return Err(Box::new(io::Error::other(format!("{} responded with {} status", server.host, response.status)))),
+        None => return Err(Box::new(io::Error::new(io::ErrorKind::InvalidData, format!("{} sent a malformed response", server.host))))
+    };
+
+    let jwks: Value = serde_json::from_slice(&body)?;
+    let Some(keys) = jwks.get("keys").and_then(Value::as_array) else {
+        return Err(Box::new(io::Error::new(io::ErrorKind::InvalidData, format!("{url} didn't return a JSON Web Key Set"))));
+    };
+
+    Ok(keys.iter()
+        .filter(|jwk| jwk.get("use").and_then(Value::as_str).is_none_or(|usage| usage.eq("sig")))
+        .filter_map(|jwk| {
+            let kid = jwk.get("kid").and_then(Value::as_str).map(String::from);
+            let alg = jwk.get("alg").and_then(Value::as_str).map(String::from);
+            Some((kid, alg, jwk_key(jwk)?))
+        })
+        .collect())
+}
+
+async fn refresh_jwks(jwt: &Jwt, url: &str, kid_missing: bool) {
+    let fetched = JWKS.read().ok().and_then(|jwks| jwks.fetched);
+    let due = match fetched {
+        Some(fetched) if kid_missing => fetched.elapsed() >= Duration::from_secs(MIN_JWKS_REFETCH_INTERVAL),
+        Some(fetched) => fetched.elapsed() >= Duration::from_secs(jwt.jwks_refresh_interval),
+        None => true
+    };
+
+    if !due {
+        return;
+    }
+
+    match fetch_jwks(url).await {
+        Ok(keys) => {
+            if let Ok(mut jwks) = JWKS.write() {
+                *jwks = JwkSet {fetched: Some(Instant::now()), keys};
+            }
+        },
+        Err(e) => {
+            if let Ok(mut jwks) = JWKS.write() {
+                jwks.fetched = Some(Instant::now());
+            }
+            warn!("An error occurred while fetching the JSON Web Key Set from {url}.\n\
+                   Error information:\n{e}\n\
+                   Continuing with the previously fetched keys...");
+        }
+    }
+}
+
+fn jwks_keys(kid: Option<&str>, alg: &str) -> Vec<Arc<Key>> {
+    let Ok(jwks) = JWKS.read() else {
+        return Vec::new();
+    };
+
+    jwks.keys.iter()
+        .filter(|(key_id, _, _)| kid.is_none_or(|kid| key_id.as_deref() == Some(kid)))
+        .filter(|(_, key_alg, _)| key_alg.as_deref().is_none_or(|key_alg| key_alg.eq(alg)))
+        .map(|(_, _, key)| Arc::new(Key::Public(key.clone())))
+        .collect()
+}
+
+async fn static_key(file: &String, secret: bool) -> Option<Arc<Key>> {
+    let path = file.clone();
+    let modified = off_worker(move || fs::metadata(path).and_then(|metadata| metadata.modified()).ok()).await.flatten();
+
+    if let Some((path, loaded, key)) = &*STATIC_KEY.lock().ok()? {
+        if path.eq(file) && loaded.is_some() && *loaded == modified {
+            return Some(key.clone());
+        }
+    }
+
+    let path = file.clone();
+    let content = match off_worker(move || fs::read(path)).await? {
+        Ok(content) => content,
+        Err(e) => {
+            error!("An error occurred while reading {file}.\n\
+                    Error information:\n{e}");
+            return None;
+        }
+    };
+
+    let key = if secret {
+        let length = content.iter().rposition(|b| !b.is_ascii_whitespace()).map_or(0, |i| i + 1);
+        Key::Secret(content[..length].to_vec())
+    } else {
+        match PKey::public_key_from_pem(&content).or_else(|_| X509::from_pem(&content).and_then(|certificate| certificate.public_key())) {
+            Ok(key) => Key::Public(key),
+            Err(e) => {
+                error!("{file} doesn't contain a PEM public key or certificate.\n\
+                        Error information:\n{e}");
+                return None;
+            }
+        }
+    };
+
+    let key = Arc::new(key);
+    *STATIC_KEY.lock().ok()? = Some((file.clone(), modified, key.clone()));
+    Some(key)
+}
+
+fn verify_signature(alg: &str, key: &Key, data: &[u8], signature: &[u8]) -> Result<bool, ErrorStack> {
+    match (alg, key, digest(alg)) {
+        ("HS256" | "HS384" | "HS512", Key::Secret(secret), Some(md)) => {
+            let hmac = PKey::hmac(secret)?;
+            let expected = Signer::new(md, &hmac)?.sign_oneshot_to_vec(data)?;
+            Ok(expected.len() == signature.len() && memcmp::eq(&expected, signature))
+        },
+        ("RS256" | "RS384" | "RS512", Key::Public(key), Some(md)) if key.id() == Id::RSA => {
+            Verifier::new(md, key)?.verify_oneshot(signature, data)
+        },
+        ("PS256" | "PS384" | "PS512", Key::Public(key), Some(md)) if key.id() == Id::RSA => {
+            let mut verifier = Verifier::new(md, key)?;
+            verifier.set_rsa_padding(Padding::PKCS1_PSS)?;
+            verifier.set_rsa_pss_saltlen(RsaPssSaltlen::DIGEST_LENGTH)?;
+            verifier.set_rsa_mgf1_md(md)?;
+            verifier.verify_oneshot(signature, data)
+        },
+        ("ES256" | "ES384" | "ES512", Key::Public(key), Some(md)) if key.id() == Id::EC => {
+            let (nid, size) = match alg {
+                "ES256" => (Nid::X9_62_PRIME256V1, 32),
+                "ES384" => (Nid::SECP384R1, 48),
+                _ => (Nid::SECP521R1, 66)
+            };
+            if key.ec_key()?.group().curve_name() != Some(nid) || signature.len() != size * 2 {
+                return Ok(false);
+            }
+
+            let der = EcdsaSig::from_private_components(BigNum::from_slice(&signature[..size])?, BigNum::from_slice(&signature[size..])?)?.to_der()?;
+            Verifier::new(md, key)?.verify_oneshot(&der, data)
+        },
+        ("EdDSA", Key::Public(key), _) if key.id() == Id::ED25519 => {
+            Verifier::new_without_digest(key)?.verify_oneshot(signature, data)
+        },
+        _ => Ok(false)
+    }
+}
+
+fn challenge(error: Option<&str>) -> HashMap<String, String> {
+    let value = match error {
+        Some(description) => format!("Bearer error=\"invalid_token\", error_description=\"{description}\""),
+        None => String::from("Bearer")
+    };
+
+    HashMap::from([(String::from("WWW-Authenticate"), value)])
+}
+
+fn check_claims(jwt: &Jwt, claims: &Map<String, Value>) -> Result<(), (u16, HashMap<String, String>)> {
+    let now = Utc::now().timestamp() as f64;
+    let leeway = jwt.leeway as f64;
+
+    match claims.get("exp").map(Value::as_f64) {
+        Some(Some(exp)) if now > exp + leeway => return Err((401, challenge(Some("the token has expired")))),
+        Some(None) => return Err((401, challenge(Some("the exp claim isn't a number")))),
+        _ => {}
+    }
+
+    match claims.get("nbf").map(Value::as_f64) {
+        Some(Some(nbf)) if now < nbf - leeway => return Err((401, challenge(Some("the token isn't valid yet")))),
+        Some(None) => return Err((401, challenge(Some("the nbf claim isn't a number")))),
+        _ => {}
+    }
+
+    if let Some(issuer) = &jwt.issuer {
+        if claims.get("iss").and_then(Value::as_str).is_none_or(|iss| !iss.eq(issuer)) {
+            return Err((403, challenge(Some("the token wasn't issued by the expected issuer"))));
+        }
+    }
+
+    if let Some(audience) = &jwt.audience {
+        let matches = match claims.get("aud") {
+            Some(Value::String(aud)) => aud.eq(audience),
+            Some(Value::Array(aud)) => aud.iter().any(|aud| aud.as_str() == Some(audience.as_str())),
+            _ => false
+        };
+
+        if !matches {
+            return Err((403, challenge(Some("the token isn't intended for this audience"))));
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn validate(jwt: &Jwt, request: &Request) -> Result<Map<String, Value>, (u16, HashMap<String, String>)> {
+    let Some(token) = request.headers()
+        .and_then(|headers| headers.get("authorization"))
+        .and_then(|authorization| authorization.trim().split_once(' '))
+        .and_then(|(scheme, token)| scheme.eq_ignore_ascii_case("Bearer").then_some(token.trim())) else {
+        return Err((401, challenge(None)));
+    };
+
+    let malformed = || (401, challenge(Some("the token is malformed")));
+
+    let mut parts = token.split('.');
+    let (Some(encoded_header), Some(encoded_claims), Some(encoded_signature), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+        return Err(malformed());
+    };
+
+    let header: Map<String, Value> = base64url_decode(encoded_header).and_then(|header| serde_json::from_slice(&header).ok()).ok_or_else(malformed)?;
+    let claims: Map<String, Value> = base64url_decode(encoded_claims).and_then(|claims| serde_json::from_slice(&claims).ok()).ok_or_else(malformed)?;
+    let signature = base64url_decode(encoded_signature).ok_or_else(malformed)?;
+
+    if header.contains_key("crit") {
+        return Err((401, challenge(Some("the token uses unsupported extensions"))));
+    }
+
+    let Some(alg) = header.get("alg").and_then(Value::as_str) else {
+        return Err(malformed());
+    };
+    let kid = header.get("kid").and_then(Value::as_str);
+
+    let keys = match (&jwt.jwks_url, &jwt.key_file, &jwt.secret_file) {
+        (Some(jwks_url), _, _) => {
+            refresh_jwks(jwt, jwks_url, false).await;
+            let mut keys = jwks_keys(kid, alg);
+            if keys.is_empty() {
+                refresh_jwks(jwt, jwks_url, true).await;
+                keys = jwks_keys(kid, alg);
+            }
+            keys
+        },
+        (None, Some(key_file), _) => static_key(key_file, false).await.into_iter().collect(),
+        (None, None, Some(secret_file)) => static_key(secret_file, true).await.into_iter().collect(),
+        (None, None, None) => Vec::new()
+    };
+
+    let data = format!("{encoded_header}.{encoded_claims}");
+    if !keys.iter().any(|key| verify_signature(alg, key, data.as_bytes(), &signature).unwrap_or(false)) {
+        return Err((401, challenge(Some("the token's signature couldn't be verified"))));
+    }
+
+    check_claims(jwt, &claims)?;
+    Ok(claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use super::*;
+
+    fn base64url_encode(data: &[u8]) -> String {
+        base64::encode_block(data).trim_end_matches('=').replace('+', "-").replace('/', "_")
+    }
+
+    fn token(secret: &[u8]) -> String {
+        let data = format!("{}.{}", base64url_encode(br#"{"alg":"HS256","typ":"JWT"}"#), base64url_encode(br#"{"sub":"alice"}"#));
+        let signature = Signer::new(MessageDigest::sha256(), &PKey::hmac(secret).unwrap()).unwrap().sign_oneshot_to_vec(data.as_bytes()).unwrap();
+        format!("{data}.{}", base64url_encode(&signature))
+    }
+
+    fn request(token: &str) -> Request {
+        let request = format!("GET /api HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer {token}\r\n\r\n");
+        Request::parse_from_string(&request, request.as_bytes(), &mut true).unwrap()
+    }
+
+    #[tokio::test]
+    async fn secret_file_is_cached_and_reloaded_after_a_change() {
+        let path = std::env::temp_dir().join(format!("drain-jwt-secret-{}", std::process::id()));
+        fs::write(&path, "first\n").unwrap();
+        let jwt = Jwt {
+            paths: vec![String::from("/api")],
+            issuer: None,
+            audience: None,
+            jwks_url: None,
+            key_file: None,
+            secret_file: Some(path.to_string_lossy().into_owned()),
+            leeway: 0,
+            jwks_refresh_interval: 0
+        };
+        let secret_file = jwt.secret_file.as_ref().unwrap();
+
+        assert!(validate(&jwt, &request(&token(b"first"))).await.is_ok());
+        let (first, cached) = (static_key(secret_file, true).await.unwrap(), static_key(secret_file, true).await.unwrap());
+        assert!(Arc::ptr_eq(&first, &cached));
+
+        fs::write(&path, "second\n").unwrap();
+        File::options().write(true).open(&path).unwrap().set_modified(SystemTime::now() + Duration::from_secs(60)).unwrap();
+
+        assert!(validate(&jwt, &request(&token(b"first"))).await.is_err());
+        assert!(validate(&jwt, &request(&token(b"second"))).await.is_ok());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,170 @@
+use std::fs::{metadata, read_dir, remove_file, rename, File, OpenOptions};
+use std::io::{self, Write};
+#[cfg(target_family = "unix")]
+use std::os::unix::fs::MetadataExt;
+#[cfg(target_family = "unix")]
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+#[cfg(target_family = "unix")]
+use std::process;
+use chrono::Local;
+use tracing::Level;
+use crate::config::{Rotation, RotationInterval};
+
+#[cfg(target_family = "unix")]
+const SYSLOG_SOCKET: &str = "/dev/log";
+#[cfg(target_family = "unix")]
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+#[cfg(target_family = "unix")]
+const SYSLOG_FACILITY_DAEMON: u8 = 3;
+
+fn period(interval: RotationInterval) -> String {
+    let format = match interval {
+        RotationInterval::Hourly => "%Y%m%d%H",
+        RotationInterval::Daily => "%Y%m%d",
+        RotationInterval::Weekly => "%G%V"
+    };
+    Local::now().format(format).to_string()
+}
+
+pub struct RotatingFile {
+    path: PathBuf,
+    rotation: Rotation,
+    file: File,
+    size: u64,
+    period: Option<String>
+}
+
+impl RotatingFile {
+    pub fn open(path: &str, rotation: &Rotation) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        let period = rotation.interval.map(period);
+        Ok(Self {path: PathBuf::from(path), rotation: rotation.clone(), file, size, period})
+    }
+
+    fn is_due(&self, length: usize) -> bool {
+        let oversized = self.rotation.max_size.is_some_and(|max_size| self.size > 0 && self.size + length as u64 > max_size);
+        let elapsed = self.rotation.interval.is_some_and(|interval| self.period.as_ref().is_some_and(|current| period(interval).ne(current)));
+        oversized || elapsed
+    }
+
+    #[cfg(target_family = "unix")]
+    fn was_rotated_elsewhere(&self) -> bool {
+        match (metadata(&self.path), self.file.metadata()) {
+            (Ok(on_disk), Ok(open)) => on_disk.ino() != open.ino() || on_disk.dev() != open.dev(),
+            _ => true
+        }
+    }
+
+    #[cfg(not(target_family = "unix"))]
+    fn was_rotated_elsewhere(&self) -> bool {
+        metadata(&self.path).is_err()
+    }
+
+    fn reopen(&mut self) -> io::Result<()> {
+        self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.size = self.file.metadata()?.len();
+        self.period = self.rotation.interval.map(period);
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if !self.was_rotated_elsewhere() {
+            rename(&self.path, format!("{}.{}", self.path.display(), Local::now().format("%Y%m%d-%H%M%S-%3f")))?;
+        }
+        self.reopen()?;
+        self.prune()
+    }
+
+    fn prune(&self) -> io::Result<()> {
+        let (Some(keep), Some(name)) = (self.rotation.keep, self.path.file_name().and_then(|name| name.to_str())) else {
+            return Ok(());
+        };
+
+        let directory = self.path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let prefix = format!("{name}.");
+        let mut rotated: Vec<PathBuf> = read_dir(directory)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(&prefix)))
+            .collect();
+        rotated.sort();
+
+        let excess = rotated.len().saturating_sub(keep);
+        for path in &rotated[..excess] {
+            remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    fn append(&mut self, content: &[u8]) -> io::Result<()> {
+        if self.is_due(content.len()) {
+            self.rotate()?;
+        }
+
+        self.file.write_all(content)?;
+        self.size += content.len() as u64;
+        Ok(())
+    }
+}
+
+#[cfg(target_family = "unix")]
+fn severity(level: Level) -> u8 {
+    match level {
+        Level::ERROR => 3,
+        Level::WARN => 4,
+        Level::INFO => 6,
+        _ => 7
+    }
+}
+
+#[cfg(target_family = "unix")]
+fn connect(socket_path: &str) -> io::Result<UnixDatagram> {
+    let socket = UnixDatagram::unbound()?;
+    socket.connect(socket_path)?;
+    Ok(socket)
+}
+
+pub enum Destination {
+    File(RotatingFile),
+    #[cfg(target_family = "unix")]
+    Syslog(UnixDatagram),
+    #[cfg(target_family = "unix")]
+    Journald(UnixDatagram)
+}
+
+impl Destination {
+    pub fn open(output: &str, rotation: &Rotation) -> io::Result<Self> {
+        match output {
+            #[cfg(target_family = "unix")]
+            "syslog" => connect(SYSLOG_SOCKET).map(Self::Syslog),
+            #[cfg(target_family = "unix")]
+            "journald" => connect(JOURNALD_SOCKET).map(Self::Journald),
+            path => RotatingFile::open(path, rotation).map(Self::File)
+        }
+    }
+
+    #[cfg_attr(not(target_family = "unix"), allow(unused_variables))]
+    pub fn write(&mut self, level: Level, message: &[u8]) -> io::Result<()> {
+        match self {
+            Self::File(file) => file.append(message),
+            #[cfg(target_family = "unix")]
+            Self::Syslog(socket) => {
+                let mut datagram = format!("<{}>drain[{}]: ", SYSLOG_FACILITY_DAEMON * 8 + severity(level), process::id()).into_bytes();
+                datagram.extend_from_slice(message.trim_ascii_end());
+                socket.send(&datagram).map(|_| ())
+            },
+            #[cfg(target_family = "unix")]
+            Self::Journald(socket) => {
+                let message = message.trim_ascii_end();
+                let mut datagram = format!("PRIORITY={}\nSYSLOG_IDENTIFIER=drain\nMESSAGE\n", severity(level)).into_bytes();
+                datagram.extend_from_slice(&(message.len() as u64).to_le_bytes());
+                datagram.extend_from_slice(message);
+                datagram.push(b'\n');
+                socket.send(&datagram).map(|_| ())
+            }
+        }
+    }
+}
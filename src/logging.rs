@@ -0,0 +1,128 @@
+use std::io::{self, stderr, stdout, Write};
+use std::sync::{Mutex, OnceLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::{error, Level, Metadata};
+use tracing_subscriber::{fmt, reload, EnvFilter, Registry};
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::prelude::*;
+use crate::config::{LogFormat, CONFIG};
+use crate::log_output::Destination;
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+static CONSOLE: AtomicBool = AtomicBool::new(true);
+
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+static OUTPUT: Mutex<Option<Destination>> = Mutex::new(None);
+
+struct Output;
+
+struct OutputWriter {
+    level: Level,
+    buffer: Vec<u8>
+}
+
+impl<'a> MakeWriter<'a> for Output {
+    type Writer = OutputWriter;
+
+    fn make_writer(&'a self) -> OutputWriter {
+        OutputWriter {level: Level::INFO, buffer: Vec::new()}
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> OutputWriter {
+        OutputWriter {level: *meta.level(), buffer: Vec::new()}
+    }
+}
+
+impl Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for OutputWriter {
+    fn drop(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+
+        if let Some(destination) = OUTPUT.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            if destination.write(self.level, &self.buffer).is_ok() {
+                return;
+            }
+        }
+
+        let _ = if self.level <= Level::WARN {
+            stderr().write_all(&self.buffer)
+        } else {
+            stdout().write_all(&self.buffer)
+        };
+    }
+}
+
+pub fn init_logging() {
+    let (filter, handle) = reload::Layer::new(EnvFilter::new("info"));
+
+    let console = fmt::layer()
+        .with_writer(Output)
+        .with_filter(filter_fn(|_| !JSON.load(Ordering::Relaxed) && CONSOLE.load(Ordering::Relaxed)));
+    let text = fmt::layer()
+        .with_ansi(false)
+        .with_writer(Output)
+        .with_filter(filter_fn(|_| !JSON.load(Ordering::Relaxed) && !CONSOLE.load(Ordering::Relaxed)));
+    let json = fmt::layer()
+        .json()
+        .with_writer(Output)
+        .with_filter(filter_fn(|_| JSON.load(Ordering::Relaxed)));
+
+    if tracing_subscriber::registry().with(filter).with(console).with(text).with(json).try_init().is_ok() {
+        let _ = FILTER.set(handle);
+    }
+}
+
+fn filter() -> EnvFilter {
+    let config = CONFIG.load();
+    let level = config.logging.level.as_deref().unwrap_or(if config.be_verbose {"debug"} else {"info"});
+
+    config.logging.filters.iter()
+        .filter_map(|(module, level)| format!("{}::{module}={level}", env!("CARGO_CRATE_NAME")).parse().ok())
+        .fold(EnvFilter::new(level), |filter, directive| filter.add_directive(directive))
+}
+
+pub fn configure_logging() {
+    JSON.store(CONFIG.load().logging.format == LogFormat::Json, Ordering::Relaxed);
+
+    let output = &CONFIG.load().logging.output;
+    let destination = match output.as_str() {
+        "console" => Ok(None),
+        output => Destination::open(output, &CONFIG.load().logging.rotation).map(Some)
+    };
+
+    match destination {
+        Ok(destination) => {
+            CONSOLE.store(destination.is_none(), Ordering::Relaxed);
+            *OUTPUT.lock().unwrap_or_else(|e| e.into_inner()) = destination;
+        },
+        Err(e) => {
+            CONSOLE.store(true, Ordering::Relaxed);
+            *OUTPUT.lock().unwrap_or_else(|e| e.into_inner()) = None;
+            error!("An error occurred while opening the log output {output}.\n\
+                    Error information:\n{e}\n\
+                    Logging to the console instead...");
+        }
+    }
+
+    if let Some(handle) = FILTER.get() {
+        if let Err(e) = handle.reload(filter()) {
+            error!("An error occurred while applying the log level.\n\
+                    Error information:\n{e}");
+        }
+    }
+}
@@ -5,45 +5,145 @@ mod config;
 mod error;
 #[cfg(feature = "cgi")]
 mod cgi;
+#[cfg(feature = "cgi")]
+mod gateway;
+mod fallback;
 mod ssl;
 mod endpoints;
+mod rate_limit;
+mod storage;
+mod integrity;
+mod metrics;
+mod dns;
+mod fs_index;
+mod multipart;
+mod filters;
+mod dictionary;
+mod routes;
+mod import;
+mod build_info;
+mod tls_fingerprint;
+mod cookie_policy;
+mod redirect_policy;
+mod method_override;
+mod zero_copy;
+mod throttle;
+mod socket;
+mod file_cache;
+mod connection_limit;
+mod forwarded;
+mod shutdown;
+mod signing;
+mod proxy;
+mod reload;
+mod s3;
+mod quarantine;
+#[cfg(target_family = "unix")]
+mod privileges;
+#[cfg(target_family = "unix")]
+mod daemon;
+mod cli;
+mod journal;
+mod fairness;
+mod noise;
+mod dev;
+mod acme;
+mod preconditions;
+mod client_certificate;
+mod prefilter;
+mod ocsp;
+mod auth;
+mod subsystems;
+mod jwt;
+mod access_log;
+mod logging;
+mod log_output;
+mod bans;
+mod cluster;
 
+use std::cell::RefCell;
 use std::collections::HashMap;
-use std::env;
 #[cfg(target_family = "unix")]
 use std::env::set_current_dir;
 use std::error::Error;
 use std::net::IpAddr;
 use std::pin::Pin;
 use std::sync::LazyLock;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 #[cfg(feature = "cgi")]
 use drain_common::RequestData;
 #[cfg(target_family = "unix")]
 use fork::{fork, Fork};
 use openssl::ssl::Ssl;
+use openssl::x509::X509VerifyResult;
+use serde_json::Value;
 use tokio::net::*;
 use tokio::*;
-use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, BufReader};
+use tokio::task::JoinSet;
 use tokio::time::timeout;
 use tokio_openssl::SslStream;
-use crate::requests::Request::{Get, Head, Options, Post, Trace, Put, Delete, Patch};
+use tracing::{debug, error, info, info_span, warn, Instrument, Span};
+use tracing::field::Empty;
+use crate::requests::Request::{Get, Head, Options, Post, Trace, Put, Delete, Patch, Connect};
 use crate::requests::*;
 use crate::util::*;
-use crate::config::CONFIG;
+use crate::config::{ClientCertificatePolicy, Config, CONFIG};
 #[cfg(feature = "cgi")]
 use crate::cgi::handle_cgi;
 #[cfg(feature = "cgi")]
 use crate::cgi::CGIStatus;
-use crate::endpoints::ENDPOINT_LIBRARY;
+use crate::endpoints::{endpoint_library, ENDPOINT_LIBRARIES, ENDPOINT_LIBRARY, VIRTUAL_HOST_LIBRARIES};
 use crate::error::ServerError;
 #[cfg(feature = "cgi")]
 use crate::pages::bad_gateway::bad_gateway;
 use crate::pages::internal_server_error::internal_server_error;
+use crate::pages::content_too_large::content_too_large;
 #[cfg(feature = "cgi")]
 use crate::pages::not_found::not_found;
-use crate::ssl::{SslInfo, SSL};
-use crate::util::ResourceType::Dynamic;
+use crate::ssl::{watch_certificates, SslInfo, SSL};
+use crate::util::ResourceType::Verbatim;
+use crate::rate_limit::check_rate_limits;
+use crate::metrics::{record_request_body_size, record_tls_fingerprint_block, send_metrics};
+use crate::tls_fingerprint::fingerprint;
+use crate::client_certificate::{client_certificate, CLIENT_CERTIFICATE_HEADERS};
+use crate::prefilter::{is_ip_allowed, sniff, was_rejected};
+use crate::ocsp::refresh_staples;
+use crate::auth::{authenticate, REMOTE_USER_HEADER};
+use crate::jwt::{validate, CLAIMS_HEADER, JWT_HEADERS, SUBJECT_HEADER};
+use crate::pages::denied::DENIED_BY_HEADER;
+use crate::zero_copy::ZeroCopy;
+use crate::fs_index::FS_INDEX;
+use crate::dictionary::DICTIONARIES;
+use crate::routes::{describe_match, print_routes};
+use crate::import::import;
+use crate::integrity::{integrity_resource, send_integrity};
+use crate::method_override::override_method;
+use crate::socket::{acceptor_count, bind_listener, listener_specs, tune_stream};
+use crate::connection_limit::{admit_connection, reject_connection, reserve_connection, should_respond_when_rejected};
+use crate::forwarded::{client_ip, PEER_ADDRESS_HEADER};
+use crate::proxy::proxy;
+use crate::s3::handle_s3;
+use crate::quarantine::{is_admin_resource, receive_upload, send_uploads_admin, upload_name};
+#[cfg(target_family = "unix")]
+use crate::privileges::{drop_privileges, CREDENTIALS};
+#[cfg(target_family = "unix")]
+use crate::daemon::{daemonize, send_signal, write_pidfile};
+#[cfg(target_family = "unix")]
+use crate::reload::reload_on_sighup;
+use crate::reload::send_reload;
+use crate::subsystems::{is_available, is_subsystems_resource, send_admin};
+use crate::cli::{Command, JournalCommand, CLI};
+use crate::journal::{export, record, verify};
+use crate::access_log::log;
+use crate::logging::{configure_logging, init_logging};
+use crate::noise::send_noise;
+use crate::acme::{renew_certificates, send_challenge, watch_certificate};
+use crate::dev::{is_dev, send_reload_events, DEV_WATCHER, RELOAD_EVENTS};
+use crate::shutdown::{drain, is_shutting_down, shutdown_requested, wait_for_signal};
+use crate::build_info::{enabled_features, print_version, GIT_HASH, RUSTC_VERSION};
+use crate::bans::is_banned;
+use crate::cluster::listen;
 
 async fn handle_connection<T>(
     stream: &mut T,
@@ -54,21 +154,264 @@ async fn handle_connection<T>(
     #[cfg(feature = "cgi")]
     https: bool) -> Result<(), Box<dyn Error + Send + Sync>>
 where
-    T: AsyncRead + AsyncWrite + Unpin
+    T: AsyncBufRead + AsyncWrite + Unpin + ZeroCopy
 {
     match receive_request(stream, keep_alive).await {
-        Ok(request) => {
+        Ok(mut request) => {
+            let config = CONFIG.load();
+            let canonical_location = match request.normalize() {
+                Ok(canonical_location) => canonical_location,
+                Err(_) => {
+                    return send_response(stream, 400, None, None, None, None).await;
+                }
+            };
+
+            let mut request = match override_method(request) {
+                Ok(request) => request,
+                Err(_) => {
+                    return send_response(stream, 400, None, None, None, None).await;
+                }
+            };
+
+            if let Some(headers) = request.headers_mut() {
+                for header in CLIENT_CERTIFICATE_HEADERS {
+                    headers.remove(header);
+                }
+                headers.remove(REMOTE_USER_HEADER);
+                for header in JWT_HEADERS {
+                    headers.remove(header);
+                }
+                headers.remove(DENIED_BY_HEADER);
+                headers.remove(PEER_ADDRESS_HEADER);
+                headers.remove(BODY_MEDIA_TYPE_HEADER);
+
+                if let Ok(Some(client_certificate)) = REQUEST_INFO.try_with(|info| info.borrow().client_certificate.clone()) {
+                    headers.extend(client_certificate.headers().into_iter().map(|(k, v)| (String::from(k), v)));
+                }
+                if let Ok(Some(media_type)) = REQUEST_INFO.try_with(|info| info.borrow().body_media_type.clone()) {
+                    headers.insert(String::from(BODY_MEDIA_TYPE_HEADER), media_type);
+                }
+            }
+
+            let client_ip = client_ip(remote_ip, request.headers());
+            if config.trusted_proxies.is_some() {
+                if let Some(headers) = request.headers_mut() {
+                    headers.insert(String::from(PEER_ADDRESS_HEADER), remote_ip.to_string());
+                }
+            }
+            let remote_ip = &client_ip;
+
+            Span::current().record("method", request.method());
+            if let Some(resource) = request.resource() {
+                Span::current().record("resource", resource.as_str());
+            }
+
+            update_request_info(|info| {
+                info.resource = request.resource().cloned();
+                info.params = request.params().cloned();
+                info.head = matches!(request, Head {..});
+                info.access.method = Some(String::from(request.method()));
+                info.access.remote_ip = Some(*remote_ip);
+                if let Some(headers) = request.headers() {
+                    info.access.user_agent = headers.get("user-agent").cloned();
+                    info.access.referer = headers.get("referer").cloned();
+                    info.accept = headers.get("accept").cloned();
+                    info.host = headers.get("host").cloned();
+                    info.available_dictionary = headers.get("available-dictionary").cloned();
+                }
+                info.virtual_host = config.resolve_virtual_host(info.host.as_ref());
+            });
+
+            if let Post {headers, ..} | Put {headers, ..} | Patch {headers, ..} | Delete {headers, ..} = &request {
+                record_request_body_size(headers.get("content-length").and_then(|l| l.parse::<u64>().ok()).unwrap_or(0));
+            }
+
+            if is_banned(remote_ip).await {
+                return send_response(stream, 403, None, None, None, None).await;
+            }
+
+            if let (Some(debug), Some(resource)) = (&config.debug, request.resource()) {
+                let host = request.headers().and_then(|headers| headers.get("host"));
+                let report = describe_match(host, resource, canonical_location.as_ref());
+                info!("{remote_ip}:{remote_port} {resource} -> {report}");
+
+                if debug.header && remote_ip.is_loopback() {
+                    update_request_info(|info| info.debug = Some(report));
+                }
+            }
+
+            if let Some(location) = canonical_location {
+                let response_headers = HashMap::from([(String::from("Location"), location)]);
+                return send_response(stream, 301, Some(response_headers), None, None, None).await;
+            }
+
+            if let Get {resource, ..} = &request {
+                if let Some(result) = send_challenge(stream, resource).await {
+                    return result;
+                }
+            }
+
+            if !REQUEST_INFO.try_with(|info| info.borrow().https).unwrap_or(false) {
+                if let Some(location) = request.https_location() {
+                    let response_headers = HashMap::from([(String::from("Location"), location)]);
+                    return send_response(stream, 301, Some(response_headers), None, None, None).await;
+                }
+            }
+
+            if let (Some(client_certificates), Some(resource)) = (config.https.as_ref().and_then(|https| https.client_certificates.as_ref()), request.resource()) {
+                if client_certificates.policy(resource) == ClientCertificatePolicy::Required
+                    && !REQUEST_INFO.try_with(|info| info.borrow().client_certificate.is_some()).unwrap_or(false) {
+                    return send_response(stream, 403, None, None, None, None).await;
+                }
+            }
+
+            if let Some(auth_rule) = request.resource().and_then(|resource| config.auth_rule(resource)) {
+                match authenticate(auth_rule, &request).await {
+                    Ok(user) => {
+                        if let Some(headers) = request.headers_mut() {
+                            headers.insert(String::from(REMOTE_USER_HEADER), user.clone());
+                        }
+                        update_request_info(|info| info.remote_user = Some((auth_rule.scheme, user)));
+                    },
+                    Err(challenge) => {
+                        return send_response(stream, 401, Some(challenge), None, None, None).await;
+                    }
+                }
+            }
+
+            if let Some(jwt) = config.jwt.as_ref().filter(|jwt| request.resource().is_some_and(|resource| jwt.protects(resource))) {
+                match validate(jwt, &request).await {
+                    Ok(claims) => {
+                        if let Some(headers) = request.headers_mut() {
+                            if let Some(subject) = claims.get("sub").and_then(|subject| subject.as_str()) {
+                                headers.insert(String::from(SUBJECT_HEADER), String::from(subject));
+                            }
+                            headers.insert(String::from(CLAIMS_HEADER), Value::Object(claims.clone()).to_string());
+                        }
+                        update_request_info(|info| info.jwt_claims = Some(claims));
+                    },
+                    Err((status, challenge)) => {
+                        return send_response(stream, status, Some(challenge), None, None, None).await;
+                    }
+                }
+            }
+
+            if let Err(e) = record(&request, remote_ip) {
+                error!("An error occurred while writing to the request journal.\n\
+                        Error information:\n{e}\n\
+                        Sending 500 status to the client...");
+                return internal_server_error(stream).await;
+            }
+
+            if let (true, Get {resource, ..}) = (is_dev(), &request) {
+                if resource.eq(RELOAD_EVENTS) {
+                    return send_reload_events(stream, keep_alive).await;
+                }
+            }
+
+            if let Get {resource, ..} | Head {resource, ..} = &request {
+                if let Some(result) = send_noise(stream, resource, matches!(request, Head {..})).await {
+                    return result;
+                }
+            }
+
+            if let (Some(metrics_endpoint), Get {resource, ..}) = (&config.metrics_endpoint, &request) {
+                if resource.eq(metrics_endpoint) {
+                    return send_metrics(stream).await;
+                }
+            }
+
+            if let (Some(reload_endpoint), Post {resource, ..}) = (&config.reload_endpoint, &request) {
+                if resource.eq(reload_endpoint) {
+                    return send_reload(stream, remote_ip).await;
+                }
+            }
+
+            if request.resource().is_some_and(is_subsystems_resource) {
+                return send_admin(stream, &request, remote_ip).await;
+            }
+
+            if let Get {resource, ..} = &request {
+                if let Some(asset) = integrity_resource(resource) {
+                    return send_integrity(stream, asset, remote_ip).await;
+                }
+            }
+
+            if let Some(headers) = request.headers() {
+                if let Some(retry_after) = check_rate_limits(remote_ip, headers).await {
+                    let response_headers = HashMap::from([(String::from("Retry-After"), retry_after.to_string())]);
+                    return send_response(stream, 429, Some(response_headers), None, None, None).await;
+                }
+            }
+
+            if let (Some(quarantine), Some(resource)) = (&config.quarantine, request.resource()) {
+                if is_admin_resource(quarantine, resource) {
+                    return send_uploads_admin(stream, quarantine, &request, remote_ip).await;
+                }
+
+                if let Some(name) = upload_name(quarantine, resource) {
+                    return match &request {
+                        Put {data, headers, ..} => receive_upload(stream, quarantine, name, data, headers).await,
+                        _ => {
+                            let allow_header = HashMap::from([(String::from("Allow"), String::from("PUT"))]);
+                            send_response(stream, 405, Some(allow_header), None, None, None).await
+                        }
+                    };
+                }
+            }
+
+            if let Some((origin, object)) = request.resource().and_then(|resource| config.s3_origin(resource)) {
+                return match &request {
+                    Get {headers, ..} =>
+                        handle_s3(stream, origin, "GET", &object, headers, None).await,
+                    Head {headers, ..} =>
+                        handle_s3(stream, origin, "HEAD", &object, headers, None).await,
+                    Put {headers, data, ..} if origin.allow_put => match raw_body(data) {
+                        Some(body) => handle_s3(stream, origin, "PUT", &object, headers, Some(body)).await,
+                        None => send_response(stream, 415, None, None, None, None).await
+                    },
+                    _ => {
+                        let allow_header = HashMap::from([(String::from("Allow"), String::from(if origin.allow_put {"GET, HEAD, PUT"} else {"GET, HEAD"}))]);
+                        send_response(stream, 405, Some(allow_header), None, None, None).await
+                    }
+                };
+            }
+
+            if let Some((name, upstream, compression)) = request.resource().and_then(|resource| config.upstream(resource)) {
+                if !is_available(&format!("upstream:{name}")) {
+                    return send_response(stream, 503, None, None, None, None).await;
+                }
+
+                return match &request {
+                    Get {resource, params, headers, ..} =>
+                        proxy(stream, upstream, compression, "GET", resource, params.as_ref(), headers, remote_ip).await,
+                    Head {resource, params, headers, ..} =>
+                        proxy(stream, upstream, compression, "HEAD", resource, params.as_ref(), headers, remote_ip).await,
+                    _ => {
+                        let allow_header = HashMap::from([(String::from("Allow"), String::from("GET, HEAD"))]);
+                        send_response(stream, 405, Some(allow_header), None, None, None).await
+                    }
+                };
+            }
+
+            #[cfg(feature = "cgi")]
+            if let (Some(cgi), Some(resource)) = (&config.cgi, request.resource()) {
+                if cgi.enabled && !is_available("cgi") && cgi.should_attempt_cgi(&String::from(resource.trim_start_matches('/'))) {
+                    return send_response(stream, 503, None, None, None, None).await;
+                }
+            }
+
             #[cfg(feature = "cgi")]
             match request {
                 Get {resource, params, query_string, headers} => {
                     let mut resource_present_in_endpoints = false;
-                    match &CONFIG.cgi {
+                    match &config.cgi {
                         Some(cgi) if cgi.enabled && cgi.should_attempt_cgi(&String::from((&resource).trim_start_matches('/'))) => {
                             match handle_cgi(stream, &headers, &resource, "GET", query_string, None, local_ip, remote_ip, remote_port, https).await {
                                 Ok(CGIStatus::Available) | Ok(CGIStatus::Denied) | Ok(CGIStatus::IndexOf) => return Ok(()),
                                 Ok(CGIStatus::Unavailable { not_found_guaranteed: true, resource_present_in_endpoints: false }) => {
                                     let response_headers: HashMap<String, String> = HashMap::new();
-                                    if let Some(library) = &*ENDPOINT_LIBRARY {
+                                    if let Some(library) = endpoint_library() {
                                         return not_found(stream, RequestData::Default, &headers, response_headers, local_ip, remote_ip, remote_port, library).await;
                                     }
                                     return send_response(stream, 404, Some(response_headers), None, None, None).await
@@ -86,13 +429,13 @@ where
                 },
                 Head {resource, params, query_string, headers} => {
                     let mut resource_present_in_endpoints = false;
-                    match &CONFIG.cgi {
+                    match &config.cgi {
                         Some(cgi) if cgi.enabled && cgi.should_attempt_cgi(&String::from((&resource).trim_start_matches('/'))) => {
                             match handle_cgi(stream, &headers, &resource, "HEAD", query_string, None, local_ip, remote_ip, remote_port, https).await {
                                 Ok(CGIStatus::Available) | Ok(CGIStatus::Denied) | Ok(CGIStatus::IndexOf) => return Ok(()),
                                 Ok(CGIStatus::Unavailable { not_found_guaranteed: true, resource_present_in_endpoints: false }) => {
                                     let response_headers: HashMap<String, String> = HashMap::new();
-                                    if let Some(library) = &*ENDPOINT_LIBRARY {
+                                    if let Some(library) = endpoint_library() {
                                         return not_found(stream, RequestData::Default, &headers, response_headers, local_ip, remote_ip, remote_port, library).await;
                                     }
                                     return send_response(stream, 404, Some(response_headers), None, None, None).await
@@ -110,13 +453,13 @@ where
                 },
                 Post {resource, params, query_string, headers, data, cgi_data} => {
                     let mut resource_present_in_endpoints = false;
-                    match &CONFIG.cgi {
+                    match &config.cgi {
                         Some(cgi) if cgi.enabled && cgi.should_attempt_cgi(&String::from((&resource).trim_start_matches('/'))) => {
                             match handle_cgi(stream, &headers, &resource, "POST", query_string, cgi_data, local_ip, remote_ip, remote_port, https).await {
                                 Ok(CGIStatus::Available) | Ok(CGIStatus::Denied) | Ok(CGIStatus::IndexOf) => return Ok(()),
                                 Ok(CGIStatus::Unavailable { not_found_guaranteed: true, resource_present_in_endpoints: false }) => {
                                     let response_headers: HashMap<String, String> = HashMap::new();
-                                    if let Some(library) = &*ENDPOINT_LIBRARY {
+                                    if let Some(library) = endpoint_library() {
                                         return not_found(stream, RequestData::Default, &headers, response_headers, local_ip, remote_ip, remote_port, library).await;
                                     }
                                     return send_response(stream, 404, Some(response_headers), None, None, None).await
@@ -134,13 +477,13 @@ where
                 },
                 Put {resource, params, query_string, headers, data, cgi_data} => {
                     let mut resource_present_in_endpoints = false;
-                    match &CONFIG.cgi {
+                    match &config.cgi {
                         Some(cgi) if cgi.enabled && cgi.should_attempt_cgi(&String::from((&resource).trim_start_matches('/'))) => {
                             match handle_cgi(stream, &headers, &resource, "PUT", query_string, cgi_data, local_ip, remote_ip, remote_port, https).await {
                                 Ok(CGIStatus::Available) | Ok(CGIStatus::Denied) | Ok(CGIStatus::IndexOf) => return Ok(()),
                                 Ok(CGIStatus::Unavailable { not_found_guaranteed: true, resource_present_in_endpoints: false }) => {
                                     let response_headers: HashMap<String, String> = HashMap::new();
-                                    if let Some(library) = &*ENDPOINT_LIBRARY {
+                                    if let Some(library) = endpoint_library() {
                                         return not_found(stream, RequestData::Default, &headers, response_headers, local_ip, remote_ip, remote_port, library).await;
                                     }
                                     return send_response(stream, 404, Some(response_headers), None, None, None).await
@@ -158,13 +501,13 @@ where
                 },
                 Delete {resource, params, query_string, headers, data, cgi_data} => {
                     let mut resource_present_in_endpoints = false;
-                    match &CONFIG.cgi {
+                    match &config.cgi {
                         Some(cgi) if cgi.enabled && cgi.should_attempt_cgi(&String::from((&resource).trim_start_matches('/'))) => {
                             match handle_cgi(stream, &headers, &resource, "DELETE", query_string, cgi_data, local_ip, remote_ip, remote_port, https).await {
                                 Ok(CGIStatus::Available) | Ok(CGIStatus::Denied) | Ok(CGIStatus::IndexOf) => return Ok(()),
                                 Ok(CGIStatus::Unavailable { not_found_guaranteed: true, resource_present_in_endpoints: false }) => {
                                     let response_headers: HashMap<String, String> = HashMap::new();
-                                    if let Some(library) = &*ENDPOINT_LIBRARY {
+                                    if let Some(library) = endpoint_library() {
                                         return not_found(stream, RequestData::Default, &headers, response_headers, local_ip, remote_ip, remote_port, library).await;
                                     }
                                     return send_response(stream, 404, Some(response_headers), None, None, None).await
@@ -184,13 +527,13 @@ where
                     handle_options(stream).await,
                 Patch {resource, params, query_string, headers, data, cgi_data} => {
                     let mut resource_present_in_endpoints = false;
-                    match &CONFIG.cgi {
+                    match &config.cgi {
                         Some(cgi) if cgi.enabled && cgi.should_attempt_cgi(&String::from((&resource).trim_start_matches('/'))) => {
                             match handle_cgi(stream, &headers, &resource, "PATCH", query_string, cgi_data, local_ip, remote_ip, remote_port, https).await {
                                 Ok(CGIStatus::Available) | Ok(CGIStatus::Denied) | Ok(CGIStatus::IndexOf) => return Ok(()),
                                 Ok(CGIStatus::Unavailable { not_found_guaranteed: true, resource_present_in_endpoints: false }) => {
                                     let response_headers: HashMap<String, String> = HashMap::new();
-                                    if let Some(library) = &*ENDPOINT_LIBRARY {
+                                    if let Some(library) = endpoint_library() {
                                         return not_found(stream, RequestData::Default, &headers, response_headers, local_ip, remote_ip, remote_port, library).await;
                                     }
                                     return send_response(stream, 404, Some(response_headers), None, None, None).await
@@ -206,18 +549,21 @@ where
                     }
                     handle_patch(stream, &headers, resource, &data, &params, local_ip, remote_ip, remote_port, resource_present_in_endpoints).await
                 },
-                Trace(request) if CONFIG.enable_trace => {
+                Trace(request) if config.enable_trace => {
                     let response_headers: HashMap<String, String> = HashMap::from([
                         (String::from("Content-Type"), String::from("message/http"))
                     ]);
 
-                    send_response(stream, 200, Some(response_headers), Some(request), None, Some(Dynamic)).await
+                    send_response(stream, 200, Some(response_headers), Some(request), None, Some(Verbatim)).await
                 },
+                Connect {host, port} if config.is_connect_enabled() =>
+                    handle_connect(stream, host, port, keep_alive).await,
                 _ => {
                     let accept_header = HashMap::from([
-                        (String::from("Accept"), format!("GET, HEAD, POST,{} OPTIONS{}",
-                                                         if (&*ENDPOINT_LIBRARY).is_some() {" PUT, DELETE, PATCH,"} else {""},
-                                                         if CONFIG.enable_trace {", TRACE"} else {""}))
+                        (String::from("Accept"), format!("GET, HEAD, POST,{} OPTIONS{}{}",
+                                                         if endpoint_library().is_some() {" PUT, DELETE, PATCH,"} else {""},
+                                                         if config.enable_trace {", TRACE"} else {""},
+                                                         if config.is_connect_enabled() {", CONNECT"} else {""}))
                     ]);
 
                     send_response(stream, 405, Some(accept_header), None, None, None).await
@@ -239,18 +585,21 @@ where
                     handle_options(stream).await,
                 Patch {resource, params, headers, data} =>
                     handle_patch(stream, &headers, resource, &data, &params, local_ip, remote_ip, remote_port).await,
-                Trace(request) if CONFIG.enable_trace => {
+                Trace(request) if config.enable_trace => {
                     let response_headers: HashMap<String, String> = HashMap::from([
                         (String::from("Content-Type"), String::from("message/http"))
                     ]);
 
-                    send_response(stream, 200, Some(response_headers), Some(request), None, Some(Dynamic)).await
+                    send_response(stream, 200, Some(response_headers), Some(request), None, Some(Verbatim)).await
                 },
+                Connect {host, port} if config.is_connect_enabled() =>
+                    handle_connect(stream, host, port, keep_alive).await,
                 _ => {
                     let accept_header = HashMap::from([
-                        (String::from("Accept"), format!("GET, HEAD, POST,{} OPTIONS{}",
-                                                         if (&*ENDPOINT_LIBRARY).is_some() {" PUT, DELETE, PATCH,"} else {""},
-                                                         if CONFIG.enable_trace {", TRACE"} else {""}))
+                        (String::from("Accept"), format!("GET, HEAD, POST,{} OPTIONS{}{}",
+                                                         if endpoint_library().is_some() {" PUT, DELETE, PATCH,"} else {""},
+                                                         if config.enable_trace {", TRACE"} else {""},
+                                                         if config.is_connect_enabled() {", CONNECT"} else {""}))
                     ]);
 
                     send_response(stream, 405, Some(accept_header), None, None, None).await
@@ -262,23 +611,40 @@ where
                 ServerError::DecompressionError(..) | ServerError::UnsupportedEncoding => {
                     send_response(stream, 406, None, None, None, None).await?
                 },
-                ServerError::InvalidRequest | ServerError::MalformedPayload => {
+                ServerError::InvalidRequest | ServerError::MalformedPayload | ServerError::UnexpectedBody => {
                     send_response(stream, 400, None, None, None, None).await?
                 },
                 ServerError::UnsupportedMediaType => {
                     let response_headers: HashMap<String, String> = HashMap::from([
-                        (String::from("Accept"), String::from("application/x-www-form-urlencoded, multipart/form-data, text/plain, application/octet-stream")),
+                        (String::from("Accept"), format!("application/x-www-form-urlencoded, multipart/form-data, text/plain, application/octet-stream{}{}",
+                                                           if cfg!(feature = "json") {", application/json"} else {""},
+                                                           CONFIG.load().accepted_media_types.iter().flatten().map(|t| format!(", {t}")).collect::<String>())),
                         (String::from("Vary"), String::from("Content-Type"))
                     ]);
 
                     send_response(stream, 415, Some(response_headers), None, None, None).await?
                 },
                 ServerError::BodyTooLarge => {
-                    send_response(stream, 413, None, None, None, None).await?
+                    content_too_large(stream, false).await?
+                },
+                ServerError::DecompressionLimitExceeded => {
+                    content_too_large(stream, true).await?
+                },
+                ServerError::HeadersTooLarge => {
+                    send_response(stream, 431, None, None, None, None).await?
+                },
+                ServerError::InsufficientStorage => {
+                    send_response(stream, 507, None, None, None, None).await?
                 },
                 ServerError::VersionNotSupported => {
                     send_response(stream, 505, None, None, None, None).await?
                 },
+                ServerError::NotImplemented => {
+                    send_response(stream, 501, None, None, None, None).await?
+                },
+                ServerError::RequestTimeout => {
+                    send_response(stream, 408, None, None, None, None).await?
+                },
                 _ => {
                     internal_server_error(stream).await?;
                 }
@@ -288,28 +654,52 @@ where
     }
 }
 
-async fn https_handler(ssl_info: &SslInfo) -> Result<(), Box<dyn Error>> {
-    let bind_host = &CONFIG.bind_host;
-    let bind_port = ssl_info.port;
-    let listener = TcpListener::bind(format!("{}:{}", bind_host, bind_port)).await?;
-    println!("Listening on {}:{} (HTTPS)", bind_host, bind_port);
+async fn https_handler(ssl_info: &'static SslInfo) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut listeners = Vec::new();
+    for spec in listener_specs(Some(ssl_info.port)) {
+        for _ in 0..acceptor_count() {
+            listeners.push((bind_listener(&spec.address, spec.port)?, spec.document_root.clone()));
+        }
+        info!("Listening on {}:{} (HTTPS)", spec.address, spec.port);
+    }
+
+    #[cfg(target_family = "unix")]
+    drop_privileges()?;
+
+    let mut acceptors = JoinSet::new();
+    for (listener, document_root) in listeners {
+        acceptors.spawn(https_acceptor(listener, ssl_info, document_root));
+    }
+
+    while let Some(acceptor) = acceptors.join_next().await {
+        acceptor??;
+    }
+    Ok(())
+}
+
+async fn https_acceptor(listener: TcpListener, ssl_info: &'static SslInfo, document_root: Option<String>) -> Result<(), Box<dyn Error + Send + Sync>> {
     loop {
-        let ssl = match Ssl::new(&ssl_info.ctx) {
+        let ssl = match Ssl::new(&ssl_info.context()) {
             Ok(ssl) => ssl,
             Err(e) => {
-                eprintln!("[https_handler():{}] An error occurred while establishing a secure connection.\n\
-                                                Error information:\n{e}", line!());
+                error!("An error occurred while establishing a secure connection.\n\
+                        Error information:\n{e}");
 
                 return Err(Box::new(e));
             }
         };
 
+        let reserved = reserve_connection().await;
         let (stream, _) = listener.accept().await?;
+        if let Err(e) = tune_stream(&stream) {
+            debug!("An error occurred while setting socket options.\n\
+                    Error information:\n{e}");
+        }
         let local_addr = match stream.local_addr() {
             Ok(addr) => addr,
             Err(e) => {
-                eprintln!("[https_handler():{}] An error occurred while getting the server's address.\n\
-                                                Error information:\n{e}", line!());
+                error!("An error occurred while getting the server's address.\n\
+                        Error information:\n{e}");
 
                 continue;
             }
@@ -317,8 +707,8 @@ async fn https_handler(ssl_info: &SslInfo) -> Result<(), Box<dyn Error>> {
         let remote_addr = match stream.peer_addr() {
             Ok(addr) => addr,
             Err(e) => {
-                eprintln!("[https_handler():{}] An error occurred while getting the client's address.\n\
-                                                Error information:\n{e}", line!());
+                error!("An error occurred while getting the client's address.\n\
+                        Error information:\n{e}");
 
                 continue;
             }
@@ -328,36 +718,93 @@ async fn https_handler(ssl_info: &SslInfo) -> Result<(), Box<dyn Error>> {
         let remote_ip = remote_addr.ip();
         let remote_port = remote_addr.port();
 
+        if !is_ip_allowed(&remote_ip) {
+            debug!("Connection from {remote_ip}:{remote_port} was dropped by prefilters.");
+            continue;
+        }
+
+        if !sniff(&stream, true).await {
+            debug!("Connection from {remote_ip}:{remote_port} was dropped, because it didn't start with a TLS handshake.");
+            continue;
+        }
+
+        let connection = admit_connection(&remote_ip, reserved);
+        if connection.is_none() {
+            debug!("Connection from {remote_ip}:{remote_port} was rejected, because the connection limit was reached.");
+
+            if !should_respond_when_rejected() {
+                continue;
+            }
+        }
+
         let mut stream = SslStream::new(ssl, stream)?;
         if let Err(e) = Pin::new(&mut stream).accept().await {
+            if was_rejected(stream.ssl()) {
+                debug!("Connection from {remote_ip}:{remote_port} was dropped by prefilters, because of its server name.");
+                continue;
+            }
+
             if let Some(ssl_error) = e.ssl_error() {
                 if ssl_error.to_string().contains("http request") {
                     continue;
                 }
             }
 
-            eprintln!("[https_handler():{}] An error occurred while establishing a secure connection.\n\
-                                            Error information:\n{e}", line!());
+            if stream.ssl().verify_result() != X509VerifyResult::OK {
+                debug!("Connection from {remote_ip}:{remote_port} was closed, because its client certificate couldn't be verified: {}.",
+                       stream.ssl().verify_result().error_string());
+                continue;
+            }
+
+            error!("An error occurred while establishing a secure connection.\n\
+                    Error information:\n{e}");
 
             return Err(Box::new(e));
         }
 
+        let tls_fingerprint = fingerprint(stream.ssl());
+        let client_certificate = client_certificate(stream.ssl());
+        if let Some(tls_fingerprint) = &tls_fingerprint {
+            debug!("TLS connection from {remote_ip}:{remote_port}, JA3 {}, JA4 {}.", tls_fingerprint.ja3, tls_fingerprint.ja4);
+
+            if matches!(&CONFIG.load().https, Some(https) if tls_fingerprint.matches(&https.blocked_tls_fingerprints)) {
+                record_tls_fingerprint_block();
+                debug!("Connection from {remote_ip}:{remote_port} was closed, because its TLS fingerprint is blocked.");
+                continue;
+            }
+        }
+
+        let document_root = document_root.clone();
         spawn(async move {
+            if connection.is_none() {
+                if let Ok(Err(e)) = timeout(Duration::from_secs(CONFIG.load().request_timeout), reject_connection(&mut stream)).await {
+                    error!("An error occurred while rejecting connection:\n{e}");
+                }
+                return;
+            }
+
+            let _connection = connection;
             let mut keep_alive = true;
-            let mut buf: [u8; 1] = [0; 1];
+            let mut stream = BufReader::new(stream);
             loop {
-                if !keep_alive {
+                if !keep_alive || is_shutting_down() {
                     break;
                 }
 
-                match timeout(Duration::from_secs((&CONFIG).request_timeout), Pin::new(&mut stream).peek(&mut buf)).await {
-                    Ok(Ok(0)) | Err(_) => break,
+                let filled = select! {
+                    filled = timeout(Duration::from_secs(CONFIG.load().request_timeout), stream.fill_buf()) => filled,
+                    _ = shutdown_requested() => break
+                };
+
+                match filled {
+                    Ok(Ok(buf)) if buf.is_empty() => break,
+                    Err(_) => break,
                     Ok(Err(e)) => {
                         if e.to_string().eq("the SSL session has been shut down") {
                             break;
                         }
 
-                        eprintln!("[https_handler():{}] An error occurred while handling connection:\n{e}", line!());
+                        error!("An error occurred while handling connection:\n{e}");
                         break;
                     },
                     _ => {}
@@ -366,34 +813,71 @@ async fn https_handler(ssl_info: &SslInfo) -> Result<(), Box<dyn Error>> {
                 #[cfg(feature = "cgi")]
                 let https_enabled = true;
 
-                if let Err(e) = handle_connection(
-                    &mut stream,
-                    &mut keep_alive,
-                    &local_ip,
-                    &remote_ip,
-                    &remote_port,
-                    #[cfg(feature = "cgi")]
-                    https_enabled
-                ).await {
-                    eprintln!("[https_handler():{}] An error occurred while handling connection:\n{e}", line!());
+                let request_info = RequestInfo {
+                    tls_fingerprint: tls_fingerprint.clone(),
+                    client_certificate: client_certificate.clone(),
+                    https: true,
+                    document_root: document_root.clone(),
+                    ..RequestInfo::default()
+                };
+
+                let started = Instant::now();
+                if let Err(e) = REQUEST_INFO.scope(RefCell::new(request_info), async {
+                    let result = handle_connection(
+                        &mut stream,
+                        &mut keep_alive,
+                        &local_ip,
+                        &remote_ip,
+                        &remote_port,
+                        #[cfg(feature = "cgi")]
+                        https_enabled
+                    ).await;
+                    log(&remote_ip, started);
+                    result
+                }.instrument(info_span!("request", method = Empty, resource = Empty))).await {
+                    error!("An error occurred while handling connection:\n{e}");
                 }
             }
-        });
+        }.instrument(info_span!("connection", %remote_ip, remote_port)));
     }
 }
 
-async fn http_handler() -> Result<(), Box<dyn Error>> {
-    let bind_host = &CONFIG.bind_host;
-    let bind_port = &CONFIG.bind_port;
-    let listener = TcpListener::bind(format!("{}:{}", bind_host, bind_port)).await?;
-    println!("Listening on {}:{} (HTTP)", bind_host, bind_port);
+async fn http_handler() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut listeners = Vec::new();
+    for spec in listener_specs(None) {
+        for _ in 0..acceptor_count() {
+            listeners.push((bind_listener(&spec.address, spec.port)?, spec.document_root.clone()));
+        }
+        info!("Listening on {}:{} (HTTP)", spec.address, spec.port);
+    }
+
+    #[cfg(target_family = "unix")]
+    drop_privileges()?;
+
+    let mut acceptors = JoinSet::new();
+    for (listener, document_root) in listeners {
+        acceptors.spawn(http_acceptor(listener, document_root));
+    }
+
+    while let Some(acceptor) = acceptors.join_next().await {
+        acceptor??;
+    }
+    Ok(())
+}
+
+async fn http_acceptor(listener: TcpListener, document_root: Option<String>) -> Result<(), Box<dyn Error + Send + Sync>> {
     loop {
-        let (mut stream, _) = listener.accept().await?;
+        let reserved = reserve_connection().await;
+        let (stream, _) = listener.accept().await?;
+        if let Err(e) = tune_stream(&stream) {
+            debug!("An error occurred while setting socket options.\n\
+                    Error information:\n{e}");
+        }
         let local_addr = match stream.local_addr() {
             Ok(addr) => addr,
             Err(e) => {
-                eprintln!("[http_handler():{}] An error occurred while getting the server's address.\n\
-                                               Error information:\n{e}", line!());
+                error!("An error occurred while getting the server's address.\n\
+                        Error information:\n{e}");
 
                 continue;
             }
@@ -401,8 +885,8 @@ async fn http_handler() -> Result<(), Box<dyn Error>> {
         let remote_addr = match stream.peer_addr() {
             Ok(addr) => addr,
             Err(e) => {
-                eprintln!("[http_handler():{}] An error occurred while getting the client's address.\n\
-                                               Error information:\n{e}", line!());
+                error!("An error occurred while getting the client's address.\n\
+                        Error information:\n{e}");
 
                 continue;
             }
@@ -412,18 +896,51 @@ async fn http_handler() -> Result<(), Box<dyn Error>> {
         let remote_ip = remote_addr.ip();
         let remote_port = remote_addr.port();
 
+        if !is_ip_allowed(&remote_ip) {
+            debug!("Connection from {remote_ip}:{remote_port} was dropped by prefilters.");
+            continue;
+        }
+
+        let connection = admit_connection(&remote_ip, reserved);
+        if connection.is_none() {
+            debug!("Connection from {remote_ip}:{remote_port} was rejected, because the connection limit was reached.");
+
+            if should_respond_when_rejected() {
+                spawn(async move {
+                    let mut stream = stream;
+                    if let Ok(Err(e)) = timeout(Duration::from_secs(CONFIG.load().request_timeout), reject_connection(&mut stream)).await {
+                        error!("An error occurred while rejecting connection:\n{e}");
+                    }
+                });
+            }
+            continue;
+        }
+
+        let document_root = document_root.clone();
         spawn(async move {
+            let _connection = connection;
+            if !sniff(&stream, false).await {
+                debug!("Connection from {remote_ip}:{remote_port} was dropped, because it didn't start with an HTTP request.");
+                return;
+            }
+
             let mut keep_alive = true;
-            let mut buf: [u8; 1] = [0; 1];
+            let mut stream = BufReader::new(stream);
             loop {
-                if !keep_alive {
+                if !keep_alive || is_shutting_down() {
                     break;
                 }
 
-                match timeout(Duration::from_secs((&CONFIG).request_timeout), stream.peek(&mut buf)).await {
-                    Ok(Ok(0)) | Err(_) => break,
+                let filled = select! {
+                    filled = timeout(Duration::from_secs(CONFIG.load().request_timeout), stream.fill_buf()) => filled,
+                    _ = shutdown_requested() => break
+                };
+
+                match filled {
+                    Ok(Ok(buf)) if buf.is_empty() => break,
+                    Err(_) => break,
                     Ok(Err(e)) => {
-                        eprintln!("[http_handler():{}] An error occurred while handling connection:\n{e}", line!());
+                        error!("An error occurred while handling connection:\n{e}");
                         break;
                     },
                     _ => {}
@@ -432,90 +949,205 @@ async fn http_handler() -> Result<(), Box<dyn Error>> {
                 #[cfg(feature = "cgi")]
                 let https_enabled = false;
 
-                if let Err(e) = handle_connection(
-                    &mut stream,
-                    &mut keep_alive,
-                    &local_ip,
-                    &remote_ip,
-                    &remote_port,
-                    #[cfg(feature = "cgi")]
-                    https_enabled
-                ).await {
-                    eprintln!("[http_handler():{}] An error occurred while handling connection:\n{e}", line!());
+                let request_info = RequestInfo {document_root: document_root.clone(), ..RequestInfo::default()};
+
+                let started = Instant::now();
+                if let Err(e) = REQUEST_INFO.scope(RefCell::new(request_info), async {
+                    let result = handle_connection(
+                        &mut stream,
+                        &mut keep_alive,
+                        &local_ip,
+                        &remote_ip,
+                        &remote_port,
+                        #[cfg(feature = "cgi")]
+                        https_enabled
+                    ).await;
+                    log(&remote_ip, started);
+                    result
+                }.instrument(info_span!("request", method = Empty, resource = Empty))).await {
+                    error!("An error occurred while handling connection:\n{e}");
                 }
             }
-        });
+        }.instrument(info_span!("connection", %remote_ip, remote_port)));
     }
 }
 
 fn http() -> io::Result<()> {
+    LazyLock::force(&FS_INDEX);
+    LazyLock::force(&DICTIONARIES);
+
     Ok(runtime::Builder::new_multi_thread()
         .enable_all()
         .build()?
         .block_on(async {
-            if let Err(e) = http_handler().await {
-                eprintln!("[http():{}] A critical error occurred inside the HTTP handler.\n\
-                                       Error information:\n{e}", line!())
+            spawn(persist_etags());
+            spawn(refresh_date());
+            spawn(listen());
+            spawn(renew_certificates());
+            #[cfg(target_family = "unix")]
+            spawn(reload_on_sighup());
+
+            select! {
+                result = http_handler() => {
+                    if let Err(e) = result {
+                        error!("A critical error occurred inside the HTTP handler.\n\
+                                Error information:\n{e}")
+                    }
+                },
+                _ = wait_for_signal() => drain().await
             }
         }))
 }
 
-fn https(ssl_info: &SslInfo) -> io::Result<()> {
+fn https(ssl_info: &'static SslInfo) -> io::Result<()> {
+    LazyLock::force(&FS_INDEX);
+    LazyLock::force(&DICTIONARIES);
+
     Ok(runtime::Builder::new_multi_thread()
         .enable_all()
         .build()?
         .block_on(async {
-            if let Err(e) = https_handler(ssl_info).await {
-                eprintln!("[https():{}] A critical error occurred inside the HTTPS handler.\n\
-                                        Error information:\n{e}\n\
-                                        Continuing with the regular HTTP...", line!())
+            spawn(persist_etags());
+            spawn(refresh_date());
+            spawn(listen());
+            spawn(watch_certificate());
+            spawn(watch_certificates(ssl_info));
+            spawn(refresh_staples());
+            #[cfg(target_family = "unix")]
+            spawn(reload_on_sighup());
+
+            select! {
+                result = https_handler(ssl_info) => {
+                    if let Err(e) = result {
+                        warn!("A critical error occurred inside the HTTPS handler.\n\
+                               Error information:\n{e}\n\
+                               Continuing with the regular HTTP...")
+                    }
+                },
+                _ = wait_for_signal() => drain().await
             }
         }))
 }
 
 fn main() -> io::Result<()> {
+    init_logging();
+
+    match &CLI.command {
+        Some(Command::Routes {host, paths}) => {
+            print_routes(host.as_ref(), paths);
+            return Ok(());
+        },
+        Some(Command::Import {from}) => {
+            import(from);
+            return Ok(());
+        },
+        Some(Command::Journal {command}) => {
+            let result = match command {
+                JournalCommand::Verify => verify(),
+                JournalCommand::Export {since} => export(*since)
+            };
+
+            if let Err(e) = &result {
+                error!("The request journal couldn't be verified.\n\
+                        Error information:\n{e}");
+            }
+            return result;
+        },
+        Some(Command::Dev {..}) | None => {}
+    }
+
+    if CLI.version {
+        print_version(CLI.json);
+        return Ok(());
+    }
+
+    if CLI.check_config {
+        if Config::new().is_none() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "the config is invalid"));
+        }
+        println!("The config is valid.");
+        return Ok(());
+    }
+
+    #[cfg(target_family = "unix")]
+    if let Some(action) = CLI.signal {
+        if let Err(e) = send_signal(action) {
+            error!("An error occurred while signaling the server.\n\
+                    Error information:\n{e}");
+            return Err(e);
+        }
+        return Ok(());
+    }
+
+    configure_logging();
+
     #[cfg(not(feature = "cgi"))]
-    println!("Drain {}, starting...", env!("CARGO_PKG_VERSION"));
+    info!("Drain {}, starting...", env!("CARGO_PKG_VERSION"));
     #[cfg(feature = "cgi")]
-    println!("Drain {} (CGI version), starting...", env!("CARGO_PKG_VERSION"));
+    info!("Drain {} (CGI version), starting...", env!("CARGO_PKG_VERSION"));
+
+    debug!("Build {GIT_HASH}, compiled with {RUSTC_VERSION}, features: {}.", enabled_features().join(", "));
 
     #[cfg(feature = "cgi")]
-    match &CONFIG.cgi {
+    match &CONFIG.load().cgi {
         Some(cgi) if cgi.enabled => {
-            println!("CGI enabled. Scripts will be executed using {}", cgi.cgi_server);
+            info!("CGI enabled. Scripts will be executed using {}", cgi.cgi_server);
         },
         _ => {
-            println!("CGI disabled.");
+            info!("CGI disabled.");
         }
     }
 
-    if CONFIG.be_verbose {
-        match &CONFIG.encoding {
-            Some(encoding) => {
-                println!("Encoding enabled and set to \"{}\".", encoding.use_encoding);
-            },
-            _ => {
-                println!("Encoding disabled.");
-            }
+    match &CONFIG.load().encoding {
+        Some(encoding) => {
+            debug!("Encoding enabled and set to \"{}\".", encoding.use_encoding);
+        },
+        _ => {
+            debug!("Encoding disabled.");
         }
+    }
 
-        println!("TRACE HTTP method is {}.\n\
-                  Server header {} be sent.",
-                if CONFIG.enable_trace { "enabled" } else { "disabled" },
-                if CONFIG.enable_server_header { "will" } else { "won't" });
+    debug!("TRACE HTTP method is {}.\n\
+            Server header {} be sent.",
+           if CONFIG.load().enable_trace { "enabled" } else { "disabled" },
+           if CONFIG.load().enable_server_header { "will" } else { "won't" });
 
-        println!("Request timeout will occur after {} seconds of inactivity from the client.", &CONFIG.request_timeout);
-    }
+    debug!("Request timeout will occur after {} seconds of inactivity from the client.", &CONFIG.load().request_timeout);
 
     LazyLock::force(&ENDPOINT_LIBRARY);
+    LazyLock::force(&ENDPOINT_LIBRARIES);
+    LazyLock::force(&VIRTUAL_HOST_LIBRARIES);
+    if is_dev() {
+        LazyLock::force(&DEV_WATCHER);
+        info!("Serving {} at http://{}:{}", CONFIG.load().document_root, CONFIG.load().bind_host, CONFIG.load().bind_port);
+    }
     LazyLock::force(&SSL);
+    #[cfg(target_family = "unix")]
+    LazyLock::force(&CREDENTIALS);
+
+    #[cfg(target_family = "unix")]
+    if CONFIG.load().daemon || CLI.daemon {
+        info!("Detaching from the terminal...");
+        if let Err(e) = daemonize() {
+            error!("An error occurred while daemonizing.\n\
+                    Error information:\n{e}");
+            return Err(e);
+        }
+    }
+
+    #[cfg(target_family = "unix")]
+    if let Err(e) = write_pidfile() {
+        error!("An error occurred while writing the pidfile.\n\
+                Error information:\n{e}");
+        return Err(e);
+    }
 
     #[cfg(target_family = "unix")]
     if *&*CHROOT {
         if let Err(e) = set_current_dir("/") {
-            eprintln!("[main():{}]  An error occurred while setting the current working directory.\n\
-                                    Cannot continue any further, as it poses a threat to the data security.\n\
-                                    Error information:", line!());
+            error!("An error occurred while setting the current working directory.\n\
+                    Cannot continue any further, as it poses a threat to the data security.\n\
+                    Error information:");
             return Err(e);
         }
     }
@@ -527,8 +1159,8 @@ fn main() -> io::Result<()> {
                 Ok(Fork::Parent(_)) => http(),
                 Ok(Fork::Child) => https(ssl_info),
                 Err(e) => {
-                    eprintln!("[main():{}] Fork failed with {e} status code.\n\
-                                           Continuing with the regular HTTP...", line!());
+                    warn!("Fork failed with {e} status code.\n\
+                           Continuing with the regular HTTP...");
 
                     http()
                 }
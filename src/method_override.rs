@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+use drain_common::RequestBody;
+use drain_common::RequestBody::{FormData, XWWWFormUrlEncoded};
+use glob::Pattern;
+use crate::config::CONFIG;
+use crate::error::ServerError;
+use crate::requests::Request;
+
+fn requested_method(resource: &String, headers: &HashMap<String, String>, data: &Option<RequestBody>) -> Result<Option<&'static str>, ServerError> {
+    let Some(method_override) = &CONFIG.load().method_override else {
+        return Ok(None);
+    };
+
+    let resource = resource.trim_start_matches('/');
+    if !method_override.paths.iter().any(|p| Pattern::new(p).is_ok_and(|p| p.matches(resource))) {
+        return Ok(None);
+    }
+
+    let requested = match headers.get("x-http-method-override") {
+        Some(method) => Some(method.clone()),
+        None => match data {
+            Some(XWWWFormUrlEncoded(form)) => form.get("_method").cloned(),
+            Some(FormData(fields)) => fields.get("_method").map(|field| String::from_utf8_lossy(&field.value).into_owned()),
+            _ => None
+        }
+    };
+
+    let Some(requested) = requested else {
+        return Ok(None);
+    };
+
+    match requested.trim().to_uppercase().as_str() {
+        "POST" => Ok(None),
+        "PUT" => Ok(Some("PUT")),
+        "PATCH" => Ok(Some("PATCH")),
+        "DELETE" => Ok(Some("DELETE")),
+        _ => Err(ServerError::InvalidRequest)
+    }
+}
+
+pub fn override_method(request: Request) -> Result<Request, ServerError> {
+    let method = match &request {
+        Request::Post {resource, headers, data, ..} => requested_method(resource, headers, data)?,
+        _ => None
+    };
+
+    let Some(method) = method else {
+        return Ok(request);
+    };
+
+    match request {
+        Request::Post {resource, params, #[cfg(feature = "cgi")] query_string, headers, data, #[cfg(feature = "cgi")] cgi_data} => Ok(match method {
+            "PUT" => Request::Put {resource, params, #[cfg(feature = "cgi")] query_string, headers, data, #[cfg(feature = "cgi")] cgi_data},
+            "PATCH" => Request::Patch {resource, params, #[cfg(feature = "cgi")] query_string, headers, data, #[cfg(feature = "cgi")] cgi_data},
+            _ => Request::Delete {resource, params, #[cfg(feature = "cgi")] query_string, headers, data, #[cfg(feature = "cgi")] cgi_data}
+        }),
+        request => Ok(request)
+    }
+}
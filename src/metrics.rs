@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{LazyLock, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use crate::build_info::{enabled_features, GIT_HASH, RUSTC_VERSION, VERSION};
+use crate::config::CONFIG;
+use crate::multipart::spooled_bytes;
+use crate::subsystems::is_available;
+use crate::util::{send_response, REQUEST_INFO};
+
+static ENDPOINTS_IN_FLIGHT: LazyLock<Mutex<HashMap<String, u64>>> = LazyLock::new(|| {
+    Mutex::new(HashMap::new())
+});
+
+static ENDPOINTS_REJECTED: LazyLock<Mutex<HashMap<String, u64>>> = LazyLock::new(|| {
+    Mutex::new(HashMap::new())
+});
+
+static TLS_FINGERPRINTS_BLOCKED: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Default)]
+struct ResourceUsage {
+    count: u64,
+    wall_seconds: f64,
+    cpu_seconds: f64,
+    max_rss_bytes: u64
+}
+
+static ENDPOINT_USAGE: LazyLock<Mutex<HashMap<String, ResourceUsage>>> = LazyLock::new(|| {
+    Mutex::new(HashMap::new())
+});
+
+#[cfg(feature = "cgi")]
+static CGI_USAGE: LazyLock<Mutex<HashMap<String, ResourceUsage>>> = LazyLock::new(|| {
+    Mutex::new(HashMap::new())
+});
+
+const BODY_SIZE_BUCKETS: [u64; 12] = [0, 256, 1024, 4096, 16384, 65536, 262144, 1048576, 4194304, 16777216, 67108864, 268435456];
+const BODY_SIZE_QUANTILES: [f64; 3] = [0.5, 0.9, 0.99];
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; BODY_SIZE_BUCKETS.len()],
+    count: u64,
+    sum: u64,
+    max: u64
+}
+
+impl Histogram {
+    fn observe(&mut self, value: u64) {
+        if let Some(bucket) = BODY_SIZE_BUCKETS.iter().position(|bound| value <= *bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.count += 1;
+        self.sum += value;
+        self.max = self.max.max(value);
+    }
+
+    fn quantile(&self, quantile: f64) -> u64 {
+        let rank = (quantile * self.count as f64).ceil() as u64;
+        let mut cumulative = 0;
+
+        for (bucket, count) in self.buckets.iter().enumerate() {
+            cumulative += count;
+            if cumulative >= rank {
+                return BODY_SIZE_BUCKETS[bucket].min(self.max);
+            }
+        }
+        self.max
+    }
+}
+
+static REQUEST_BODY_SIZES: LazyLock<Mutex<HashMap<String, Histogram>>> = LazyLock::new(|| {
+    Mutex::new(HashMap::new())
+});
+
+static RESPONSE_BODY_SIZES: LazyLock<Mutex<HashMap<String, Histogram>>> = LazyLock::new(|| {
+    Mutex::new(HashMap::new())
+});
+
+pub struct InFlightGuard {
+    endpoint: String
+}
+
+impl InFlightGuard {
+    pub fn new(endpoint: &str) -> Self {
+        if let Ok(mut in_flight) = ENDPOINTS_IN_FLIGHT.lock() {
+            *in_flight.entry(String::from(endpoint)).or_insert(0) += 1;
+        }
+
+        Self {endpoint: String::from(endpoint)}
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if let Ok(mut in_flight) = ENDPOINTS_IN_FLIGHT.lock() {
+            if let Some(count) = in_flight.get_mut(&self.endpoint) {
+                *count = count.saturating_sub(1);
+            }
+        }
+    }
+}
+
+pub fn record_endpoint_rejection(endpoint: &str) {
+    if let Ok(mut rejected) = ENDPOINTS_REJECTED.lock() {
+        *rejected.entry(String::from(endpoint)).or_insert(0) += 1;
+    }
+}
+
+#[cfg(target_family = "unix")]
+pub fn thread_cpu_time() -> Duration {
+    let mut time = libc::timespec {tv_sec: 0, tv_nsec: 0};
+    if unsafe {libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut time)} != 0 {
+        return Duration::ZERO;
+    }
+
+    Duration::new(time.tv_sec as u64, time.tv_nsec as u32)
+}
+
+#[cfg(not(target_family = "unix"))]
+pub fn thread_cpu_time() -> Duration {
+    Duration::ZERO
+}
+
+fn record_usage(usage: &Mutex<HashMap<String, ResourceUsage>>, label: &str, wall: Duration, cpu: Duration, max_rss_bytes: u64) {
+    if let Ok(mut usage) = usage.lock() {
+        let usage = usage.entry(String::from(label)).or_default();
+        usage.count += 1;
+        usage.wall_seconds += wall.as_secs_f64();
+        usage.cpu_seconds += cpu.as_secs_f64();
+        usage.max_rss_bytes = usage.max_rss_bytes.max(max_rss_bytes);
+    }
+}
+
+pub fn record_endpoint_usage(endpoint: &str, wall: Duration, cpu: Duration) {
+    record_usage(&ENDPOINT_USAGE, endpoint, wall, cpu, 0);
+}
+
+#[cfg(feature = "cgi")]
+pub fn record_cgi_usage(script: &str, wall: Duration, cpu: Duration, max_rss_bytes: u64) {
+    record_usage(&CGI_USAGE, script, wall, cpu, max_rss_bytes);
+}
+
+fn render_usage(metrics: &mut String, usage: &Mutex<HashMap<String, ResourceUsage>>, prefix: &str, label: &str, description: &str) {
+    let Ok(usage) = usage.lock() else {
+        return;
+    };
+
+    metrics.push_str(&*format!("# HELP {prefix}_requests_total Number of requests handled by a {description}.\n\
+                                # TYPE {prefix}_requests_total counter\n"));
+    for (name, u) in usage.iter() {
+        metrics.push_str(&*format!("{prefix}_requests_total{{{label}=\"{name}\"}} {}\n", u.count));
+    }
+
+    metrics.push_str(&*format!("# HELP {prefix}_wall_seconds_total Wall-clock time spent handling requests by a {description}.\n\
+                                # TYPE {prefix}_wall_seconds_total counter\n"));
+    for (name, u) in usage.iter() {
+        metrics.push_str(&*format!("{prefix}_wall_seconds_total{{{label}=\"{name}\"}} {}\n", u.wall_seconds));
+    }
+
+    metrics.push_str(&*format!("# HELP {prefix}_cpu_seconds_total CPU time spent handling requests by a {description}.\n\
+                                # TYPE {prefix}_cpu_seconds_total counter\n"));
+    for (name, u) in usage.iter() {
+        metrics.push_str(&*format!("{prefix}_cpu_seconds_total{{{label}=\"{name}\"}} {}\n", u.cpu_seconds));
+    }
+}
+
+fn record_body_size(sizes: &Mutex<HashMap<String, Histogram>>, size: u64) {
+    let resource = REQUEST_INFO.try_with(|info| info.borrow().resource.clone()).ok().flatten().unwrap_or_default();
+    let config = CONFIG.load();
+    let location = config.metrics_location(&resource);
+
+    if let Ok(mut sizes) = sizes.lock() {
+        sizes.entry(String::from(location)).or_default().observe(size);
+    }
+}
+
+pub fn record_request_body_size(size: u64) {
+    record_body_size(&REQUEST_BODY_SIZES, size);
+}
+
+pub fn record_response_body_size(size: u64) {
+    record_body_size(&RESPONSE_BODY_SIZES, size);
+}
+
+fn render_body_sizes(metrics: &mut String, sizes: &Mutex<HashMap<String, Histogram>>, name: &str, description: &str) {
+    let Ok(sizes) = sizes.lock() else {
+        return;
+    };
+
+    metrics.push_str(&*format!("# HELP {name} Size of {description} bodies.\n\
+                                # TYPE {name} histogram\n"));
+    for (location, histogram) in sizes.iter() {
+        let mut cumulative = 0;
+        for (bound, count) in BODY_SIZE_BUCKETS.iter().zip(histogram.buckets.iter()) {
+            cumulative += count;
+            metrics.push_str(&*format!("{name}_bucket{{location=\"{location}\",le=\"{bound}\"}} {cumulative}\n"));
+        }
+        metrics.push_str(&*format!("{name}_bucket{{location=\"{location}\",le=\"+Inf\"}} {}\n", histogram.count));
+        metrics.push_str(&*format!("{name}_sum{{location=\"{location}\"}} {}\n", histogram.sum));
+        metrics.push_str(&*format!("{name}_count{{location=\"{location}\"}} {}\n", histogram.count));
+    }
+
+    metrics.push_str(&*format!("# HELP {name}_quantile Estimated percentiles of {description} body sizes, rounded up to the histogram's bucket bounds.\n\
+                                # TYPE {name}_quantile gauge\n"));
+    for (location, histogram) in sizes.iter() {
+        for quantile in BODY_SIZE_QUANTILES {
+            metrics.push_str(&*format!("{name}_quantile{{location=\"{location}\",quantile=\"{quantile}\"}} {}\n", histogram.quantile(quantile)));
+        }
+    }
+}
+
+pub fn record_tls_fingerprint_block() {
+    TLS_FINGERPRINTS_BLOCKED.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn reset() {
+    if let Ok(mut rejected) = ENDPOINTS_REJECTED.lock() {
+        rejected.clear();
+    }
+
+    if let Ok(mut usage) = ENDPOINT_USAGE.lock() {
+        usage.clear();
+    }
+
+    #[cfg(feature = "cgi")]
+    if let Ok(mut usage) = CGI_USAGE.lock() {
+        usage.clear();
+    }
+
+    for sizes in [&REQUEST_BODY_SIZES, &RESPONSE_BODY_SIZES] {
+        if let Ok(mut sizes) = sizes.lock() {
+            sizes.clear();
+        }
+    }
+
+    TLS_FINGERPRINTS_BLOCKED.store(0, Ordering::Relaxed);
+}
+
+fn render_metrics() -> String {
+    let mut metrics = String::new();
+
+    metrics.push_str("# HELP drain_build_info Build metadata of the running server.\n\
+                      # TYPE drain_build_info gauge\n");
+    metrics.push_str(&*format!("drain_build_info{{version=\"{VERSION}\",git_hash=\"{GIT_HASH}\",rustc_version=\"{RUSTC_VERSION}\",features=\"{}\"}} 1\n",
+                               enabled_features().join(",")));
+
+    metrics.push_str("# HELP drain_endpoint_in_flight Number of requests currently being handled by a dynamic endpoint.\n\
+                      # TYPE drain_endpoint_in_flight gauge\n");
+    if let Ok(in_flight) = ENDPOINTS_IN_FLIGHT.lock() {
+        for (endpoint, count) in in_flight.iter() {
+            metrics.push_str(&*format!("drain_endpoint_in_flight{{endpoint=\"{endpoint}\"}} {count}\n"));
+        }
+    }
+
+    metrics.push_str("# HELP drain_endpoint_rejected_total Number of requests rejected due to the endpoint's concurrency limit.\n\
+                      # TYPE drain_endpoint_rejected_total counter\n");
+    if let Ok(rejected) = ENDPOINTS_REJECTED.lock() {
+        for (endpoint, count) in rejected.iter() {
+            metrics.push_str(&*format!("drain_endpoint_rejected_total{{endpoint=\"{endpoint}\"}} {count}\n"));
+        }
+    }
+
+    render_usage(&mut metrics, &ENDPOINT_USAGE, "drain_endpoint", "endpoint", "dynamic endpoint");
+
+    #[cfg(feature = "cgi")]
+    {
+        render_usage(&mut metrics, &CGI_USAGE, "drain_cgi", "script", "CGI script");
+
+        metrics.push_str("# HELP drain_cgi_max_rss_bytes Highest peak resident set size of a CGI script's process.\n\
+                          # TYPE drain_cgi_max_rss_bytes gauge\n");
+        if let Ok(usage) = CGI_USAGE.lock() {
+            for (script, u) in usage.iter() {
+                metrics.push_str(&*format!("drain_cgi_max_rss_bytes{{script=\"{script}\"}} {}\n", u.max_rss_bytes));
+            }
+        }
+    }
+
+    render_body_sizes(&mut metrics, &REQUEST_BODY_SIZES, "drain_request_body_size_bytes", "request");
+    render_body_sizes(&mut metrics, &RESPONSE_BODY_SIZES, "drain_response_body_size_bytes", "response");
+
+    metrics.push_str("# HELP drain_upload_spool_bytes Size of uploaded files currently spooled to upload_spool_dir.\n\
+                      # TYPE drain_upload_spool_bytes gauge\n");
+    metrics.push_str(&*format!("drain_upload_spool_bytes {}\n", spooled_bytes()));
+    if let Some(quota) = CONFIG.load().upload_spool_quota {
+        metrics.push_str("# HELP drain_upload_spool_quota_bytes Maximum size of uploaded files spooled to upload_spool_dir at once.\n\
+                          # TYPE drain_upload_spool_quota_bytes gauge\n");
+        metrics.push_str(&*format!("drain_upload_spool_quota_bytes {quota}\n"));
+    }
+
+    metrics.push_str("# HELP drain_tls_fingerprint_blocked_total Number of TLS connections closed due to a blocked client fingerprint.\n\
+                      # TYPE drain_tls_fingerprint_blocked_total counter\n");
+    metrics.push_str(&*format!("drain_tls_fingerprint_blocked_total {}\n", TLS_FINGERPRINTS_BLOCKED.load(Ordering::Relaxed)));
+
+    metrics
+}
+
+pub async fn send_metrics<T>(stream: &mut T) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+    T: AsyncRead + AsyncWrite + Unpin
+{
+    if !is_available("metrics") {
+        return send_response(stream, 503, None, None, None, None).await;
+    }
+
+    let response_headers = HashMap::from([
+        (String::from("Content-Type"), String::from("text/plain; version=0.0.4; charset=utf-8")),
+        (String::from("Cache-Control"), String::from("no-store"))
+    ]);
+
+    send_response(stream, 200, Some(response_headers), Some(Vec::from(render_metrics())), None, None).await
+}
@@ -0,0 +1,456 @@
+use std::collections::HashMap;
+use std::fs::{remove_file, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use bstr::ByteSlice;
+use drain_common::FormDataValue;
+use encoding_rs::{Encoding, UTF_8};
+use openssl::base64::decode_block;
+use tokio::task::block_in_place;
+use tracing::error;
+use crate::config::CONFIG;
+use crate::error::ServerError;
+
+const MAX_PART_HEADERS_LENGTH: usize = 16384;
+pub const SPOOL_PATH_HEADER: &str = "x-drain-spool-path";
+
+static SPOOL_COUNTER: AtomicU64 = AtomicU64::new(0);
+static SPOOLED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+pub struct SpooledFile {
+    path: PathBuf,
+    size: u64
+}
+
+impl Drop for SpooledFile {
+    fn drop(&mut self) {
+        SPOOLED_BYTES.fetch_sub(self.size, Ordering::Relaxed);
+        let _ = remove_file(&self.path);
+    }
+}
+
+pub fn spooled_bytes() -> u64 {
+    SPOOLED_BYTES.load(Ordering::Relaxed)
+}
+
+fn reserve_spool_space(length: u64) -> Result<(), ServerError> {
+    let Some(quota) = CONFIG.load().upload_spool_quota else {
+        SPOOLED_BYTES.fetch_add(length, Ordering::Relaxed);
+        return Ok(());
+    };
+
+    SPOOLED_BYTES.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |spooled| {
+        spooled.checked_add(length).filter(|total| *total <= quota)
+    }).map(|_| ()).map_err(|_| ServerError::InsufficientStorage)
+}
+
+fn create_spool_file(upload_spool_dir: &String) -> Result<(PathBuf, File), ServerError> {
+    let path = PathBuf::from(format!("{upload_spool_dir}/drain-upload-{}-{}", std::process::id(), SPOOL_COUNTER.fetch_add(1, Ordering::Relaxed)));
+    let file = block_in_place(|| OpenOptions::new().write(true).create_new(true).open(&path)).map_err(|e| {
+        error!("An error occurred while creating a spool file for an uploaded file.\n\
+                Error information:\n{e}");
+        ServerError::MalformedPayload
+    })?;
+
+    Ok((path, file))
+}
+
+#[cfg(feature = "cgi")]
+pub struct BodySpool {
+    file: File,
+    spooled_file: SpooledFile
+}
+
+#[cfg(feature = "cgi")]
+impl BodySpool {
+    pub fn new(length: usize) -> Result<Option<Self>, ServerError> {
+        let config = CONFIG.load();
+        let Some(upload_spool_dir) = &config.upload_spool_dir else {
+            return Ok(None);
+        };
+
+        if length <= config.upload_spool_threshold {
+            return Ok(None);
+        }
+
+        let (path, file) = create_spool_file(upload_spool_dir)?;
+        Ok(Some(Self {file, spooled_file: SpooledFile {path, size: 0}}))
+    }
+
+    pub fn write(&mut self, data: &[u8]) -> Result<(), ServerError> {
+        reserve_spool_space(data.len() as u64)?;
+        self.spooled_file.size += data.len() as u64;
+
+        block_in_place(|| self.file.write_all(data)).map_err(|e| {
+            error!("An error occurred while writing a request body to disk.\n\
+                    Error information:\n{e}");
+            ServerError::MalformedPayload
+        })
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.spooled_file.path
+    }
+
+    pub fn size(&self) -> u64 {
+        self.spooled_file.size
+    }
+
+    pub fn into_spooled_file(self) -> SpooledFile {
+        self.spooled_file
+    }
+}
+
+enum State {
+    Preamble,
+    Delimiter,
+    Headers,
+    Body,
+    Done
+}
+
+enum TransferDecoder {
+    Base64(Vec<u8>),
+    QuotedPrintable(Vec<u8>)
+}
+
+impl TransferDecoder {
+    fn new(encoding: &str) -> Result<Option<Self>, ServerError> {
+        match encoding.trim().to_ascii_lowercase().as_str() {
+            "base64" => Ok(Some(Self::Base64(Vec::new()))),
+            "quoted-printable" => Ok(Some(Self::QuotedPrintable(Vec::new()))),
+            "7bit" | "8bit" | "binary" => Ok(None),
+            _ => Err(ServerError::MalformedPayload)
+        }
+    }
+
+    fn decode_base64(block: &[u8]) -> Result<Vec<u8>, ServerError> {
+        if block.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let block = std::str::from_utf8(block).map_err(|_| ServerError::MalformedPayload)?;
+        decode_block(block).map_err(|_| ServerError::MalformedPayload)
+    }
+
+    fn decode(&mut self, data: &[u8]) -> Result<Vec<u8>, ServerError> {
+        match self {
+            Self::Base64(pending) => {
+                pending.extend(data.iter().filter(|b| !b.is_ascii_whitespace()));
+                let length = pending.len() - pending.len() % 4;
+                let block: Vec<u8> = pending.drain(..length).collect();
+                Self::decode_base64(&block)
+            },
+            Self::QuotedPrintable(pending) => {
+                pending.extend_from_slice(data);
+                let mut decoded = Vec::with_capacity(pending.len());
+                let mut i = 0;
+
+                while i < pending.len() {
+                    if pending[i] != b'=' {
+                        decoded.push(pending[i]);
+                        i += 1;
+                        continue;
+                    }
+
+                    let rest = &pending[i + 1..];
+                    if rest.starts_with(b"\r\n") {
+                        i += 3;
+                    } else if rest.starts_with(b"\n") {
+                        i += 2;
+                    } else if rest.len() < 2 {
+                        break;
+                    } else {
+                        let hex = std::str::from_utf8(&rest[..2]).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                        let Some(byte) = hex else {
+                            return Err(ServerError::MalformedPayload);
+                        };
+                        decoded.push(byte);
+                        i += 3;
+                    }
+                }
+
+                pending.drain(..i);
+                Ok(decoded)
+            }
+        }
+    }
+
+    fn finish(self) -> Result<Vec<u8>, ServerError> {
+        match self {
+            Self::Base64(pending) if pending.is_empty() => Ok(Vec::new()),
+            Self::Base64(_) => Err(ServerError::MalformedPayload),
+            Self::QuotedPrintable(pending) => Ok(pending)
+        }
+    }
+}
+
+struct Part {
+    name: String,
+    filename: Option<String>,
+    headers: HashMap<String, String>,
+    value: Vec<u8>,
+    spool: Option<(PathBuf, File)>,
+    decoder: Option<TransferDecoder>
+}
+
+pub struct MultipartParser {
+    delimiter: Vec<u8>,
+    buffer: Vec<u8>,
+    state: State,
+    part: Option<Part>,
+    fields: HashMap<String, FormDataValue>,
+    spooled_files: Vec<SpooledFile>
+}
+
+pub fn multipart_boundary(content_type: &String) -> Option<String> {
+    let (media_type, parameters) = content_type.split_once(';')?;
+    if !media_type.trim().eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+
+    parameters
+        .split(';')
+        .filter_map(|p| p.trim().split_once('='))
+        .find(|(k, _)| k.trim().eq_ignore_ascii_case("boundary"))
+        .map(|(_, v)| String::from(v.trim().trim_matches('"')))
+        .filter(|b| !b.is_empty())
+}
+
+impl MultipartParser {
+    pub fn new(boundary: &String) -> Self {
+        Self {
+            delimiter: Vec::from(format!("\r\n--{boundary}")),
+            buffer: Vec::from("\r\n"),
+            state: State::Preamble,
+            part: None,
+            fields: HashMap::new(),
+            spooled_files: Vec::new()
+        }
+    }
+
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<(), ServerError> {
+        self.buffer.extend_from_slice(chunk);
+
+        loop {
+            match self.state {
+                State::Preamble => {
+                    let Some(position) = self.buffer.find(&self.delimiter) else {
+                        let keep = self.buffer.len().min(self.delimiter.len() - 1);
+                        self.buffer.drain(..self.buffer.len() - keep);
+                        return Ok(());
+                    };
+
+                    self.buffer.drain(..position + self.delimiter.len());
+                    self.state = State::Delimiter;
+                },
+                State::Delimiter => {
+                    if self.buffer.len() < 2 {
+                        return Ok(());
+                    }
+
+                    if self.buffer.starts_with(b"--") {
+                        self.buffer.clear();
+                        self.state = State::Done;
+                    } else if self.buffer.starts_with(b"\r\n") {
+                        self.buffer.drain(..2);
+                        self.state = State::Headers;
+                    } else {
+                        return Err(ServerError::MalformedPayload);
+                    }
+                },
+                State::Headers => {
+                    let Some(position) = self.buffer.find(b"\r\n\r\n") else {
+                        if self.buffer.len() > MAX_PART_HEADERS_LENGTH {
+                            return Err(ServerError::MalformedPayload);
+                        }
+                        return Ok(());
+                    };
+
+                    let part = Self::parse_part_headers(&self.buffer[..position])?;
+                    self.buffer.drain(..position + 4);
+                    self.part = Some(part);
+                    self.state = State::Body;
+                },
+                State::Body => {
+                    match self.buffer.find(&self.delimiter) {
+                        Some(position) => {
+                            let data: Vec<u8> = self.buffer.drain(..position + self.delimiter.len()).take(position).collect();
+                            self.write_to_part(&data)?;
+                            self.finish_part()?;
+                            self.state = State::Delimiter;
+                        },
+                        None => {
+                            let keep = self.buffer.len().min(self.delimiter.len() - 1);
+                            let data: Vec<u8> = self.buffer.drain(..self.buffer.len() - keep).collect();
+                            self.write_to_part(&data)?;
+                            return Ok(());
+                        }
+                    }
+                },
+                State::Done => {
+                    self.buffer.clear();
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    pub fn finish(self) -> Result<(HashMap<String, FormDataValue>, Vec<SpooledFile>), ServerError> {
+        let State::Done = self.state else {
+            return Err(ServerError::MalformedPayload);
+        };
+
+        Ok((self.fields, self.spooled_files))
+    }
+
+    fn parse_part_headers(raw: &[u8]) -> Result<Part, ServerError> {
+        let mut headers: HashMap<String, String> = HashMap::new();
+
+        for line in raw.split_str("\r\n") {
+            let Some((name, value)) = line.split_once_str(":") else {
+                return Err(ServerError::MalformedPayload);
+            };
+
+            headers.insert(String::from_utf8_lossy(name.trim_ascii()).to_lowercase(), String::from_utf8_lossy(value.trim_ascii()).to_string());
+        }
+        headers.remove(SPOOL_PATH_HEADER);
+
+        let Some(content_disp) = headers.get("content-disposition") else {
+            return Err(ServerError::MalformedPayload);
+        };
+
+        let mut content_disp_split = content_disp.split(';');
+        if !content_disp_split.next().is_some_and(|f| f.trim().eq("form-data")) {
+            return Err(ServerError::MalformedPayload);
+        }
+
+        let mut name: Option<String> = None;
+        let mut filename: Option<String> = None;
+
+        for parameter in content_disp_split {
+            let Some((k, v)) = parameter.split_once('=') else {
+                return Err(ServerError::MalformedPayload);
+            };
+
+            match k.trim() {
+                "name" => name = Some(String::from(v.trim().trim_matches('"'))),
+                "filename" => filename = Some(String::from(v.trim().trim_matches('"'))),
+                _ => {}
+            }
+        }
+
+        let Some(name) = name else {
+            return Err(ServerError::MalformedPayload);
+        };
+
+        let decoder = match headers.get("content-transfer-encoding") {
+            Some(encoding) => TransferDecoder::new(encoding)?,
+            None => None
+        };
+
+        if decoder.is_some() {
+            headers.remove("content-transfer-encoding");
+        }
+
+        Ok(Part {name, filename, headers, value: Vec::new(), spool: None, decoder})
+    }
+
+    fn write_to_part(&mut self, data: &[u8]) -> Result<(), ServerError> {
+        let decoded = match self.part.as_mut().and_then(|part| part.decoder.as_mut()) {
+            Some(decoder) => decoder.decode(data)?,
+            None => return self.write_decoded(data)
+        };
+
+        self.write_decoded(&decoded)
+    }
+
+    fn write_decoded(&mut self, data: &[u8]) -> Result<(), ServerError> {
+        let Some(part) = &mut self.part else {
+            return Err(ServerError::MalformedPayload);
+        };
+
+        if part.spool.is_none() && part.filename.is_some() && part.value.len() + data.len() > CONFIG.load().upload_spool_threshold {
+            if let Some(upload_spool_dir) = &CONFIG.load().upload_spool_dir {
+                let (path, file) = create_spool_file(upload_spool_dir)?;
+                self.spooled_files.push(SpooledFile {path: path.clone(), size: 0});
+                part.spool = Some((path, file));
+                let buffered = std::mem::take(&mut part.value);
+                Self::write_to_spool(part, self.spooled_files.last_mut(), &buffered)?;
+            }
+        }
+
+        if part.spool.is_some() {
+            return Self::write_to_spool(part, self.spooled_files.last_mut(), data);
+        }
+
+        part.value.extend_from_slice(data);
+        Ok(())
+    }
+
+    fn write_to_spool(part: &mut Part, spooled_file: Option<&mut SpooledFile>, data: &[u8]) -> Result<(), ServerError> {
+        let (Some((_, file)), Some(spooled_file)) = (&mut part.spool, spooled_file) else {
+            return Ok(());
+        };
+
+        reserve_spool_space(data.len() as u64)?;
+        spooled_file.size += data.len() as u64;
+
+        block_in_place(|| file.write_all(data)).map_err(|e| {
+            error!("An error occurred while writing an uploaded file to disk.\n\
+                    Error information:\n{e}");
+            ServerError::MalformedPayload
+        })
+    }
+
+    fn transcode(part: &mut Part) {
+        let Some(content_type) = part.headers.get("content-type") else {
+            return;
+        };
+
+        let (media_type, parameters) = content_type.split_once(';').unwrap_or((content_type, ""));
+        let charset = parameters
+            .split(';')
+            .filter_map(|p| p.trim().split_once('='))
+            .find(|(k, _)| k.trim().eq_ignore_ascii_case("charset"))
+            .map(|(_, v)| v.trim().trim_matches('"'));
+
+        let Some(encoding) = charset.and_then(|charset| Encoding::for_label(charset.as_bytes())) else {
+            return;
+        };
+
+        if encoding != UTF_8 {
+            part.value = encoding.decode_without_bom_handling(&part.value).0.into_owned().into_bytes();
+            let content_type = format!("{}; charset=utf-8", media_type.trim());
+            part.headers.insert(String::from("content-type"), content_type);
+        }
+    }
+
+    fn finish_part(&mut self) -> Result<(), ServerError> {
+        if let Some(decoder) = self.part.as_mut().and_then(|part| part.decoder.take()) {
+            let tail = decoder.finish()?;
+            self.write_decoded(&tail)?;
+        }
+
+        let Some(mut part) = self.part.take() else {
+            return Err(ServerError::MalformedPayload);
+        };
+
+        if part.spool.is_none() && part.filename.is_none() {
+            Self::transcode(&mut part);
+        }
+
+        if let Some((path, file)) = part.spool.take() {
+            drop(file);
+            part.headers.insert(String::from(SPOOL_PATH_HEADER), path.to_string_lossy().to_string());
+        }
+
+        self.fields.insert(part.name, FormDataValue {
+            filename: part.filename,
+            headers: part.headers,
+            value: part.value
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use glob::Pattern;
+use tokio::fs;
+use tokio::io::{AsyncRead, AsyncWrite};
+use crate::config::CONFIG;
+use crate::util::ResourceType::Verbatim;
+use crate::util::{document_root, send_response};
+
+#[derive(Clone)]
+enum NoiseResponse {
+    File {content: Vec<u8>, content_type: String},
+    Missing,
+    TooLarge
+}
+
+static CACHE: LazyLock<Mutex<HashMap<String, (Instant, NoiseResponse)>>> = LazyLock::new(|| {
+    Mutex::new(HashMap::new())
+});
+
+fn is_noise(resource: &str) -> bool {
+    let Some(noise_paths) = &CONFIG.load().noise_paths else {
+        return false;
+    };
+
+    noise_paths.patterns.iter().any(|p| Pattern::new(p).is_ok_and(|p| p.matches(resource)))
+}
+
+async fn load(path: &String, max_size: u64) -> NoiseResponse {
+    match fs::metadata(path).await {
+        Ok(metadata) if metadata.is_file() && metadata.len() > max_size => NoiseResponse::TooLarge,
+        Ok(metadata) if metadata.is_file() => match fs::read(path).await {
+            Ok(content) => NoiseResponse::File {
+                content,
+                content_type: mime_guess::from_path(path).first_or_octet_stream().to_string()
+            },
+            Err(_) => NoiseResponse::TooLarge
+        },
+        _ => NoiseResponse::Missing
+    }
+}
+
+pub async fn send_noise<T>(stream: &mut T, resource: &String, head: bool) -> Option<Result<(), Box<dyn Error + Send + Sync>>>
+where
+    T: AsyncRead + AsyncWrite + Unpin
+{
+    let config = CONFIG.load();
+    let noise_paths = config.noise_paths.as_ref()?;
+    if !is_noise(resource) {
+        return None;
+    }
+
+    let path = format!("{}{resource}", document_root());
+    let ttl = Duration::from_secs(noise_paths.max_age);
+
+    let cached = CACHE.lock().ok()?.get(&path)
+        .filter(|(loaded, _)| loaded.elapsed() < ttl)
+        .map(|(_, response)| response.clone());
+
+    let response = match cached {
+        Some(response) => response,
+        None => {
+            let response = load(&path, noise_paths.max_size).await;
+            if let Ok(mut cache) = CACHE.lock() {
+                cache.insert(path, (Instant::now(), response.clone()));
+            }
+            response
+        }
+    };
+
+    let mut response_headers = HashMap::from([
+        (String::from("Cache-Control"), format!("public, max-age={}", noise_paths.max_age))
+    ]);
+
+    Some(match response {
+        NoiseResponse::TooLarge => return None,
+        NoiseResponse::Missing => send_response(stream, 204, Some(response_headers), None, None, None).await,
+        NoiseResponse::File {content_type, ..} if head => {
+            response_headers.insert(String::from("Content-Type"), content_type);
+            send_response(stream, 200, Some(response_headers), None, None, None).await
+        },
+        NoiseResponse::File {content, content_type} => {
+            response_headers.insert(String::from("Content-Type"), content_type);
+            send_response(stream, 200, Some(response_headers), Some(content), None, Some(Verbatim)).await
+        }
+    })
+}
+
+pub fn purge<F>(matches: F)
+where
+    F: Fn(&str) -> bool
+{
+    if let Ok(mut cache) = CACHE.lock() {
+        cache.retain(|path, _| !matches(path));
+    }
+}
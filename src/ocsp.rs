@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::sync::{LazyLock, RwLock};
+use std::time::{Duration, Instant};
+use openssl::error::ErrorStack;
+use openssl::hash::MessageDigest;
+use openssl::ocsp::{OcspCertId, OcspFlag, OcspRequest, OcspResponse, OcspResponseStatus};
+use openssl::ssl::SslContextBuilder;
+use openssl::stack::Stack;
+use openssl::x509::X509;
+use openssl::x509::store::X509StoreBuilder;
+use tokio::time::{sleep, timeout};
+use tracing::error;
+use crate::acme::certificate_path;
+use crate::config::CONFIG;
+use crate::proxy::{parse_response, send_to_server, UpstreamServer};
+
+const REFRESH_INTERVAL: u64 = 43200;
+
+const CHECK_INTERVAL: u64 = 3600;
+
+const DISCARD_AFTER: u64 = 259200;
+
+static RESPONSES: LazyLock<RwLock<HashMap<Vec<u8>, (Instant, Vec<u8>)>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
+
+pub fn configure_stapling(ssl_ctx_builder: &mut SslContextBuilder) -> Result<(), ErrorStack> {
+    ssl_ctx_builder.set_status_callback(|ssl| {
+        let Some(certificate) = ssl.certificate() else {
+            return Ok(false);
+        };
+
+        let digest = certificate.digest(MessageDigest::sha256())?;
+        let response = RESPONSES.read().ok().and_then(|responses| responses.get(&*digest).map(|(_, response)| response.clone()));
+
+        match response {
+            Some(response) => {
+                ssl.set_ocsp_status(&response)?;
+                Ok(true)
+            },
+            None => Ok(false)
+        }
+    })
+}
+
+fn certificate_files() -> Vec<String> {
+    let mut files = Vec::new();
+
+    if let Some(https) = &CONFIG.load().https {
+        files.push(https.ssl_certificate_file.clone());
+    }
+
+    for virtual_host in CONFIG.load().virtual_hosts.iter().flatten() {
+        files.extend(virtual_host.ssl_certificate_file.clone());
+    }
+
+    if let Some(acme) = &CONFIG.load().acme {
+        files.push(certificate_path(acme));
+    }
+
+    files
+}
+
+async fn fetch(url: &str, request: &[u8]) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let Some(server) = UpstreamServer::parse(url) else {
+        return Err(Box::new(io::Error::new(io::ErrorKind::InvalidInput, format!("{url} isn't a valid URL"))));
+    };
+
+    let path = if server.path.is_empty() {"/"} else {server.path.as_str()};
+    let mut message = format!("POST {path} HTTP/1.1\r\n\
+                               Host: {}\r\n\
+                               User-Agent: Drain/{}\r\n\
+                               Content-Type: application/ocsp-request\r\n\
+                               Content-Length: {}\r\n\
+                               Connection: close\r\n\r\n", server.host_header(), env!("CARGO_PKG_VERSION"), request.len()).into_bytes();
+    message.extend_from_slice(request);
+
+    let response = match timeout(Duration::from_secs(CONFIG.load().request_timeout), send_to_server(&server, &message)).await {
+        Ok(response) => response?,
+        Err(_) => return Err(Box::new(io::Error::new(io::ErrorKind::TimedOut, format!("{} didn't respond in {} seconds", server.host, CONFIG.load().request_timeout))))
+    };
+
+    match parse_response(&response, false) {
+        Some(response) if response.status == 200 => Ok(response.body),
+        Some(response) => Err(Box::new(io::Error::other(format!("{} responded with {} status", server.host, response.status)))),
+        None => Err(Box::new(io::Error::new(io::ErrorKind::InvalidData, format!("{} sent a malformed response", server.host))))
+    }
+}
+
+fn certificate_digest(certificate_file: &String) -> Option<Vec<u8>> {
+    let certificate = X509::from_pem(&fs::read(certificate_file).ok()?).ok()?;
+    certificate.digest(MessageDigest::sha256()).ok().map(|digest| digest.to_vec())
+}
+
+async fn staple(certificate_file: &String) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let chain = X509::stack_from_pem(&fs::read(certificate_file)?)?;
+    let (Some(certificate), Some(issuer)) = (chain.first(), chain.get(1)) else {
+        return Err(Box::new(io::Error::new(io::ErrorKind::InvalidData, "the file doesn't contain the issuer's certificate")));
+    };
+
+    let Some(url) = certificate.ocsp_responders()?.iter().next().map(|url| url.to_string()) else {
+        return Err(Box::new(io::Error::new(io::ErrorKind::InvalidData, "the certificate doesn't name an OCSP responder")));
+    };
+
+    let request = {
+        let mut request = OcspRequest::new()?;
+        request.add_id(OcspCertId::from_cert(MessageDigest::sha1(), certificate, issuer)?)?;
+        request.to_der()?
+    };
+    let der = fetch(&url, &request).await?;
+
+    let response = OcspResponse::from_der(&der)?;
+    if response.status() != OcspResponseStatus::SUCCESSFUL {
+        return Err(Box::new(io::Error::other(format!("{url} didn't return a successful OCSP response"))));
+    }
+
+    let basic = response.basic()?;
+    let mut store = X509StoreBuilder::new()?;
+    store.add_cert(issuer.clone())?;
+    let mut certificates = Stack::new()?;
+    certificates.push(issuer.clone())?;
+    basic.verify(&certificates, &store.build(), OcspFlag::TRUST_OTHER)?;
+
+    let id = OcspCertId::from_cert(MessageDigest::sha1(), certificate, issuer)?;
+    let Some(status) = basic.find_status(&id) else {
+        return Err(Box::new(io::Error::new(io::ErrorKind::InvalidData, format!("{url} didn't return the certificate's status"))));
+    };
+    status.check_validity(300, None)?;
+
+    Ok(der)
+}
+
+pub async fn refresh_staples() {
+    loop {
+        if !CONFIG.load().https.as_ref().is_some_and(|https| https.ocsp_stapling) {
+            return;
+        }
+
+        for certificate_file in certificate_files() {
+            let Some(digest) = certificate_digest(&certificate_file) else {
+                continue;
+            };
+
+            let fetched = RESPONSES.read().ok().and_then(|responses| responses.get(&digest).map(|(fetched, _)| *fetched));
+            if fetched.is_some_and(|fetched| fetched.elapsed() < Duration::from_secs(REFRESH_INTERVAL)) {
+                continue;
+            }
+
+            match staple(&certificate_file).await {
+                Ok(response) => {
+                    if let Ok(mut responses) = RESPONSES.write() {
+                        responses.insert(digest, (Instant::now(), response));
+                    }
+                },
+                Err(e) => {
+                    if fetched.is_some_and(|fetched| fetched.elapsed() >= Duration::from_secs(DISCARD_AFTER)) {
+                        if let Ok(mut responses) = RESPONSES.write() {
+                            responses.remove(&digest);
+                        }
+                    }
+                    error!("An error occurred while fetching the OCSP response for {certificate_file}.\n\
+                            Error information:\n{e}\n\
+                            Retrying in {CHECK_INTERVAL} seconds...");
+                }
+            }
+        }
+
+        sleep(Duration::from_secs(CHECK_INTERVAL)).await;
+    }
+}
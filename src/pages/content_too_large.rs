@@ -0,0 +1,30 @@
+use std::collections::HashMap;
+use std::error::Error;
+use serde_json::json;
+use tokio::io::{AsyncRead, AsyncWrite};
+use crate::config::CONFIG;
+use crate::pages::problem;
+use crate::util::ResourceType::Dynamic;
+use crate::util::send_response;
+
+pub async fn content_too_large<T>(stream: &mut T, decompression: bool) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+    T: AsyncRead + AsyncWrite + Unpin
+{
+    let mut details = if decompression {
+        problem(413, Some("Decompressed request body exceeded the allowed size or compression ratio."))
+    } else {
+        problem(413, Some("Request body exceeded the allowed size."))
+    };
+
+    details["max_content_length"] = json!(CONFIG.load().max_content_length);
+    if decompression {
+        details["max_decompression_ratio"] = json!(CONFIG.load().max_decompression_ratio);
+    }
+
+    let response_headers = HashMap::from([
+        (String::from("Content-Type"), String::from("application/problem+json"))
+    ]);
+
+    send_response(stream, 413, Some(response_headers), Some(Vec::from(details.to_string())), None, Some(Dynamic)).await
+}
@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::net::IpAddr;
+use drain_common::RequestData;
+use tokio::fs;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tracing::error;
+use crate::config::{Denial, DenyAction};
+use crate::endpoints::endpoint_library;
+use crate::pages::forbidden::forbidden;
+use crate::pages::not_found::not_found;
+use crate::util::ResourceType::Dynamic;
+use crate::util::{document_root, send_response};
+
+pub const DENIED_BY_HEADER: &str = "x-drain-denied-by";
+
+pub async fn denied<'a, T>(stream: &mut T,
+                           denial: Denial,
+                           request_data: RequestData<'a>,
+                           headers: &HashMap<String, String>,
+                           mut response_headers: HashMap<String, String>,
+                           local_ip: &IpAddr,
+                           remote_ip: &IpAddr,
+                           remote_port: &u16) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+    T: AsyncRead + AsyncWrite + Unpin
+{
+    match denial.action {
+        DenyAction::Redirect {redirect, status} => {
+            response_headers.insert(String::from("Location"), redirect);
+            send_response(stream, status, Some(response_headers), None, None, None).await
+        },
+        DenyAction::Page {page, status} => {
+            let path = format!("{}/{}", document_root(), page.trim_start_matches('/'));
+            match fs::read(&path).await {
+                Ok(content) => {
+                    let content_type = mime_guess::from_path(&path).first_or_octet_stream();
+                    response_headers.insert(String::from("Content-Type"), content_type.to_string());
+                    send_response(stream, status, Some(response_headers), Some(content), None, Some(Dynamic)).await
+                },
+                Err(e) => {
+                    error!("An error occurred while reading the deny page {path}.\n\
+                            Error information:\n{e}\n\
+                            Sending {status} status without it...");
+                    send_response(stream, status, Some(response_headers), None, None, None).await
+                }
+            }
+        },
+        DenyAction::Status(status) => {
+            let mut headers = headers.clone();
+            headers.insert(String::from(DENIED_BY_HEADER), denial.rule);
+
+            match (status, endpoint_library()) {
+                (403, Some(library)) => forbidden(stream, request_data, &headers, response_headers, local_ip, remote_ip, remote_port, library).await,
+                (404, Some(library)) => not_found(stream, request_data, &headers, response_headers, local_ip, remote_ip, remote_port, library).await,
+                _ => send_response(stream, status, Some(response_headers), None, None, None).await
+            }
+        }
+    }
+}
@@ -1,54 +1,123 @@
 use std::collections::HashMap;
 use std::error::Error;
-use std::fs::read_dir;
+use serde_json::json;
+use tokio::fs::{metadata, read_dir};
 use tokio::io::{AsyncRead, AsyncWrite};
 use crate::config::CONFIG;
+use crate::pages::{negotiate_page_format, PageFormat};
 use crate::util::ResourceType::Dynamic;
-use crate::util::send_response;
-#[cfg(target_family = "unix")]
-use crate::util::CHROOT;
+use crate::util::{document_root, send_response, REQUEST_INFO};
+
+struct Pagination {
+    page: usize,
+    per_page: usize,
+    after: Option<String>
+}
+
+impl Pagination {
+    fn from_params(params: &HashMap<String, String>) -> Option<Self> {
+        let page = match params.get("page") {
+            Some(page) => page.parse::<usize>().ok().filter(|page| *page > 0)?,
+            None => 1
+        };
+
+        let per_page = match params.get("per_page") {
+            Some(per_page) => per_page.parse::<usize>().ok().filter(|per_page| *per_page > 0)?.min(CONFIG.load().index_of_page_size),
+            None => CONFIG.load().index_of_page_size
+        };
+
+        Some(Self {page, per_page, after: params.get("after").cloned()})
+    }
+}
+
+fn entry_name(entry: &str) -> &str {
+    entry.rsplit('/').next().unwrap_or(entry)
+}
 
 pub async fn index_of<T>(mut stream: &mut T, directory: &String, head: bool, headers: &HashMap<String, String>) -> Result<(), Box<dyn Error + Send + Sync>>
 where
     T: AsyncRead + AsyncWrite + Unpin
 {
-    #[cfg(target_family = "unix")]
-    let document_root = if *&*CHROOT {&String::from("")} else {&CONFIG.document_root};
-    #[cfg(not(target_family = "unix"))]
-    let document_root = &CONFIG.document_root;
-
-    let mut directory_list = String::new();
-
-    match &CONFIG.access_control {
-        Some(access_control) => {
-            for dir in read_dir(format!("{document_root}/{directory}"))? {
-                let dir = dir?;
-                let path = dir.path();
-                let path_str = String::from(path.to_string_lossy());
-                let mut path_trim = path_str.trim_start_matches(document_root);
-                path_trim = path_trim.trim_start_matches('/');
-
-                if !access_control.is_access_allowed(&String::from(path_trim)) {
-                    continue;
-                }
-
-                directory_list.push_str(&*format!("<li><a href=/{path_trim}>{path_trim}</a></li>"));
-            }
-        },
-        _ => {
-            for dir in read_dir(format!("{document_root}/{directory}"))? {
-                let dir = dir?;
-                let path = dir.path();
-                let path_str = String::from(path.to_string_lossy());
-                let mut path_trim = path_str.trim_start_matches(document_root);
-                path_trim = path_trim.trim_start_matches('/');
-
-                directory_list.push_str(&*format!("<li><a href=/{path_trim}>{path_trim}</a></li>"));
+    let document_root = &document_root();
+
+    let params = REQUEST_INFO.try_with(|info| info.borrow().params.clone()).ok().flatten().unwrap_or_default();
+    let Some(pagination) = Pagination::from_params(&params) else {
+        return send_response(&mut stream, 400, None, None, None, None).await;
+    };
+
+    let mut entries: Vec<String> = Vec::new();
+    let mut dirs = read_dir(format!("{document_root}/{directory}")).await?;
+    while let Some(dir) = dirs.next_entry().await? {
+        let path = dir.path();
+        let path_str = String::from(path.to_string_lossy());
+        let mut path_trim = path_str.trim_start_matches(document_root);
+        path_trim = path_trim.trim_start_matches('/');
+
+        if CONFIG.load().sensitive_paths.is_sensitive(path_trim) {
+            continue;
+        }
+
+        if let Some(access_control) = CONFIG.load().access_control() {
+            if !access_control.is_access_allowed(&String::from(path_trim)) {
+                continue;
             }
         }
+
+        entries.push(String::from(path_trim));
     }
 
-    let content: Vec<u8> = Vec::from(format!(r#"
+    entries.sort_unstable();
+    let total = entries.len();
+
+    let start = match &pagination.after {
+        Some(after) => entries.partition_point(|entry| entry_name(entry) <= after.as_str()),
+        None => (pagination.page - 1).saturating_mul(pagination.per_page).min(total)
+    };
+    let end = start.saturating_add(pagination.per_page).min(total);
+    let entries = &entries[start..end];
+
+    let mut modified: HashMap<String, String> = HashMap::new();
+    for entry in entries {
+        if let Ok(time) = metadata(format!("{document_root}/{entry}")).await.and_then(|m| m.modified()) {
+            modified.insert(entry.clone(), CONFIG.load().time_format.format(time));
+        }
+    }
+
+    let next = if end < total {entries.last().map(|entry| entry_name(entry))} else {None};
+    let per_page = pagination.per_page;
+
+    let (content, mime_type, general_type) = match negotiate_page_format() {
+        PageFormat::Json => (
+            Vec::from(json!({
+                "directory": format!("/{directory}"),
+                "entries": entries,
+                "modified": modified,
+                "total": total,
+                "per_page": per_page,
+                "next": next
+            }).to_string()),
+            "application/json",
+            "application"
+        ),
+        PageFormat::Html => {
+            let directory_list: String = entries
+                .iter()
+                .map(|entry| format!("<li><a href=/{entry}>{entry}</a> {}</li>", modified.get(entry).map_or("", |m| m.as_str())))
+                .collect();
+
+            let page = start / per_page + 1;
+            let mut navigation = String::new();
+            if start > 0 {
+                navigation.push_str(&*format!("<a href=\"?page={}&per_page={per_page}\">Previous</a> ", page.saturating_sub(1).max(1)));
+            }
+            if total > per_page {
+                navigation.push_str(&*format!("Page {page} of {}", total.div_ceil(per_page)));
+            }
+            if end < total {
+                navigation.push_str(&*format!(" <a href=\"?page={}&per_page={per_page}\">Next</a>", page + 1));
+            }
+
+            (Vec::from(format!(r#"
     <!DOCTYPE html>
     <html lang="en">
         <head>
@@ -62,17 +131,24 @@ where
             <ul>
                 {directory_list}
             </ul>
+            <p>{navigation}</p>
             <hr>
             <small>Drain {}</small>
         </body>
     </html>
-    "#, env!("CARGO_PKG_VERSION")));
+    "#, env!("CARGO_PKG_VERSION"))), "text/html", "text")
+        }
+    };
 
-    let mut response_headers = HashMap::from([(String::from("Content-Type"), String::from("text/html; charset=utf-8"))]);
+    let content_type = if mime_type == "text/html" {String::from("text/html; charset=utf-8")} else {String::from(mime_type)};
+    let mut response_headers = HashMap::from([
+        (String::from("Content-Type"), content_type),
+        (String::from("Vary"), String::from("Accept"))
+    ]);
 
-    if let Some(encoding) = CONFIG.get_response_encoding(&content, &String::from("text/html"), &String::from("text"), headers) {
+    if let Some(encoding) = CONFIG.load().get_response_encoding(&content, &String::from(mime_type), &String::from(general_type), headers) {
         response_headers.insert(String::from("Content-Encoding"), String::from(encoding));
-        response_headers.insert(String::from("Vary"), String::from("Accept-Encoding"));
+        response_headers.insert(String::from("Vary"), String::from("Accept, Accept-Encoding"));
     }
 
     if !head {
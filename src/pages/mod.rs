@@ -1,7 +1,172 @@
 pub mod internal_server_error;
 pub mod index_of;
 
-#[cfg(feature = "cgi")]
 pub mod bad_gateway;
 pub mod not_found;
-pub mod forbidden;
\ No newline at end of file
+pub mod forbidden;
+pub mod content_too_large;
+pub mod denied;
+
+use std::collections::HashMap;
+use openssl::rand::rand_bytes;
+use serde_json::{json, Value};
+use crate::config::CONFIG;
+use crate::util::{status_text, REQUEST_INFO};
+
+#[derive(PartialEq)]
+pub enum PageFormat {
+    Html,
+    Json
+}
+
+fn media_type_quality(accept: &str, media_type: &str) -> f32 {
+    let (general_type, _) = media_type.split_once('/').unwrap_or((media_type, ""));
+    let mut best: Option<(u8, f32)> = None;
+
+    for range in accept.split(',') {
+        let mut parameters = range.split(';');
+        let range = parameters.next().unwrap_or("").trim().to_lowercase();
+
+        let specificity = if range == media_type {
+            3
+        } else if range == format!("{general_type}/*") {
+            2
+        } else if range == "*/*" {
+            1
+        } else {
+            continue;
+        };
+
+        let quality = parameters
+            .filter_map(|p| p.trim().strip_prefix("q=").map(|q| q.trim().parse::<f32>().unwrap_or(0.0)))
+            .next()
+            .unwrap_or(1.0);
+
+        if best.is_none_or(|(s, _)| specificity > s) {
+            best = Some((specificity, quality));
+        }
+    }
+
+    best.map_or(0.0, |(_, q)| q)
+}
+
+pub fn negotiate_page_format() -> PageFormat {
+    let accept = REQUEST_INFO.try_with(|info| info.borrow().accept.clone()).ok().flatten();
+
+    match accept {
+        Some(accept) if media_type_quality(&accept, "application/json") > media_type_quality(&accept, "text/html") => PageFormat::Json,
+        _ => PageFormat::Html
+    }
+}
+
+pub fn negotiate_problem_details() -> bool {
+    if !CONFIG.load().problem_details {
+        return false;
+    }
+
+    let accept = REQUEST_INFO.try_with(|info| info.borrow().accept.clone()).ok().flatten();
+
+    match accept {
+        Some(accept) => {
+            let problem_quality = media_type_quality(&accept, "application/problem+json")
+                .max(media_type_quality(&accept, "application/json"));
+            problem_quality > media_type_quality(&accept, "text/html")
+        },
+        None => false
+    }
+}
+
+pub fn request_id() -> Option<String> {
+    REQUEST_INFO.try_with(|info| {
+        let mut info = info.borrow_mut();
+        if info.request_id.is_none() {
+            let mut bytes = [0u8; 8];
+            rand_bytes(&mut bytes).ok()?;
+            info.request_id = Some(bytes.iter().map(|b| format!("{b:02x}")).collect());
+        }
+        info.request_id.clone()
+    }).ok().flatten()
+}
+
+fn problem_detail(status: u16) -> Option<&'static str> {
+    Some(match status {
+        400 => "The request could not be understood by the server.",
+        403 => "Access to the requested resource is forbidden.",
+        404 => "The requested resource could not be found.",
+        405 => "The request method is not supported by the requested resource.",
+        406 => "The request body was encoded in an unsupported or malformed way.",
+        408 => "The client did not send a complete request in time.",
+        413 => "The request body exceeded the allowed size.",
+        415 => "The request body was sent in an unsupported media type.",
+        500 => "The server encountered an unexpected error while handling the request.",
+        502 => "The server received an invalid response from the upstream server.",
+        505 => "The HTTP version used in the request is not supported.",
+        507 => "The server doesn't have enough space left to store the uploaded files.",
+        _ => return None
+    })
+}
+
+pub fn problem(status: u16, detail: Option<&str>) -> Value {
+    let mut problem = json!({
+        "type": format!("https://www.rfc-editor.org/rfc/rfc9110#status.{status}"),
+        "title": status_text(status).unwrap_or("Unknown Status"),
+        "status": status
+    });
+
+    if let Some(detail) = detail.or(problem_detail(status)) {
+        problem["detail"] = json!(detail);
+    }
+
+    if let Some(request_id) = request_id() {
+        problem["request_id"] = json!(request_id);
+    }
+
+    problem
+}
+
+pub fn problem_page(status: u16) -> (Vec<u8>, HashMap<String, String>) {
+    let response_headers = HashMap::from([
+        (String::from("Content-Type"), String::from("application/problem+json")),
+        (String::from("Vary"), String::from("Accept"))
+    ]);
+
+    (Vec::from(problem(status, None).to_string()), response_headers)
+}
+
+pub fn generated_page(status: u16, title: &str) -> (Vec<u8>, HashMap<String, String>) {
+    if negotiate_problem_details() {
+        return problem_page(status);
+    }
+
+    let (content, content_type) = match negotiate_page_format() {
+        PageFormat::Json => (
+            json!({"status": status, "title": title}).to_string(),
+            "application/json"
+        ),
+        PageFormat::Html => (
+            format!(r#"
+    <!DOCTYPE html>
+    <html lang="en">
+        <head>
+            <meta charset="utf-8">
+            <meta name="viewport" content="width=device-width, initial-scale=1.0">
+            <title>{status}</title>
+        </head>
+        <body>
+            <h2>{status} {title}</h2>
+            <hr>
+            <small>Drain {}, {}</small>
+        </body>
+    </html>
+    "#, env!("CARGO_PKG_VERSION"), CONFIG.load().time_format.now()),
+            "text/html; charset=utf-8"
+        )
+    };
+
+    let response_headers = HashMap::from([
+        (String::from("Content-Type"), String::from(content_type)),
+        (String::from("Vary"), String::from("Accept"))
+    ]);
+
+    (Vec::from(content), response_headers)
+}
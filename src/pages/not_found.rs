@@ -4,11 +4,11 @@ use std::net::IpAddr;
 use std::str::FromStr;
 use drain_common::cookies::SetCookie;
 use drain_common::RequestData;
-use libloading::Library;
 use mime_guess::Mime;
 use tokio::io::{AsyncRead, AsyncWrite};
 use crate::config::CONFIG;
-use crate::endpoints::endpoint;
+use crate::endpoints::{endpoint, LoadedLibrary};
+use crate::pages::{generated_page, negotiate_page_format, PageFormat};
 use crate::util::ResourceType::Dynamic;
 use crate::util::send_response;
 
@@ -19,7 +19,7 @@ pub async fn not_found<'a, T>(stream: &mut T,
                           local_ip: &IpAddr,
                           remote_ip: &IpAddr,
                           remote_port: &u16,
-                          library: &Library) -> Result<(), Box<dyn Error + Send + Sync>>
+                          library: &LoadedLibrary) -> Result<(), Box<dyn Error + Send + Sync>>
 where
     T: AsyncRead + AsyncWrite + Unpin
 {
@@ -46,7 +46,7 @@ where
             return send_response(stream, 404, Some(response_headers), None, Some(set_cookie), None).await;
         };
 
-        if let Some(encoding) = CONFIG.get_response_encoding(&c, &mime_type, &general_type, headers) {
+        if let Some(encoding) = CONFIG.load().get_response_encoding(&c, &mime_type, &general_type, headers) {
             response_headers.insert(String::from("Content-Encoding"), String::from(encoding));
             response_headers.insert(String::from("Vary"), String::from("Accept-Encoding"));
         }
@@ -54,5 +54,11 @@ where
         return send_response(stream, 404, Some(response_headers), Some(c), Some(set_cookie), Some(Dynamic)).await;
     }
 
+    if negotiate_page_format() == PageFormat::Json {
+        let (content, page_headers) = generated_page(404, "Not Found");
+        response_headers.extend(page_headers);
+        return send_response(stream, 404, Some(response_headers), Some(content), Some(set_cookie), Some(Dynamic)).await;
+    }
+
     send_response(stream, 404, Some(response_headers), None, Some(set_cookie), None).await
 }
\ No newline at end of file
@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+
+pub enum Precondition {
+    Proceed,
+    NotModified,
+    Failed
+}
+
+struct EntityTag<'a> {
+    weak: bool,
+    opaque: &'a str
+}
+
+fn entity_tag(tag: &str) -> EntityTag<'_> {
+    let tag = tag.trim();
+    let (weak, tag) = match tag.strip_prefix("W/") {
+        Some(tag) => (true, tag),
+        None => (false, tag)
+    };
+
+    EntityTag {weak, opaque: tag.strip_prefix('"').and_then(|tag| tag.strip_suffix('"')).unwrap_or(tag)}
+}
+
+fn entity_tags(list: &str) -> Vec<&str> {
+    let mut tags = Vec::new();
+    let (mut start, mut quoted) = (0, false);
+
+    for (i, c) in list.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                tags.push(list[start..i].trim());
+                start = i + 1;
+            },
+            _ => {}
+        }
+    }
+    tags.push(list[start..].trim());
+
+    tags.into_iter().filter(|tag| !tag.is_empty()).collect()
+}
+
+fn strong_match(a: &str, b: &str) -> bool {
+    let (a, b) = (entity_tag(a), entity_tag(b));
+    !a.weak && !b.weak && a.opaque.eq(b.opaque)
+}
+
+fn weak_match(a: &str, b: &str) -> bool {
+    entity_tag(a).opaque.eq(entity_tag(b).opaque)
+}
+
+fn list_matches(list: &str, current: Option<&str>, matches: fn(&str, &str) -> bool) -> bool {
+    let tags = entity_tags(list);
+
+    if tags.iter().any(|tag| tag.eq(&"*")) {
+        return current.is_some();
+    }
+
+    current.is_some_and(|current| tags.iter().any(|tag| matches(tag, current)))
+}
+
+pub fn evaluate(headers: &HashMap<String, String>, current: Option<&str>, safe: bool) -> Precondition {
+    if let Some(if_match) = headers.get("if-match") {
+        if !list_matches(if_match, current, strong_match) {
+            return Precondition::Failed;
+        }
+    }
+
+    if let Some(if_none_match) = headers.get("if-none-match") {
+        if list_matches(if_none_match, current, weak_match) {
+            return if safe {Precondition::NotModified} else {Precondition::Failed};
+        }
+    }
+
+    Precondition::Proceed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (String::from(*k), String::from(*v))).collect()
+    }
+
+    #[test]
+    fn no_preconditions_proceed() {
+        assert!(matches!(evaluate(&headers(&[]), Some("\"a\""), true), Precondition::Proceed));
+        assert!(matches!(evaluate(&headers(&[]), None, false), Precondition::Proceed));
+    }
+
+    #[test]
+    fn if_match_any_requires_a_current_representation() {
+        let h = headers(&[("if-match", "*")]);
+        assert!(matches!(evaluate(&h, Some("\"a\""), true), Precondition::Proceed));
+        assert!(matches!(evaluate(&h, Some("\"a\""), false), Precondition::Proceed));
+        assert!(matches!(evaluate(&h, None, true), Precondition::Failed));
+        assert!(matches!(evaluate(&h, None, false), Precondition::Failed));
+    }
+
+    #[test]
+    fn if_match_uses_strong_comparison() {
+        let h = headers(&[("if-match", "\"a\"")]);
+        assert!(matches!(evaluate(&h, Some("\"a\""), false), Precondition::Proceed));
+        assert!(matches!(evaluate(&h, Some("W/\"a\""), false), Precondition::Failed));
+        assert!(matches!(evaluate(&h, Some("\"b\""), false), Precondition::Failed));
+
+        let h = headers(&[("if-match", "W/\"a\"")]);
+        assert!(matches!(evaluate(&h, Some("\"a\""), true), Precondition::Failed));
+        assert!(matches!(evaluate(&h, Some("W/\"a\""), true), Precondition::Failed));
+    }
+
+    #[test]
+    fn if_match_list() {
+        let h = headers(&[("if-match", "\"x\", \"a\" , W/\"b\"")]);
+        assert!(matches!(evaluate(&h, Some("\"a\""), false), Precondition::Proceed));
+        assert!(matches!(evaluate(&h, Some("\"x\""), true), Precondition::Proceed));
+        assert!(matches!(evaluate(&h, Some("\"b\""), false), Precondition::Failed));
+        assert!(matches!(evaluate(&h, Some("\"c\""), true), Precondition::Failed));
+    }
+
+    #[test]
+    fn if_none_match_any() {
+        let h = headers(&[("if-none-match", "*")]);
+        assert!(matches!(evaluate(&h, Some("\"a\""), true), Precondition::NotModified));
+        assert!(matches!(evaluate(&h, Some("\"a\""), false), Precondition::Failed));
+        assert!(matches!(evaluate(&h, None, true), Precondition::Proceed));
+        assert!(matches!(evaluate(&h, None, false), Precondition::Proceed));
+    }
+
+    #[test]
+    fn if_none_match_uses_weak_comparison() {
+        let h = headers(&[("if-none-match", "W/\"a\"")]);
+        assert!(matches!(evaluate(&h, Some("\"a\""), true), Precondition::NotModified));
+        assert!(matches!(evaluate(&h, Some("W/\"a\""), true), Precondition::NotModified));
+        assert!(matches!(evaluate(&h, Some("W/\"a\""), false), Precondition::Failed));
+        assert!(matches!(evaluate(&h, Some("\"b\""), true), Precondition::Proceed));
+        assert!(matches!(evaluate(&h, Some("\"b\""), false), Precondition::Proceed));
+    }
+
+    #[test]
+    fn weak_etags_use_weak_comparison() {
+        let current = "W/\"1f-7-18c3\"";
+        assert!(matches!(evaluate(&headers(&[("if-none-match", current)]), Some(current), true), Precondition::NotModified));
+        assert!(matches!(evaluate(&headers(&[("if-none-match", "\"1f-7-18c3\"")]), Some(current), true), Precondition::NotModified));
+        assert!(matches!(evaluate(&headers(&[("if-none-match", "W/\"1f-7-18c4\"")]), Some(current), true), Precondition::Proceed));
+        assert!(matches!(evaluate(&headers(&[("if-match", current)]), Some(current), true), Precondition::Failed));
+    }
+
+    #[test]
+    fn if_none_match_list() {
+        let h = headers(&[("if-none-match", "\"x\", W/\"a\"")]);
+        assert!(matches!(evaluate(&h, Some("\"a\""), true), Precondition::NotModified));
+        assert!(matches!(evaluate(&h, Some("\"x\""), false), Precondition::Failed));
+        assert!(matches!(evaluate(&h, Some("\"y\""), true), Precondition::Proceed));
+    }
+
+    #[test]
+    fn quoted_commas_stay_inside_a_tag() {
+        let h = headers(&[("if-match", "\"a,b\"")]);
+        assert!(matches!(evaluate(&h, Some("\"a,b\""), false), Precondition::Proceed));
+        assert!(matches!(evaluate(&h, Some("\"a\""), false), Precondition::Failed));
+    }
+
+    #[test]
+    fn if_match_is_evaluated_before_if_none_match() {
+        let h = headers(&[("if-match", "\"b\""), ("if-none-match", "\"a\"")]);
+        assert!(matches!(evaluate(&h, Some("\"a\""), true), Precondition::Failed));
+
+        let h = headers(&[("if-match", "\"a\""), ("if-none-match", "\"a\"")]);
+        assert!(matches!(evaluate(&h, Some("\"a\""), true), Precondition::NotModified));
+        assert!(matches!(evaluate(&h, Some("\"a\""), false), Precondition::Failed));
+    }
+}
@@ -0,0 +1,77 @@
+use std::net::IpAddr;
+use std::sync::LazyLock;
+use std::time::Duration;
+use glob::Pattern;
+use openssl::ex_data::Index;
+use openssl::ssl::{Ssl, SslRef};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+use tracing::warn;
+use crate::config::CONFIG;
+use crate::forwarded::Cidr;
+use crate::tls_fingerprint::server_name;
+
+const TLS_HANDSHAKE_RECORD: u8 = 0x16;
+
+pub static REJECTED_INDEX: LazyLock<Option<Index<Ssl, ()>>> = LazyLock::new(|| {
+    match Ssl::new_ex_index() {
+        Ok(index) => Some(index),
+        Err(e) => {
+            warn!("An error occurred while registering TLS prefilter storage.\n\
+                   Error information:\n{e}\n\
+                   Continuing without server name prefilters...");
+            None
+        }
+    }
+});
+
+pub fn is_ip_allowed(ip: &IpAddr) -> bool {
+    let Some(prefilters) = &CONFIG.load().prefilters else {
+        return true;
+    };
+
+    let in_ranges = |ranges: &Vec<String>| ranges.iter().filter_map(|cidr| Cidr::parse(cidr)).any(|cidr| cidr.contains(ip));
+
+    if prefilters.deny_ips.as_ref().is_some_and(in_ranges) {
+        return false;
+    }
+
+    prefilters.allow_ips.as_ref().is_none_or(in_ranges)
+}
+
+pub fn is_server_name_allowed(ssl: &mut SslRef) -> bool {
+    let Some(prefilters) = &CONFIG.load().prefilters else {
+        return true;
+    };
+
+    let allowed = match server_name(ssl) {
+        Some(name) => {
+            let name = name.trim_end_matches('.').to_lowercase();
+            !prefilters.blocked_server_names.iter().any(|pattern| Pattern::new(&pattern.to_lowercase()).is_ok_and(|pattern| pattern.matches(&name)))
+        },
+        None => !prefilters.require_server_name
+    };
+
+    if !allowed {
+        if let Some(index) = *REJECTED_INDEX {
+            ssl.set_ex_data(index, ());
+        }
+    }
+    allowed
+}
+
+pub fn was_rejected(ssl: &SslRef) -> bool {
+    REJECTED_INDEX.is_some_and(|index| ssl.ex_data(index).is_some())
+}
+
+pub async fn sniff(stream: &TcpStream, tls: bool) -> bool {
+    if !CONFIG.load().prefilters.as_ref().is_some_and(|prefilters| prefilters.sniff_protocol) {
+        return true;
+    }
+
+    let mut first_byte = [0u8; 1];
+    match timeout(Duration::from_secs(CONFIG.load().request_timeout), stream.peek(&mut first_byte)).await {
+        Ok(Ok(1)) => (first_byte[0] == TLS_HANDSHAKE_RECORD) == tls,
+        _ => false
+    }
+}
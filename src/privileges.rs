@@ -0,0 +1,94 @@
+use std::ffi::CString;
+use std::io;
+use std::sync::LazyLock;
+use tracing::{error, info};
+use crate::config::CONFIG;
+
+pub struct Credentials {
+    uid: Option<libc::uid_t>,
+    gid: Option<libc::gid_t>
+}
+
+fn resolve_user(user: &String) -> Option<(libc::uid_t, Option<libc::gid_t>)> {
+    if let Ok(uid) = user.parse::<libc::uid_t>() {
+        return Some((uid, None));
+    }
+
+    let name = CString::new(user.as_str()).ok()?;
+    let passwd = unsafe {libc::getpwnam(name.as_ptr())};
+    if passwd.is_null() {
+        return None;
+    }
+
+    Some(unsafe {((*passwd).pw_uid, Some((*passwd).pw_gid))})
+}
+
+fn resolve_group(group: &String) -> Option<libc::gid_t> {
+    if let Ok(gid) = group.parse::<libc::gid_t>() {
+        return Some(gid);
+    }
+
+    let name = CString::new(group.as_str()).ok()?;
+    let group = unsafe {libc::getgrnam(name.as_ptr())};
+    if group.is_null() {
+        return None;
+    }
+
+    Some(unsafe {(*group).gr_gid})
+}
+
+pub static CREDENTIALS: LazyLock<Credentials> = LazyLock::new(|| {
+    let user = CONFIG.load().user.as_ref().map(|user| match resolve_user(user) {
+        Some(ids) => ids,
+        None => {
+            error!("User {user} specified in config.json doesn't exist.");
+            panic!("Unrecoverable error occurred while trying to set up connection.");
+        }
+    });
+
+    let group = CONFIG.load().group.as_ref().map(|group| match resolve_group(group) {
+        Some(gid) => gid,
+        None => {
+            error!("Group {group} specified in config.json doesn't exist.");
+            panic!("Unrecoverable error occurred while trying to set up connection.");
+        }
+    });
+
+    Credentials {
+        uid: user.map(|(uid, _)| uid),
+        gid: group.or(user.and_then(|(_, gid)| gid))
+    }
+});
+
+pub fn drop_privileges() -> io::Result<()> {
+    let Credentials {uid, gid} = *CREDENTIALS;
+    if uid.is_none() && gid.is_none() {
+        return Ok(());
+    }
+
+    if unsafe {libc::geteuid()} != 0 {
+        if uid.is_some_and(|uid| uid != unsafe {libc::geteuid()}) {
+            return Err(io::Error::other("the server has to be started as root in order to switch to another user"));
+        }
+        return Ok(());
+    }
+
+    if let Some(gid) = gid {
+        if unsafe {libc::setgroups(1, &gid)} != 0 || unsafe {libc::setgid(gid)} != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    if let Some(uid) = uid {
+        if unsafe {libc::setuid(uid)} != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        if uid != 0 && unsafe {libc::setuid(0)} == 0 {
+            return Err(io::Error::other("root privileges could be regained after switching the user"));
+        }
+    }
+
+    info!("Switched to uid {}, gid {}.", unsafe {libc::getuid()}, unsafe {libc::getgid()});
+    Ok(())
+}
@@ -0,0 +1,373 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::io;
+use std::io::ErrorKind;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::LazyLock;
+use std::time::Duration;
+use std::str::FromStr;
+use bstr::ByteSlice;
+use mime_guess::Mime;
+use openssl::ssl::{SslConnector, SslMethod};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::time::timeout;
+use tokio_openssl::SslStream;
+use tracing::{error, warn};
+use crate::config::{ProxyCompression, Upstream, CONFIG};
+use crate::dictionary::DCB_ENCODING;
+use crate::dns;
+use crate::error::ServerError;
+use crate::fallback;
+use crate::forwarded::PEER_ADDRESS_HEADER;
+use crate::pages::bad_gateway::bad_gateway;
+use crate::signing::{canonical_query, encode_path, sign_request};
+use crate::util::ResourceType::Verbatim;
+use crate::util::{compress, decompress_bounded, send_response, status_text, REQUEST_INFO};
+
+const HOP_BY_HOP_HEADERS: [&str; 10] = [
+    "connection", "keep-alive", "proxy-authenticate", "proxy-authorization", "te",
+    "trailer", "transfer-encoding", "upgrade", "content-length", "host"
+];
+
+static CONNECTOR: LazyLock<Option<SslConnector>> = LazyLock::new(|| {
+    match SslConnector::builder(SslMethod::tls()) {
+        Ok(builder) => Some(builder.build()),
+        Err(e) => {
+            error!("An error occurred while setting up TLS for upstream connections.\n\
+                    Error information:\n{e}");
+            None
+        }
+    }
+});
+
+pub struct UpstreamServer {
+    pub https: bool,
+    pub host: String,
+    pub port: u16,
+    pub path: String
+}
+
+impl UpstreamServer {
+    pub fn parse(url: &str) -> Option<Self> {
+        let (https, rest) = match url.split_once("://")? {
+            ("http", rest) => (false, rest),
+            ("https", rest) => (true, rest),
+            _ => return None
+        };
+
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], rest[i..].trim_end_matches('/')),
+            None => (rest, "")
+        };
+
+        let default_port = if https {443} else {80};
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => (host, port.parse::<u16>().ok()?),
+            _ => (authority, default_port)
+        };
+
+        if host.is_empty() {
+            return None;
+        }
+
+        Some(Self {https, host: String::from(host), port, path: String::from(path)})
+    }
+
+    pub fn host_header(&self) -> String {
+        if self.port == if self.https {443} else {80} {
+            self.host.clone()
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+}
+
+pub struct UpstreamResponse {
+    pub status: u16,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>
+}
+
+fn forwarded_for(headers: &HashMap<String, String>, remote_ip: &IpAddr) -> String {
+    let peer = headers.get(PEER_ADDRESS_HEADER).cloned().unwrap_or_else(|| remote_ip.to_string());
+
+    match headers.get("x-forwarded-for") {
+        Some(x_forwarded_for) if peer != remote_ip.to_string() => format!("{x_forwarded_for}, {peer}"),
+        _ => peer
+    }
+}
+
+pub fn decode_chunked(mut body: &[u8]) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(body.len());
+
+    loop {
+        let (size_line, rest) = body.split_once_str("\r\n")?;
+        let size_line = size_line.to_str().ok()?;
+        let size = usize::from_str_radix(size_line.split(';').next()?.trim(), 16).ok()?;
+        if size == 0 {
+            return Some(decoded);
+        }
+
+        decoded.extend_from_slice(rest.get(..size)?);
+        body = rest.get(size + 2..)?;
+    }
+}
+
+pub fn parse_head(head_bytes: &[u8]) -> Option<(u16, HashMap<String, String>)> {
+    let mut lines = head_bytes.split_str("\r\n");
+
+    let status_line = lines.next()?.to_str().ok()?;
+    let status = status_line.split(' ').nth(1)?.parse::<u16>().ok()?;
+
+    let mut headers = HashMap::new();
+    for line in lines {
+        let (name, value) = line.split_once_str(":")?;
+        headers.insert(String::from_utf8_lossy(name).trim().to_lowercase(), String::from(String::from_utf8_lossy(value).trim()));
+    }
+
+    Some((status, headers))
+}
+
+pub fn parse_response(response: &[u8], head: bool) -> Option<UpstreamResponse> {
+    let (head_bytes, body) = response.split_once_str("\r\n\r\n")?;
+    let (status, headers) = parse_head(head_bytes)?;
+
+    let body = if head || status == 204 || status == 304 {
+        Vec::new()
+    } else if headers.get("transfer-encoding").is_some_and(|t| t.to_lowercase().contains("chunked")) {
+        decode_chunked(body)?
+    } else {
+        match headers.get("content-length").and_then(|l| l.parse::<usize>().ok()) {
+            Some(length) => body.get(..length)?.to_vec(),
+            None => body.to_vec()
+        }
+    };
+
+    Some(UpstreamResponse {status, headers, body})
+}
+
+async fn exchange<S>(mut stream: S, request: &[u8]) -> io::Result<Vec<u8>>
+where
+    S: AsyncRead + AsyncWrite + Unpin
+{
+    stream.write_all(request).await?;
+    stream.flush().await?;
+
+    let mut response = Vec::new();
+    match stream.read_to_end(&mut response).await {
+        Ok(_) => Ok(response),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof && !response.is_empty() => Ok(response),
+        Err(e) => Err(e)
+    }
+}
+
+pub trait UpstreamConnection: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<S> UpstreamConnection for S where S: AsyncRead + AsyncWrite + Unpin + Send {}
+
+pub async fn connect(server: &UpstreamServer) -> io::Result<Box<dyn UpstreamConnection>> {
+    let stream = dns::connect(&server.host, server.port).await?;
+    if !server.https {
+        return Ok(Box::new(stream));
+    }
+
+    let Some(connector) = &*CONNECTOR else {
+        return Err(io::Error::other("TLS for upstream connections is unavailable"));
+    };
+
+    let ssl = connector.configure().and_then(|c| c.into_ssl(&server.host)).map_err(io::Error::other)?;
+    let mut stream = SslStream::new(ssl, stream).map_err(io::Error::other)?;
+    Pin::new(&mut stream).connect().await.map_err(io::Error::other)?;
+
+    Ok(Box::new(stream))
+}
+
+pub async fn send_to_server(server: &UpstreamServer, request: &[u8]) -> io::Result<Vec<u8>> {
+    exchange(connect(server).await?, request).await
+}
+
+pub async fn fetch(upstream: &Upstream,
+                   method: &str,
+                   resource: &str,
+                   params: Option<&HashMap<String, String>>,
+                   headers: &[(String, String)]) -> Option<UpstreamResponse> {
+    let query = canonical_query(params);
+
+    for (address, server) in upstream.servers.iter().filter_map(|address| Some((address, UpstreamServer::parse(address)?))) {
+        if upstream.fallback_dir.is_some() && !fallback::is_healthy(address, upstream.fallback_retry).await {
+            continue;
+        }
+
+        let path = encode_path(&format!("{}/{}", server.path, resource.trim_start_matches('/')));
+        let host = server.host_header();
+
+        let mut request = format!("{method} {path}{}{query} HTTP/1.1\r\nHost: {host}\r\nConnection: close\r\n", if query.is_empty() {""} else {"?"});
+        for (name, value) in headers {
+            request.push_str(&format!("{name}: {value}\r\n"));
+        }
+        if let Some(signing) = &upstream.signing {
+            for (name, value) in sign_request(signing, method, &host, &path, &query, &[]) {
+                request.push_str(&format!("{name}: {value}\r\n"));
+            }
+        }
+        request.push_str("\r\n");
+
+        match timeout(Duration::from_secs(CONFIG.load().request_timeout), send_to_server(&server, request.as_bytes())).await {
+            Ok(Ok(response)) => match parse_response(&response, method.eq("HEAD")) {
+                Some(response) => {
+                    fallback::mark_healthy(address).await;
+                    return Some(response);
+                },
+                None => error!("{}:{} sent a malformed response.", server.host, server.port)
+            },
+            Ok(Err(e)) => {
+                error!("An error occurred while connecting to {}:{}.\n\
+                        Error information:\n{e}", server.host, server.port);
+            },
+            Err(_) => error!("{}:{} didn't respond in {} seconds.", server.host, server.port, CONFIG.load().request_timeout)
+        }
+
+        if upstream.fallback_dir.is_some() {
+            fallback::mark_unhealthy(address).await;
+        }
+    }
+
+    None
+}
+
+fn recompress(response: &mut UpstreamResponse, headers: &HashMap<String, String>, head: bool) -> Result<(), ServerError> {
+    if let Some(content_encoding) = response.headers.remove("content-encoding") {
+        if !head && !content_encoding.eq_ignore_ascii_case("identity") {
+            response.body = decompress_bounded(&content_encoding.to_lowercase(), &response.body)?;
+        }
+    }
+    response.headers.remove("content-length");
+
+    let vary = match response.headers.remove("vary") {
+        Some(vary) if !vary.to_lowercase().contains("accept-encoding") => format!("{vary}, Accept-Encoding"),
+        Some(vary) => vary,
+        None => String::from("Accept-Encoding")
+    };
+    response.headers.insert(String::from("vary"), vary);
+
+    if let Some(etag) = response.headers.get_mut("etag").filter(|etag| !etag.starts_with("W/")) {
+        *etag = format!("W/{etag}");
+    }
+
+    if head || response.body.is_empty() {
+        return Ok(());
+    }
+
+    let Some(mime) = response.headers.get("content-type").and_then(|content_type| Mime::from_str(content_type).ok()) else {
+        return Ok(());
+    };
+    let config = CONFIG.load();
+    let Some(encoding) = config.get_response_encoding(&response.body, &String::from(mime.essence_str()), &mime.type_().to_string(), headers)
+        .map(|encoding| if encoding.eq(&*DCB_ENCODING) {"br"} else {encoding.as_str()}) else {
+        return Ok(());
+    };
+
+    match compress(encoding, &response.body) {
+        Ok(compressed) => {
+            response.body = compressed;
+            response.headers.insert(String::from("content-encoding"), String::from(encoding));
+        },
+        Err(e) => {
+            warn!("An error occurred while compressing a response from upstream.\n\
+                   Error information:\n{e}\n\
+                   Continuing without compression...");
+        }
+    }
+    Ok(())
+}
+
+pub async fn proxy<T>(stream: &mut T,
+                      upstream: &Upstream,
+                      compression: ProxyCompression,
+                      method: &str,
+                      resource: &String,
+                      params: Option<&HashMap<String, String>>,
+                      headers: &HashMap<String, String>,
+                      remote_ip: &IpAddr) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+    T: AsyncRead + AsyncWrite + Unpin
+{
+    let https = REQUEST_INFO.try_with(|info| info.borrow().https).unwrap_or(false);
+
+    let mut forwarded_headers: Vec<(String, String)> = headers.iter()
+        .filter(|(name, _)| !HOP_BY_HOP_HEADERS.contains(&name.as_str()) && !name.starts_with("x-forwarded-") && name.as_str() != PEER_ADDRESS_HEADER)
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    forwarded_headers.push((String::from("X-Forwarded-For"), forwarded_for(headers, remote_ip)));
+    forwarded_headers.push((String::from("X-Forwarded-Proto"), String::from(if https {"https"} else {"http"})));
+    if let Some(host) = headers.get("host") {
+        forwarded_headers.push((String::from("X-Forwarded-Host"), host.clone()));
+    }
+
+    match compression {
+        ProxyCompression::Passthrough => {},
+        ProxyCompression::Identity | ProxyCompression::Recompress => {
+            forwarded_headers.retain(|(name, _)| name.as_str() != "accept-encoding");
+            forwarded_headers.push((String::from("Accept-Encoding"), String::from(match compression {
+                ProxyCompression::Recompress => "br, gzip",
+                _ => "identity"
+            })));
+        }
+    }
+
+    let head = method.eq("HEAD");
+    let Some(mut response) = fetch(upstream, method, resource, params, &forwarded_headers).await else {
+        return match &upstream.fallback_dir {
+            Some(fallback_dir) if fallback::serve_fallback(stream, fallback_dir, resource, head, headers).await? => Ok(()),
+            Some(_) => send_response(stream, 404, None, None, None, None).await,
+            None => bad_gateway(stream).await
+        };
+    };
+
+    if status_text(response.status).is_none() {
+        return bad_gateway(stream).await;
+    }
+
+    if let ProxyCompression::Recompress = compression {
+        if let Err(e) = recompress(&mut response, headers, head) {
+            error!("An error occurred while decompressing a response from upstream.\n\
+                    Error information:\n{e}");
+            return bad_gateway(stream).await;
+        }
+    }
+
+    let response_headers: HashMap<String, String> = response.headers.into_iter()
+        .filter(|(name, _)| !HOP_BY_HOP_HEADERS.contains(&name.as_str()) || (head && name.eq("content-length")))
+        .filter(|(name, _)| name.as_str() != "date" && name.as_str() != "server")
+        .collect();
+
+    send_response(stream,
+                  response.status,
+                  Some(response_headers),
+                  if response.body.is_empty() {None} else {Some(response.body)},
+                  None,
+                  Some(Verbatim)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Upstream;
+    use crate::fallback::is_healthy;
+    use super::fetch;
+
+    #[tokio::test]
+    async fn unreachable_servers_are_skipped_while_a_fallback_is_set() {
+        let address = String::from("http://127.0.0.1:1");
+        let upstream = Upstream {
+            servers: vec![address.clone()],
+            signing: None,
+            fallback_dir: Some(String::from(env!("CARGO_MANIFEST_DIR"))),
+            fallback_retry: 60
+        };
+
+        assert!(fetch(&upstream, "GET", "/", None, &[]).await.is_none());
+        assert!(!is_healthy(&address, upstream.fallback_retry).await);
+        assert!(is_healthy(&address, 0).await);
+    }
+}
@@ -0,0 +1,418 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::io;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::{LazyLock, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use chrono::Utc;
+use drain_common::RequestBody;
+use openssl::rand::rand_bytes;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::fs;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::process::Command;
+use tokio::spawn;
+use tokio::sync::Mutex as AsyncMutex;
+use tracing::{debug, error, warn};
+use crate::auth::REMOTE_USER_HEADER;
+use crate::config::{Quarantine, CONFIG};
+use crate::requests::Request;
+use crate::util::ResourceType::Dynamic;
+use crate::preconditions::{evaluate, Precondition};
+use crate::storage::{Store, STORAGE};
+use crate::util::{document_root, file_etag, raw_body, send_response};
+
+#[derive(Clone, Copy, PartialEq, Default)]
+enum UploadState {
+    #[default]
+    Pending,
+    Approved,
+    Rejected
+}
+
+impl UploadState {
+    fn as_str(&self) -> &'static str {
+        match self {
+            UploadState::Pending => "pending",
+            UploadState::Approved => "approved",
+            UploadState::Rejected => "rejected"
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Upload {
+    name: String,
+    user: Option<String>,
+    size: usize,
+    received: String,
+    #[serde(skip)]
+    state: UploadState,
+    #[serde(skip)]
+    decided: Option<Instant>
+}
+
+static UPLOADS: LazyLock<Mutex<HashMap<String, Upload>>> = LazyLock::new(|| Mutex::new(restore_uploads()));
+static QUOTA_LOCK: AsyncMutex<()> = AsyncMutex::const_new(());
+const PENDING_BYTES_TTL: Duration = Duration::from_secs(86400);
+
+fn restore_uploads() -> HashMap<String, Upload> {
+    let config = CONFIG.load();
+    let Some(quarantine) = &config.quarantine else {
+        return HashMap::new();
+    };
+    let Ok(entries) = std::fs::read_dir(&quarantine.quarantine_dir) else {
+        return HashMap::new();
+    };
+
+    entries.filter_map(Result::ok).filter_map(|entry| {
+        let id = String::from(entry.file_name().to_str()?.strip_suffix(".json")?);
+        let upload = serde_json::from_slice::<Upload>(&std::fs::read(entry.path()).ok()?).ok()?;
+        Path::new(&quarantined_path(quarantine, &id)).is_file().then_some((id, upload))
+    }).collect()
+}
+
+fn uploads() -> Option<MutexGuard<'static, HashMap<String, Upload>>> {
+    let retention = CONFIG.load().quarantine.as_ref().map_or(0, |quarantine| quarantine.retention);
+    let mut uploads = UPLOADS.lock().ok()?;
+    uploads.retain(|_, upload| upload.decided.is_none_or(|decided| decided.elapsed() < Duration::from_secs(retention)));
+    Some(uploads)
+}
+
+fn upload_json(id: &String, upload: &Upload) -> Value {
+    json!({
+        "id": id,
+        "name": upload.name,
+        "user": upload.user,
+        "size": upload.size,
+        "received": upload.received,
+        "state": upload.state.as_str()
+    })
+}
+
+fn set_state(id: &String, state: UploadState) {
+    if let Some(mut uploads) = uploads() {
+        if let Some(upload) = uploads.get_mut(id) {
+            upload.state = state;
+            upload.decided = Some(Instant::now());
+        }
+    }
+}
+
+fn quarantined_path(quarantine: &Quarantine, id: &String) -> String {
+    format!("{}/{id}", quarantine.quarantine_dir.trim_end_matches('/'))
+}
+
+fn metadata_path(quarantine: &Quarantine, id: &String) -> String {
+    format!("{}.json", quarantined_path(quarantine, id))
+}
+
+pub fn upload_name<'a>(quarantine: &Quarantine, resource: &'a String) -> Option<&'a str> {
+    let name = resource.strip_prefix(quarantine.location.trim_end_matches('/'))?.strip_prefix('/')?;
+    if name.is_empty() || name.contains('/') || name.contains('\\') || name.starts_with('.') {
+        return None;
+    }
+
+    Some(name)
+}
+
+pub fn is_admin_resource(quarantine: &Quarantine, resource: &String) -> bool {
+    let Some(admin_endpoint) = &quarantine.admin_endpoint else {
+        return false;
+    };
+
+    resource.strip_prefix(admin_endpoint.trim_end_matches('/')).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+async fn move_into_serving_tree(quarantine: &Quarantine, id: &String, name: &String) -> io::Result<()> {
+    let target_dir = format!("{}/{}", document_root(), quarantine.target_dir.trim_matches('/'));
+    let source = quarantined_path(quarantine, id);
+    let target = format!("{target_dir}/{name}");
+
+    fs::create_dir_all(&target_dir).await?;
+    if fs::rename(&source, &target).await.is_ok() {
+        return Ok(());
+    }
+
+    let temporary = format!("{target_dir}/.{name}.{id}");
+    fs::copy(&source, &temporary).await?;
+    fs::rename(&temporary, &target).await?;
+    fs::remove_file(&source).await
+}
+
+async fn decide(id: &String, approve: bool) -> bool {
+    let config = CONFIG.load();
+    let Some(quarantine) = &config.quarantine else {
+        return false;
+    };
+
+    let (name, user, size) = match uploads() {
+        Some(uploads) => match uploads.get(id) {
+            Some(upload) if upload.state == UploadState::Pending => (upload.name.clone(), upload.user.clone(), upload.size as u64),
+            _ => return false
+        },
+        None => return false
+    };
+
+    if approve {
+        if let Err(e) = move_into_serving_tree(quarantine, id, &name).await {
+            error!("An error occurred while moving upload {id} ({name}) out of quarantine.\n\
+                    Error information:\n{e}");
+            return false;
+        }
+    } else {
+        let _ = fs::remove_file(quarantined_path(quarantine, id)).await;
+    }
+
+    let quota_guard = QUOTA_LOCK.lock().await;
+    if let Some(user) = &user {
+        store_pending_bytes(user, pending_bytes(user).await.saturating_sub(size)).await;
+    }
+    set_state(id, if approve {UploadState::Approved} else {UploadState::Rejected});
+    drop(quota_guard);
+
+    let _ = fs::remove_file(metadata_path(quarantine, id)).await;
+
+    debug!("Upload {id} ({name}) was {}.", if approve {"approved"} else {"rejected"});
+
+    true
+}
+
+async fn scan(id: String, path: String, scanner: String) {
+    let approve = match Command::new(&scanner).arg(&path).status().await {
+        Ok(status) => status.success(),
+        Err(e) => {
+            error!("An error occurred while running {scanner} on upload {id}.\n\
+                    Error information:\n{e}\n\
+                    Leaving the upload pending...");
+            return;
+        }
+    };
+
+    decide(&id, approve).await;
+}
+
+async fn directory_size(path: &str) -> u64 {
+    let Ok(mut entries) = fs::read_dir(path).await else {
+        return 0;
+    };
+
+    let mut size = 0;
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if let Ok(metadata) = entry.metadata().await {
+            if metadata.is_file() {
+                size += metadata.len();
+            }
+        }
+    }
+
+    size
+}
+
+fn pending_bytes_key(user: &String) -> String {
+    format!("quarantine:pending:{user}")
+}
+
+fn local_pending_bytes(user: &String) -> u64 {
+    uploads().map_or(0, |uploads| {
+        uploads.values()
+            .filter(|upload| upload.state == UploadState::Pending && upload.user.as_ref() == Some(user))
+            .map(|upload| upload.size as u64)
+            .sum()
+    })
+}
+
+async fn pending_bytes(user: &String) -> u64 {
+    match STORAGE.get(&pending_bytes_key(user)).await {
+        Ok(Some(bytes)) => bytes.parse::<u64>().unwrap_or(0),
+        Ok(None) => local_pending_bytes(user),
+        Err(e) => {
+            warn!("An error occurred while reading the pending upload size of {user}.\n\
+                   Error information:\n{e}");
+            local_pending_bytes(user)
+        }
+    }
+}
+
+async fn store_pending_bytes(user: &String, bytes: u64) {
+    let key = pending_bytes_key(user);
+    let result = match bytes {
+        0 => STORAGE.delete(&key).await,
+        bytes => STORAGE.set(&key, &bytes.to_string(), PENDING_BYTES_TTL).await
+    };
+
+    if let Err(e) = result {
+        warn!("An error occurred while storing the pending upload size of {user}.\n\
+               Error information:\n{e}");
+    }
+}
+
+async fn exceeds_quota(quarantine: &Quarantine, user: Option<&String>, length: u64) -> bool {
+    if let Some(quota) = quarantine.quota {
+        let target_dir = format!("{}/{}", document_root(), quarantine.target_dir.trim_matches('/'));
+
+        for root in [&quarantine.quarantine_dir, &target_dir] {
+            if directory_size(root).await + length > quota {
+                return true;
+            }
+        }
+    }
+
+    if let (Some(user_quota), Some(user)) = (quarantine.user_quota, user) {
+        if pending_bytes(user).await + length > user_quota {
+            return true;
+        }
+    }
+
+    false
+}
+
+async fn current_etag(quarantine: &Quarantine, name: &str) -> Option<String> {
+    let pending = uploads().and_then(|uploads| {
+        uploads.iter()
+            .find(|(_, upload)| upload.name.eq(name) && upload.state == UploadState::Pending)
+            .map(|(id, _)| format!("\"{id}\""))
+    });
+    if pending.is_some() {
+        return pending;
+    }
+
+    let target = format!("{}/{}/{name}", document_root(), quarantine.target_dir.trim_matches('/'));
+    file_etag(&target, None).await
+}
+
+pub async fn receive_upload<T>(stream: &mut T,
+                               quarantine: &Quarantine,
+                               name: &str,
+                               data: &Option<RequestBody>,
+                               headers: &HashMap<String, String>) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+    T: AsyncRead + AsyncWrite + Unpin
+{
+    let Some(content) = raw_body(data) else {
+        return send_response(stream, 415, None, None, None, None).await;
+    };
+
+    let user = headers.get(REMOTE_USER_HEADER);
+    let quota_guard = QUOTA_LOCK.lock().await;
+    if let Precondition::Failed = evaluate(headers, current_etag(quarantine, name).await.as_deref(), false) {
+        drop(quota_guard);
+        return send_response(stream, 412, None, None, None, None).await;
+    }
+
+    if exceeds_quota(quarantine, user, content.len() as u64).await {
+        drop(quota_guard);
+        return send_response(stream, 507, None, None, None, None).await;
+    }
+
+    let mut bytes = [0u8; 16];
+    rand_bytes(&mut bytes)?;
+    let id: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    let path = quarantined_path(quarantine, &id);
+
+    let upload = Upload {
+        name: String::from(name),
+        user: user.cloned(),
+        size: content.len(),
+        received: Utc::now().to_rfc3339(),
+        state: UploadState::Pending,
+        decided: None
+    };
+
+    fs::create_dir_all(&quarantine.quarantine_dir).await?;
+    fs::write(&path, &content).await?;
+    fs::write(metadata_path(quarantine, &id), serde_json::to_vec(&upload)?).await?;
+
+    if let Some(user) = user {
+        store_pending_bytes(user, pending_bytes(user).await + content.len() as u64).await;
+    }
+
+    let body = upload_json(&id, &upload).to_string();
+    if let Some(mut uploads) = uploads() {
+        uploads.insert(id.clone(), upload);
+    }
+
+    drop(quota_guard);
+
+    let etag = format!("\"{id}\"");
+    if let Some(scanner) = &quarantine.scanner {
+        spawn(scan(id, path, scanner.clone()));
+    }
+
+    let response_headers = HashMap::from([
+        (String::from("Content-Type"), String::from("application/json")),
+        (String::from("ETag"), etag)
+    ]);
+    send_response(stream, 202, Some(response_headers), Some(Vec::from(body)), None, Some(Dynamic)).await
+}
+
+pub async fn send_uploads_admin<T>(stream: &mut T,
+                                   quarantine: &Quarantine,
+                                   request: &Request,
+                                   remote_ip: &IpAddr) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+    T: AsyncRead + AsyncWrite + Unpin
+{
+    if !remote_ip.is_loopback() {
+        return send_response(stream, 403, None, None, None, None).await;
+    }
+
+    let (Some(admin_endpoint), Some(resource)) = (&quarantine.admin_endpoint, request.resource()) else {
+        return send_response(stream, 404, None, None, None, None).await;
+    };
+    let path: Vec<&str> = resource[admin_endpoint.trim_end_matches('/').len()..].split('/').filter(|s| !s.is_empty()).collect();
+
+    let response_headers = HashMap::from([
+        (String::from("Content-Type"), String::from("application/json")),
+        (String::from("Cache-Control"), String::from("no-store"))
+    ]);
+
+    match (request, path.as_slice()) {
+        (Request::Get {..}, []) => {
+            let uploads: Vec<Value> = match uploads() {
+                Some(uploads) => uploads.iter().map(|(id, upload)| upload_json(id, upload)).collect(),
+                None => Vec::new()
+            };
+            send_response(stream, 200, Some(response_headers), Some(Vec::from(Value::Array(uploads).to_string())), None, Some(Dynamic)).await
+        },
+        (Request::Get {..}, [id]) => {
+            let upload = uploads().and_then(|uploads| uploads.get(*id).map(|upload| upload_json(&String::from(*id), upload)));
+            match upload {
+                Some(upload) => send_response(stream, 200, Some(response_headers), Some(Vec::from(upload.to_string())), None, Some(Dynamic)).await,
+                None => send_response(stream, 404, None, None, None, None).await
+            }
+        },
+        (Request::Post {..}, [id, action @ ("approve" | "reject")]) => {
+            let id = String::from(*id);
+            if !uploads().is_some_and(|uploads| uploads.contains_key(&id)) {
+                return send_response(stream, 404, None, None, None, None).await;
+            }
+
+            if decide(&id, *action == "approve").await {
+                send_response(stream, 204, None, None, None, None).await
+            } else {
+                send_response(stream, 409, None, None, None, None).await
+            }
+        },
+        _ => send_response(stream, 404, None, None, None, None).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pending_bytes, store_pending_bytes};
+
+    #[tokio::test]
+    async fn pending_bytes_are_kept_in_storage() {
+        let user = String::from("quarantine-test");
+        assert_eq!(pending_bytes(&user).await, 0);
+
+        store_pending_bytes(&user, 1024).await;
+        assert_eq!(pending_bytes(&user).await, 1024);
+
+        store_pending_bytes(&user, 0).await;
+        assert_eq!(pending_bytes(&user).await, 0);
+    }
+}
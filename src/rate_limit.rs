@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::error;
+use crate::config::{RateLimitKey, CONFIG};
+use crate::storage::{Store, STORAGE};
+use crate::util::REQUEST_INFO;
+
+const MAX_TRACKED_KEYS: usize = 65536;
+
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant
+}
+
+static BUCKETS: LazyLock<Vec<Mutex<HashMap<String, TokenBucket>>>> = LazyLock::new(|| {
+    match &CONFIG.load().rate_limits {
+        Some(rate_limits) => rate_limits.iter().map(|_| Mutex::new(HashMap::new())).collect(),
+        None => Vec::new()
+    }
+});
+
+impl RateLimitKey {
+    pub fn derive(&self, remote_ip: &IpAddr, headers: &HashMap<String, String>) -> Option<String> {
+        match self {
+            RateLimitKey::Ip => Some(remote_ip.to_string()),
+            RateLimitKey::Header(name) => headers.get(&name.to_lowercase()).cloned(),
+            RateLimitKey::Cookie(name) => {
+                headers.get("cookie")?
+                    .split(';')
+                    .filter_map(|cookie| cookie.trim().split_once('='))
+                    .find(|(k, _)| k.eq(name))
+                    .map(|(_, v)| String::from(v))
+            },
+            RateLimitKey::JwtSubject => {
+                REQUEST_INFO.try_with(|info| {
+                    info.borrow().jwt_claims.as_ref()?.get("sub")?.as_str().map(String::from)
+                }).ok().flatten()
+            }
+        }
+    }
+}
+
+pub async fn check_rate_limits(remote_ip: &IpAddr, headers: &HashMap<String, String>) -> Option<u64> {
+    let Some(rate_limits) = &CONFIG.load().rate_limits else {
+        return None;
+    };
+
+    let mut retry_after: Option<u64> = None;
+
+    for (i, (rate_limit, buckets)) in rate_limits.iter().zip(BUCKETS.iter()).enumerate() {
+        let Some(key) = rate_limit.key.derive(remote_ip, headers) else {
+            continue;
+        };
+
+        if STORAGE.is_shared() {
+            let window = Duration::from_secs_f64(rate_limit.capacity as f64 / rate_limit.refill_per_second);
+            match STORAGE.increment(&format!("rate_limit:{i}:{key}"), window).await {
+                Ok((count, remaining)) if count > rate_limit.capacity as u64 => {
+                    let wait = remaining.as_secs_f64().ceil() as u64;
+                    retry_after = Some(retry_after.map_or(wait, |r| r.max(wait)));
+                },
+                Ok(_) => {},
+                Err(e) => {
+                    error!("An error occurred while updating a shared rate limit counter.\n\
+                            Error information:\n{e}\n\
+                            Letting the request through...");
+                }
+            }
+            continue;
+        }
+
+        let capacity = rate_limit.capacity as f64;
+        let refill_per_second = rate_limit.refill_per_second;
+        let now = Instant::now();
+        let mut buckets = buckets.lock().await;
+
+        if buckets.len() >= MAX_TRACKED_KEYS {
+            buckets.retain(|_, b| b.tokens + now.duration_since(b.last_refill).as_secs_f64() * refill_per_second < capacity);
+        }
+
+        let bucket = buckets.entry(key).or_insert(TokenBucket {tokens: capacity, last_refill: now});
+        bucket.tokens = (bucket.tokens + now.duration_since(bucket.last_refill).as_secs_f64() * refill_per_second).min(capacity);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1f64 {
+            bucket.tokens -= 1f64;
+            continue;
+        }
+
+        let wait = ((1f64 - bucket.tokens) / refill_per_second).ceil() as u64;
+        retry_after = Some(retry_after.map_or(wait, |r| r.max(wait)));
+    }
+
+    retry_after
+}
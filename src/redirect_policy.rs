@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+use glob::Pattern;
+use tracing::error;
+use crate::config::{RedirectPolicyAction, CONFIG};
+use crate::util::REQUEST_INFO;
+
+fn strip_port(host: &str) -> &str {
+    match host.rsplit_once(':') {
+        Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) && !name.ends_with(':') => name,
+        _ => host
+    }
+}
+
+fn is_relative_location_safe(location: &str) -> bool {
+    let path = location.split(['?', '#']).next().unwrap_or("");
+    let mut depth: usize = 0;
+
+    for segment in path.split('/').filter(|s| !s.is_empty() && *s != ".") {
+        if segment == ".." {
+            if depth == 0 {
+                return false;
+            }
+            depth -= 1;
+        } else {
+            depth += 1;
+        }
+    }
+    true
+}
+
+fn is_host_allowed(host: &str, allowed_hosts: &Vec<String>) -> bool {
+    let host = strip_port(host).to_lowercase();
+    if host.is_empty() {
+        return false;
+    }
+
+    let own_host = REQUEST_INFO.try_with(|info| info.borrow().host.clone()).ok().flatten();
+    if own_host.is_some_and(|h| strip_port(&h).eq_ignore_ascii_case(&host)) {
+        return true;
+    }
+
+    allowed_hosts.iter().any(|a| matches!(Pattern::new(&a.to_lowercase()), Ok(pattern) if pattern.matches(&host)))
+}
+
+fn is_location_safe(location: &str, allowed_hosts: &Vec<String>) -> bool {
+    let location = location.trim();
+
+    if location.contains('\\') || location.chars().any(|c| c.is_ascii_control()) {
+        return false;
+    }
+
+    let authority = if let Some(rest) = location.strip_prefix("//") {
+        rest
+    } else if let Some((scheme, rest)) = location.split_once("://") {
+        if !scheme.eq_ignore_ascii_case("http") && !scheme.eq_ignore_ascii_case("https") {
+            return false;
+        }
+        rest
+    } else if location.split(['/', '?', '#']).next().is_some_and(|first| first.contains(':')) {
+        return false;
+    } else {
+        return is_relative_location_safe(location);
+    };
+
+    let host = authority.split(['/', '?', '#']).next().unwrap_or("");
+    let host = host.rsplit_once('@').map_or(host, |(_, h)| h);
+    is_host_allowed(host, allowed_hosts)
+}
+
+pub fn check_redirect(status: &mut u16, headers: &mut HashMap<String, String>) {
+    let Some(redirect_policy) = &CONFIG.load().redirect_policy else {
+        return;
+    };
+
+    let Some(name) = headers.keys().find(|k| k.eq_ignore_ascii_case("location")).cloned() else {
+        return;
+    };
+
+    if is_location_safe(&headers[&name], &redirect_policy.allowed_hosts) {
+        return;
+    }
+
+    let resource = REQUEST_INFO.try_with(|info| info.borrow().resource.clone()).ok().flatten().unwrap_or_default();
+    error!("A response to /{} redirects to a location outside the site: {}.{}",
+           resource.trim_start_matches('/'), headers[&name],
+           if redirect_policy.action == RedirectPolicyAction::Block {" The redirect was blocked."} else {""});
+
+    if redirect_policy.action == RedirectPolicyAction::Block {
+        headers.remove(&name);
+        *status = 500;
+    }
+}
@@ -0,0 +1,62 @@
+use std::error::Error;
+use std::net::IpAddr;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::task::spawn_blocking;
+use tracing::error;
+use crate::cluster::{publish, ClusterEvent};
+use crate::config::reload_config;
+use crate::util::send_response;
+
+pub async fn reload() -> bool {
+    if !matches!(spawn_blocking(reload_config).await, Ok(true)) {
+        return false;
+    }
+
+    #[cfg(target_family = "unix")]
+    {
+        use crate::config::CONFIG;
+        use crate::ssl::SSL;
+
+        if let (Some(ssl_info), false) = (&*SSL, CONFIG.load().chroot) {
+            ssl_info.reload();
+        }
+    }
+
+    true
+}
+
+#[cfg(target_family = "unix")]
+pub async fn reload_on_sighup() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sighup = match signal(SignalKind::hangup()) {
+        Ok(sighup) => sighup,
+        Err(e) => {
+            error!("An error occurred while installing the SIGHUP handler.\n\
+                    Error information:\n{e}");
+            return;
+        }
+    };
+
+    while sighup.recv().await.is_some() {
+        if reload().await {
+            publish(ClusterEvent::Reload).await;
+        }
+    }
+}
+
+pub async fn send_reload<T>(stream: &mut T, remote_ip: &IpAddr) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+    T: AsyncRead + AsyncWrite + Unpin
+{
+    if !remote_ip.is_loopback() {
+        return send_response(stream, 403, None, None, None, None).await;
+    }
+
+    if !reload().await {
+        return send_response(stream, 500, None, None, None, None).await;
+    }
+
+    publish(ClusterEvent::Reload).await;
+    send_response(stream, 204, None, None, None, None).await
+}
@@ -1,29 +1,37 @@
 use std::error::Error;
 use std::collections::HashMap;
 use std::net::IpAddr;
-use std::path::Path;
 use std::str::FromStr;
 use bstr::ByteSlice;
 use tokio::fs::*;
 use regex::*;
-use libloading::Error as LibError;
 use mime_guess::Mime;
-use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use std::time::Duration;
+use tokio::io::{copy_bidirectional, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::time::timeout;
+use tracing::{debug, error};
 use crate::util::*;
-use crate::config::CONFIG;
+use crate::config::{DenyAction, CONFIG};
 use crate::error::ServerError;
 use crate::pages::index_of::index_of;
-use crate::pages::internal_server_error::internal_server_error;
 use drain_common::RequestBody;
 use drain_common::RequestData::*;
 use drain_common::cookies::SetCookie;
-use tokio::sync::Semaphore;
-use crate::util::ResourceType::{Dynamic, Static};
+use crate::util::ResourceType::{Dynamic, Precompressed, Static};
 #[cfg(feature = "cgi")]
 use crate::cgi::CGIData;
-use crate::endpoints::{endpoint, ENDPOINT_LIBRARY};
-use crate::pages::forbidden::forbidden;
+use crate::endpoints::{endpoint, endpoint_library, endpoint_names, streaming_endpoint, MissingEndpoint};
 use crate::pages::not_found::not_found;
+use crate::pages::denied::denied;
+use crate::dns;
+use crate::fs_index;
+use crate::file_cache::open_file;
+use crate::filters::sub_filters_apply;
+use crate::zero_copy::ZeroCopy;
+use crate::preconditions::{evaluate, Precondition};
+use crate::access_log::{record_bytes_sent, record_status};
+
+const SNIFF_LENGTH: usize = 8192;
 
 pub enum Request {
     Get {
@@ -68,7 +76,10 @@ pub enum Request {
         data: Option<RequestBody>,
         #[cfg(feature = "cgi")]
         cgi_data: Option<CGIData>},
-    Connect,
+    Connect {
+        host: String,
+        port: u16
+    },
     Options,
     Trace(Vec<u8>),
     Patch {
@@ -84,9 +95,13 @@ pub enum Request {
 }
 
 impl Request {
-    pub fn parse_from_string(request_string: &String, keep_alive: &mut bool) -> Result<Self, ServerError> {
+    pub fn parse_from_string(request_string: &String, request_bytes: &[u8], keep_alive: &mut bool) -> Result<Self, ServerError> {
+        if request_string.starts_with("CONNECT ") {
+            return Self::parse_connect(request_string);
+        }
+
         let general_regex = Regex::new(
-        r#"^((GET|HEAD|POST|PUT|DELETE|CONNECT|OPTIONS|TRACE|PATCH) /(((([A-Za-z0-9\-_]*\.[[:alnum:]]+/?)+)+|([A-Za-z0-9\-_]+/?)+)+(\?([[:alnum:]]+=[[:alnum:]]+)(&[[:alnum:]]+=[[:alnum:]]+)*)?)? (HTTP/((0\.9)|(1\.0)|(1\.1)|(2)|(3))))(\r\n(([[:alnum]]+(([-_])[[:alnum:]]+)*)(: )([A-Za-z0-9_ :;.,/"'?!(){}\[\]@<>=\-+*#$&`|~^%]+)))*[\S\s]*\z"#
+        r#"^((GET|HEAD|POST|PUT|DELETE|CONNECT|OPTIONS|TRACE|PATCH) /+(((([A-Za-z0-9\-_]*\.[[:alnum:]]+/*)+)+|([A-Za-z0-9\-_]+/*)+)+(\?([[:alnum:]]+=[[:alnum:]]+)(&[[:alnum:]]+=[[:alnum:]]+)*)?)? (HTTP/((0\.9)|(1\.0)|(1\.1)|(2)|(3))))(\r\n(([[:alnum]]+(([-_])[[:alnum:]]+)*)(: )([A-Za-z0-9_ :;.,/"'?!(){}\[\]@<>=\-+*#$&`|~^%]+)))*[\S\s]*\z"#
         ).unwrap();
 
         if !general_regex.is_match(request_string.as_str()) {
@@ -105,7 +120,7 @@ impl Request {
         let mut params: HashMap<String, String> = HashMap::new();
 
         if req_type.eq("TRACE") {
-            return Ok(Self::Trace(Vec::from(request_string.as_bytes())));
+            return Ok(Self::Trace(Self::prepare_trace_echo(request_bytes)?));
         }
 
         if !http_version.eq("HTTP/1.1") {
@@ -201,7 +216,6 @@ impl Request {
                 #[cfg(feature = "cgi")]
                 cgi_data: None
             },
-            "CONNECT" => Self::Connect,
             "OPTIONS" => Self::Options,
             "PATCH" => Self::Patch {
                 resource,
@@ -217,14 +231,313 @@ impl Request {
         };
         Ok(req)
     }
+
+    pub fn method(&self) -> &'static str {
+        match self {
+            Self::Get {..} => "GET",
+            Self::Head {..} => "HEAD",
+            Self::Post {..} => "POST",
+            Self::Put {..} => "PUT",
+            Self::Delete {..} => "DELETE",
+            Self::Connect {..} => "CONNECT",
+            Self::Options => "OPTIONS",
+            Self::Trace(_) => "TRACE",
+            Self::Patch {..} => "PATCH"
+        }
+    }
+
+    pub fn headers(&self) -> Option<&HashMap<String, String>> {
+        match self {
+            Self::Get {headers, ..} |
+            Self::Head {headers, ..} |
+            Self::Post {headers, ..} |
+            Self::Put {headers, ..} |
+            Self::Delete {headers, ..} |
+            Self::Patch {headers, ..} => Some(headers),
+            _ => None
+        }
+    }
+
+    pub fn headers_mut(&mut self) -> Option<&mut HashMap<String, String>> {
+        match self {
+            Self::Get {headers, ..} |
+            Self::Head {headers, ..} |
+            Self::Post {headers, ..} |
+            Self::Put {headers, ..} |
+            Self::Delete {headers, ..} |
+            Self::Patch {headers, ..} => Some(headers),
+            _ => None
+        }
+    }
+
+    pub fn params(&self) -> Option<&HashMap<String, String>> {
+        match self {
+            Self::Get {params, ..} |
+            Self::Head {params, ..} |
+            Self::Post {params, ..} |
+            Self::Put {params, ..} |
+            Self::Delete {params, ..} |
+            Self::Patch {params, ..} => params.as_ref(),
+            _ => None
+        }
+    }
+
+    pub fn resource(&self) -> Option<&String> {
+        match self {
+            Self::Get {resource, ..} |
+            Self::Head {resource, ..} |
+            Self::Post {resource, ..} |
+            Self::Put {resource, ..} |
+            Self::Delete {resource, ..} |
+            Self::Patch {resource, ..} => Some(resource),
+            _ => None
+        }
+    }
+
+    pub fn normalize(&mut self) -> Result<Option<String>, ServerError> {
+        let normalization = &CONFIG.load().normalization;
+        let (resource, params, headers) = match self {
+            Self::Get {resource, params, headers, ..} |
+            Self::Head {resource, params, headers, ..} |
+            Self::Post {resource, params, headers, ..} |
+            Self::Put {resource, params, headers, ..} |
+            Self::Delete {resource, params, headers, ..} |
+            Self::Patch {resource, params, headers, ..} => (resource, params, headers),
+            _ => return Ok(None)
+        };
+
+        let mut resource_changed = false;
+        let mut host_changed = false;
+
+        if resource.contains("//") {
+            if !normalization.merge_slashes {
+                return Err(ServerError::InvalidRequest);
+            }
+
+            let mut merged = String::with_capacity(resource.len());
+            for c in resource.chars() {
+                if !(c == '/' && merged.ends_with('/')) {
+                    merged.push(c);
+                }
+            }
+
+            *resource = merged;
+            resource_changed = true;
+        }
+
+        if let Some(host) = headers.get_mut("host") {
+            let mut normalized = if normalization.lowercase_host {host.to_lowercase()} else {host.clone()};
+            if normalization.strip_default_port {
+                let default_port = if REQUEST_INFO.try_with(|info| info.borrow().https).unwrap_or(false) {":443"} else {":80"};
+                if let Some(stripped) = normalized.strip_suffix(default_port).map(String::from) {
+                    normalized = stripped;
+                }
+            }
+
+            if normalized.ne(host) {
+                *host = normalized;
+                host_changed = true;
+            }
+        }
+
+        if !normalization.redirect || !(resource_changed || host_changed) {
+            return Ok(None);
+        }
+
+        let query = params.iter().flatten()
+            .map(|(k, v)| format!("{}={}", urlencoding::encode(k), urlencoding::encode(v)))
+            .collect::<Vec<String>>()
+            .join("&");
+
+        Ok(Some(format!("{}{resource}{}",
+                        match headers.get("host") {
+                            Some(host) if host_changed => format!("//{host}"),
+                            _ => String::new()
+                        },
+                        if query.is_empty() {String::new()} else {format!("?{query}")})))
+    }
+
+    pub fn https_location(&self) -> Option<String> {
+        let config = CONFIG.load();
+        let https = config.https.as_ref().filter(|https| https.enabled && https.redirect_http)?;
+        let resource = self.resource()?;
+        let host = self.headers()?.get("host")?;
+        let host = match host.rsplit_once(':') {
+            Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) => name,
+            _ => host.as_str()
+        };
+
+        let query = self.params().into_iter().flatten()
+            .map(|(k, v)| format!("{}={}", urlencoding::encode(k), urlencoding::encode(v)))
+            .collect::<Vec<String>>()
+            .join("&");
+
+        Some(format!("https://{host}{}{resource}{}",
+                     if https.bind_port == 443 {String::new()} else {format!(":{}", https.bind_port)},
+                     if query.is_empty() {String::new()} else {format!("?{query}")}))
+    }
+
+    fn parse_connect(request_string: &String) -> Result<Self, ServerError> {
+        let connect_regex = Regex::new(r#"^CONNECT ([A-Za-z0-9\-.]+|\[[0-9A-Fa-f:.]+\]):([0-9]{1,5}) HTTP/1\.1\r\n"#).unwrap();
+
+        let Some(captures) = connect_regex.captures(request_string.as_str()) else {
+            return Err(ServerError::InvalidRequest);
+        };
+
+        let host = String::from(captures[1].trim_matches(|c| c == '[' || c == ']'));
+        let Ok(port) = captures[2].parse::<u16>() else {
+            return Err(ServerError::InvalidRequest);
+        };
+
+        Ok(Self::Connect {host, port})
+    }
+
+    fn prepare_trace_echo(request_bytes: &[u8]) -> Result<Vec<u8>, ServerError> {
+        let mut echo: Vec<u8> = Vec::new();
+
+        for (i, line) in request_bytes.split_inclusive(|b| *b == b'\n').enumerate() {
+            let line_content = line.trim_end_with(|c| c == '\r' || c == '\n');
+            if line_content.is_empty() {
+                break;
+            }
+
+            if i > 0 {
+                if let Some((name, value)) = line_content.split_once_str(":") {
+                    let name = String::from_utf8_lossy(name);
+                    let name_lower = name.trim().to_lowercase();
+                    if CONFIG.load().trace_redacted_headers.iter().any(|h| h.eq_ignore_ascii_case(&name_lower)) {
+                        continue;
+                    }
+
+                    if name_lower.eq("max-forwards") {
+                        let Ok(max_forwards) = String::from_utf8_lossy(value).trim().parse::<u32>() else {
+                            return Err(ServerError::InvalidRequest);
+                        };
+
+                        echo.extend_from_slice(format!("{}: {}\r\n", name.trim(), max_forwards.saturating_sub(1)).as_bytes());
+                        continue;
+                    }
+                }
+            }
+
+            echo.extend_from_slice(line);
+            if echo.len() > CONFIG.load().trace_max_length {
+                return Err(ServerError::HeadersTooLarge);
+            }
+        }
+        echo.extend_from_slice(b"\r\n");
+
+        Ok(echo)
+    }
+}
+
+async fn precompressed_sidecar(path: &String, headers: &HashMap<String, String>) -> Option<(String, String)> {
+    let config = CONFIG.load();
+    for encoding in config.precompressed_encodings(headers) {
+        let extension = if encoding.eq("gzip") {"gz"} else {"br"};
+        let sidecar_path = format!("{path}.{extension}");
+
+        if fs_index::is_file(&sidecar_path).await && is_inside_document_root(&sidecar_path).await {
+            return Some((sidecar_path, encoding.clone()));
+        }
+    }
+    None
+}
+
+fn static_media_type(resource: &str, content: Option<&[u8]>) -> (String, String) {
+    match mime_guess::from_path(resource).first() {
+        Some(guess) => (guess.to_string(), guess.type_().to_string()),
+        None if content.is_some_and(|content| content.is_utf8()) => (String::from("text/plain"), String::from("text")),
+        None => (String::from("application/octet-stream"), String::from("application"))
+    }
 }
 
-static FILE_HANDLE_LIMIT: Semaphore = Semaphore::const_new(
-    if cfg!(target_os = "linux") { 1023 }
-    else if cfg!(target_os = "windows") { 16777215 }
-    else if cfg!(target_os = "macos") { 10239 }
-    else { 255 }
-);
+fn static_encoding(sidecar: &Option<(String, String)>,
+                   length: usize,
+                   guess: &String,
+                   general_type: &String,
+                   headers: &HashMap<String, String>) -> Option<String> {
+    let config = CONFIG.load();
+    match sidecar {
+        Some((_, encoding)) => Some(encoding.clone()),
+        None if config.is_precompressed_only() => None,
+        None => config.response_encoding_for_length(length, guess, general_type, headers).cloned()
+    }
+}
+
+fn insert_encoding(response_headers: &mut HashMap<String, String>, encoding: &Option<String>) {
+    if let Some(encoding) = encoding {
+        response_headers.insert(String::from("Content-Encoding"), encoding.clone());
+        response_headers.insert(String::from("Vary"), String::from("Accept-Encoding"));
+    }
+}
+
+fn is_large_file(length: u64) -> bool {
+    let zero_copy_threshold = CONFIG.load().zero_copy_threshold;
+    zero_copy_threshold != 0 && length >= zero_copy_threshold
+}
+
+async fn sniff(path: &String) -> Option<Vec<u8>> {
+    let mut prefix = Vec::with_capacity(SNIFF_LENGTH);
+    let mut file = open_file(path).await.ok()?;
+    (&mut *file).take(SNIFF_LENGTH as u64).read_to_end(&mut prefix).await.ok()?;
+
+    let complete = match std::str::from_utf8(&prefix) {
+        Ok(_) => prefix.len(),
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        Err(_) => return Some(prefix)
+    };
+    prefix.truncate(complete);
+    Some(prefix)
+}
+
+async fn send_large_file<T>(stream: &mut T,
+                            response_headers: &mut HashMap<String, String>,
+                            file_path: &String,
+                            resource: &String,
+                            sidecar: &Option<(String, String)>,
+                            headers: &HashMap<String, String>) -> Option<Result<(), Box<dyn Error + Send + Sync>>>
+where
+    T: AsyncRead + AsyncWrite + Unpin + ZeroCopy
+{
+    let meta = metadata(file_path).await.ok()?;
+    if !is_large_file(meta.len()) {
+        return None;
+    }
+
+    let (guess, general_type) = static_media_type(resource, None);
+    let encoding = static_encoding(sidecar, meta.len() as usize, &guess, &general_type, headers);
+    let encoder = match &encoding {
+        Some(encoding) if sidecar.is_none() => Some(StreamingEncoder::new(encoding)?),
+        _ => None
+    };
+
+    if sub_filters_apply(&guess) {
+        return None;
+    }
+
+    insert_encoding(response_headers, &encoding);
+
+    let etag = file_etag(file_path, None).await;
+    if let Some(etag) = &etag {
+        response_headers.insert(String::from("ETag"), etag.clone());
+    }
+    response_headers.insert(String::from("Cache-Control"), CONFIG.load().cache_control());
+
+    match evaluate(headers, etag.as_deref(), true) {
+        Precondition::NotModified => return Some(send_response(stream, 304, Some(response_headers.clone()), None, None, None).await),
+        Precondition::Failed => return Some(send_response(stream, 412, None, None, None, None).await),
+        Precondition::Proceed => {}
+    }
+
+    response_headers.insert(String::from("Content-Type"), guess);
+
+    let mut file = open_file(file_path).await.ok()?;
+    match encoder {
+        Some(encoder) => Some(send_compressed_file(stream, 200, response_headers.clone(), &mut file, encoder).await),
+        None => Some(send_file(stream, 200, response_headers.clone(), &mut file, meta.len()).await)
+    }
+}
 
 pub async fn handle_get<T>(stream: &mut T,
                            headers: &HashMap<String, String>,
@@ -236,51 +549,39 @@ pub async fn handle_get<T>(stream: &mut T,
                            #[cfg(feature = "cgi")]
                            mut resource_present_in_endpoints: bool) -> Result<(), Box<dyn Error + Send + Sync>>
 where
-    T: AsyncRead + AsyncWrite + Unpin
+    T: AsyncRead + AsyncWrite + Unpin + ZeroCopy
 {
-    #[cfg(target_family = "unix")]
-    let document_root = if *&*CHROOT {&String::from("")} else {&CONFIG.document_root};
-    #[cfg(not(target_family = "unix"))]
-    let document_root = &CONFIG.document_root;
+    let document_root = &document_root();
     let mut resource = String::from((&resource).trim_start_matches('/'));
 
     let mut response_headers: HashMap<String, String> = HashMap::new();
 
     #[cfg(feature = "cgi")] {
         if !resource_present_in_endpoints {
-            if let Some(access_control) = &CONFIG.access_control {
-                if !access_control.is_access_allowed(&resource) {
-                    let deny_action = access_control.deny_action;
-                    if let Some(library) = &*ENDPOINT_LIBRARY {
-                        if deny_action == 403u16 {
-                            return forbidden(stream, Get(params), headers, response_headers, local_ip, remote_ip, remote_port, library).await;
-                        }
-                        return not_found(stream, Get(params), headers, response_headers, local_ip, remote_ip, remote_port, library).await;
-                    }
-                    return send_response(stream, deny_action, Some(response_headers), None, None, None).await;
-                }
+            if let Some(denial) = CONFIG.load().denial(&resource, "GET", remote_ip) {
+                return denied(stream, denial, Get(params), headers, response_headers, local_ip, remote_ip, remote_port).await;
             }
 
-            if Path::new(&format!("{document_root}/{resource}")).is_dir() {
+            if fs_index::is_dir(&format!("{document_root}/{resource}")).await && is_inside_document_root(&format!("{document_root}/{resource}")).await {
                 let mut res_tmp = String::from("");
-                for index in CONFIG.indices.iter() {
-                    if Path::new(&format!("{document_root}/{resource}/{index}")).is_file() {
+                for index in CONFIG.load().indices.iter() {
+                    if fs_index::is_file(&format!("{document_root}/{resource}/{index}")).await {
                         res_tmp = format!("{resource}/{index}");
                         break;
                     }
                 }
 
-                if Path::new(&format!("{document_root}/{res_tmp}")).is_dir() {
-                    if CONFIG.should_display_index_of(&resource) {
+                if fs_index::is_dir(&format!("{document_root}/{res_tmp}")).await {
+                    if CONFIG.load().should_display_index_of(&resource) {
                         return index_of(stream, &resource, false, headers).await;
                     }
                 }
 
                 let res_tmp_trim = String::from(res_tmp.trim_start_matches("/"));
 
-                if !Path::new(&format!("{document_root}/{res_tmp}")).is_file() && CONFIG.should_display_index_of(&resource) {
-                    match &CONFIG.endpoints {
-                        Some(endpoints) if (&ENDPOINT_LIBRARY).is_some() && endpoints.contains(&res_tmp_trim) => {
+                if !fs_index::is_file(&format!("{document_root}/{res_tmp}")).await && CONFIG.load().should_display_index_of(&resource) {
+                    match endpoint_names() {
+                        Some(endpoints) if endpoint_library().is_some() && endpoints.contains(&res_tmp_trim) => {
                             resource_present_in_endpoints = true;
                         },
                         _ => {
@@ -293,7 +594,7 @@ where
             }
 
             if !resource_present_in_endpoints {
-                if let Some(endpoints) = &CONFIG.endpoints {
+                if let Some(endpoints) = endpoint_names() {
                     if endpoints.contains(&resource) {
                         resource_present_in_endpoints = true;
                     }
@@ -306,39 +607,30 @@ where
     let mut resource_present_in_endpoints = false;
 
     #[cfg(not(feature = "cgi"))] {
-        if let Some(access_control) = &CONFIG.access_control {
-            if !access_control.is_access_allowed(&resource) {
-                let deny_action = access_control.deny_action;
-                if let Some(library) = &*ENDPOINT_LIBRARY {
-                    if deny_action == 403u16 {
-                        return forbidden(stream, Get(params), headers, response_headers, local_ip, remote_ip, remote_port, library).await;
-                    }
-                    return not_found(stream, Get(params), headers, response_headers, local_ip, remote_ip, remote_port, library).await;
-                }
-                return send_response(stream, deny_action, Some(response_headers), None, None, None).await;
-            }
+        if let Some(denial) = CONFIG.load().denial(&resource, "GET", remote_ip) {
+            return denied(stream, denial, Get(params), headers, response_headers, local_ip, remote_ip, remote_port).await;
         }
 
-        if Path::new(&format!("{document_root}/{resource}")).is_dir() {
+        if fs_index::is_dir(&format!("{document_root}/{resource}")).await && is_inside_document_root(&format!("{document_root}/{resource}")).await {
             let mut res_tmp = String::from("");
-            for index in CONFIG.indices.iter() {
-                if Path::new(&format!("{document_root}/{resource}/{index}")).is_file() {
+            for index in CONFIG.load().indices.iter() {
+                if fs_index::is_file(&format!("{document_root}/{resource}/{index}")).await {
                     res_tmp = format!("{resource}/{index}");
                     break;
                 }
             }
 
-            if Path::new(&format!("{document_root}/{res_tmp}")).is_dir() {
-                if CONFIG.should_display_index_of(&resource) {
+            if fs_index::is_dir(&format!("{document_root}/{res_tmp}")).await {
+                if CONFIG.load().should_display_index_of(&resource) {
                     return index_of(stream, &resource, false, headers).await;
                 }
             }
 
             let res_tmp_trim = String::from(res_tmp.trim_start_matches("/"));
 
-            if !Path::new(&format!("{document_root}/{res_tmp}")).is_file() && CONFIG.should_display_index_of(&resource) {
-                match &CONFIG.endpoints {
-                    Some(endpoints) if (&ENDPOINT_LIBRARY).is_some() && endpoints.contains(&res_tmp_trim) => {
+            if !fs_index::is_file(&format!("{document_root}/{res_tmp}")).await && CONFIG.load().should_display_index_of(&resource) {
+                match endpoint_names() {
+                    Some(endpoints) if endpoint_library().is_some() && endpoints.contains(&res_tmp_trim) => {
                         resource_present_in_endpoints = true;
                     },
                     _ => {
@@ -351,7 +643,7 @@ where
         }
 
         if !resource_present_in_endpoints {
-            if let Some(endpoints) = &CONFIG.endpoints {
+            if let Some(endpoints) = endpoint_names() {
                 if endpoints.contains(&resource) {
                     resource_present_in_endpoints = true;
                 }
@@ -359,8 +651,20 @@ where
         }
     }
 
-    if let Some(library) = &*ENDPOINT_LIBRARY {
-        if resource_present_in_endpoints {
+    if let Some(library) = endpoint_library() {
+        if resource_present_in_endpoints && CONFIG.load().is_streaming_endpoint(&resource) {
+            let mut set_cookie: HashMap<String, SetCookie> = HashMap::new();
+            let mut status: u16 = 200;
+            match streaming_endpoint(&*resource, stream, Get(params), headers, &mut response_headers, &mut set_cookie, &mut status, local_ip, remote_ip, remote_port, library).await {
+                Ok(Some((content, event_stream))) => {
+                    return send_event_stream(stream, status, response_headers, content, Some(set_cookie), event_stream).await;
+                },
+                Ok(None) => {
+                    return send_response(stream, status, Some(response_headers), None, Some(set_cookie), None).await;
+                },
+                Err(MissingEndpoint) => {}
+            }
+        } else if resource_present_in_endpoints {
             let mut set_cookie: HashMap<String, SetCookie> = HashMap::new();
             let mut status: u16 = 200;
             let content = endpoint(
@@ -386,7 +690,7 @@ where
                         return send_response(stream, status, Some(response_headers), None, Some(set_cookie), None).await
                     };
 
-                    if let Some(encoding) = CONFIG.get_response_encoding(&c, &mime_type, &general_type, headers) {
+                    if let Some(encoding) = CONFIG.load().get_response_encoding(&c, &mime_type, &general_type, headers) {
                         response_headers.insert(String::from("Content-Encoding"), String::from(encoding));
                         response_headers.insert(String::from("Vary"), String::from("Accept-Encoding"));
                     }
@@ -404,80 +708,56 @@ where
 
                     return send_response(stream, status, Some(response_headers), None, Some(set_cookie), None).await;
                 },
-                (Err(e), _) => {
-                    match e {
-                        LibError::DlSym { .. } => {},
-                        _ => {
-                            eprintln!("[handle_get():{}] An unknown error occurred while executing the endpoint.\
-                                                             Attempting to send Internal Server Error page to the client...", line!());
-                            if let Err(e) = internal_server_error(stream).await {
-                                eprintln!("[handle_get():{}] FAILED. Error information:\n{e}", line!());
-                            }
-                            eprintln!("Attempting to close connection...");
-                            if let Err(e) = stream.shutdown().await {
-                                eprintln!("[handle_get():{}] FAILED. Error information:\n{e}", line!());
-                            }
-                            panic!("Unrecoverable error occurred while handling connection.");
-                        }
-                    }
-                }
+                (Err(MissingEndpoint), _) => {}
             }
         }
     }
 
     let path = format!("{document_root}/{resource}");
-    let path = Path::new(&path);
-    if path.is_file() {
-        let _ = FILE_HANDLE_LIMIT.acquire().await?;
-        let file = File::open(format!("{document_root}/{}", &resource)).await;
+    if fs_index::is_file(&path).await && is_inside_document_root(&path).await {
+        let sidecar = precompressed_sidecar(&path, headers).await;
+        let file_path = sidecar.as_ref().map_or(&path, |(sidecar_path, _)| sidecar_path);
+
+        if let Some(result) = send_large_file(stream, &mut response_headers, file_path, &resource, &sidecar, headers).await {
+            return result;
+        }
+
+        let file = open_file(file_path).await;
         match file {
             Ok(mut f) => {
                 let mut content: Vec<u8> = Vec::new();
                 rte_wrapper(&mut f, &mut content, stream).await;
-                let content_empty = content.is_empty();
 
-                let (guess, general_type) = if let Some(guess) = mime_guess::from_path(resource).first() {
-                    (guess.to_string(), guess.type_().to_string())
-                } else {
-                    if content.is_utf8() {
-                        (String::from("text/plain"), String::from("text"))
-                    } else {
-                        (String::from("application/octet-stream"), String::from("application"))
-                    }
-                };
-
-                if let Some(encoding) = CONFIG.get_response_encoding(&content, &guess, &general_type, headers) {
-                    response_headers.insert(String::from("Content-Encoding"), String::from(encoding));
-                    response_headers.insert(String::from("Vary"), String::from("Accept-Encoding"));
-                }
+                let (guess, general_type) = static_media_type(&resource, Some(&content));
+                insert_encoding(&mut response_headers, &static_encoding(&sidecar, content.len(), &guess, &general_type, headers));
 
                 response_headers.insert(String::from("Content-Type"), guess);
 
-                return if content_empty {
-                    send_response(stream, 200, Some(response_headers), None, None, None).await
-                } else {
-                    if let Some(if_none_match) = headers.get("if-none-match") {
-                        let mut excluded_etags = if_none_match.split(",")
-                            .map(|e| e.trim_matches(|x: char| x.is_whitespace() || x == '"').to_string());
-
-                        let etags = ETAGS.lock().await;
-                        while let Some(etag) = excluded_etags.next() {
-                            if etags.contains(&etag) {
-                                response_headers.insert(String::from("ETag"), etag);
-                                response_headers.insert(String::from("Cache-Control"), format!("max-age={}", CONFIG.cache_max_age));
-
-                                return send_response(stream, 304, Some(response_headers), None, None, None).await;
-                            }
+                let etag = file_etag(file_path, Some(&content)).await;
+
+                match evaluate(headers, etag.as_deref(), true) {
+                    Precondition::NotModified => {
+                        if let Some(etag) = &etag {
+                            response_headers.insert(String::from("ETag"), etag.clone());
                         }
-                    }
-                    send_response(stream, 200, Some(response_headers), Some(content), None, Some(Static)).await
+                        response_headers.insert(String::from("Cache-Control"), CONFIG.load().cache_control());
+
+                        return send_response(stream, 304, Some(response_headers), None, None, None).await;
+                    },
+                    Precondition::Failed => return send_response(stream, 412, None, None, None, None).await,
+                    Precondition::Proceed => {}
+                }
+
+                if let Some(etag) = etag {
+                    response_headers.insert(String::from("ETag"), etag);
                 }
+                return send_response(stream, 200, Some(response_headers), Some(content), None, Some(if sidecar.is_some() {Precompressed} else {Static})).await;
             },
             Err(_) => {}
         }
     }
 
-    if let Some(library) = &*ENDPOINT_LIBRARY {
+    if let Some(library) = endpoint_library() {
         return not_found(stream, Get(params), headers, response_headers, local_ip, remote_ip, remote_port, library).await;
     }
     send_response(stream, 404, Some(response_headers), None, None, None).await
@@ -495,43 +775,40 @@ pub async fn handle_head<T>(stream: &mut T,
 where
     T: AsyncRead + AsyncWrite + Unpin
 {
-    #[cfg(target_family = "unix")]
-    let document_root = if *&*CHROOT {&String::from("")} else {&CONFIG.document_root};
-    #[cfg(not(target_family = "unix"))]
-    let document_root = &CONFIG.document_root;
+    let document_root = &document_root();
     let mut resource = String::from((&resource).trim_start_matches('/'));
 
     let mut response_headers: HashMap<String, String> = HashMap::new();
 
     #[cfg(feature = "cgi")] {
         if !resource_present_in_endpoints {
-            if let Some(access_control) = &CONFIG.access_control {
-                if !access_control.is_access_allowed(&resource) {
-                    let deny_action = access_control.deny_action;
-                    return send_response(stream, deny_action, Some(response_headers), None, None, None).await;
+            if let Some(denial) = CONFIG.load().denial(&resource, "HEAD", remote_ip) {
+                if let DenyAction::Redirect {redirect, ..} = &denial.action {
+                    response_headers.insert(String::from("Location"), redirect.clone());
                 }
+                return send_response(stream, denial.action.status(), Some(response_headers), None, None, None).await;
             }
 
-            if Path::new(&format!("{document_root}/{resource}")).is_dir() {
+            if fs_index::is_dir(&format!("{document_root}/{resource}")).await && is_inside_document_root(&format!("{document_root}/{resource}")).await {
                 let mut res_tmp = String::from("");
-                for index in CONFIG.indices.iter() {
-                    if Path::new(&format!("{document_root}/{resource}/{index}")).is_file() {
+                for index in CONFIG.load().indices.iter() {
+                    if fs_index::is_file(&format!("{document_root}/{resource}/{index}")).await {
                         res_tmp = format!("{resource}/{index}");
                         break;
                     }
                 }
 
-                if Path::new(&format!("{document_root}/{res_tmp}")).is_dir() {
-                    if CONFIG.should_display_index_of(&resource) {
+                if fs_index::is_dir(&format!("{document_root}/{res_tmp}")).await {
+                    if CONFIG.load().should_display_index_of(&resource) {
                         return index_of(stream, &resource, true, headers).await;
                     }
                 }
 
                 let res_tmp_trim = String::from(res_tmp.trim_start_matches("/"));
 
-                if !Path::new(&format!("{document_root}/{res_tmp}")).is_file() && CONFIG.should_display_index_of(&resource) {
-                    match &CONFIG.endpoints {
-                        Some(endpoints) if (&ENDPOINT_LIBRARY).is_some() && endpoints.contains(&res_tmp_trim) => {
+                if !fs_index::is_file(&format!("{document_root}/{res_tmp}")).await && CONFIG.load().should_display_index_of(&resource) {
+                    match endpoint_names() {
+                        Some(endpoints) if endpoint_library().is_some() && endpoints.contains(&res_tmp_trim) => {
                             resource_present_in_endpoints = true;
                         },
                         _ => {
@@ -544,7 +821,7 @@ where
             }
 
             if !resource_present_in_endpoints {
-                if let Some(endpoints) = &CONFIG.endpoints {
+                if let Some(endpoints) = endpoint_names() {
                     if endpoints.contains(&resource) {
                         resource_present_in_endpoints = true;
                     }
@@ -557,33 +834,33 @@ where
     let mut resource_present_in_endpoints = false;
 
     #[cfg(not(feature = "cgi"))] {
-        if let Some(access_control) = &CONFIG.access_control {
-            if !access_control.is_access_allowed(&resource) {
-                let deny_action = access_control.deny_action;
-                return send_response(stream, deny_action, Some(response_headers), None, None, None).await;
+        if let Some(denial) = CONFIG.load().denial(&resource, "HEAD", remote_ip) {
+            if let DenyAction::Redirect {redirect, ..} = &denial.action {
+                response_headers.insert(String::from("Location"), redirect.clone());
             }
+            return send_response(stream, denial.action.status(), Some(response_headers), None, None, None).await;
         }
 
-        if Path::new(&format!("{document_root}/{resource}")).is_dir() {
+        if fs_index::is_dir(&format!("{document_root}/{resource}")).await && is_inside_document_root(&format!("{document_root}/{resource}")).await {
             let mut res_tmp = String::from("");
-            for index in CONFIG.indices.iter() {
-                if Path::new(&format!("{document_root}/{resource}/{index}")).is_file() {
+            for index in CONFIG.load().indices.iter() {
+                if fs_index::is_file(&format!("{document_root}/{resource}/{index}")).await {
                     res_tmp = format!("{resource}/{index}");
                     break;
                 }
             }
 
-            if Path::new(&format!("{document_root}/{res_tmp}")).is_dir() {
-                if CONFIG.should_display_index_of(&resource) {
+            if fs_index::is_dir(&format!("{document_root}/{res_tmp}")).await {
+                if CONFIG.load().should_display_index_of(&resource) {
                     return index_of(stream, &resource, true, headers).await;
                 }
             }
 
             let res_tmp_trim = String::from(res_tmp.trim_start_matches("/"));
 
-            if !Path::new(&format!("{document_root}/{res_tmp}")).is_file() && CONFIG.should_display_index_of(&resource) {
-                match &CONFIG.endpoints {
-                    Some(endpoints) if (&ENDPOINT_LIBRARY).is_some() && endpoints.contains(&res_tmp_trim) => {
+            if !fs_index::is_file(&format!("{document_root}/{res_tmp}")).await && CONFIG.load().should_display_index_of(&resource) {
+                match endpoint_names() {
+                    Some(endpoints) if endpoint_library().is_some() && endpoints.contains(&res_tmp_trim) => {
                         resource_present_in_endpoints = true;
                     },
                     _ => {
@@ -596,7 +873,7 @@ where
         }
 
         if !resource_present_in_endpoints {
-            if let Some(endpoints) = &CONFIG.endpoints {
+            if let Some(endpoints) = endpoint_names() {
                 if endpoints.contains(&resource) {
                     resource_present_in_endpoints = true;
                 }
@@ -604,7 +881,7 @@ where
         }
     }
 
-    if let Some(library) = &*ENDPOINT_LIBRARY {
+    if let Some(library) = endpoint_library() {
         if resource_present_in_endpoints {
             let mut set_cookie: HashMap<String, SetCookie> = HashMap::new();
             let mut status: u16 = 200;
@@ -621,48 +898,42 @@ where
 
                     return send_response(stream, status, Some(response_headers), None, Some(set_cookie), None).await;
                 },
-                Err(e) => {
-                    match e {
-                        LibError::DlSym { .. } => {},
-                        _ => {
-                            eprintln!("[handle_head():{}] An unknown error occurred while executing the endpoint.\
-                                                          Attempting to send Internal Server Error page to the client...", line!());
-                            if let Err(e) = internal_server_error(stream).await {
-                                eprintln!("[handle_head():{}] FAILED. Error information:\n{e}", line!());
-                            }
-                            eprintln!("Attempting to close connection...");
-                            if let Err(e) = stream.shutdown().await {
-                                eprintln!("[handle_head():{}] FAILED. Error information:\n{e}", line!());
-                            }
-                            panic!("Unrecoverable error occurred while handling connection.");
-                        }
-                    }
-                }
+                Err(MissingEndpoint) => {}
             }
         }
     }
 
     let path = format!("{document_root}/{resource}");
-    let path = Path::new(&path);
-    if path.is_file() {
-        let _ = FILE_HANDLE_LIMIT.acquire().await?;
-        let file = File::open(path).await;
+    if fs_index::is_file(&path).await && is_inside_document_root(&path).await {
+        let sidecar = precompressed_sidecar(&path, headers).await;
+        let file_path = sidecar.as_ref().map_or(&path, |(sidecar_path, _)| sidecar_path);
 
-        match file {
-            Ok(mut f) => {
-                let mut content: Vec<u8> = Vec::new();
-                rte_wrapper(&mut f, &mut content, stream).await;
+        if let Ok(meta) = metadata(file_path).await {
+            let prefix = if is_large_file(meta.len()) {None} else {sniff(file_path).await};
+            let (guess, general_type) = static_media_type(&resource, prefix.as_deref());
 
-                return if content.is_empty() {
-                    send_response(stream, 200, None, None, None, None).await
-                } else {
-                    let content_length = content.len().to_string();
-                    response_headers.insert(String::from("Content-Length"), content_length);
+            let encoding = static_encoding(&sidecar, meta.len() as usize, &guess, &general_type, headers);
+            insert_encoding(&mut response_headers, &encoding);
+            response_headers.insert(String::from("Content-Type"), guess);
 
-                    send_response(stream, 200, Some(response_headers), None, None, None).await
-                }
-            },
-            Err(_) => {}
+            let etag = file_etag(file_path, None).await;
+            if let Some(etag) = &etag {
+                response_headers.insert(String::from("ETag"), etag.clone());
+            }
+            response_headers.insert(String::from("Cache-Control"), CONFIG.load().cache_control());
+
+            match evaluate(headers, etag.as_deref(), true) {
+                Precondition::NotModified => return send_response(stream, 304, Some(response_headers), None, None, None).await,
+                Precondition::Failed => return send_response(stream, 412, None, None, None, None).await,
+                Precondition::Proceed => {}
+            }
+
+            if encoding.is_some() && sidecar.is_none() {
+                response_headers.insert(String::from("Transfer-Encoding"), String::from("chunked"));
+            } else {
+                response_headers.insert(String::from("Content-Length"), meta.len().to_string());
+            }
+            return send_response(stream, 200, Some(response_headers), None, None, None).await;
         }
     }
     send_response(stream, 404, Some(response_headers), None, None, None).await
@@ -679,51 +950,39 @@ pub async fn handle_post<'a, T>(stream: &mut T,
                                 #[cfg(feature = "cgi")]
                                 mut resource_present_in_endpoints: bool) -> Result<(), Box<dyn Error + Send + Sync>>
 where
-    T: AsyncRead + AsyncWrite + Unpin
+    T: AsyncRead + AsyncWrite + Unpin + ZeroCopy
 {
-    #[cfg(target_family = "unix")]
-    let document_root = if *&*CHROOT {&String::from("")} else {&CONFIG.document_root};
-    #[cfg(not(target_family = "unix"))]
-    let document_root = &CONFIG.document_root;
+    let document_root = &document_root();
     let mut resource = String::from((&resource).trim_start_matches('/'));
 
     let mut response_headers: HashMap<String, String> = HashMap::new();
 
     #[cfg(feature = "cgi")] {
         if !resource_present_in_endpoints {
-            if let Some(access_control) = &CONFIG.access_control {
-                if !access_control.is_access_allowed(&resource) {
-                    let deny_action = access_control.deny_action;
-                    if let Some(library) = &*ENDPOINT_LIBRARY {
-                        if deny_action == 403u16 {
-                            return forbidden(stream, Post { data, params }, headers, response_headers, local_ip, remote_ip, remote_port, library).await;
-                        }
-                        return not_found(stream, Post { data, params }, headers, response_headers, local_ip, remote_ip, remote_port, library).await;
-                    }
-                    return send_response(stream, deny_action, Some(response_headers), None, None, None).await;
-                }
+            if let Some(denial) = CONFIG.load().denial(&resource, "POST", remote_ip) {
+                return denied(stream, denial, Post { data, params }, headers, response_headers, local_ip, remote_ip, remote_port).await;
             }
 
-            if Path::new(&format!("{document_root}/{resource}")).is_dir() {
+            if fs_index::is_dir(&format!("{document_root}/{resource}")).await && is_inside_document_root(&format!("{document_root}/{resource}")).await {
                 let mut res_tmp = String::from("");
-                for index in CONFIG.indices.iter() {
-                    if Path::new(&format!("{document_root}/{resource}/{index}")).is_file() {
+                for index in CONFIG.load().indices.iter() {
+                    if fs_index::is_file(&format!("{document_root}/{resource}/{index}")).await {
                         res_tmp = format!("{resource}/{index}");
                         break;
                     }
                 }
 
-                if Path::new(&format!("{document_root}/{res_tmp}")).is_dir() {
-                    if CONFIG.should_display_index_of(&resource) {
+                if fs_index::is_dir(&format!("{document_root}/{res_tmp}")).await {
+                    if CONFIG.load().should_display_index_of(&resource) {
                         return index_of(stream, &resource, false, headers).await;
                     }
                 }
 
                 let res_tmp_trim = String::from(res_tmp.trim_start_matches("/"));
 
-                if !Path::new(&format!("{document_root}/{res_tmp}")).is_file() && CONFIG.should_display_index_of(&resource) {
-                    match &CONFIG.endpoints {
-                        Some(endpoints) if (&ENDPOINT_LIBRARY).is_some() && endpoints.contains(&res_tmp_trim) => {
+                if !fs_index::is_file(&format!("{document_root}/{res_tmp}")).await && CONFIG.load().should_display_index_of(&resource) {
+                    match endpoint_names() {
+                        Some(endpoints) if endpoint_library().is_some() && endpoints.contains(&res_tmp_trim) => {
                             resource_present_in_endpoints = true;
                         },
                         _ => {
@@ -736,7 +995,7 @@ where
             }
 
             if !resource_present_in_endpoints {
-                if let Some(endpoints) = &CONFIG.endpoints {
+                if let Some(endpoints) = endpoint_names() {
                     if endpoints.contains(&resource) {
                         resource_present_in_endpoints = true;
                     }
@@ -749,39 +1008,30 @@ where
     let mut resource_present_in_endpoints = false;
 
     #[cfg(not(feature = "cgi"))] {
-        if let Some(access_control) = &CONFIG.access_control {
-            if !access_control.is_access_allowed(&resource) {
-                let deny_action = access_control.deny_action;
-                if let Some(library) = &*ENDPOINT_LIBRARY {
-                    if deny_action == 403u16 {
-                        return forbidden(stream, Get(params), headers, response_headers, local_ip, remote_ip, remote_port, library).await;
-                    }
-                    return not_found(stream, Get(params), headers, response_headers, local_ip, remote_ip, remote_port, library).await;
-                }
-                return send_response(stream, deny_action, Some(response_headers), None, None, None).await;
-            }
+        if let Some(denial) = CONFIG.load().denial(&resource, "POST", remote_ip) {
+            return denied(stream, denial, Get(params), headers, response_headers, local_ip, remote_ip, remote_port).await;
         }
 
-        if Path::new(&format!("{document_root}/{resource}")).is_dir() {
+        if fs_index::is_dir(&format!("{document_root}/{resource}")).await && is_inside_document_root(&format!("{document_root}/{resource}")).await {
             let mut res_tmp = String::from("");
-            for index in CONFIG.indices.iter() {
-                if Path::new(&format!("{document_root}/{resource}/{index}")).is_file() {
+            for index in CONFIG.load().indices.iter() {
+                if fs_index::is_file(&format!("{document_root}/{resource}/{index}")).await {
                     res_tmp = format!("{resource}/{index}");
                     break;
                 }
             }
 
-            if Path::new(&format!("{document_root}/{res_tmp}")).is_dir() {
-                if CONFIG.should_display_index_of(&resource) {
+            if fs_index::is_dir(&format!("{document_root}/{res_tmp}")).await {
+                if CONFIG.load().should_display_index_of(&resource) {
                     return index_of(stream, &resource, false, headers).await;
                 }
             }
 
             let res_tmp_trim = String::from(res_tmp.trim_start_matches("/"));
 
-            if !Path::new(&format!("{document_root}/{res_tmp}")).is_file() && CONFIG.should_display_index_of(&resource) {
-                match &CONFIG.endpoints {
-                    Some(endpoints) if (&ENDPOINT_LIBRARY).is_some() && endpoints.contains(&res_tmp_trim) => {
+            if !fs_index::is_file(&format!("{document_root}/{res_tmp}")).await && CONFIG.load().should_display_index_of(&resource) {
+                match endpoint_names() {
+                    Some(endpoints) if endpoint_library().is_some() && endpoints.contains(&res_tmp_trim) => {
                         resource_present_in_endpoints = true;
                     },
                     _ => {
@@ -794,7 +1044,7 @@ where
         }
 
         if !resource_present_in_endpoints {
-            if let Some(endpoints) = &CONFIG.endpoints {
+            if let Some(endpoints) = endpoint_names() {
                 if endpoints.contains(&resource) {
                     resource_present_in_endpoints = true;
                 }
@@ -802,7 +1052,7 @@ where
         }
     }
 
-    if let Some(library) = &*ENDPOINT_LIBRARY {
+    if let Some(library) = endpoint_library() {
         if resource_present_in_endpoints {
             let mut set_cookie: HashMap<String, SetCookie> = HashMap::new();
             let mut status: u16 = 200;
@@ -829,7 +1079,7 @@ where
                         return send_response(stream, status, Some(response_headers), None, Some(set_cookie), None).await;
                     };
 
-                    if let Some(encoding) = CONFIG.get_response_encoding(&c, &mime_type, &general_type, headers) {
+                    if let Some(encoding) = CONFIG.load().get_response_encoding(&c, &mime_type, &general_type, headers) {
                         response_headers.insert(String::from("Content-Encoding"), String::from(encoding));
                         response_headers.insert(String::from("Vary"), String::from("Accept-Encoding"));
                     }
@@ -847,37 +1097,25 @@ where
 
                     return send_response(stream, status, Some(response_headers), None, Some(set_cookie), None).await;
                 },
-                (Err(e), _) => {
-                    match e {
-                        LibError::DlSym { .. } => {},
-                        _ => {
-                            eprintln!("[handle_post():{}] An unknown error occurred while executing the endpoint.\
-                                                          Attempting to send Internal Server Error page to the client...", line!());
-                            if let Err(e) = internal_server_error(stream).await {
-                                eprintln!("[handle_post():{}] FAILED. Error information:\n{e}", line!());
-                            }
-                            eprintln!("Attempting to close connection...");
-                            if let Err(e) = stream.shutdown().await {
-                                eprintln!("[handle_post():{}] FAILED. Error information:\n{e}", line!());
-                            }
-                            panic!("Unrecoverable error occurred while handling connection.");
-                        }
-                    }
-                }
+                (Err(MissingEndpoint), _) => {}
             }
         }
     }
 
     let path = format!("{document_root}/{resource}");
-    let path = Path::new(&path);
-    if path.is_file() {
-        let _ = FILE_HANDLE_LIMIT.acquire().await?;
-        let file = File::open(format!("{document_root}/{}", &resource)).await;
+    if fs_index::is_file(&path).await && is_inside_document_root(&path).await {
+        let sidecar = precompressed_sidecar(&path, headers).await;
+        let file_path = sidecar.as_ref().map_or(&path, |(sidecar_path, _)| sidecar_path);
+
+        if let Some(result) = send_large_file(stream, &mut response_headers, file_path, &resource, &sidecar, headers).await {
+            return result;
+        }
+
+        let file = open_file(file_path).await;
         match file {
             Ok(mut f) => {
                 let mut content: Vec<u8> = Vec::new();
                 rte_wrapper(&mut f, &mut content, stream).await;
-                let content_empty = content.is_empty();
 
                 let (guess, general_type) = if let Some(guess) = mime_guess::from_path(resource).first() {
                     (guess.to_string(), guess.type_().to_string())
@@ -889,38 +1127,43 @@ where
                     }
                 };
 
-                if let Some(encoding) = CONFIG.get_response_encoding(&content, &guess, &general_type, headers) {
-                    response_headers.insert(String::from("Content-Encoding"), String::from(encoding));
+                if let Some((_, encoding)) = &sidecar {
+                    response_headers.insert(String::from("Content-Encoding"), encoding.clone());
                     response_headers.insert(String::from("Vary"), String::from("Accept-Encoding"));
+                } else if !CONFIG.load().is_precompressed_only() {
+                    if let Some(encoding) = CONFIG.load().get_response_encoding(&content, &guess, &general_type, headers) {
+                        response_headers.insert(String::from("Content-Encoding"), String::from(encoding));
+                        response_headers.insert(String::from("Vary"), String::from("Accept-Encoding"));
+                    }
                 }
 
                 response_headers.insert(String::from("Content-Type"), guess);
 
-                return if content_empty {
-                    send_response(stream, 200, Some(response_headers), None, None, None).await
-                } else {
-                    if let Some(if_none_match) = headers.get("if-none-match") {
-                        let mut excluded_etags = if_none_match.split(",")
-                            .map(|e| e.trim_matches(|x: char| x.is_whitespace() || x == '"').to_string());
-
-                        let etags = ETAGS.lock().await;
-                        while let Some(etag) = excluded_etags.next() {
-                            if etags.contains(&etag) {
-                                response_headers.insert(String::from("ETag"), etag);
-                                response_headers.insert(String::from("Cache-Control"), format!("max-age={}", CONFIG.cache_max_age));
-
-                                return send_response(stream, 304, Some(response_headers), None, None, None).await;
-                            }
+                let etag = file_etag(file_path, Some(&content)).await;
+
+                match evaluate(headers, etag.as_deref(), true) {
+                    Precondition::NotModified => {
+                        if let Some(etag) = &etag {
+                            response_headers.insert(String::from("ETag"), etag.clone());
                         }
-                    }
-                    send_response(stream, 200, Some(response_headers), Some(content), None, Some(Static)).await
+                        response_headers.insert(String::from("Cache-Control"), CONFIG.load().cache_control());
+
+                        return send_response(stream, 304, Some(response_headers), None, None, None).await;
+                    },
+                    Precondition::Failed => return send_response(stream, 412, None, None, None, None).await,
+                    Precondition::Proceed => {}
+                }
+
+                if let Some(etag) = etag {
+                    response_headers.insert(String::from("ETag"), etag);
                 }
+                return send_response(stream, 200, Some(response_headers), Some(content), None, Some(if sidecar.is_some() {Precompressed} else {Static})).await;
             },
             Err(_) => {}
         }
     }
 
-    if let Some(library) = &*ENDPOINT_LIBRARY {
+    if let Some(library) = endpoint_library() {
         return not_found(stream, Post { data, params }, headers, response_headers, local_ip, remote_ip, remote_port, library).await;
     }
     send_response(stream, 404, Some(response_headers), None, None, None).await
@@ -931,14 +1174,52 @@ where
     T: AsyncRead + AsyncWrite + Unpin
 {
     let response_headers = HashMap::from([
-        (String::from("Accept"), format!("GET, HEAD, POST,{} OPTIONS{}",
-                                         if (&*ENDPOINT_LIBRARY).is_some() {" PUT, DELETE, PATCH,"} else {""},
-                                         if CONFIG.enable_trace {", TRACE"} else {""}))
+        (String::from("Accept"), format!("GET, HEAD, POST,{} OPTIONS{}{}",
+                                         if endpoint_library().is_some() {" PUT, DELETE, PATCH,"} else {""},
+                                         if CONFIG.load().enable_trace {", TRACE"} else {""},
+                                         if CONFIG.load().is_connect_enabled() {", CONNECT"} else {""})),
+        (String::from("Max-Content-Length"), CONFIG.load().max_content_length.to_string()),
+        (String::from("Max-Decompression-Ratio"), CONFIG.load().max_decompression_ratio.to_string())
     ]);
 
     send_response(stream,204, Some(response_headers), None, None, None).await
 }
 
+pub async fn handle_connect<T>(stream: &mut T, host: String, port: u16, keep_alive: &mut bool) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+    T: AsyncRead + AsyncWrite + Unpin
+{
+    let Some(connect) = &CONFIG.load().connect else {
+        return send_response(stream, 405, None, None, None, None).await;
+    };
+
+    if !connect.is_target_allowed(&host, port) {
+        debug!("CONNECT to {host}:{port} refused, as it isn't present in allowed_targets.");
+        return send_response(stream, 403, None, None, None, None).await;
+    }
+
+    let mut upstream = match timeout(Duration::from_secs(CONFIG.load().request_timeout), dns::connect(&host, port)).await {
+        Ok(Ok(upstream)) => upstream,
+        Ok(Err(e)) => {
+            error!("An error occurred while connecting to {host}:{port}.\n\
+                    Error information:\n{e}");
+            return send_response(stream, 502, None, None, None, None).await;
+        },
+        Err(_) => {
+            return send_response(stream, 504, None, None, None, None).await;
+        }
+    };
+
+    *keep_alive = false;
+    stream.write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n").await?;
+    stream.flush().await?;
+    record_status(200);
+
+    let (_, sent) = copy_bidirectional(stream, &mut upstream).await?;
+    record_bytes_sent(sent);
+    Ok(())
+}
+
 pub async fn handle_put<T>(stream: &mut T,
                            headers: &HashMap<String, String>,
                            resource: String,
@@ -954,22 +1235,13 @@ where
 {
     let mut response_headers: HashMap<String, String> = HashMap::new();
 
-    if let (Some(endpoints), Some(library)) = (&CONFIG.endpoints, &*ENDPOINT_LIBRARY) {
+    if let (Some(endpoints), Some(library)) = (endpoint_names(), endpoint_library()) {
         let resource = String::from((&resource).trim_start_matches('/'));
 
         #[cfg(feature = "cgi")] {
             if !resource_present_in_endpoints {
-                if let Some(access_control) = &CONFIG.access_control {
-                    if !access_control.is_access_allowed(&resource) {
-                        let deny_action = access_control.deny_action;
-                        if let Some(library) = &*ENDPOINT_LIBRARY {
-                            if deny_action == 403u16 {
-                                return forbidden(stream, Put { data, params }, headers, response_headers, local_ip, remote_ip, remote_port, library).await;
-                            }
-                            return not_found(stream, Put { data, params }, headers, response_headers, local_ip, remote_ip, remote_port, library).await;
-                        }
-                        return send_response(stream, deny_action, Some(response_headers), None, None, None).await;
-                    }
+                if let Some(denial) = CONFIG.load().denial(&resource, "PUT", remote_ip) {
+                    return denied(stream, denial, Put { data, params }, headers, response_headers, local_ip, remote_ip, remote_port).await;
                 }
 
                 if endpoints.contains(&resource) {
@@ -1003,7 +1275,7 @@ where
                             return send_response(stream, status, Some(response_headers), None, Some(set_cookie), None).await;
                         };
 
-                        if let Some(encoding) = CONFIG.get_response_encoding(&c, &mime_type, &general_type, headers) {
+                        if let Some(encoding) = CONFIG.load().get_response_encoding(&c, &mime_type, &general_type, headers) {
                             response_headers.insert(String::from("Content-Encoding"), String::from(encoding));
                             response_headers.insert(String::from("Vary"), String::from("Accept-Encoding"));
                         }
@@ -1021,39 +1293,14 @@ where
 
                         return send_response(stream, status, Some(response_headers), None, Some(set_cookie), None).await;
                     },
-                    (Err(e), _) => {
-                        match e {
-                            LibError::DlSym { .. } => {},
-                            _ => {
-                                eprintln!("[handle_put():{}] An unknown error occurred while executing the endpoint.\
-                                                                Attempting to send Internal Server Error page to the client...", line!());
-                                if let Err(e) = internal_server_error(stream).await {
-                                    eprintln!("[handle_put():{}] FAILED. Error information:\n{e}", line!());
-                                }
-                                eprintln!("Attempting to close connection...");
-                                if let Err(e) = stream.shutdown().await {
-                                    eprintln!("[handle_put():{}] FAILED. Error information:\n{e}", line!());
-                                }
-                                panic!("Unrecoverable error occurred while handling connection.");
-                            }
-                        }
-                    }
+                    (Err(MissingEndpoint), _) => {}
                 }
             }
         }
 
         #[cfg(not(feature = "cgi"))] {
-            if let Some(access_control) = &CONFIG.access_control {
-                if !access_control.is_access_allowed(&resource) {
-                    let deny_action = access_control.deny_action;
-                    if let Some(library) = &*ENDPOINT_LIBRARY {
-                        if deny_action == 403u16 {
-                            return forbidden(stream, Put { data, params }, headers, response_headers, local_ip, remote_ip, remote_port, library).await;
-                        }
-                        return not_found(stream, Put { data, params }, headers, response_headers, local_ip, remote_ip, remote_port, library).await;
-                    }
-                    return send_response(stream, deny_action, Some(response_headers), None, None, None).await;
-                }
+            if let Some(denial) = CONFIG.load().denial(&resource, "PUT", remote_ip) {
+                return denied(stream, denial, Put { data, params }, headers, response_headers, local_ip, remote_ip, remote_port).await;
             }
 
             if endpoints.contains(&resource) {
@@ -1082,7 +1329,7 @@ where
                             return send_response(stream, status, Some(response_headers), None, Some(set_cookie), None).await;
                         };
 
-                        if let Some(encoding) = CONFIG.get_response_encoding(&c, &mime_type, &general_type, headers) {
+                        if let Some(encoding) = CONFIG.load().get_response_encoding(&c, &mime_type, &general_type, headers) {
                             response_headers.insert(String::from("Content-Encoding"), String::from(encoding));
                             response_headers.insert(String::from("Vary"), String::from("Accept-Encoding"));
                         }
@@ -1100,23 +1347,7 @@ where
 
                         return send_response(stream, status, Some(response_headers), None, Some(set_cookie), None).await;
                     },
-                    (Err(e), _) => {
-                        match e {
-                            LibError::DlSym { .. } => {},
-                            _ => {
-                                eprintln!("[handle_put():{}] An unknown error occurred while executing the endpoint.\
-                                                                Attempting to send Internal Server Error page to the client...", line!());
-                                if let Err(e) = internal_server_error(stream).await {
-                                    eprintln!("[handle_put():{}] FAILED. Error information:\n{e}", line!());
-                                }
-                                eprintln!("Attempting to close connection...");
-                                if let Err(e) = stream.shutdown().await {
-                                    eprintln!("[handle_put():{}] FAILED. Error information:\n{e}", line!());
-                                }
-                                panic!("Unrecoverable error occurred while handling connection.");
-                            }
-                        }
-                    }
+                    (Err(MissingEndpoint), _) => {}
                 }
             }
         }
@@ -1125,7 +1356,7 @@ where
     }
 
     response_headers = HashMap::from([
-        (String::from("Accept"), format!("GET, HEAD, POST, OPTIONS{}", if CONFIG.enable_trace {", TRACE"} else {""}))
+        (String::from("Accept"), format!("GET, HEAD, POST, OPTIONS{}", if CONFIG.load().enable_trace {", TRACE"} else {""}))
     ]);
 
     send_response(stream,405, Some(response_headers), None, None, None).await
@@ -1146,22 +1377,13 @@ where
 {
     let mut response_headers: HashMap<String, String> = HashMap::new();
 
-    if let (Some(endpoints), Some(library)) = (&CONFIG.endpoints, &*ENDPOINT_LIBRARY) {
+    if let (Some(endpoints), Some(library)) = (endpoint_names(), endpoint_library()) {
         let resource = String::from((&resource).trim_start_matches('/'));
 
         #[cfg(feature = "cgi")] {
             if !resource_present_in_endpoints {
-                if let Some(access_control) = &CONFIG.access_control {
-                    if !access_control.is_access_allowed(&resource) {
-                        let deny_action = access_control.deny_action;
-                        if let Some(library) = &*ENDPOINT_LIBRARY {
-                            if deny_action == 403u16 {
-                                return forbidden(stream, Delete { data, params }, headers, response_headers, local_ip, remote_ip, remote_port, library).await;
-                            }
-                            return not_found(stream, Delete { data, params }, headers, response_headers, local_ip, remote_ip, remote_port, library).await;
-                        }
-                        return send_response(stream, deny_action, Some(response_headers), None, None, None).await;
-                    }
+                if let Some(denial) = CONFIG.load().denial(&resource, "DELETE", remote_ip) {
+                    return denied(stream, denial, Delete { data, params }, headers, response_headers, local_ip, remote_ip, remote_port).await;
                 }
 
                 if endpoints.contains(&resource) {
@@ -1195,7 +1417,7 @@ where
                             return send_response(stream, status, Some(response_headers), None, Some(set_cookie), None).await;
                         };
 
-                        if let Some(encoding) = CONFIG.get_response_encoding(&c, &mime_type, &general_type, headers) {
+                        if let Some(encoding) = CONFIG.load().get_response_encoding(&c, &mime_type, &general_type, headers) {
                             response_headers.insert(String::from("Content-Encoding"), String::from(encoding));
                             response_headers.insert(String::from("Vary"), String::from("Accept-Encoding"));
                         }
@@ -1213,39 +1435,14 @@ where
 
                         return send_response(stream, status, Some(response_headers), None, Some(set_cookie), None).await;
                     },
-                    (Err(e), _) => {
-                        match e {
-                            LibError::DlSym { .. } => {},
-                            _ => {
-                                eprintln!("[handle_delete():{}] An unknown error occurred while executing the endpoint.\
-                                                                Attempting to send Internal Server Error page to the client...", line!());
-                                if let Err(e) = internal_server_error(stream).await {
-                                    eprintln!("[handle_delete():{}] FAILED. Error information:\n{e}", line!());
-                                }
-                                eprintln!("Attempting to close connection...");
-                                if let Err(e) = stream.shutdown().await {
-                                    eprintln!("[handle_delete():{}] FAILED. Error information:\n{e}", line!());
-                                }
-                                panic!("Unrecoverable error occurred while handling connection.");
-                            }
-                        }
-                    }
+                    (Err(MissingEndpoint), _) => {}
                 }
             }
         }
 
         #[cfg(not(feature = "cgi"))] {
-            if let Some(access_control) = &CONFIG.access_control {
-                if !access_control.is_access_allowed(&resource) {
-                    let deny_action = access_control.deny_action;
-                    if let Some(library) = &*ENDPOINT_LIBRARY {
-                        if deny_action == 403u16 {
-                            return forbidden(stream, Delete { data, params }, headers, response_headers, local_ip, remote_ip, remote_port, library).await;
-                        }
-                        return not_found(stream, Delete { data, params }, headers, response_headers, local_ip, remote_ip, remote_port, library).await;
-                    }
-                    return send_response(stream, deny_action, Some(response_headers), None, None, None).await;
-                }
+            if let Some(denial) = CONFIG.load().denial(&resource, "DELETE", remote_ip) {
+                return denied(stream, denial, Delete { data, params }, headers, response_headers, local_ip, remote_ip, remote_port).await;
             }
 
             if endpoints.contains(&resource) {
@@ -1274,7 +1471,7 @@ where
                             return send_response(stream, status, Some(response_headers), None, Some(set_cookie), None).await;
                         };
 
-                        if let Some(encoding) = CONFIG.get_response_encoding(&c, &mime_type, &general_type, headers) {
+                        if let Some(encoding) = CONFIG.load().get_response_encoding(&c, &mime_type, &general_type, headers) {
                             response_headers.insert(String::from("Content-Encoding"), String::from(encoding));
                             response_headers.insert(String::from("Vary"), String::from("Accept-Encoding"));
                         }
@@ -1292,23 +1489,7 @@ where
 
                         return send_response(stream, status, Some(response_headers), None, Some(set_cookie), None).await;
                     },
-                    (Err(e), _) => {
-                        match e {
-                            LibError::DlSym { .. } => {},
-                            _ => {
-                                eprintln!("[handle_delete():{}] An unknown error occurred while executing the endpoint.\
-                                                                Attempting to send Internal Server Error page to the client...", line!());
-                                if let Err(e) = internal_server_error(stream).await {
-                                    eprintln!("[handle_delete():{}] FAILED. Error information:\n{e}", line!());
-                                }
-                                eprintln!("Attempting to close connection...");
-                                if let Err(e) = stream.shutdown().await {
-                                    eprintln!("[handle_delete():{}] FAILED. Error information:\n{e}", line!());
-                                }
-                                panic!("Unrecoverable error occurred while handling connection.");
-                            }
-                        }
-                    }
+                    (Err(MissingEndpoint), _) => {}
                 }
             }
         }
@@ -1317,7 +1498,7 @@ where
     }
 
     response_headers = HashMap::from([
-        (String::from("Accept"), format!("GET, HEAD, POST, OPTIONS{}", if CONFIG.enable_trace {", TRACE"} else {""}))
+        (String::from("Accept"), format!("GET, HEAD, POST, OPTIONS{}", if CONFIG.load().enable_trace {", TRACE"} else {""}))
     ]);
 
     send_response(stream,405, Some(response_headers), None, None, None).await
@@ -1338,22 +1519,13 @@ where
 {
     let mut response_headers: HashMap<String, String> = HashMap::new();
 
-    if let (Some(endpoints), Some(library)) = (&CONFIG.endpoints, &*ENDPOINT_LIBRARY) {
+    if let (Some(endpoints), Some(library)) = (endpoint_names(), endpoint_library()) {
         let resource = String::from((&resource).trim_start_matches('/'));
 
         #[cfg(feature = "cgi")] {
             if !resource_present_in_endpoints {
-                if let Some(access_control) = &CONFIG.access_control {
-                    if !access_control.is_access_allowed(&resource) {
-                        let deny_action = access_control.deny_action;
-                        if let Some(library) = &*ENDPOINT_LIBRARY {
-                            if deny_action == 403u16 {
-                                return forbidden(stream, Patch { data, params }, headers, response_headers, local_ip, remote_ip, remote_port, library).await;
-                            }
-                            return not_found(stream, Patch { data, params }, headers, response_headers, local_ip, remote_ip, remote_port, library).await;
-                        }
-                        return send_response(stream, deny_action, Some(response_headers), None, None, None).await;
-                    }
+                if let Some(denial) = CONFIG.load().denial(&resource, "PATCH", remote_ip) {
+                    return denied(stream, denial, Patch { data, params }, headers, response_headers, local_ip, remote_ip, remote_port).await;
                 }
 
                 if endpoints.contains(&resource) {
@@ -1387,7 +1559,7 @@ where
                             return send_response(stream, status, Some(response_headers), None, Some(set_cookie), None).await;
                         };
 
-                        if let Some(encoding) = CONFIG.get_response_encoding(&c, &mime_type, &general_type, headers) {
+                        if let Some(encoding) = CONFIG.load().get_response_encoding(&c, &mime_type, &general_type, headers) {
                             response_headers.insert(String::from("Content-Encoding"), String::from(encoding));
                             response_headers.insert(String::from("Vary"), String::from("Accept-Encoding"));
                         }
@@ -1405,39 +1577,14 @@ where
 
                         return send_response(stream, status, Some(response_headers), None, Some(set_cookie), None).await;
                     },
-                    (Err(e), _) => {
-                        match e {
-                            LibError::DlSym { .. } => {},
-                            _ => {
-                                eprintln!("[handle_patch():{}] An unknown error occurred while executing the endpoint.\
-                                                          Attempting to send Internal Server Error page to the client...", line!());
-                                if let Err(e) = internal_server_error(stream).await {
-                                    eprintln!("[handle_patch():{}] FAILED. Error information:\n{e}", line!());
-                                }
-                                eprintln!("Attempting to close connection...");
-                                if let Err(e) = stream.shutdown().await {
-                                    eprintln!("[handle_patch():{}] FAILED. Error information:\n{e}", line!());
-                                }
-                                panic!("Unrecoverable error occurred while handling connection.");
-                            }
-                        }
-                    }
+                    (Err(MissingEndpoint), _) => {}
                 }
             }
         }
 
         #[cfg(not(feature = "cgi"))] {
-            if let Some(access_control) = &CONFIG.access_control {
-                if !access_control.is_access_allowed(&resource) {
-                    let deny_action = access_control.deny_action;
-                    if let Some(library) = &*ENDPOINT_LIBRARY {
-                        if deny_action == 403u16 {
-                            return forbidden(stream, Patch { data, params }, headers, response_headers, local_ip, remote_ip, remote_port, library).await;
-                        }
-                        return not_found(stream, Patch { data, params }, headers, response_headers, local_ip, remote_ip, remote_port, library).await;
-                    }
-                    return send_response(stream, deny_action, Some(response_headers), None, None, None).await;
-                }
+            if let Some(denial) = CONFIG.load().denial(&resource, "PATCH", remote_ip) {
+                return denied(stream, denial, Patch { data, params }, headers, response_headers, local_ip, remote_ip, remote_port).await;
             }
 
             if endpoints.contains(&resource) {
@@ -1466,7 +1613,7 @@ where
                             return send_response(stream, status, Some(response_headers), None, Some(set_cookie), None).await;
                         };
 
-                        if let Some(encoding) = CONFIG.get_response_encoding(&c, &mime_type, &general_type, headers) {
+                        if let Some(encoding) = CONFIG.load().get_response_encoding(&c, &mime_type, &general_type, headers) {
                             response_headers.insert(String::from("Content-Encoding"), String::from(encoding));
                             response_headers.insert(String::from("Vary"), String::from("Accept-Encoding"));
                         }
@@ -1484,23 +1631,7 @@ where
 
                         return send_response(stream, status, Some(response_headers), None, Some(set_cookie), None).await;
                     },
-                    (Err(e), _) => {
-                        match e {
-                            LibError::DlSym { .. } => {},
-                            _ => {
-                                eprintln!("[handle_patch():{}] An unknown error occurred while executing the endpoint.\
-                                                               Attempting to send Internal Server Error page to the client...", line!());
-                                if let Err(e) = internal_server_error(stream).await {
-                                    eprintln!("[handle_patch():{}] FAILED. Error information:\n{e}", line!());
-                                }
-                                eprintln!("Attempting to close connection...");
-                                if let Err(e) = stream.shutdown().await {
-                                    eprintln!("[handle_patch():{}] FAILED. Error information:\n{e}", line!());
-                                }
-                                panic!("Unrecoverable error occurred while handling connection.");
-                            }
-                        }
-                    }
+                    (Err(MissingEndpoint), _) => {}
                 }
             }
         }
@@ -1509,8 +1640,98 @@ where
     }
 
     response_headers = HashMap::from([
-        (String::from("Accept"), format!("GET, HEAD, POST, OPTIONS{}", if CONFIG.enable_trace {", TRACE"} else {""}))
+        (String::from("Accept"), format!("GET, HEAD, POST, OPTIONS{}", if CONFIG.load().enable_trace {", TRACE"} else {""}))
     ]);
 
     send_response(stream,405, Some(response_headers), None, None, None).await
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::net::Ipv4Addr;
+    use tokio::io::{duplex, AsyncReadExt};
+    use super::*;
+
+    async fn head(root: &str, request_headers: &[(&str, &str)]) -> String {
+        let headers: HashMap<String, String> = request_headers.iter().map(|(k, v)| (String::from(*k), String::from(*v))).collect();
+        let (mut client, mut server) = duplex(65536);
+        let ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let request_info = RequestInfo {document_root: Some(String::from(root)), ..RequestInfo::default()};
+
+        #[cfg(feature = "cgi")]
+        let response = handle_head(&mut server, &headers, String::from("/page.txt"), &None, &ip, &ip, &0, false);
+        #[cfg(not(feature = "cgi"))]
+        let response = handle_head(&mut server, &headers, String::from("/page.txt"), &None, &ip, &ip, &0);
+        REQUEST_INFO.scope(RefCell::new(request_info), response).await.unwrap();
+        drop(server);
+
+        let mut output = String::new();
+        client.read_to_string(&mut output).await.unwrap();
+        output
+    }
+
+    fn etag(response: &str) -> String {
+        let line = response.lines().find(|line| line.to_lowercase().starts_with("etag:")).unwrap();
+        String::from(line.split_once(':').unwrap().1.trim())
+    }
+
+    async fn normalized_host(host: &str, https: bool) -> String {
+        let mut request = Request::Get {
+            resource: String::from("/"),
+            params: None,
+            #[cfg(feature = "cgi")]
+            query_string: String::new(),
+            headers: HashMap::from([(String::from("host"), String::from(host))])
+        };
+
+        let request_info = RequestInfo {https, ..RequestInfo::default()};
+        REQUEST_INFO.scope(RefCell::new(request_info), async {request.normalize().ok()}).await.unwrap();
+        request.headers().unwrap().get("host").unwrap().clone()
+    }
+
+    #[tokio::test]
+    async fn only_the_default_port_of_the_scheme_is_stripped() {
+        assert_eq!(normalized_host("example.com:80", false).await, "example.com");
+        assert_eq!(normalized_host("example.com:443", false).await, "example.com:443");
+        assert_eq!(normalized_host("example.com:443", true).await, "example.com");
+        assert_eq!(normalized_host("example.com:80", true).await, "example.com:80");
+        assert_eq!(normalized_host("example.com:8080", false).await, "example.com:8080");
+    }
+
+    #[tokio::test]
+    async fn head_evaluates_preconditions() {
+        let root = std::env::temp_dir().join(format!("drain-head-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("page.txt"), "content").unwrap();
+        let root = root.to_string_lossy().into_owned();
+
+        let response = head(&root, &[]).await;
+        assert!(response.starts_with("HTTP/1.1 200"));
+        let etag = etag(&response);
+
+        assert!(head(&root, &[("if-none-match", &etag)]).await.starts_with("HTTP/1.1 304"));
+        assert!(head(&root, &[("if-match", "\"other\"")]).await.starts_with("HTTP/1.1 412"));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn head_shares_representation_with_get() {
+        let root = std::env::temp_dir().join(format!("drain-head-representation-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("page.txt"), "content").unwrap();
+        let root = root.to_string_lossy().into_owned();
+
+        let response = head(&root, &[]).await.to_lowercase();
+        assert!(response.contains("content-length: 7\r\n"));
+        assert!(response.contains("content-type: text/plain"));
+
+        let sidecar = Some((format!("{root}/page.txt.br"), String::from("br")));
+        let (text, general_type) = (String::from("text/plain"), String::from("text"));
+        assert_eq!(static_encoding(&sidecar, 7, &text, &general_type, &HashMap::new()).as_deref(), Some("br"));
+        assert_eq!(static_encoding(&None, 7, &text, &general_type, &HashMap::new()), None);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
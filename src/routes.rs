@@ -0,0 +1,224 @@
+use std::cell::RefCell;
+use std::path::Path;
+use glob::Pattern;
+use tokio::task::block_in_place;
+use crate::config::{VirtualHost, CONFIG};
+#[cfg(feature = "cgi")]
+use crate::config::CGIRule;
+use crate::util::{document_root, RequestInfo, REQUEST_INFO};
+
+fn print_listeners() {
+    println!("Listeners:");
+    println!("  http://{}:{}", CONFIG.load().bind_host, CONFIG.load().bind_port);
+    if let Some(https) = &CONFIG.load().https {
+        if https.enabled {
+            println!("  https://{}:{}", CONFIG.load().bind_host, https.bind_port);
+        }
+    }
+    if CONFIG.load().is_connect_enabled() {
+        println!("  CONNECT tunnel enabled");
+    }
+}
+
+fn print_locations() {
+    #[cfg(target_family = "unix")]
+    println!("\nDocument root: {}{}", CONFIG.load().document_root, if CONFIG.load().chroot {" (chroot)"} else {""});
+    #[cfg(not(target_family = "unix"))]
+    println!("\nDocument root: {}", CONFIG.load().document_root);
+    println!("Index files: {}", CONFIG.load().indices.join(", "));
+
+    println!("\nEndpoint libraries:");
+    if let Some(endpoint_libraries) = &CONFIG.load().endpoint_libraries {
+        for endpoint_library in endpoint_libraries {
+            println!("  host {} location /{} -> {} [{}]",
+                     endpoint_library.host.as_deref().unwrap_or("*"),
+                     endpoint_library.location.as_deref().unwrap_or("").trim_start_matches('/'),
+                     endpoint_library.endpoints_library,
+                     endpoint_library.endpoints.as_ref().map(|e| e.join(", ")).unwrap_or_default());
+        }
+    }
+    match &CONFIG.load().endpoints_library {
+        Some(endpoints_library) => println!("  default -> {endpoints_library} [{}]", CONFIG.load().endpoints.as_ref().map(|e| e.join(", ")).unwrap_or_default()),
+        None => println!("  default -> none")
+    }
+    if let Some(streaming_endpoints) = &CONFIG.load().streaming_endpoints {
+        println!("  streaming: {}", streaming_endpoints.join(", "));
+    }
+    #[cfg(feature = "json")]
+    if let Some(json_endpoints) = &CONFIG.load().json_endpoints {
+        println!("  json: {}", json_endpoints.join(", "));
+    }
+
+    if let Some(virtual_hosts) = &CONFIG.load().virtual_hosts {
+        println!("\nVirtual hosts:");
+        for virtual_host in virtual_hosts {
+            println!("  {}{} -> {} [{}]",
+                     virtual_host.hosts.join(", "),
+                     if virtual_host.default {" (default)"} else {""},
+                     virtual_host.document_root.as_deref().unwrap_or(&CONFIG.load().document_root),
+                     virtual_host.endpoints_library.as_deref().unwrap_or("no endpoints library"));
+        }
+    }
+
+    #[cfg(feature = "cgi")]
+    if let Some(cgi) = &CONFIG.load().cgi {
+        println!("\nCGI ({}, {}):", if cgi.enabled {"enabled"} else {"disabled"}, cgi.cgi_server);
+        for (pattern, rule) in &cgi.cgi_rules {
+            match rule {
+                CGIRule::Enabled(enabled) => println!("  {pattern} -> {}", if *enabled {"pass"} else {"skip"}),
+                CGIRule::Options(options) => println!("  {pattern} -> options{}{}",
+                                                      options.address.as_ref().map(|a| format!(", backend at {a}")).unwrap_or_default(),
+                                                      options.interpreter.as_ref().map(|i| format!(", interpreter {i}")).unwrap_or_default())
+            }
+        }
+    }
+
+    if let Some(index_of_page_rules) = &CONFIG.load().index_of_page_rules {
+        println!("\nIndex of pages:");
+        for (pattern, enabled) in index_of_page_rules {
+            println!("  {pattern} -> {}", if *enabled {"shown"} else {"hidden"});
+        }
+    }
+}
+
+fn print_limits() {
+    if let Some(access_control) = &CONFIG.load().access_control {
+        println!("\nAccess control (denied with {}):", access_control.deny_action.status());
+        for (pattern, action) in &access_control.list {
+            println!("  {pattern} -> {action}");
+        }
+    }
+
+    println!("\nLimits:");
+    println!("  max_content_length: {} bytes", CONFIG.load().max_content_length);
+    println!("  max_decompression_ratio: {}", CONFIG.load().max_decompression_ratio);
+    println!("  request_timeout: {} s", CONFIG.load().request_timeout);
+    println!("  endpoint_pool_size: {}", CONFIG.load().endpoint_pool_size);
+    if let Some(endpoint_concurrency) = &CONFIG.load().endpoint_concurrency {
+        for (endpoint, limit) in endpoint_concurrency {
+            println!("  concurrency of {endpoint}: {limit}");
+        }
+    }
+    if let Some(rate_limits) = &CONFIG.load().rate_limits {
+        println!("  {} rate limit(s)", rate_limits.len());
+        for rate_limit in rate_limits {
+            println!("    capacity {}, refill {}/s", rate_limit.capacity, rate_limit.refill_per_second);
+        }
+    }
+}
+
+fn resolve_handler(host: Option<&String>, path: &String) -> String {
+    let config = CONFIG.load();
+    let document_root = &document_root();
+
+    let resource = String::from(path.trim_start_matches('/'));
+    let file_path = format!("{document_root}/{resource}");
+
+    #[cfg(feature = "cgi")]
+    if let Some(cgi) = &config.cgi {
+        if cgi.enabled && cgi.should_attempt_cgi(&resource) && Path::new(&file_path).is_file() {
+            return String::from("CGI");
+        }
+    }
+
+    if config.sensitive_paths.is_sensitive(&resource) {
+        return format!("denied ({})", config.access_control().map_or(404, |access_control| access_control.deny_action.status()));
+    }
+
+    if let Some(access_control) = config.access_control() {
+        if !access_control.is_access_allowed(&resource) {
+            return format!("denied ({})", access_control.deny_action.status());
+        }
+    }
+
+    let (endpoints, library) = match (config.virtual_host(), config.endpoint_libraries.iter().flatten().find(|l| l.matches(host, Some(&resource)))) {
+        (Some(VirtualHost {endpoints_library: Some(endpoints_library), endpoints, ..}), _) => (endpoints, Some(endpoints_library)),
+        (_, Some(endpoint_library)) => (&endpoint_library.endpoints, Some(&endpoint_library.endpoints_library)),
+        _ => (&config.endpoints, config.endpoints_library.as_ref())
+    };
+
+    if let (Some(endpoints), Some(library)) = (endpoints, library) {
+        if endpoints.contains(&resource) {
+            return format!("{} endpoint {resource} in {library}", if config.is_streaming_endpoint(&resource) {"streaming"} else {"dynamic"});
+        }
+    }
+
+    if Path::new(&file_path).is_dir() {
+        for index in &config.indices {
+            if Path::new(&format!("{file_path}/{index}")).is_file() {
+                return format!("static (index {index})");
+            }
+        }
+
+        return if config.should_display_index_of(&resource) {String::from("index of")} else {String::from("not found")};
+    }
+
+    if Path::new(&file_path).is_file() {
+        return String::from("static");
+    }
+
+    String::from("not found")
+}
+
+pub fn describe_match(host: Option<&String>, path: &String, rewrite: Option<&String>) -> String {
+    let resource = String::from(path.trim_start_matches('/'));
+
+    let location = match CONFIG.load().endpoint_libraries.iter().flatten().find(|l| l.matches(host, Some(&resource))) {
+        Some(endpoint_library) => format!("{}/{}", endpoint_library.host.as_deref().unwrap_or("*"),
+                                          endpoint_library.location.as_deref().unwrap_or("").trim_start_matches('/')),
+        None => String::from("default")
+    };
+
+    let virtual_host = match CONFIG.load().virtual_host() {
+        Some(virtual_host) if virtual_host.hosts.is_empty() => String::from("default"),
+        Some(virtual_host) => virtual_host.hosts.join(","),
+        None => String::from("-")
+    };
+
+    let access = match CONFIG.load().access_control().and_then(|access_control| access_control.denying_rule(&resource)) {
+        _ if CONFIG.load().sensitive_paths.is_sensitive(&resource) => String::from("deny (sensitive_paths)"),
+        Some(pattern) => format!("deny ({pattern})"),
+        None => String::from("allow")
+    };
+
+    let cache = CONFIG.load().cache_control_rules.iter().flatten()
+        .find(|rule| Pattern::new(&rule.pattern).is_ok_and(|p| p.matches(&resource)))
+        .map_or(String::from("default"), |rule| rule.pattern.clone());
+
+    let handler = match rewrite {
+        Some(location) => format!("redirect to {location}"),
+        None => block_in_place(|| resolve_handler(host, path))
+    };
+
+    format!("host={}; vhost={virtual_host}; location={location}; access={access}; cache={cache}; handler={handler}", host.map_or("-", |h| h.as_str()))
+}
+
+pub fn print_routes(host: Option<&String>, paths: &[String]) {
+    let mut paths = paths.to_vec();
+    if paths.is_empty() {
+        paths.push(String::from("/"));
+        paths.extend(CONFIG.load().endpoints.iter().flatten().map(|e| format!("/{e}")));
+    }
+
+    print_listeners();
+    print_locations();
+    print_limits();
+
+    let request_info = RequestInfo {
+        host: host.cloned(),
+        virtual_host: CONFIG.load().resolve_virtual_host(host),
+        ..RequestInfo::default()
+    };
+
+    REQUEST_INFO.sync_scope(RefCell::new(request_info), || {
+        println!("\nRoutes{}:", host.map(|h| format!(" for host {h}")).unwrap_or_default());
+        if let Some(virtual_host) = CONFIG.load().virtual_host() {
+            println!("  virtual host {} (document root {})",
+                     if virtual_host.hosts.is_empty() {String::from("default")} else {virtual_host.hosts.join(", ")},
+                     document_root());
+        }
+        for path in &paths {
+            println!("  {path} -> {}", resolve_handler(host, path));
+        }
+    });
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::io;
+use std::io::{Cursor, ErrorKind};
+use std::time::Duration;
+use bstr::ByteSlice;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::time::timeout;
+use tracing::{debug, error};
+use crate::config::{S3Origin, CONFIG};
+use crate::pages::bad_gateway::bad_gateway;
+use crate::proxy::{connect, decode_chunked, parse_head, UpstreamConnection, UpstreamServer};
+use crate::signing::{encode_path, sign_sigv4};
+use crate::util::ResourceType::Verbatim;
+use crate::util::{send_response, send_stream};
+
+const MAX_HEAD_LENGTH: usize = 65536;
+
+const FORWARDED_HEADERS: [&str; 5] = ["range", "if-match", "if-none-match", "if-modified-since", "if-unmodified-since"];
+
+const RELAYED_HEADERS: [&str; 11] = [
+    "content-type", "content-length", "content-range", "content-encoding", "content-disposition", "content-language",
+    "etag", "last-modified", "accept-ranges", "cache-control", "expires"
+];
+
+fn object_key(origin: &S3Origin, object: &str) -> String {
+    let mut key = String::from(object);
+    if key.is_empty() || key.ends_with('/') {
+        key.push_str(CONFIG.load().indices.first().map_or("index.html", |index| index.as_str()));
+    }
+
+    match origin.prefix.trim_matches('/') {
+        "" => key,
+        prefix => format!("{prefix}/{key}")
+    }
+}
+
+async fn read_head(connection: &mut Box<dyn UpstreamConnection>) -> io::Result<(Vec<u8>, Vec<u8>)> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 8192];
+
+    loop {
+        if let Some(end) = buffer.find("\r\n\r\n") {
+            let rest = buffer.split_off(end + 4);
+            buffer.truncate(end);
+            return Ok((buffer, rest));
+        }
+
+        if buffer.len() > MAX_HEAD_LENGTH {
+            return Err(io::Error::new(ErrorKind::InvalidData, "response head is too large"));
+        }
+
+        let read = connection.read(&mut chunk).await?;
+        if read == 0 {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof));
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    }
+}
+
+async fn request_object(server: &UpstreamServer, request: &[u8]) -> io::Result<(Box<dyn UpstreamConnection>, Vec<u8>, Vec<u8>)> {
+    let mut connection = connect(server).await?;
+    connection.write_all(request).await?;
+    connection.flush().await?;
+
+    let (head, rest) = read_head(&mut connection).await?;
+    Ok((connection, head, rest))
+}
+
+pub async fn handle_s3<T>(stream: &mut T,
+                          origin: &S3Origin,
+                          method: &str,
+                          object: &str,
+                          headers: &HashMap<String, String>,
+                          body: Option<Vec<u8>>) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+    T: AsyncRead + AsyncWrite + Unpin
+{
+    let Some(mut server) = UpstreamServer::parse(&origin.endpoint) else {
+        return bad_gateway(stream).await;
+    };
+
+    let key = object_key(origin, object);
+    let path = if origin.path_style {
+        encode_path(&format!("{}/{}/{key}", server.path, origin.bucket))
+    } else {
+        server.host = format!("{}.{}", origin.bucket, server.host);
+        encode_path(&format!("{}/{key}", server.path))
+    };
+    let host = server.host_header();
+    let body = body.unwrap_or_default();
+
+    let mut request = format!("{method} {path} HTTP/1.1\r\nHost: {host}\r\nConnection: close\r\n");
+    for name in FORWARDED_HEADERS {
+        if let Some(value) = headers.get(name) {
+            request.push_str(&format!("{name}: {value}\r\n"));
+        }
+    }
+    if method.eq("PUT") {
+        if let Some(content_type) = headers.get("content-type") {
+            request.push_str(&format!("Content-Type: {content_type}\r\n"));
+        }
+        request.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    }
+    for (name, value) in sign_sigv4(&origin.access_key, &origin.secret_key, &origin.region, "s3", method, &host, &path, "", &body) {
+        request.push_str(&format!("{name}: {value}\r\n"));
+    }
+    request.push_str("\r\n");
+
+    let mut request = request.into_bytes();
+    request.extend_from_slice(&body);
+
+    let (connection, head, rest) = match timeout(Duration::from_secs(CONFIG.load().request_timeout), request_object(&server, &request)).await {
+        Ok(Ok(response)) => response,
+        Ok(Err(e)) => {
+            error!("An error occurred while requesting {key} from {}.\n\
+                    Error information:\n{e}", server.host);
+            return bad_gateway(stream).await;
+        },
+        Err(_) => {
+            error!("{} didn't respond in {} seconds.", server.host, CONFIG.load().request_timeout);
+            return bad_gateway(stream).await;
+        }
+    };
+
+    let Some((status, upstream_headers)) = parse_head(&head) else {
+        return bad_gateway(stream).await;
+    };
+
+    let mut response_headers: HashMap<String, String> = upstream_headers.iter()
+        .filter(|(name, _)| RELAYED_HEADERS.contains(&name.as_str()))
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+
+    match status {
+        200 | 201 | 204 if method.eq("PUT") => {
+            response_headers.retain(|name, _| name.eq("etag"));
+            send_response(stream, 200, Some(response_headers), None, None, None).await
+        },
+        200 | 206 => {
+            if !response_headers.contains_key("cache-control") {
+                response_headers.insert(String::from("Cache-Control"), CONFIG.load().cache_control());
+            }
+
+            if method.eq("HEAD") {
+                return send_response(stream, status, Some(response_headers), None, None, None).await;
+            }
+
+            if let Some(length) = upstream_headers.get("content-length").and_then(|l| l.parse::<u64>().ok()) {
+                let mut body = Cursor::new(rest).chain(connection);
+                return send_stream(stream, status, response_headers, &mut body, length).await;
+            }
+
+            let mut content = rest;
+            let mut connection = connection;
+            if let Err(e) = connection.read_to_end(&mut content).await {
+                if e.kind() != ErrorKind::UnexpectedEof {
+                    return bad_gateway(stream).await;
+                }
+            }
+            if upstream_headers.get("transfer-encoding").is_some_and(|t| t.to_lowercase().contains("chunked")) {
+                let Some(decoded) = decode_chunked(&content) else {
+                    return bad_gateway(stream).await;
+                };
+                content = decoded;
+            }
+            response_headers.remove("content-length");
+
+            send_response(stream, status, Some(response_headers), if content.is_empty() {None} else {Some(content)}, None, Some(Verbatim)).await
+        },
+        304 | 412 | 416 => {
+            response_headers.remove("content-length");
+            response_headers.remove("content-type");
+            send_response(stream, status, Some(response_headers), None, None, None).await
+        },
+        403 | 404 => {
+            if status == 403 {
+                debug!("{} denied access to {key}, check the credentials of the origin.", server.host);
+            }
+            send_response(stream, 404, None, None, None, None).await
+        },
+        _ => {
+            debug!("{} responded with {status} status to a request for {key}.", server.host);
+            bad_gateway(stream).await
+        }
+    }
+}
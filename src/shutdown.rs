@@ -0,0 +1,63 @@
+use std::sync::LazyLock;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::{sleep, Instant};
+use tracing::{error, info};
+use crate::config::CONFIG;
+use crate::connection_limit::active_connections;
+#[cfg(target_family = "unix")]
+use crate::daemon::remove_pidfile;
+
+static SHUTDOWN: LazyLock<watch::Sender<bool>> = LazyLock::new(|| watch::Sender::new(false));
+
+pub fn is_shutting_down() -> bool {
+    *SHUTDOWN.borrow()
+}
+
+pub async fn shutdown_requested() {
+    let _ = SHUTDOWN.subscribe().wait_for(|shutting_down| *shutting_down).await;
+}
+
+#[cfg(target_family = "unix")]
+pub async fn wait_for_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sigterm = match signal(SignalKind::terminate()) {
+        Ok(sigterm) => sigterm,
+        Err(e) => {
+            error!("An error occurred while installing the SIGTERM handler.\n\
+                    Error information:\n{e}");
+            let _ = tokio::signal::ctrl_c().await;
+            return;
+        }
+    };
+
+    tokio::select! {
+        _ = sigterm.recv() => {},
+        _ = tokio::signal::ctrl_c() => {}
+    }
+}
+
+#[cfg(not(target_family = "unix"))]
+pub async fn wait_for_signal() {
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+pub async fn drain() {
+    SHUTDOWN.send_replace(true);
+    #[cfg(target_family = "unix")]
+    remove_pidfile();
+
+    let deadline = Instant::now() + Duration::from_secs(CONFIG.load().shutdown_grace_period);
+    info!("Shutting down, waiting for {} active connection(s) to finish...", active_connections());
+
+    while active_connections() > 0 {
+        if Instant::now() >= deadline {
+            info!("Grace period elapsed, closing {} remaining connection(s).", active_connections());
+            return;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+
+    info!("All connections finished.");
+}
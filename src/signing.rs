@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use chrono::Utc;
+use openssl::hash::{hash, MessageDigest};
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use urlencoding::encode;
+use crate::config::RequestSigning;
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    match hash(MessageDigest::sha256(), data) {
+        Ok(digest) => to_hex(&digest),
+        Err(_) => String::new()
+    }
+}
+
+pub fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let Ok(key) = PKey::hmac(key) else {
+        return Vec::new();
+    };
+    let Ok(mut signer) = Signer::new(MessageDigest::sha256(), &key) else {
+        return Vec::new();
+    };
+
+    signer.sign_oneshot_to_vec(data).unwrap_or_default()
+}
+
+pub fn encode_path(path: &str) -> String {
+    path.split('/').map(|segment| encode(segment).into_owned()).collect::<Vec<String>>().join("/")
+}
+
+pub fn canonical_query(params: Option<&HashMap<String, String>>) -> String {
+    let mut pairs: Vec<(String, String)> = params.iter()
+        .flat_map(|params| params.iter())
+        .map(|(k, v)| (encode(k).into_owned(), encode(v).into_owned()))
+        .collect();
+    pairs.sort();
+
+    pairs.into_iter().map(|(k, v)| format!("{k}={v}")).collect::<Vec<String>>().join("&")
+}
+
+pub fn sign_request(signing: &RequestSigning, method: &str, host: &str, path: &str, query: &str, body: &[u8]) -> Vec<(String, String)> {
+    match signing {
+        RequestSigning::Hmac {header, secret} => {
+            let timestamp = Utc::now().timestamp();
+            let string_to_sign = format!("{method}\n{path}{}{query}\n{timestamp}", if query.is_empty() {""} else {"?"});
+            let signature = to_hex(&hmac_sha256(secret.as_bytes(), string_to_sign.as_bytes()));
+
+            vec![(header.clone(), format!("t={timestamp},sig={signature}"))]
+        },
+        RequestSigning::SigV4 {access_key, secret_key, region, service} =>
+            sign_sigv4(access_key, secret_key, region, service, method, host, path, query, body)
+    }
+}
+
+pub fn sign_sigv4(access_key: &str,
+                  secret_key: &str,
+                  region: &str,
+                  service: &str,
+                  method: &str,
+                  host: &str,
+                  path: &str,
+                  query: &str,
+                  body: &[u8]) -> Vec<(String, String)> {
+    let now = Utc::now();
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date_stamp = now.format("%Y%m%d").to_string();
+    let payload_hash = sha256_hex(body);
+
+    let canonical_request = format!("{method}\n{path}\n{query}\n\
+                                     host:{host}\nx-amz-content-sha256:{payload_hash}\nx-amz-date:{amz_date}\n\n\
+                                     host;x-amz-content-sha256;x-amz-date\n{payload_hash}");
+    let scope = format!("{date_stamp}/{region}/{service}/aws4_request");
+    let string_to_sign = format!("AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}", sha256_hex(canonical_request.as_bytes()));
+
+    let date_key = hmac_sha256(format!("AWS4{secret_key}").as_bytes(), date_stamp.as_bytes());
+    let region_key = hmac_sha256(&date_key, region.as_bytes());
+    let service_key = hmac_sha256(&region_key, service.as_bytes());
+    let signing_key = hmac_sha256(&service_key, b"aws4_request");
+    let signature = to_hex(&hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+
+    vec![
+        (String::from("x-amz-date"), amz_date),
+        (String::from("x-amz-content-sha256"), payload_hash),
+        (String::from("Authorization"), format!("AWS4-HMAC-SHA256 Credential={access_key}/{scope}, \
+                                                 SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature={signature}"))
+    ]
+}
@@ -0,0 +1,118 @@
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::num::NonZeroUsize;
+use std::thread::available_parallelism;
+use std::time::Duration;
+use socket2::{Domain, Protocol, SockRef, Socket, TcpKeepalive, Type};
+use tokio::net::{TcpListener, TcpStream};
+use crate::config::CONFIG;
+
+pub fn acceptor_count() -> usize {
+    match CONFIG.load().socket.acceptors {
+        0 => available_parallelism().map_or(1, NonZeroUsize::get),
+        acceptors => acceptors
+    }
+}
+
+pub struct ListenerSpec {
+    pub address: String,
+    pub port: u16,
+    pub document_root: Option<String>
+}
+
+pub fn listener_specs(tls_port: Option<u16>) -> Vec<ListenerSpec> {
+    let config = CONFIG.load();
+    let port = tls_port.unwrap_or(config.bind_port);
+
+    let mut specs: Vec<ListenerSpec> = [Some(&config.bind_host), config.bind_host_v6.as_ref()].into_iter()
+        .flatten()
+        .map(|address| ListenerSpec {address: address.clone(), port, document_root: None})
+        .collect();
+
+    specs.extend(config.listeners.iter().flatten()
+        .filter(|listener| listener.tls == tls_port.is_some())
+        .map(|listener| ListenerSpec {address: listener.address.clone(), port: listener.port, document_root: listener.document_root.clone()}));
+
+    specs
+}
+
+pub fn bind_listener(host: &String, port: u16) -> io::Result<TcpListener> {
+    let Some(address) = (host.as_str(), port).to_socket_addrs()?.next() else {
+        return Err(io::Error::new(io::ErrorKind::AddrNotAvailable, format!("{host} couldn't be resolved")));
+    };
+
+    let socket = Socket::new(Domain::for_address(address), Type::STREAM, Some(Protocol::TCP))?;
+
+    #[cfg(target_family = "unix")]
+    socket.set_reuse_address(true)?;
+    #[cfg(all(target_family = "unix", not(any(target_os = "solaris", target_os = "illumos"))))]
+    if CONFIG.load().socket.reuseport {
+        socket.set_reuse_port(true)?;
+    }
+
+    if let (Some(ipv6_only), SocketAddr::V6(_)) = (CONFIG.load().socket.ipv6_only, address) {
+        socket.set_only_v6(ipv6_only)?;
+    }
+
+    if let Some(interface) = &CONFIG.load().socket.interface {
+        bind_interface(&socket, interface, &address)?;
+    }
+
+    socket.set_nonblocking(true)?;
+    socket.bind(&SocketAddr::into(address))?;
+    socket.listen(CONFIG.load().socket.backlog)?;
+
+    TcpListener::from_std(socket.into())
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "fuchsia"))]
+fn bind_interface(socket: &Socket, interface: &String, _: &SocketAddr) -> io::Result<()> {
+    socket.bind_device(Some(interface.as_bytes()))
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn bind_interface(socket: &Socket, interface: &String, address: &SocketAddr) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::num::NonZeroU32;
+
+    let name = CString::new(interface.as_str()).map_err(io::Error::other)?;
+    let Some(index) = NonZeroU32::new(unsafe {libc::if_nametoindex(name.as_ptr())}) else {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("network interface {interface} wasn't found")));
+    };
+
+    match address {
+        SocketAddr::V4(_) => socket.bind_device_by_index_v4(Some(index)),
+        SocketAddr::V6(_) => socket.bind_device_by_index_v6(Some(index))
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "fuchsia", target_os = "macos", target_os = "ios")))]
+fn bind_interface(_: &Socket, interface: &String, _: &SocketAddr) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, format!("binding to the network interface {interface} isn't supported on this OS")))
+}
+
+pub fn tune_stream(stream: &TcpStream) -> io::Result<()> {
+    let socket = &CONFIG.load().socket;
+
+    if socket.nodelay {
+        stream.set_nodelay(true)?;
+    }
+
+    if let Some(keepalive) = &socket.keepalive {
+        let mut tcp_keepalive = TcpKeepalive::new().with_time(Duration::from_secs(keepalive.time));
+
+        #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios", target_os = "netbsd", target_os = "windows"))]
+        if let Some(interval) = keepalive.interval {
+            tcp_keepalive = tcp_keepalive.with_interval(Duration::from_secs(interval));
+        }
+
+        #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios", target_os = "netbsd"))]
+        if let Some(retries) = keepalive.retries {
+            tcp_keepalive = tcp_keepalive.with_retries(retries);
+        }
+
+        SockRef::from(stream).set_tcp_keepalive(&tcp_keepalive)?;
+    }
+
+    Ok(())
+}
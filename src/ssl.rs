@@ -1,29 +1,103 @@
-use std::sync::LazyLock;
+use std::fs;
+use std::sync::{LazyLock, RwLock};
+use std::time::{Duration, SystemTime};
 use openssl::ssl::SslContext;
+use tokio::time::sleep;
+use tracing::{error, info};
 use crate::config::CONFIG;
 
 pub struct SslInfo {
-    pub ctx: SslContext,
+    ctx: RwLock<SslContext>,
     pub port: u16
 }
 
 pub static SSL: LazyLock<Option<SslInfo>> = LazyLock::new(|| {
-    match &CONFIG.https {
+    match &CONFIG.load().https {
         Some(https) if https.enabled => {
-            match https.configure_ssl() {
+            match https.configure_ssl(CONFIG.load().virtual_hosts.as_deref().unwrap_or_default(), CONFIG.load().acme.as_ref()) {
                 Ok(ctx) => {
-                    println!("SSL enabled.");
-                    return Some(SslInfo {ctx, port: https.bind_port})
+                    info!("SSL enabled.");
+                    return Some(SslInfo {ctx: RwLock::new(ctx), port: https.bind_port})
                 },
                 Err(e) => {
-                    eprintln!("[SSL:{}] An error occurred while configuring SSL for a secure connection.\n\
-                                        Error information:\n{e}", line!());
+                    error!("An error occurred while configuring SSL for a secure connection.\n\
+                            Error information:\n{e}");
                 }
             }
         },
         _ => {}
     }
 
-    println!("SSL disabled.");
+    info!("SSL disabled.");
     None
-});
\ No newline at end of file
+});
+
+impl SslInfo {
+    pub fn context(&self) -> SslContext {
+        self.ctx.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn reload(&self) -> bool {
+        let Some(https) = &CONFIG.load().https else {
+            return false;
+        };
+
+        match https.configure_ssl(CONFIG.load().virtual_hosts.as_deref().unwrap_or_default(), CONFIG.load().acme.as_ref()) {
+            Ok(ctx) => {
+                *self.ctx.write().unwrap_or_else(|e| e.into_inner()) = ctx;
+                info!("TLS certificates reloaded.");
+                true
+            },
+            Err(e) => {
+                error!("An error occurred while reloading TLS certificates.\n\
+                        Error information:\n{e}\n\
+                        The previous certificates are still in use.");
+                false
+            }
+        }
+    }
+}
+
+fn certificate_files() -> Vec<(String, Option<SystemTime>)> {
+    let mut files = Vec::new();
+
+    if let Some(https) = &CONFIG.load().https {
+        files.push(https.ssl_private_key_file.clone());
+        files.push(https.ssl_certificate_file.clone());
+        files.extend(https.client_certificates.as_ref().map(|client_certificates| client_certificates.ca_file.clone()));
+    }
+
+    for virtual_host in CONFIG.load().virtual_hosts.iter().flatten() {
+        files.extend(virtual_host.ssl_private_key_file.clone());
+        files.extend(virtual_host.ssl_certificate_file.clone());
+    }
+
+    files.into_iter()
+        .map(|file| {
+            let modified = fs::metadata(&file).and_then(|metadata| metadata.modified()).ok();
+            (file, modified)
+        })
+        .collect()
+}
+
+pub async fn watch_certificates(ssl_info: &'static SslInfo) {
+    if CONFIG.load().chroot {
+        return;
+    }
+
+    let mut loaded = certificate_files();
+
+    loop {
+        let interval = CONFIG.load().https.as_ref().map_or(0, |https| https.certificate_reload_interval);
+        if interval == 0 {
+            return;
+        }
+
+        sleep(Duration::from_secs(interval)).await;
+
+        let current = certificate_files();
+        if current != loaded && ssl_info.reload() {
+            loaded = current;
+        }
+    }
+}
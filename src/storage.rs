@@ -0,0 +1,409 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tokio::task::spawn_blocking;
+use tracing::warn;
+use crate::config::{StorageBackend, CONFIG};
+
+pub trait Store: Send + Sync {
+    fn get(&self, key: &str) -> impl Future<Output = io::Result<Option<String>>> + Send;
+    fn set(&self, key: &str, value: &str, ttl: Duration) -> impl Future<Output = io::Result<()>> + Send;
+    fn increment(&self, key: &str, ttl: Duration) -> impl Future<Output = io::Result<(u64, Duration)>> + Send;
+    fn delete(&self, key: &str) -> impl Future<Output = io::Result<()>> + Send;
+}
+
+pub struct MemoryStore {
+    entries: Mutex<HashMap<String, (String, Instant)>>
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self {entries: Mutex::new(HashMap::new())}
+    }
+}
+
+impl Store for MemoryStore {
+    async fn get(&self, key: &str) -> io::Result<Option<String>> {
+        let entries = self.entries.lock().await;
+        Ok(entries.get(key).filter(|(_, expiry)| *expiry > Instant::now()).map(|(value, _)| value.clone()))
+    }
+
+    async fn set(&self, key: &str, value: &str, ttl: Duration) -> io::Result<()> {
+        let mut entries = self.entries.lock().await;
+        let now = Instant::now();
+        entries.retain(|_, (_, expiry)| *expiry > now);
+        entries.insert(String::from(key), (String::from(value), now + ttl));
+        Ok(())
+    }
+
+    async fn increment(&self, key: &str, ttl: Duration) -> io::Result<(u64, Duration)> {
+        let mut entries = self.entries.lock().await;
+        let now = Instant::now();
+
+        let entry = entries.entry(String::from(key)).or_insert((String::from("0"), now + ttl));
+        if entry.1 <= now {
+            *entry = (String::from("0"), now + ttl);
+        }
+
+        let count = entry.0.parse::<u64>().unwrap_or(0) + 1;
+        entry.0 = count.to_string();
+        Ok((count, entry.1 - now))
+    }
+
+    async fn delete(&self, key: &str) -> io::Result<()> {
+        self.entries.lock().await.remove(key);
+        Ok(())
+    }
+}
+
+pub struct FileStore {
+    directory: PathBuf
+}
+
+impl FileStore {
+    pub fn new(directory: &String) -> Self {
+        Self {directory: PathBuf::from(directory)}
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.directory.join(key.bytes().map(|b| format!("{b:02x}")).collect::<String>())
+    }
+
+    fn unix_now() -> u64 {
+        SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64)
+    }
+
+    fn read_entry(content: &str) -> Option<(u64, &str)> {
+        let (expiry, value) = content.split_once('\n')?;
+        let expiry = expiry.parse::<u64>().ok()?;
+        (expiry > Self::unix_now()).then_some((expiry, value))
+    }
+
+    async fn locked<F, R>(&self, key: &str, f: F) -> io::Result<R>
+    where
+        F: FnOnce(&mut std::fs::File) -> io::Result<R> + Send + 'static,
+        R: Send + 'static
+    {
+        let path = self.path(key);
+        spawn_blocking(move || {
+            let mut file = std::fs::OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+            file.lock()?;
+            f(&mut file)
+        }).await.map_err(io::Error::other)?
+    }
+}
+
+fn rewrite(file: &mut std::fs::File, content: &str) -> io::Result<()> {
+    use std::io::{Seek, SeekFrom, Write};
+
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(content.as_bytes())
+}
+
+fn read_all(file: &mut std::fs::File) -> io::Result<String> {
+    use std::io::Read;
+
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+    Ok(content)
+}
+
+impl Store for FileStore {
+    async fn get(&self, key: &str) -> io::Result<Option<String>> {
+        match tokio::fs::read_to_string(self.path(key)).await {
+            Ok(content) => Ok(Self::read_entry(&content).map(|(_, value)| String::from(value))),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e)
+        }
+    }
+
+    async fn set(&self, key: &str, value: &str, ttl: Duration) -> io::Result<()> {
+        let content = format!("{}\n{value}", Self::unix_now() + ttl.as_millis() as u64);
+        self.locked(key, move |file| rewrite(file, &content)).await
+    }
+
+    async fn increment(&self, key: &str, ttl: Duration) -> io::Result<(u64, Duration)> {
+        self.locked(key, move |file| {
+            let content = read_all(file)?;
+            let now = Self::unix_now();
+            let (expiry, count) = match Self::read_entry(&content) {
+                Some((expiry, value)) => (expiry, value.parse::<u64>().unwrap_or(0) + 1),
+                None => (now + ttl.as_millis() as u64, 1)
+            };
+
+            rewrite(file, &format!("{expiry}\n{count}"))?;
+            Ok((count, Duration::from_millis(expiry.saturating_sub(now))))
+        }).await
+    }
+
+    async fn delete(&self, key: &str) -> io::Result<()> {
+        match tokio::fs::remove_file(self.path(key)).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+            _ => Ok(())
+        }
+    }
+}
+
+pub struct RedisStore {
+    address: String,
+    password: Option<String>,
+    database: u8,
+    connection: Mutex<Option<BufReader<TcpStream>>>
+}
+
+enum RedisReply {
+    Status,
+    Integer(i64),
+    Bulk(Option<String>)
+}
+
+impl RedisStore {
+    pub fn new(address: &String, password: &Option<String>, database: u8) -> Self {
+        Self {
+            address: address.clone(),
+            password: password.clone(),
+            database,
+            connection: Mutex::new(None)
+        }
+    }
+
+    async fn write_command(stream: &mut BufReader<TcpStream>, command: &[&str]) -> io::Result<()> {
+        let mut request = format!("*{}\r\n", command.len());
+        for argument in command {
+            request.push_str(&format!("${}\r\n{argument}\r\n", argument.len()));
+        }
+        stream.get_mut().write_all(request.as_bytes()).await
+    }
+
+    async fn send(stream: &mut BufReader<TcpStream>, command: &[&str]) -> io::Result<RedisReply> {
+        Self::write_command(stream, command).await?;
+        Self::read_reply(stream).await
+    }
+
+    async fn read_reply(stream: &mut BufReader<TcpStream>) -> io::Result<RedisReply> {
+        let mut line = String::new();
+        if stream.read_line(&mut line).await? == 0 {
+            return Err(io::Error::new(ErrorKind::UnexpectedEof, "Redis closed the connection"));
+        }
+        let line = line.trim_end();
+
+        match line.split_at_checked(1) {
+            Some(("+", _)) => Ok(RedisReply::Status),
+            Some(("-", error)) => Err(io::Error::other(format!("Redis error: {error}"))),
+            Some((":", integer)) => integer.parse::<i64>().map(RedisReply::Integer).map_err(io::Error::other),
+            Some(("$", length)) => {
+                let Ok(length) = length.parse::<usize>() else {
+                    return Ok(RedisReply::Bulk(None));
+                };
+
+                let mut bulk = vec![0u8; length + 2];
+                stream.read_exact(&mut bulk).await?;
+                bulk.truncate(length);
+                Ok(RedisReply::Bulk(Some(String::from_utf8_lossy(&bulk).into_owned())))
+            },
+            _ => Err(io::Error::new(ErrorKind::InvalidData, "unsupported Redis reply"))
+        }
+    }
+
+    async fn connect(&self) -> io::Result<BufReader<TcpStream>> {
+        let mut stream = BufReader::new(TcpStream::connect(self.address.as_str()).await?);
+        if let Some(password) = &self.password {
+            Self::send(&mut stream, &["AUTH", password]).await?;
+        }
+        if self.database != 0 {
+            Self::send(&mut stream, &["SELECT", &self.database.to_string()]).await?;
+        }
+        Ok(stream)
+    }
+
+    async fn command(&self, commands: &[&[&str]]) -> io::Result<Vec<RedisReply>> {
+        let mut connection = self.connection.lock().await;
+
+        if connection.is_none() {
+            *connection = Some(self.connect().await?);
+        }
+
+        let Some(stream) = connection.as_mut() else {
+            return Err(io::Error::new(ErrorKind::NotConnected, "not connected to Redis"));
+        };
+
+        let mut replies = Vec::with_capacity(commands.len());
+        for command in commands {
+            match Self::send(stream, command).await {
+                Ok(reply) => replies.push(reply),
+                Err(e) => {
+                    *connection = None;
+                    return Err(e);
+                }
+            }
+        }
+        Ok(replies)
+    }
+
+    pub async fn publish(&self, channel: &str, message: &str) -> io::Result<()> {
+        self.command(&[&["PUBLISH", channel, message]]).await?;
+        Ok(())
+    }
+
+    pub async fn subscribe(&self, channel: &str) -> io::Result<RedisSubscription> {
+        let mut stream = self.connect().await?;
+        Self::write_command(&mut stream, &["SUBSCRIBE", channel]).await?;
+        Ok(RedisSubscription {stream})
+    }
+}
+
+pub struct RedisSubscription {
+    stream: BufReader<TcpStream>
+}
+
+impl RedisSubscription {
+    pub async fn next_message(&mut self) -> io::Result<String> {
+        loop {
+            let mut line = String::new();
+            if self.stream.read_line(&mut line).await? == 0 {
+                return Err(io::Error::new(ErrorKind::UnexpectedEof, "Redis closed the connection"));
+            }
+
+            let Some(length) = line.trim_end().strip_prefix('*').and_then(|length| length.parse::<usize>().ok()) else {
+                return Err(io::Error::new(ErrorKind::InvalidData, "unsupported Redis push message"));
+            };
+
+            let mut parts = Vec::with_capacity(length);
+            for _ in 0..length {
+                parts.push(RedisStore::read_reply(&mut self.stream).await?);
+            }
+
+            if let [RedisReply::Bulk(Some(kind)), _, RedisReply::Bulk(Some(message))] = parts.as_slice() {
+                if kind.eq("message") {
+                    return Ok(message.clone());
+                }
+            }
+        }
+    }
+}
+
+impl Store for RedisStore {
+    async fn get(&self, key: &str) -> io::Result<Option<String>> {
+        match self.command(&[&["GET", key]]).await?.pop() {
+            Some(RedisReply::Bulk(value)) => Ok(value),
+            _ => Ok(None)
+        }
+    }
+
+    async fn set(&self, key: &str, value: &str, ttl: Duration) -> io::Result<()> {
+        let ttl = ttl.as_millis().max(1).to_string();
+        match self.command(&[&["SET", key, value, "PX", &ttl]]).await?.pop() {
+            Some(RedisReply::Status) => Ok(()),
+            _ => Err(io::Error::new(ErrorKind::InvalidData, "unexpected reply to SET"))
+        }
+    }
+
+    async fn increment(&self, key: &str, ttl: Duration) -> io::Result<(u64, Duration)> {
+        let ttl = ttl.as_millis().max(1).to_string();
+        let replies = self.command(&[&["INCR", key], &["PEXPIRE", key, &ttl, "NX"], &["PTTL", key]]).await?;
+
+        match (replies.first(), replies.last()) {
+            (Some(RedisReply::Integer(count)), Some(RedisReply::Integer(remaining))) => {
+                Ok((*count as u64, Duration::from_millis((*remaining).max(0) as u64)))
+            },
+            _ => Err(io::Error::new(ErrorKind::InvalidData, "unexpected reply to INCR"))
+        }
+    }
+
+    async fn delete(&self, key: &str) -> io::Result<()> {
+        self.command(&[&["DEL", key]]).await?;
+        Ok(())
+    }
+}
+
+pub enum Storage {
+    Memory(MemoryStore),
+    File(FileStore),
+    Redis(RedisStore)
+}
+
+impl Storage {
+    pub fn is_shared(&self) -> bool {
+        !matches!(self, Storage::Memory(_))
+    }
+}
+
+impl Store for Storage {
+    async fn get(&self, key: &str) -> io::Result<Option<String>> {
+        match self {
+            Storage::Memory(store) => store.get(key).await,
+            Storage::File(store) => store.get(key).await,
+            Storage::Redis(store) => store.get(key).await
+        }
+    }
+
+    async fn set(&self, key: &str, value: &str, ttl: Duration) -> io::Result<()> {
+        match self {
+            Storage::Memory(store) => store.set(key, value, ttl).await,
+            Storage::File(store) => store.set(key, value, ttl).await,
+            Storage::Redis(store) => store.set(key, value, ttl).await
+        }
+    }
+
+    async fn increment(&self, key: &str, ttl: Duration) -> io::Result<(u64, Duration)> {
+        match self {
+            Storage::Memory(store) => store.increment(key, ttl).await,
+            Storage::File(store) => store.increment(key, ttl).await,
+            Storage::Redis(store) => store.increment(key, ttl).await
+        }
+    }
+
+    async fn delete(&self, key: &str) -> io::Result<()> {
+        match self {
+            Storage::Memory(store) => store.delete(key).await,
+            Storage::File(store) => store.delete(key).await,
+            Storage::Redis(store) => store.delete(key).await
+        }
+    }
+}
+
+pub static STORAGE: LazyLock<Storage> = LazyLock::new(|| {
+    match &CONFIG.load().storage {
+        StorageBackend::Memory => Storage::Memory(MemoryStore::new()),
+        StorageBackend::File {path} => {
+            if let Err(e) = std::fs::create_dir_all(path) {
+                warn!("An error occurred while creating the storage directory.\n\
+                       Error information:\n{e}\n\
+                       Continuing with in-memory storage...");
+                return Storage::Memory(MemoryStore::new());
+            }
+            Storage::File(FileStore::new(path))
+        },
+        StorageBackend::Redis {address, password, database} => Storage::Redis(RedisStore::new(address, password, *database))
+    }
+});
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use tokio::time::sleep;
+    use super::{MemoryStore, Store};
+
+    #[tokio::test]
+    async fn memory_entries_expire_and_can_be_deleted() {
+        let store = MemoryStore::new();
+        store.set("short", "1", Duration::from_millis(50)).await.unwrap();
+        store.set("long", "2", Duration::from_secs(60)).await.unwrap();
+        assert_eq!(store.get("short").await.unwrap().as_deref(), Some("1"));
+
+        sleep(Duration::from_millis(100)).await;
+        assert_eq!(store.get("short").await.unwrap(), None);
+        assert_eq!(store.get("long").await.unwrap().as_deref(), Some("2"));
+
+        store.delete("long").await.unwrap();
+        assert_eq!(store.get("long").await.unwrap(), None);
+    }
+}
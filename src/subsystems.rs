@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::net::IpAddr;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+use chrono::Utc;
+use serde_json::{json, Value};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tracing::debug;
+use crate::bans::bans;
+use crate::cluster;
+use crate::config::CONFIG;
+use crate::dns;
+use crate::journal;
+use crate::metrics;
+use crate::proxy::UpstreamServer;
+use crate::requests::Request;
+use crate::util::ResourceType::Dynamic;
+use crate::util::send_response;
+
+#[derive(Default)]
+struct SubsystemState {
+    disabled: bool,
+    restarts: u64,
+    last_restart: Option<String>
+}
+
+static STATES: LazyLock<Mutex<HashMap<String, SubsystemState>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn subsystems() -> Vec<(String, Vec<String>)> {
+    let mut subsystems = vec![
+        (String::from("dns"), Vec::new()),
+        (String::from("journal"), Vec::new()),
+        (String::from("metrics"), Vec::new())
+    ];
+
+    #[cfg(feature = "cgi")]
+    subsystems.push((String::from("cgi"), Vec::new()));
+
+    let config = CONFIG.load();
+    let mut upstreams: Vec<&String> = config.upstreams.iter().flat_map(|upstreams| upstreams.keys()).collect();
+    upstreams.sort();
+    subsystems.extend(upstreams.into_iter().map(|name| (format!("upstream:{name}"), vec![String::from("dns")])));
+
+    subsystems
+}
+
+fn dependencies(name: &str) -> Option<Vec<String>> {
+    subsystems().into_iter().find(|(subsystem, _)| subsystem.eq(name)).map(|(_, dependencies)| dependencies)
+}
+
+fn dependents(name: &str) -> Vec<String> {
+    subsystems().into_iter()
+        .filter(|(_, dependencies)| dependencies.iter().any(|dependency| dependency.eq(name)))
+        .map(|(subsystem, _)| subsystem)
+        .collect()
+}
+
+fn is_disabled(name: &str) -> bool {
+    STATES.lock().is_ok_and(|states| states.get(name).is_some_and(|state| state.disabled))
+}
+
+pub fn is_available(name: &str) -> bool {
+    !is_disabled(name) && dependencies(name).unwrap_or_default().iter().all(|dependency| is_available(dependency))
+}
+
+fn set_disabled(name: &str, disabled: bool) {
+    if let Ok(mut states) = STATES.lock() {
+        states.entry(String::from(name)).or_default().disabled = disabled;
+    }
+}
+
+async fn reset(name: &str) {
+    match name {
+        "dns" => dns::forget(None).await,
+        "journal" => journal::close(),
+        "metrics" => metrics::reset(),
+        _ => {}
+    }
+
+    let config = CONFIG.load();
+    if let Some(upstream) = name.strip_prefix("upstream:").and_then(|upstream| config.upstreams.as_ref()?.get(upstream)) {
+        let hosts = upstream.servers.iter().filter_map(|server| UpstreamServer::parse(server)).map(|server| server.host).collect();
+        dns::forget(Some(hosts)).await;
+    }
+
+    if let Ok(mut states) = STATES.lock() {
+        let state = states.entry(String::from(name)).or_default();
+        state.restarts += 1;
+        state.last_restart = Some(Utc::now().to_rfc3339());
+    }
+}
+
+async fn restart(name: &str, actions: &mut Vec<String>) {
+    let dependents: Vec<String> = dependents(name).into_iter().filter(|dependent| !is_disabled(dependent)).collect();
+
+    for dependent in &dependents {
+        set_disabled(dependent, true);
+        actions.push(format!("stopped {dependent}"));
+    }
+
+    let was_disabled = is_disabled(name);
+    set_disabled(name, true);
+    reset(name).await;
+    set_disabled(name, was_disabled);
+    actions.push(format!("restarted {name}"));
+
+    for dependent in dependents.iter().rev() {
+        set_disabled(dependent, false);
+        actions.push(format!("started {dependent}"));
+    }
+}
+
+fn status(name: &str, dependencies: &Vec<String>) -> Value {
+    let state = if is_disabled(name) {"disabled"} else if is_available(name) {"running"} else {"blocked"};
+    let (restarts, last_restart) = STATES.lock().ok()
+        .and_then(|states| states.get(name).map(|state| (state.restarts, state.last_restart.clone())))
+        .unwrap_or_default();
+
+    json!({
+        "name": name,
+        "state": state,
+        "depends_on": dependencies,
+        "restarts": restarts,
+        "last_restart": last_restart
+    })
+}
+
+pub async fn send_admin<T>(stream: &mut T, request: &Request, remote_ip: &IpAddr) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+    T: AsyncRead + AsyncWrite + Unpin
+{
+    if !remote_ip.is_loopback() {
+        return send_response(stream, 403, None, None, None, None).await;
+    }
+
+    let (Some(admin_endpoint), Some(resource)) = (&CONFIG.load().admin_endpoint, request.resource()) else {
+        return send_response(stream, 404, None, None, None, None).await;
+    };
+    let path: Vec<&str> = resource[admin_endpoint.trim_end_matches('/').len()..].split('/').filter(|s| !s.is_empty()).collect();
+
+    let response_headers = HashMap::from([
+        (String::from("Content-Type"), String::from("application/json")),
+        (String::from("Cache-Control"), String::from("no-store"))
+    ]);
+
+    let body = match (request, path.as_slice()) {
+        (Request::Get {..}, ["subsystems"]) => {
+            Value::Array(subsystems().iter().map(|(name, dependencies)| status(name, dependencies)).collect())
+        },
+        (Request::Get {..}, ["subsystems", name]) => match dependencies(name) {
+            Some(dependencies) => status(name, &dependencies),
+            None => return send_response(stream, 404, None, None, None, None).await
+        },
+        (Request::Post {..}, ["subsystems", name, action @ ("restart" | "disable" | "enable")]) => {
+            let Some(dependencies) = dependencies(name) else {
+                return send_response(stream, 404, None, None, None, None).await;
+            };
+
+            let mut actions = Vec::new();
+            match *action {
+                "restart" => restart(name, &mut actions).await,
+                "disable" if name.eq(&"dns") => return send_response(stream, 409, None, None, None, None).await,
+                "disable" => {
+                    set_disabled(name, true);
+                    actions.push(format!("disabled {name}"));
+                },
+                _ => {
+                    set_disabled(name, false);
+                    actions.push(format!("enabled {name}"));
+                }
+            }
+
+            debug!("Subsystem actions: {}.", actions.join(", "));
+
+            json!({
+                "actions": actions,
+                "subsystem": status(name, &dependencies)
+            })
+        },
+        (Request::Post {..}, ["cache", "purge", prefix @ ..]) => {
+            let prefix = (!prefix.is_empty()).then(|| prefix.join("/"));
+            cluster::purge(prefix.clone()).await;
+
+            json!({
+                "purged": prefix.map_or(String::from("/"), |prefix| format!("/{prefix}"))
+            })
+        },
+        (Request::Get {..}, ["bans"]) => {
+            Value::Array(bans().iter().map(|(ip, remaining)| json!({"ip": ip, "expires_in": remaining.as_secs()})).collect())
+        },
+        (Request::Post {..}, ["bans", ip]) => {
+            let Ok(ip) = ip.parse::<IpAddr>() else {
+                return send_response(stream, 400, None, None, None, None).await;
+            };
+            cluster::ban(ip, Duration::from_secs(CONFIG.load().ban_duration)).await;
+
+            json!({
+                "ip": ip,
+                "expires_in": CONFIG.load().ban_duration
+            })
+        },
+        (Request::Get {..} | Request::Post {..}, _) => return send_response(stream, 404, None, None, None, None).await,
+        _ => {
+            let allow_header = HashMap::from([(String::from("Allow"), String::from("GET, POST"))]);
+            return send_response(stream, 405, Some(allow_header), None, None, None).await;
+        }
+    };
+
+    send_response(stream, 200, Some(response_headers), Some(Vec::from(body.to_string())), None, Some(Dynamic)).await
+}
+
+pub fn is_subsystems_resource(resource: &String) -> bool {
+    let Some(admin_endpoint) = &CONFIG.load().admin_endpoint else {
+        return false;
+    };
+
+    resource.strip_prefix(admin_endpoint.trim_end_matches('/')).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
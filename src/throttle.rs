@@ -0,0 +1,76 @@
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio::time::{sleep_until, Instant, Sleep};
+use crate::zero_copy::ZeroCopy;
+
+const MAX_SLICE_LENGTH: u64 = 65536;
+
+pub struct Throttled<'a, T> {
+    stream: &'a mut T,
+    rate: Option<u64>,
+    started: Instant,
+    written: u64,
+    delay: Option<Pin<Box<Sleep>>>
+}
+
+impl<'a, T> Throttled<'a, T> {
+    pub fn new(stream: &'a mut T, rate: Option<u64>) -> Self {
+        Self {
+            stream,
+            rate: rate.filter(|rate| *rate > 0),
+            started: Instant::now(),
+            written: 0,
+            delay: None
+        }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Throttled<'_, T> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.get_mut().stream).poll_read(cx, buf)
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Throttled<'_, T> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let Some(rate) = this.rate else {
+            return Pin::new(&mut *this.stream).poll_write(cx, buf);
+        };
+
+        let due = this.started + Duration::from_secs_f64(this.written as f64 / rate as f64);
+        if due > Instant::now() {
+            let delay = this.delay.get_or_insert_with(|| Box::pin(sleep_until(due)));
+            delay.as_mut().reset(due);
+            ready!(delay.as_mut().poll(cx));
+        }
+
+        let slice_length = (rate / 10).clamp(1, MAX_SLICE_LENGTH) as usize;
+        let written = ready!(Pin::new(&mut *this.stream).poll_write(cx, &buf[..buf.len().min(slice_length)]))?;
+        this.written += written as u64;
+
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.get_mut().stream).poll_shutdown(cx)
+    }
+}
+
+impl<T: ZeroCopy> ZeroCopy for Throttled<'_, T> {
+    fn tcp_stream(&self) -> Option<&TcpStream> {
+        match self.rate {
+            Some(_) => None,
+            None => self.stream.tcp_stream()
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use std::ffi::{c_int, c_uchar, c_uint, c_void};
+use std::ptr;
+use std::slice;
+use std::sync::LazyLock;
+use foreign_types::ForeignTypeRef;
+use openssl::ex_data::Index;
+use openssl::hash::{hash, MessageDigest};
+use openssl::ssl::{Ssl, SslRef};
+use openssl_sys::{CRYPTO_free, SSL_client_hello_get0_ciphers, SSL_client_hello_get0_ext, SSL_client_hello_get0_legacy_version, SSL_client_hello_get1_extensions_present};
+use tracing::warn;
+
+const EXT_SERVER_NAME: u16 = 0;
+const EXT_SUPPORTED_GROUPS: u16 = 10;
+const EXT_EC_POINT_FORMATS: u16 = 11;
+const EXT_SIGNATURE_ALGORITHMS: u16 = 13;
+const EXT_ALPN: u16 = 16;
+const EXT_SUPPORTED_VERSIONS: u16 = 43;
+
+#[derive(Clone)]
+pub struct TlsFingerprint {
+    pub ja3: String,
+    pub ja4: String
+}
+
+impl TlsFingerprint {
+    pub fn matches(&self, fingerprints: &Vec<String>) -> bool {
+        fingerprints.iter().any(|f| f.eq_ignore_ascii_case(&self.ja3) || f.eq(&self.ja4))
+    }
+}
+
+pub static FINGERPRINT_INDEX: LazyLock<Option<Index<Ssl, TlsFingerprint>>> = LazyLock::new(|| {
+    match Ssl::new_ex_index() {
+        Ok(index) => Some(index),
+        Err(e) => {
+            warn!("An error occurred while registering TLS fingerprint storage.\n\
+                   Error information:\n{e}\n\
+                   Continuing without TLS fingerprinting...");
+            None
+        }
+    }
+});
+
+fn is_grease(value: u16) -> bool {
+    value & 0x0f0f == 0x0a0a && value >> 8 == value & 0xff
+}
+
+fn read_u16_list(data: &[u8]) -> Vec<u16> {
+    data.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).filter(|v| !is_grease(*v)).collect()
+}
+
+fn hex_digest(digest: MessageDigest, data: &str) -> String {
+    match hash(digest, data.as_bytes()) {
+        Ok(digest) => digest.iter().map(|b| format!("{b:02x}")).collect(),
+        Err(_) => String::new()
+    }
+}
+
+fn truncated_sha256(values: &str) -> String {
+    if values.is_empty() {
+        return String::from("000000000000");
+    }
+
+    hex_digest(MessageDigest::sha256(), values).chars().take(12).collect()
+}
+
+fn join<T: ToString>(values: &[T], separator: &str) -> String {
+    values.iter().map(|v| v.to_string()).collect::<Vec<String>>().join(separator)
+}
+
+fn join_hex(values: &[u16]) -> String {
+    values.iter().map(|v| format!("{v:04x}")).collect::<Vec<String>>().join(",")
+}
+
+fn extension<'a>(ssl: &'a SslRef, ext_type: u16) -> Option<&'a [u8]> {
+    let mut data: *const c_uchar = ptr::null();
+    let mut length: usize = 0;
+
+    unsafe {
+        if SSL_client_hello_get0_ext(ssl.as_ptr(), ext_type as c_uint, &mut data, &mut length) != 1 || data.is_null() {
+            return None;
+        }
+
+        Some(slice::from_raw_parts(data, length))
+    }
+}
+
+fn extensions_present(ssl: &SslRef) -> Vec<u16> {
+    let mut types: *mut c_int = ptr::null_mut();
+    let mut length: usize = 0;
+
+    unsafe {
+        if SSL_client_hello_get1_extensions_present(ssl.as_ptr(), &mut types, &mut length) != 1 || types.is_null() {
+            return Vec::new();
+        }
+
+        let extensions = slice::from_raw_parts(types, length).iter().map(|t| *t as u16).filter(|t| !is_grease(*t)).collect();
+        CRYPTO_free(types as *mut c_void, concat!(file!(), "\0").as_ptr() as *const _, line!() as c_int);
+        extensions
+    }
+}
+
+fn ciphers(ssl: &SslRef) -> Vec<u16> {
+    let mut data: *const c_uchar = ptr::null();
+
+    unsafe {
+        let length = SSL_client_hello_get0_ciphers(ssl.as_ptr(), &mut data);
+        if data.is_null() {
+            return Vec::new();
+        }
+
+        read_u16_list(slice::from_raw_parts(data, length))
+    }
+}
+
+fn ja4_version(version: u16) -> &'static str {
+    match version {
+        0x0304 => "13",
+        0x0303 => "12",
+        0x0302 => "11",
+        0x0301 => "10",
+        0x0300 => "s3",
+        _ => "00"
+    }
+}
+
+fn alpn_marker(ssl: &SslRef) -> String {
+    let Some(alpn) = extension(ssl, EXT_ALPN) else {
+        return String::from("00");
+    };
+
+    let Some(&length) = alpn.get(2) else {
+        return String::from("00");
+    };
+
+    match alpn.get(3..3 + length as usize) {
+        Some(protocol) if !protocol.is_empty() && protocol[0].is_ascii_alphanumeric() && protocol[protocol.len() - 1].is_ascii_alphanumeric() => {
+            format!("{}{}", protocol[0] as char, protocol[protocol.len() - 1] as char)
+        },
+        Some(protocol) if !protocol.is_empty() => {
+            let hex = format!("{:02x}{:02x}", protocol[0], protocol[protocol.len() - 1]);
+            format!("{}{}", &hex[..1], &hex[3..])
+        },
+        _ => String::from("00")
+    }
+}
+
+pub fn compute(ssl: &SslRef) -> TlsFingerprint {
+    let legacy_version = unsafe {SSL_client_hello_get0_legacy_version(ssl.as_ptr())} as u16;
+    let ciphers = ciphers(ssl);
+    let extensions = extensions_present(ssl);
+    let curves = extension(ssl, EXT_SUPPORTED_GROUPS).map(|e| read_u16_list(e.get(2..).unwrap_or(&[]))).unwrap_or_default();
+    let point_formats: Vec<u8> = extension(ssl, EXT_EC_POINT_FORMATS).map(|e| e.get(1..).unwrap_or(&[]).to_vec()).unwrap_or_default();
+    let signature_algorithms = extension(ssl, EXT_SIGNATURE_ALGORITHMS).map(|e| read_u16_list(e.get(2..).unwrap_or(&[]))).unwrap_or_default();
+
+    let ja3_raw = format!("{legacy_version},{},{},{},{}", join(&ciphers, "-"), join(&extensions, "-"), join(&curves, "-"), join(&point_formats, "-"));
+    let ja3 = hex_digest(MessageDigest::md5(), &ja3_raw);
+
+    let version = extension(ssl, EXT_SUPPORTED_VERSIONS)
+        .and_then(|e| read_u16_list(e.get(1..).unwrap_or(&[])).into_iter().max())
+        .unwrap_or(legacy_version);
+
+    let mut sorted_ciphers = ciphers.clone();
+    sorted_ciphers.sort();
+
+    let mut sorted_extensions: Vec<u16> = extensions.iter().copied().filter(|e| *e != EXT_SERVER_NAME && *e != EXT_ALPN).collect();
+    sorted_extensions.sort();
+
+    let mut extensions_raw = join_hex(&sorted_extensions);
+    if !signature_algorithms.is_empty() {
+        extensions_raw.push('_');
+        extensions_raw.push_str(&join_hex(&signature_algorithms));
+    }
+
+    let ja4 = format!("t{}{}{:02}{:02}{}_{}_{}",
+                      ja4_version(version),
+                      if extensions.contains(&EXT_SERVER_NAME) {"d"} else {"i"},
+                      ciphers.len().min(99),
+                      extensions.len().min(99),
+                      alpn_marker(ssl),
+                      truncated_sha256(&join_hex(&sorted_ciphers)),
+                      truncated_sha256(&extensions_raw));
+
+    TlsFingerprint {ja3, ja4}
+}
+
+pub fn server_name(ssl: &SslRef) -> Option<String> {
+    let extension = extension(ssl, EXT_SERVER_NAME)?;
+    if *extension.get(2)? != 0 {
+        return None;
+    }
+
+    let length = u16::from_be_bytes([*extension.get(3)?, *extension.get(4)?]) as usize;
+    String::from_utf8(extension.get(5..5 + length)?.to_vec()).ok()
+}
+
+pub fn fingerprint(ssl: &SslRef) -> Option<TlsFingerprint> {
+    let index = (*FINGERPRINT_INDEX)?;
+    ssl.ex_data(index).cloned()
+}
@@ -1,79 +1,370 @@
-use std::collections::{HashMap, HashSet};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::error::Error;
-use std::io::Read;
+use std::fs::Metadata;
+use std::io;
+use std::io::{Read, Write};
+use std::path::{Component, Path};
 #[cfg(target_family = "unix")]
-use std::os::unix::fs::chroot;
-use std::sync::LazyLock;
-use chrono::Utc;
-use brotli::{BrotliCompress, BrotliDecompress};
+use std::os::unix::fs::{chroot, MetadataExt};
+use std::fmt::Write as _;
+use std::sync::{Arc, LazyLock, Mutex as StdMutex, RwLock as StdRwLock};
+use std::time::{Duration, SystemTime};
+use chrono::{DateTime, Utc};
+use brotli::{CompressorWriter, Decompressor};
 use brotli::enc::BrotliEncoderParams;
 use flate2::Compression;
-use flate2::read::{GzDecoder, GzEncoder};
-use openssl::hash::{hash, MessageDigest};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use openssl::hash::{hash, Hasher, MessageDigest};
+use openssl::sha::Sha256;
 use openssl::base64;
 use openssl::error::ErrorStack;
 use tokio::fs::File;
-use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, BufReader};
+use tokio::io::{copy, sink, AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite};
 use tokio::io::AsyncWriteExt;
 use tokio::sync::Mutex;
+use tokio::select;
+use tokio::time::{interval_at, timeout, Instant};
 use bstr::ByteSlice;
 use bytes::BytesMut;
+use lru::LruCache;
 use drain_common::cookies::{SetCookie, SameSite};
-use drain_common::{FormDataValue, RequestBody};
+use drain_common::RequestBody;
 use drain_common::RequestBody::{FormData, OctetStream, Plain, XWWWFormUrlEncoded};
 use regex::bytes::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tracing::{debug, error, info, warn};
+use crate::shutdown::{is_shutting_down, shutdown_requested};
 use crate::pages::internal_server_error::internal_server_error;
-use crate::config::CONFIG;
+use crate::pages::{negotiate_problem_details, problem_page};
+use crate::config::{AuthScheme, EtagMode, UnexpectedBodyPolicy, CONFIG};
 use crate::requests::Request;
 #[cfg(feature = "cgi")]
-use crate::cgi::CGIData;
+use crate::cgi::{CGIBody, CGIData};
+#[cfg(feature = "cgi")]
+use crate::multipart::BodySpool;
 use crate::error::*;
+use crate::endpoints::EventStream;
+use crate::cookie_policy::{apply_cookie_policy, apply_cookie_policy_to_headers};
+use crate::dictionary::{dictionary_compress, use_as_dictionary, DCB_ENCODING};
+use crate::filters::apply_sub_filters;
+use crate::redirect_policy::check_redirect;
+use crate::tls_fingerprint::TlsFingerprint;
+use crate::client_certificate::ClientCertificate;
+use crate::zero_copy::{transmit, ZeroCopy};
+use crate::throttle::Throttled;
+use crate::multipart::{multipart_boundary, MultipartParser, SpooledFile};
+use crate::journal::hex;
+use crate::fs_index::off_worker;
+use crate::metrics::record_response_body_size;
+use crate::dev::log_response;
+use crate::access_log::{record_bytes_sent, record_status, AccessRecord};
+
+pub const BODY_MEDIA_TYPE_HEADER: &str = "x-drain-body-media-type";
 
 pub static HEADERS_REGEX: LazyLock<Regex> = LazyLock::new(|| {
     Regex::new(r#"^([[:alnum:]]+(([-_])[[:alnum:]]+)*)(: ?)([A-Za-z0-9_ :;.,/"'?!(){}\[\]@<>=\-+*#$&`|~^%]+)$"#).unwrap()
 });
 
-pub static ETAGS: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(|| {
-    Mutex::new(HashSet::new())
+#[derive(Default)]
+pub struct RequestInfo {
+    pub accept: Option<String>,
+    pub host: Option<String>,
+    pub resource: Option<String>,
+    pub params: Option<HashMap<String, String>>,
+    pub document_root: Option<String>,
+    pub virtual_host: Option<usize>,
+    pub debug: Option<String>,
+    pub available_dictionary: Option<String>,
+    pub tls_fingerprint: Option<TlsFingerprint>,
+    pub client_certificate: Option<ClientCertificate>,
+    pub remote_user: Option<(AuthScheme, String)>,
+    pub jwt_claims: Option<Map<String, Value>>,
+    pub https: bool,
+    pub head: bool,
+    pub request_id: Option<String>,
+    pub spooled_files: Vec<SpooledFile>,
+    pub body_sha256: Option<String>,
+    pub body_media_type: Option<String>,
+    #[cfg(feature = "json")]
+    pub json_body: Option<Arc<Value>>,
+    pub access: AccessRecord
+}
+
+tokio::task_local! {
+    pub static REQUEST_INFO: RefCell<RequestInfo>;
+}
+
+pub fn update_request_info<F>(f: F)
+where
+    F: FnOnce(&mut RequestInfo)
+{
+    let _ = REQUEST_INFO.try_with(|info| f(&mut info.borrow_mut()));
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Validator {
+    etag: String,
+    modified: Option<SystemTime>,
+    size: u64,
+    #[serde(default)]
+    integrity: Option<String>
+}
+
+const RESPONSE_HEAD_CAPACITY: usize = 512;
+
+static CURRENT_DATE: LazyLock<StdRwLock<(i64, String)>> = LazyLock::new(|| {
+    StdRwLock::new((0, String::new()))
+});
+
+pub static ETAGS: LazyLock<Mutex<LruCache<String, Validator>>> = LazyLock::new(|| {
+    let mut etags = LruCache::unbounded();
+    let Some(etag_store) = &CONFIG.load().etag_store else {
+        return Mutex::new(etags);
+    };
+
+    match std::fs::read(etag_store) {
+        Ok(content) => match serde_json::from_slice::<HashMap<String, Validator>>(&content) {
+            Ok(stored) => {
+                for (path, validator) in stored.into_iter().take(CONFIG.load().etag_cache_size) {
+                    etags.put(path, validator);
+                }
+            },
+            Err(e) => {
+                error!("An error occurred while parsing the ETag store.\n\
+                        Error information:\n{e}\n\
+                        Starting with an empty one...");
+            }
+        },
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {},
+        Err(e) => {
+            error!("An error occurred while reading the ETag store.\n\
+                    Error information:\n{e}\n\
+                    Starting with an empty one...");
+        }
+    }
+
+    Mutex::new(etags)
 });
 
+pub async fn persist_etags() {
+    let Some(etag_store) = &CONFIG.load().etag_store else {
+        return;
+    };
+
+    loop {
+        tokio::time::sleep(Duration::from_secs(CONFIG.load().etag_store_interval.max(1))).await;
+
+        let content = match serde_json::to_vec(&ETAGS.lock().await.iter().collect::<HashMap<&String, &Validator>>()) {
+            Ok(content) => content,
+            Err(e) => {
+                error!("An error occurred while serializing the ETag store.\n\
+                        Error information:\n{e}");
+                continue;
+            }
+        };
+
+        let temporary = format!("{etag_store}.{}", std::process::id());
+        if let Err(e) = tokio::fs::write(&temporary, content).await {
+            error!("An error occurred while writing the ETag store.\n\
+                    Error information:\n{e}");
+            continue;
+        }
+
+        if let Err(e) = tokio::fs::rename(&temporary, etag_store).await {
+            error!("An error occurred while replacing the ETag store.\n\
+                    Error information:\n{e}");
+        }
+    }
+}
+
+pub async fn purge_etags<F>(matches: F)
+where
+    F: Fn(&str) -> bool
+{
+    remove_etags(&mut *ETAGS.lock().await, matches);
+}
+
+pub fn remove_etags<F>(etags: &mut LruCache<String, Validator>, matches: F)
+where
+    F: Fn(&str) -> bool
+{
+    let stale: Vec<String> = etags.iter().filter(|(path, _)| matches(path)).map(|(path, _)| path.clone()).collect();
+    for path in stale {
+        etags.pop(&path);
+    }
+}
+
 #[cfg(target_family = "unix")]
 pub static CHROOT: LazyLock<bool> = LazyLock::new(|| {
-    if CONFIG.chroot {
-        if let Err(e) = chroot(&CONFIG.document_root) {
-            eprintln!("[main():{}] An error occurred while chrooting.\n\
-                                   Error information:\n{e}\n\
-                                   Continuing without it...", line!());
+    if CONFIG.load().chroot {
+        if let Err(e) = chroot(&CONFIG.load().document_root) {
+            warn!("An error occurred while chrooting.\n\
+                   Error information:\n{e}\n\
+                   Continuing without it...");
         } else {
-            println!("Chroot enabled.");
+            info!("Chroot enabled.");
             return true;
         }
     }
 
-    println!("Chroot disabled.");
+    info!("Chroot disabled.");
     false
 });
 
+pub fn raw_body(data: &Option<RequestBody>) -> Option<Vec<u8>> {
+    match data {
+        Some(OctetStream(data)) => Some(data.clone()),
+        Some(Plain(data)) => Some(Vec::from(data.as_bytes())),
+        None => Some(Vec::new()),
+        _ => None
+    }
+}
+
+pub fn document_root() -> String {
+    let root = CONFIG.load().virtual_host()
+        .and_then(|virtual_host| virtual_host.document_root.clone())
+        .or_else(|| REQUEST_INFO.try_with(|info| info.borrow().document_root.clone()).ok().flatten());
+
+    #[cfg(target_family = "unix")]
+    if *CHROOT {
+        return root
+            .and_then(|root| root.strip_prefix(CONFIG.load().document_root.trim_end_matches('/')).map(String::from))
+            .unwrap_or_default();
+    }
+
+    root.unwrap_or_else(|| CONFIG.load().document_root.clone())
+}
+
+pub async fn is_inside_document_root(path: &String) -> bool {
+    is_inside_root(&document_root(), path).await
+}
+
+pub async fn is_inside_root(root: &str, path: &String) -> bool {
+    #[cfg(target_family = "unix")]
+    if *CHROOT {
+        return true;
+    }
+
+    let (Ok(root), Ok(resolved)) = (tokio::fs::canonicalize(root).await, tokio::fs::canonicalize(path).await) else {
+        return false;
+    };
+
+    if resolved.starts_with(&root) {
+        return true;
+    }
+
+    CONFIG.load().follow_external_symlinks && !Path::new(path).components().any(|c| c == Component::ParentDir)
+}
+
 pub fn generate_etag(content: &[u8]) -> Result<String, ErrorStack>  {
     Ok(base64::encode_block(&*hash(MessageDigest::md5(), content)?))
 }
 
+fn insert_validator(etags: &mut LruCache<String, Validator>, path: &String, validator: Validator) {
+    if !etags.contains(path) {
+        while etags.len() >= CONFIG.load().etag_cache_size && etags.pop_lru().is_some() {}
+    }
+
+    etags.put(path.clone(), validator);
+}
+
+pub async fn file_integrity(path: &String) -> Option<String> {
+    let meta = tokio::fs::metadata(path).await.ok()?;
+    let (modified, size) = (meta.modified().ok(), meta.len());
+
+    let cached = ETAGS.lock().await.get(path).map(|validator| (validator.integrity.clone(), validator.modified, validator.size));
+    if let Some((Some(integrity), cached_modified, cached_size)) = cached {
+        if cached_modified.is_some() && cached_modified == modified && cached_size == size {
+            return Some(integrity);
+        }
+    }
+
+    let content = tokio::fs::read(path).await.ok()?;
+    let integrity = match hash(MessageDigest::sha256(), &content) {
+        Ok(digest) => format!("sha256-{}", base64::encode_block(&*digest)),
+        Err(e) => {
+            error!("An error occurred while hashing {path}:\n{e}");
+            return None;
+        }
+    };
+
+    if CONFIG.load().etag_cache_size > 0 {
+        let etag = generate_etag(&content).ok();
+        let mut etags = ETAGS.lock().await;
+        match etags.get_mut(path) {
+            Some(validator) if validator.modified.is_some() && validator.modified == modified && validator.size == size => {
+                validator.integrity = Some(integrity.clone());
+            },
+            _ => {
+                if let Some(etag) = etag {
+                    insert_validator(&mut etags, path, Validator {etag, modified, size, integrity: Some(integrity.clone())});
+                }
+            }
+        }
+    }
+
+    Some(integrity)
+}
+
+fn generate_file_etag(path: String) -> io::Result<String> {
+    let mut hasher = Hasher::new(MessageDigest::md5())?;
+    io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+    Ok(base64::encode_block(&*hasher.finish()?))
+}
+
+pub async fn file_etag(path: &String, content: Option<&[u8]>) -> Option<String> {
+    let meta = tokio::fs::metadata(path).await.ok()?;
+    if let EtagMode::Metadata = CONFIG.load().etag_mode {
+        return Some(validator_etag(&meta));
+    }
+
+    let (modified, size) = (meta.modified().ok(), meta.len());
+
+    let cached = ETAGS.lock().await.get(path).map(|validator| (validator.etag.clone(), validator.modified, validator.size));
+    if let Some((etag, cached_modified, cached_size)) = cached {
+        if cached_modified.is_some() && cached_modified == modified && cached_size == size {
+            return Some(etag);
+        }
+    }
+
+    let generated = match content {
+        Some(content) => generate_etag(content).map_err(io::Error::from),
+        None => {
+            let path = path.clone();
+            off_worker(move || generate_file_etag(path)).await.unwrap_or_else(|| Err(io::Error::other("the ETag task was cancelled")))
+        }
+    };
+
+    if CONFIG.load().etag_cache_size == 0 {
+        return generated.ok();
+    }
+
+    match generated {
+        Ok(etag) => {
+            insert_validator(&mut *ETAGS.lock().await, path, Validator {etag: etag.clone(), modified, size, integrity: None});
+            Some(etag)
+        },
+        Err(e) => {
+            ETAGS.lock().await.pop(path);
+            debug!("An error occurred while generating an ETag:\n{e}\n\
+                    Continuing without ETag...");
+            None
+        }
+    }
+}
+
 pub enum ResourceType {
     Static,
-    Dynamic
+    Precompressed,
+    Dynamic,
+    Verbatim
 }
 
-pub async fn send_response<T>(stream: &mut T,
-                              status: u16,
-                              local_response_headers: Option<HashMap<String, String>>,
-                              content: Option<Vec<u8>>,
-                              set_cookie: Option<HashMap<String, SetCookie>>,
-                              resource_type: Option<ResourceType>) -> Result<(), Box<dyn Error + Send + Sync>>
-where
-    T: AsyncRead + AsyncWrite + Unpin
-{
-    let mut response = String::new();
-    let status_text = match status {
+pub fn status_text(status: u16) -> Option<&'static str> {
+    Some(match status {
         100 => "Continue",
         101 => "Switching Protocols",
         102 => "Processing",
@@ -135,53 +426,63 @@ where
         508 => "Loop Detected",
         510 => "Not Extended",
         511 => "Network Authentication Required",
-        _ => return Err(Box::new(ServerError::InvalidStatusCode(status)))
+        _ => return None
+    })
+}
+
+fn response_head(status: u16, set_cookie: Option<HashMap<String, SetCookie>>) -> Result<String, ServerError> {
+    let mut response = String::with_capacity(RESPONSE_HEAD_CAPACITY);
+    let Some(status_text) = status_text(status) else {
+        return Err(ServerError::InvalidStatusCode(status));
     };
-    let status_line = format!("HTTP/1.1 {status} {status_text}\r\n");
-    response.push_str(&*status_line);
+    let _ = write!(response, "HTTP/1.1 {status} {status_text}\r\n");
+    log_response(status);
+    record_status(status);
 
-    let date = get_current_date();
-    let date_header = format!("Date: {}\r\n", date);
-    response.push_str(&*date_header);
+    response.push_str("Date: ");
+    push_current_date(&mut response);
+    response.push_str("\r\n");
 
-    if CONFIG.enable_server_header {
-        let server_header = format!("Server: Drain {}\r\n", env!("CARGO_PKG_VERSION"));
-        response.push_str(&*server_header);
+    if CONFIG.load().enable_server_header {
+        response.push_str(concat!("Server: Drain ", env!("CARGO_PKG_VERSION"), "\r\n"));
     }
 
-    let global_response_headers = match &CONFIG.global_response_headers {
-        Some(global_response_headers) => {
-            global_response_headers.to_owned()
-        },
-        _ => {
-            HashMap::from([(String::from("Connection"), String::from("close"))])
+    if let Some(hsts) = CONFIG.load().https.as_ref().and_then(|https| https.hsts.as_ref()) {
+        if REQUEST_INFO.try_with(|info| info.borrow().https).unwrap_or(false) {
+            let _ = write!(response, "Strict-Transport-Security: {}\r\n", hsts.header_value());
         }
-    };
+    }
+
+    if let Ok(Some(debug)) = REQUEST_INFO.try_with(|info| info.borrow().debug.clone()) {
+        let _ = write!(response, "X-Drain-Debug: {debug}\r\n");
+    }
+
+    if let Some(mut set_cookie) = set_cookie {
+        apply_cookie_policy(&mut set_cookie);
 
-    if let Some(set_cookie) = set_cookie {
         if !set_cookie.is_empty() {
             for (k, v) in set_cookie {
-                response.push_str(&format!("Set-Cookie: {}={}", k, v.value));
+                let _ = write!(response, "Set-Cookie: {}={}", k, v.value);
                 if let Some(domain) = &v.domain {
-                    response.push_str(&format!("; Domain={}", domain));
+                    let _ = write!(response, "; Domain={}", domain);
                 }
                 if let Some(expires) = &v.expires {
-                    response.push_str(&format!("; Expires={}", expires));
+                    let _ = write!(response, "; Expires={}", expires);
                 }
                 if v.httponly {
                     response.push_str("; HttpOnly");
                 }
                 if let Some(max_age) = &v.max_age {
-                    response.push_str(&format!("; Max-Age={}", max_age));
+                    let _ = write!(response, "; Max-Age={}", max_age);
                 }
                 if v.partitioned {
                     response.push_str("; Partitioned");
                 }
                 if let Some(path) = &v.path {
-                    response.push_str(&format!("; Path={}", path));
+                    let _ = write!(response, "; Path={}", path);
                 }
                 if v.secure {
-                    response.push_str("Secure");
+                    response.push_str("; Secure");
                 }
                 if let Some(samesite) = &v.samesite {
                     match samesite {
@@ -201,66 +502,135 @@ where
         }
     }
 
+    Ok(response)
+}
+
+fn global_response_headers() -> HashMap<String, String> {
+    let mut global_response_headers = match &CONFIG.load().global_response_headers {
+        Some(global_response_headers) => {
+            global_response_headers.to_owned()
+        },
+        _ => {
+            HashMap::from([(String::from("Connection"), String::from("close"))])
+        }
+    };
+
+    if let Some(headers) = CONFIG.load().virtual_host().and_then(|virtual_host| virtual_host.headers.as_ref()) {
+        for (k, v) in headers {
+            global_response_headers.retain(|name, _| !name.eq_ignore_ascii_case(k));
+            global_response_headers.insert(k.clone(), v.clone());
+        }
+    }
+
+    for (k, v) in CONFIG.load().security_headers() {
+        global_response_headers.retain(|name, _| !name.eq_ignore_ascii_case(k));
+        global_response_headers.insert(String::from(k), v);
+    }
+
+    if is_shutting_down() {
+        global_response_headers.retain(|k, _| !k.eq_ignore_ascii_case("connection"));
+        global_response_headers.insert(String::from("Connection"), String::from("close"));
+    }
+
+    global_response_headers
+}
+
+pub async fn send_response<T>(stream: &mut T,
+                              status: u16,
+                              local_response_headers: Option<HashMap<String, String>>,
+                              content: Option<Vec<u8>>,
+                              set_cookie: Option<HashMap<String, SetCookie>>,
+                              resource_type: Option<ResourceType>) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+    T: AsyncRead + AsyncWrite + Unpin
+{
+    let mut status = status;
+    let mut local_response_headers = local_response_headers;
+    let mut content = content;
+    let mut resource_type = resource_type;
+
+    if let Some(h) = &mut local_response_headers {
+        apply_cookie_policy_to_headers(h);
+        check_redirect(&mut status, h);
+    }
+
+    let head = REQUEST_INFO.try_with(|info| info.borrow().head).unwrap_or(false);
+    if status >= 400 && content.is_none() && !head && negotiate_problem_details() {
+        let (problem, problem_headers) = problem_page(status);
+        local_response_headers.get_or_insert_with(HashMap::new).extend(problem_headers);
+        content = Some(problem);
+        resource_type = Some(ResourceType::Dynamic);
+    }
+
+    let mut response = response_head(status, set_cookie)?;
+    let global_response_headers = global_response_headers();
     let mut response_bytes: Vec<u8>;
+    let mut compressed_body: Option<(StreamingEncoder, Vec<u8>)> = None;
 
     match (local_response_headers, content) {
         (Some(ref mut h), Some(mut c)) => {
             h.extend(global_response_headers);
+            let precompressed = matches!(resource_type, Some(ResourceType::Precompressed | ResourceType::Verbatim));
 
-            for (k, v) in &mut *h {
-                response.push_str(&*format!("{k}: {v}\r\n"));
-            }
+            if !precompressed {
+                apply_sub_filters(h, &mut c);
 
-            if c.is_utf8() {
-                c = Vec::from(c.trim_ascii());
+                if c.is_utf8() {
+                    c = Vec::from(c.trim_ascii());
+                }
             }
 
-            if let Some(ResourceType::Static) = resource_type {
-                match generate_etag(&*c) {
-                    Ok(etag) => {
-                        let mut etags = ETAGS.lock().await;
-
-                        let etag_cache_headers = format!("ETag: {etag}\r\nCache-Control: max-age={}\r\n", CONFIG.cache_max_age);
-                        etags.insert(etag);
-                        response.push_str(&*etag_cache_headers);
-                    },
-                    Err(e) => {
-                        if CONFIG.be_verbose {
-                            eprintln!("[send_response():{}] An error occurred while generating an ETag:\n{e}\n\
-                                        Continuing without ETag...", line!());
+            if let Some(ResourceType::Static | ResourceType::Precompressed) = resource_type {
+                if !h.contains_key("ETag") {
+                    match generate_etag(&*c) {
+                        Ok(etag) => {
+                            h.insert(String::from("ETag"), etag);
+                        },
+                        Err(e) => {
+                            debug!("An error occurred while generating an ETag:\n{e}\n\
+                                    Continuing without ETag...");
                         }
                     }
                 }
-            }
+                h.insert(String::from("Cache-Control"), CONFIG.load().cache_control());
 
-            let mut content_prepared: Vec<u8> = Vec::new();
+                if let Some(use_as_dictionary) = use_as_dictionary() {
+                    h.insert(String::from("Use-As-Dictionary"), use_as_dictionary);
+                }
+            }
 
-            if let Some(encoding) = h.get("Content-Encoding") {
-                if encoding.eq("gzip") {
-                    if let Err(e) = GzEncoder::new(&*c, Compression::default()).read_to_end(&mut content_prepared) {
-                        eprintln!("[send_response():{}] An error occurred while compressing the content of a response using GZIP:\n{e}\n\
-                                    Attempting to send uncompressed data...", line!());
-                        content_prepared = c;
-                    }
-                } else if encoding.eq("br") {
-                    if let Err(e) = BrotliCompress(&mut (c.as_bytes()), &mut content_prepared, &BrotliEncoderParams::default()) {
-                        eprintln!("[send_response():{}] An error occurred while compressing the content of a response using Brotli:\n{e}\n\
-                                    Attempting to send uncompressed data...", line!());
-                        content_prepared = c;
+            if h.get("Content-Encoding").is_some_and(|e| e.eq(&*DCB_ENCODING)) {
+                match dictionary_compress(&c) {
+                    Some(compressed) => {
+                        c = compressed;
+                        h.insert(String::from("Vary"), String::from("Accept-Encoding, Available-Dictionary"));
+                    },
+                    None => {
+                        h.insert(String::from("Content-Encoding"), String::from("br"));
                     }
-                } else {
-                    content_prepared = c;
                 }
-            } else {
-                content_prepared = c;
             }
 
-            let content_length_header = format!("Content-Length: {}\r\n\r\n", content_prepared.len());
-            response.push_str(&*content_length_header);
+            for (k, v) in &mut *h {
+                let _ = write!(response, "{k}: {v}\r\n");
+            }
 
-            response_bytes = Vec::from(response);
-            for x in content_prepared {
-                response_bytes.push(x);
+            match h.get("Content-Encoding").filter(|_| !precompressed).and_then(|encoding| StreamingEncoder::new(encoding)) {
+                Some(encoder) => {
+                    response.push_str("Transfer-Encoding: chunked\r\n\r\n");
+                    response_bytes = Vec::from(response);
+                    compressed_body = Some((encoder, c));
+                },
+                None => {
+                    record_response_body_size(c.len() as u64);
+                    record_bytes_sent(c.len() as u64);
+
+                    let content_length_header = format!("Content-Length: {}\r\n\r\n", c.len());
+                    response.push_str(&*content_length_header);
+
+                    response_bytes = Vec::from(response);
+                    response_bytes.extend(c);
+                }
             }
         },
         (None, Some(mut c)) => {
@@ -271,21 +641,18 @@ where
             if let Some(ResourceType::Static) = resource_type {
                 match generate_etag(&*c) {
                     Ok(etag) => {
-                        let mut etags = ETAGS.lock().await;
-
-                        let etag_cache_headers = format!("ETag: {etag}\r\nCache-Control: max-age={}\r\n", CONFIG.cache_max_age);
-                        etags.insert(etag);
-                        response.push_str(&*etag_cache_headers);
+                        let _ = write!(response, "ETag: {etag}\r\nCache-Control: {}\r\n", CONFIG.load().cache_control());
                     },
                     Err(e) => {
-                        if CONFIG.be_verbose {
-                            eprintln!("[send_response():{}] An error occurred while generating an ETag:\n{e}\n\
-                                        Continuing without ETag...", line!());
-                        }
+                        debug!("An error occurred while generating an ETag:\n{e}\n\
+                                Continuing without ETag...");
                     }
                 }
             }
 
+            record_response_body_size(c.len() as u64);
+            record_bytes_sent(c.len() as u64);
+
             let content_length_header = format!("Content-Length: {}\r\n\r\n", c.len());
             response.push_str(&*content_length_header);
 
@@ -297,10 +664,11 @@ where
         (Some(ref mut h), None) => {
             h.extend(global_response_headers);
 
+            let content_length_present = h.keys().any(|k| k.eq_ignore_ascii_case("content-length") || k.eq_ignore_ascii_case("transfer-encoding"));
             for (k, v) in h {
-                response.push_str(&*format!("{k}: {v}\r\n"));
+                let _ = write!(response, "{k}: {v}\r\n");
             }
-            response.push_str("Content-Length: 0\r\n\r\n");
+            response.push_str(if content_length_present {"\r\n"} else {"Content-Length: 0\r\n\r\n"});
 
             response_bytes = Vec::from(response);
         },
@@ -310,128 +678,621 @@ where
         }
     }
 
+    let stream = &mut Throttled::new(stream, CONFIG.load().bandwidth_limit());
+
     if let Err(e1) = stream.write_all(&*response_bytes).await {
-        eprintln!("[send_response():{}] An error occurred while writing a response to a client:\n{e1}\n\
-                    Attempting to close connection...", line!());
+        error!("An error occurred while writing a response to a client:\n{e1}\n\
+                Attempting to close connection...");
         if let Err(e2) = stream.shutdown().await {
-            eprintln!("[receive_request():{}] FAILED. Error information:\n{e2}", line!());
+            error!("FAILED. Error information:\n{e2}");
         }
         panic!("Unrecoverable error occurred while handling connection.");
     }
 
+    if let Some((encoder, content)) = compressed_body {
+        match encoder.write_chunked(stream, content.as_slice()).await {
+            Ok(written) => {
+                record_response_body_size(written);
+                record_bytes_sent(written);
+            },
+            Err(e1) => {
+                error!("An error occurred while writing a compressed response to a client:\n{e1}\n\
+                        Attempting to close connection...");
+                if let Err(e2) = stream.shutdown().await {
+                    error!("FAILED. Error information:\n{e2}");
+                }
+                panic!("Unrecoverable error occurred while handling connection.");
+            }
+        }
+    }
+
     if let Err(e) = stream.flush().await {
-        eprintln!("[send_response():{}] An error occurred while flushing the output stream:\n{e}", line!());
+        error!("An error occurred while flushing the output stream:\n{e}");
     }
 
     Ok(())
 }
 
-pub async fn receive_request<T>(stream: &mut T, keep_alive: &mut bool) -> Result<Request, ServerError>
+pub async fn send_compressed_file<T>(stream: &mut T,
+                                     status: u16,
+                                     mut local_response_headers: HashMap<String, String>,
+                                     file: &mut File,
+                                     encoder: StreamingEncoder) -> Result<(), Box<dyn Error + Send + Sync>>
 where
     T: AsyncRead + AsyncWrite + Unpin
 {
-    let mut reader = BufReader::new(&mut *stream);
-    let mut request_string = String::new();
+    let mut response = response_head(status, None)?;
 
-    loop {
-        match reader.read_line(&mut request_string).await {
-            Ok(l) => {
-                if l == 2 {
-                    break;
-                }
-            },
-            Err(e1) => {
-                eprintln!("[receive_request():{}] An error occurred while reading a request from a client.\n\
-                            Error information:\n{e1}\n\
-                            Attempting to close connection...", line!());
-                if let Err(e2) = stream.shutdown().await {
-                    eprintln!("[receive_request():{}] FAILED. Error information:\n{e2}", line!());
-                }
-                panic!("Unrecoverable error occurred while handling connection.");
-            }
-        };
+    local_response_headers.extend(global_response_headers());
+    for (k, v) in &local_response_headers {
+        let _ = write!(response, "{k}: {v}\r\n");
     }
+    response.push_str("Transfer-Encoding: chunked\r\n\r\n");
 
-    let mut request = Request::parse_from_string(&request_string, keep_alive)?;
-
-    #[cfg(feature = "cgi")]
-    if let  Request::Post {data, headers, cgi_data, ..} |
-            Request::Put {data, headers, cgi_data, ..} |
-            Request::Patch {data, headers, cgi_data, ..} |
-            Request::Delete {data, headers, cgi_data, ..} = &mut request {
-        let mut buffer = BytesMut::with_capacity(
-            match headers.get("content-length").unwrap_or(&String::from("0")).parse::<usize>() {
-                Ok(l) if l > 0 => {
-                    if l > CONFIG.max_content_length {
-                        return Err(ServerError::BodyTooLarge);
-                    }
-                    l
-                },
-                Ok(l) if l == 0 => {
-                    return Ok(request);
-                },
-                _ => {
-                    return Err(ServerError::InvalidRequest);
-                }
-            }
-        );
+    let stream = &mut Throttled::new(stream, CONFIG.load().bandwidth_limit());
+    stream.write_all(response.as_bytes()).await?;
 
-        if let Err(e1) = reader.read_buf(&mut buffer).await {
-            eprintln!("[receive_request():{}] An error occurred while reading a request from a client.\n\
-                        Error information:\n{e1}\n\
-                        Attempting to close connection...", line!());
+    match encoder.write_chunked(stream, file).await {
+        Ok(written) => {
+            record_response_body_size(written);
+            record_bytes_sent(written);
+            Ok(())
+        },
+        Err(e1) => {
+            error!("An error occurred while sending a compressed file to a client:\n{e1}\n\
+                    Attempting to close connection...");
             if let Err(e2) = stream.shutdown().await {
-                eprintln!("[receive_request():{}] FAILED. Error information:\n{e2}", line!());
+                error!("FAILED. Error information:\n{e2}");
             }
-            panic!("Unrecoverable error occurred while handling connection.");
+            Err(Box::new(e1))
         }
+    }
+}
 
-        let mut payload: Vec<u8> = Vec::new();
+pub async fn send_file<T>(stream: &mut T,
+                          status: u16,
+                          mut local_response_headers: HashMap<String, String>,
+                          file: &mut File,
+                          length: u64) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+    T: AsyncRead + AsyncWrite + Unpin + ZeroCopy
+{
+    let mut response = response_head(status, None)?;
 
-        match (headers.get("content-encoding"), CONFIG.get_supported_encodings()) {
-            (Some(content_encoding), Some(supported_encodings))
-            if supported_encodings.contains(content_encoding) => {
-                if content_encoding.eq("gzip") {
-                    if let Err(e) = GzDecoder::new(&*buffer).read_to_end(&mut payload) {
-                        eprintln!("[receive_request():{}] An error occurred while decompressing the request body using GZIP:\n{e}\n\
-                                    Sending 406 status to the client...", line!());
+    local_response_headers.extend(global_response_headers());
+    for (k, v) in &local_response_headers {
+        let _ = write!(response, "{k}: {v}\r\n");
+    }
+    let _ = write!(response, "Content-Length: {length}\r\n\r\n");
+    record_response_body_size(length);
+    record_bytes_sent(length);
 
-                        return Err(ServerError::DecompressionError(e));
-                    }
-                } else if content_encoding.eq("br") {
-                    if let Err(e) = BrotliDecompress(&mut &*buffer, &mut payload) {
-                        eprintln!("[receive_request():{}] An error occurred while decompressing the request body using Brotli:\n{e}\n\
-                                    Sending 406 status to the client...", line!());
+    let stream = &mut Throttled::new(stream, CONFIG.load().bandwidth_limit());
+    stream.write_all(response.as_bytes()).await?;
 
-                        return Err(ServerError::DecompressionError(e));
-                    }
-                } else {
-                    return Err(ServerError::UnsupportedEncoding);
-                }
-            }
-            (Some(content_encoding), Some(supported_encodings))
-            if !supported_encodings.contains(content_encoding) => {
-                return Err(ServerError::UnsupportedEncoding);
-            },
-            (Some(_), None) => {
-                return Err(ServerError::UnsupportedEncoding);
-            },
-            _ => {
-                payload = buffer.to_vec();
-            }
+    if let Err(e1) = transmit(stream, file, length).await {
+        error!("An error occurred while sending a file to a client:\n{e1}\n\
+                Attempting to close connection...");
+        if let Err(e2) = stream.shutdown().await {
+            error!("FAILED. Error information:\n{e2}");
         }
+        return Err(Box::new(e1));
+    }
 
-        let body: RequestBody;
+    Ok(())
+}
 
-        match headers.get("content-type") {
-            Some(content_type) if content_type.eq("application/octet-stream") => {
-                body = OctetStream(payload.clone());
-                *cgi_data = Some(CGIData {data: payload, content_type: content_type.clone()});
-            },
-            Some(content_type) if content_type.starts_with("application/x-www-form-urlencoded") => {
-                let x_www_urlencoded_raw = String::from(String::from_utf8_lossy(&payload));
-                let mut body_hm: HashMap<String, String> = HashMap::new();
-                for kv in x_www_urlencoded_raw.split('&') {
+pub async fn send_stream<T, R>(stream: &mut T,
+                               status: u16,
+                               mut local_response_headers: HashMap<String, String>,
+                               body: &mut R,
+                               length: u64) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+    R: AsyncRead + Unpin
+{
+    let mut response = response_head(status, None)?;
+
+    local_response_headers.retain(|k, _| !k.eq_ignore_ascii_case("content-length") && !k.eq_ignore_ascii_case("transfer-encoding"));
+    local_response_headers.extend(global_response_headers());
+    for (k, v) in &local_response_headers {
+        let _ = write!(response, "{k}: {v}\r\n");
+    }
+    let _ = write!(response, "Content-Length: {length}\r\n\r\n");
+    record_response_body_size(length);
+    record_bytes_sent(length);
+
+    let stream = &mut Throttled::new(stream, CONFIG.load().bandwidth_limit());
+    stream.write_all(response.as_bytes()).await?;
+
+    let copied = copy(&mut body.take(length), stream).await?;
+    if copied < length {
+        error!("The response body ended after {copied} out of {length} bytes.\n\
+                Attempting to close connection...");
+        if let Err(e) = stream.shutdown().await {
+            error!("FAILED. Error information:\n{e}");
+        }
+        return Err(Box::new(io::Error::from(io::ErrorKind::UnexpectedEof)));
+    }
+
+    stream.flush().await?;
+    Ok(())
+}
+
+pub fn validator_etag(meta: &Metadata) -> String {
+    let modified = meta.modified().ok()
+        .and_then(|m| m.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_nanos());
+
+    #[cfg(target_family = "unix")]
+    return format!("W/\"{:x}-{:x}-{modified:x}\"", meta.ino(), meta.len());
+    #[cfg(not(target_family = "unix"))]
+    return format!("W/\"{:x}-{modified:x}\"", meta.len());
+}
+
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<StdMutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().map_err(|_| io::Error::other("poisoned output buffer"))?.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SharedBuffer {
+    fn take(&self) -> Vec<u8> {
+        self.0.lock().map(|mut buffer| std::mem::take(&mut *buffer)).unwrap_or_default()
+    }
+}
+
+enum Encoder {
+    Gzip(GzEncoder<SharedBuffer>),
+    Brotli(Box<CompressorWriter<SharedBuffer>>)
+}
+
+pub struct StreamingEncoder {
+    encoder: Encoder,
+    output: SharedBuffer
+}
+
+impl StreamingEncoder {
+    pub fn new(encoding: &str) -> Option<Self> {
+        let output = SharedBuffer::default();
+        let params = BrotliEncoderParams::default();
+
+        let encoder = match encoding {
+            "gzip" => Encoder::Gzip(GzEncoder::new(output.clone(), Compression::default())),
+            "br" => Encoder::Brotli(Box::new(CompressorWriter::new(output.clone(), BODY_CHUNK_LENGTH, params.quality as u32, params.lgwin as u32))),
+            _ => return None
+        };
+
+        Some(Self {encoder, output})
+    }
+
+    async fn write_chunk<T>(stream: &mut T, chunk: &[u8]) -> io::Result<u64>
+    where
+        T: AsyncWrite + Unpin
+    {
+        if chunk.is_empty() {
+            return Ok(0);
+        }
+
+        stream.write_all(format!("{:X}\r\n", chunk.len()).as_bytes()).await?;
+        stream.write_all(chunk).await?;
+        stream.write_all(b"\r\n").await?;
+        Ok(chunk.len() as u64)
+    }
+
+    async fn write_chunked<T, R>(mut self, stream: &mut T, mut content: R) -> io::Result<u64>
+    where
+        T: AsyncWrite + Unpin,
+        R: AsyncRead + Unpin
+    {
+        let mut input = vec![0u8; BODY_CHUNK_LENGTH];
+        let mut written = 0;
+
+        loop {
+            let read = content.read(&mut input).await?;
+            if read == 0 {
+                break;
+            }
+
+            match &mut self.encoder {
+                Encoder::Gzip(encoder) => encoder.write_all(&input[..read])?,
+                Encoder::Brotli(encoder) => encoder.write_all(&input[..read])?
+            }
+
+            written += Self::write_chunk(stream, &self.output.take()).await?;
+        }
+
+        match self.encoder {
+            Encoder::Gzip(encoder) => {
+                encoder.finish()?;
+            },
+            Encoder::Brotli(encoder) => {
+                encoder.into_inner();
+            }
+        }
+
+        written += Self::write_chunk(stream, &self.output.take()).await?;
+        stream.write_all(b"0\r\n\r\n").await?;
+        Ok(written)
+    }
+}
+
+pub async fn send_event_stream<T>(stream: &mut T,
+                                  status: u16,
+                                  mut local_response_headers: HashMap<String, String>,
+                                  content: Option<Vec<u8>>,
+                                  set_cookie: Option<HashMap<String, SetCookie>>,
+                                  event_stream: EventStream) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+    T: AsyncRead + AsyncWrite + Unpin
+{
+    let mut response = response_head(status, set_cookie)?;
+
+    local_response_headers.retain(|k, _| !k.eq_ignore_ascii_case("content-length") && !k.eq_ignore_ascii_case("transfer-encoding"));
+    if !local_response_headers.keys().any(|k| k.eq_ignore_ascii_case("content-type")) {
+        local_response_headers.insert(String::from("Content-Type"), String::from("text/event-stream"));
+    }
+    if !local_response_headers.keys().any(|k| k.eq_ignore_ascii_case("cache-control")) {
+        local_response_headers.insert(String::from("Cache-Control"), String::from("no-cache"));
+    }
+    let server_sent_events = local_response_headers.iter()
+        .any(|(k, v)| k.eq_ignore_ascii_case("content-type") && v.trim_start().to_ascii_lowercase().starts_with("text/event-stream"));
+    local_response_headers.extend(global_response_headers());
+    apply_cookie_policy_to_headers(&mut local_response_headers);
+
+    for (k, v) in &local_response_headers {
+        let _ = write!(response, "{k}: {v}\r\n");
+    }
+    response.push_str("Transfer-Encoding: chunked\r\n\r\n");
+
+    stream.write_all(response.as_bytes()).await?;
+
+    if let Some(c) = content {
+        if !c.is_empty() {
+            stream.write_all(format!("{:X}\r\n", c.len()).as_bytes()).await?;
+            stream.write_all(&*c).await?;
+            record_bytes_sent(c.len() as u64);
+            stream.write_all(b"\r\n").await?;
+        }
+    }
+    stream.flush().await?;
+
+    let mut event_stream = event_stream;
+    let mut keepalive = interval_at(Instant::now() + EVENT_STREAM_KEEPALIVE, EVENT_STREAM_KEEPALIVE);
+    loop {
+        let chunk = select! {
+            chunk = event_stream.receiver.recv() => match chunk {
+                Some(chunk) if chunk.is_empty() => continue,
+                Some(chunk) => chunk,
+                None => break
+            },
+            _ = keepalive.tick(), if server_sent_events => Vec::from(&b":\n\n"[..]),
+            _ = shutdown_requested() => break
+        };
+
+        stream.write_all(format!("{:X}\r\n", chunk.len()).as_bytes()).await?;
+        stream.write_all(&*chunk).await?;
+        stream.write_all(b"\r\n").await?;
+        stream.flush().await?;
+        record_bytes_sent(chunk.len() as u64);
+    }
+
+    stream.write_all(b"0\r\n\r\n").await?;
+    stream.flush().await?;
+
+    Ok(())
+}
+
+const BODY_CHUNK_LENGTH: usize = 65536;
+const EVENT_STREAM_KEEPALIVE: Duration = Duration::from_secs(15);
+
+async fn read_body<T, F>(stream: &mut T, body_length: usize, keep_alive: &mut bool, mut sink: F) -> Result<(), ServerError>
+where
+    T: AsyncBufRead + AsyncWrite + Unpin,
+    F: FnMut(&[u8]) -> Result<(), ServerError>
+{
+    let mut hasher = CONFIG.load().journal.as_ref().map(|_| Sha256::new());
+
+    read_chunks(stream, body_length, Duration::from_secs(CONFIG.load().request_timeout), keep_alive, |chunk| {
+        if let Some(hasher) = &mut hasher {
+            hasher.update(chunk);
+        }
+        sink(chunk)
+    }).await?;
+
+    if let Some(hasher) = hasher {
+        let body_sha256 = hex(&hasher.finish());
+        update_request_info(|info| info.body_sha256 = Some(body_sha256));
+    }
+
+    Ok(())
+}
+
+async fn read_chunks<T, F>(stream: &mut T, body_length: usize, inactivity_timeout: Duration, keep_alive: &mut bool, mut sink: F) -> Result<(), ServerError>
+where
+    T: AsyncBufRead + AsyncWrite + Unpin,
+    F: FnMut(&[u8]) -> Result<(), ServerError>
+{
+    let mut chunk = vec![0u8; body_length.min(BODY_CHUNK_LENGTH)];
+    let mut received = 0;
+
+    while received < body_length {
+        let length = (body_length - received).min(chunk.len());
+        match timeout(inactivity_timeout, stream.read(&mut chunk[..length])).await {
+            Ok(Ok(0)) => {
+                *keep_alive = false;
+                return Err(ServerError::InvalidRequest);
+            },
+            Ok(Ok(n)) => {
+                received += n;
+                if let Err(e) = sink(&chunk[..n]) {
+                    *keep_alive = false;
+                    return Err(e);
+                }
+            },
+            Ok(Err(e1)) => {
+                error!("An error occurred while reading a request from a client.\n\
+                        Error information:\n{e1}\n\
+                        Attempting to close connection...");
+                if let Err(e2) = stream.shutdown().await {
+                    error!("FAILED. Error information:\n{e2}");
+                }
+                panic!("Unrecoverable error occurred while handling connection.");
+            },
+            Err(_) => {
+                *keep_alive = false;
+                return Err(ServerError::RequestTimeout);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_framing(request_string: &String) -> Result<(), ServerError> {
+    let mut content_length_present = false;
+    let mut transfer_encoding: Option<&str> = None;
+
+    for line in request_string.split("\r\n").skip(1) {
+        if line.is_empty() {
+            break;
+        }
+
+        if line.contains('\n') || line.starts_with([' ', '\t']) {
+            return Err(ServerError::InvalidRequest);
+        }
+
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(ServerError::InvalidRequest);
+        };
+
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(ServerError::InvalidRequest);
+        }
+
+        if name.eq_ignore_ascii_case("content-length") {
+            let value = value.trim();
+            if content_length_present || value.is_empty() || !value.chars().all(|c| c.is_ascii_digit()) {
+                return Err(ServerError::InvalidRequest);
+            }
+            content_length_present = true;
+        } else if name.eq_ignore_ascii_case("transfer-encoding") {
+            if transfer_encoding.is_some() {
+                return Err(ServerError::InvalidRequest);
+            }
+            transfer_encoding = Some(value.trim());
+        }
+    }
+
+    match transfer_encoding {
+        None => Ok(()),
+        Some(_) if content_length_present => Err(ServerError::InvalidRequest),
+        Some(value) if value.split(',').all(|coding| is_token(coding.split(';').next().unwrap_or("").trim())) => Err(ServerError::NotImplemented),
+        Some(_) => Err(ServerError::InvalidRequest)
+    }
+}
+
+fn is_token(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c))
+}
+
+fn unconsumed_body_length(request_string: &String) -> Result<usize, ServerError> {
+    let mut body_length = 0;
+
+    for line in request_string.lines().skip(1) {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+
+        if name.trim().eq_ignore_ascii_case("transfer-encoding") {
+            return Err(ServerError::UnexpectedBody);
+        }
+
+        if name.trim().eq_ignore_ascii_case("content-length") {
+            body_length = value.trim().parse::<usize>().map_err(|_| ServerError::InvalidRequest)?;
+        }
+    }
+
+    Ok(body_length)
+}
+
+pub async fn receive_request<T>(stream: &mut T, keep_alive: &mut bool) -> Result<Request, ServerError>
+where
+    T: AsyncBufRead + AsyncWrite + Unpin
+{
+    let mut request_bytes: Vec<u8> = Vec::new();
+    update_request_info(|info| info.body_sha256 = None);
+
+    loop {
+        match stream.read_until(b'\n', &mut request_bytes).await {
+            Ok(0) => {
+                *keep_alive = false;
+                return Err(ServerError::InvalidRequest);
+            },
+            Ok(l) => {
+                if l == 2 {
+                    break;
+                }
+            },
+            Err(e1) => {
+                error!("An error occurred while reading a request from a client.\n\
+                        Error information:\n{e1}\n\
+                        Attempting to close connection...");
+                if let Err(e2) = stream.shutdown().await {
+                    error!("FAILED. Error information:\n{e2}");
+                }
+                panic!("Unrecoverable error occurred while handling connection.");
+            }
+        };
+    }
+
+    let request_string = String::from_utf8_lossy(&request_bytes).into_owned();
+
+    if let Err(e) = validate_framing(&request_string) {
+        *keep_alive = false;
+        return Err(e);
+    }
+
+    let mut request = Request::parse_from_string(&request_string, &request_bytes, keep_alive)?;
+
+    if !matches!(request, Request::Post {..} | Request::Put {..} | Request::Patch {..} | Request::Delete {..} | Request::Connect {..}) {
+        let body_length = match unconsumed_body_length(&request_string) {
+            Ok(body_length) => body_length,
+            Err(e) => {
+                *keep_alive = false;
+                return Err(e);
+            }
+        };
+
+        if body_length > 0 {
+            if let UnexpectedBodyPolicy::Reject = CONFIG.load().unexpected_body_policy {
+                *keep_alive = false;
+                return Err(ServerError::UnexpectedBody);
+            }
+
+            if body_length > CONFIG.load().max_content_length {
+                *keep_alive = false;
+                return Err(ServerError::BodyTooLarge);
+            }
+
+            if let Err(e1) = copy(&mut (&mut *stream).take(body_length as u64), &mut sink()).await {
+                error!("An error occurred while discarding a request body sent by a client.\n\
+                        Error information:\n{e1}\n\
+                        Attempting to close connection...");
+                if let Err(e2) = stream.shutdown().await {
+                    error!("FAILED. Error information:\n{e2}");
+                }
+                panic!("Unrecoverable error occurred while handling connection.");
+            }
+        }
+    }
+
+    #[cfg(feature = "cgi")]
+    if let  Request::Post {data, headers, cgi_data, ..} |
+            Request::Put {data, headers, cgi_data, ..} |
+            Request::Patch {data, headers, cgi_data, ..} |
+            Request::Delete {data, headers, cgi_data, ..} = &mut request {
+        let body_length = match headers.get("content-length").unwrap_or(&String::from("0")).parse::<usize>() {
+            Ok(l) if l > 0 => {
+                if l > CONFIG.load().max_content_length {
+                    *keep_alive = false;
+                    return Err(ServerError::BodyTooLarge);
+                }
+                l
+            },
+            Ok(l) if l == 0 => {
+                return Ok(request);
+            },
+            _ => {
+                return Err(ServerError::InvalidRequest);
+            }
+        };
+
+        if let (None, Some(content_type)) = (headers.get("content-encoding"), headers.get("content-type")) {
+            if let Some(boundary) = multipart_boundary(content_type) {
+                let mut parser = MultipartParser::new(&boundary);
+                let mut spool = BodySpool::new(body_length)?;
+                let mut raw: Vec<u8> = Vec::new();
+
+                read_body(stream, body_length, keep_alive, |chunk| {
+                    match &mut spool {
+                        Some(spool) => spool.write(chunk)?,
+                        None => raw.extend_from_slice(chunk)
+                    }
+                    parser.feed(chunk)
+                }).await?;
+
+                let (fields, spooled_files) = parser.finish()?;
+                update_request_info(|info| info.spooled_files.extend(spooled_files));
+
+                let body = match spool {
+                    Some(spool) => {
+                        let body = CGIBody::Spooled {path: spool.path().clone(), length: spool.size()};
+                        update_request_info(|info| info.spooled_files.push(spool.into_spooled_file()));
+                        body
+                    },
+                    None => CGIBody::Memory(raw)
+                };
+
+                *cgi_data = Some(CGIData {body, content_type: content_type.clone()});
+                *data = Some(FormData(fields));
+                return Ok(request);
+            }
+        }
+
+        let mut buffer = BytesMut::with_capacity(body_length);
+        read_body(stream, body_length, keep_alive, |chunk| {
+            buffer.extend_from_slice(chunk);
+            Ok(())
+        }).await?;
+
+        let payload: Vec<u8>;
+
+        match (headers.get("content-encoding"), CONFIG.load().get_supported_encodings()) {
+            (Some(content_encoding), Some(supported_encodings))
+            if supported_encodings.contains(content_encoding) => {
+                payload = match decompress_bounded(content_encoding, &*buffer) {
+                    Ok(payload) => payload,
+                    Err(ServerError::DecompressionError(e)) => {
+                        error!("An error occurred while decompressing the request body using {content_encoding}:\n{e}\n\
+                                Sending 406 status to the client...");
+
+                        return Err(ServerError::DecompressionError(e));
+                    },
+                    Err(e) => return Err(e)
+                };
+            }
+            (Some(content_encoding), Some(supported_encodings))
+            if !supported_encodings.contains(content_encoding) => {
+                return Err(ServerError::UnsupportedEncoding);
+            },
+            (Some(_), None) => {
+                return Err(ServerError::UnsupportedEncoding);
+            },
+            _ => {
+                payload = buffer.to_vec();
+            }
+        }
+
+        let body: RequestBody;
+
+        match headers.get("content-type") {
+            Some(content_type) if content_type.eq("application/octet-stream") => {
+                body = OctetStream(payload.clone());
+                *cgi_data = Some(CGIData {body: CGIBody::Memory(payload), content_type: content_type.clone()});
+            },
+            Some(content_type) if content_type.starts_with("application/x-www-form-urlencoded") => {
+                let x_www_urlencoded_raw = String::from(String::from_utf8_lossy(&payload));
+                let mut body_hm: HashMap<String, String> = HashMap::new();
+                for kv in x_www_urlencoded_raw.split('&') {
                     let Some(kv_split) = kv.split_once('=') else {
                         return Err(ServerError::MalformedPayload);
                     };
@@ -445,88 +1306,45 @@ where
                     }
                 }
                 body = XWWWFormUrlEncoded(body_hm);
-                *cgi_data = Some(CGIData {data: payload, content_type: content_type.clone()});
+                *cgi_data = Some(CGIData {body: CGIBody::Memory(payload), content_type: content_type.clone()});
             },
             Some(content_type) if content_type.starts_with("text/plain") => {
                 let plain_raw = String::from(String::from_utf8_lossy(&payload));
                 body = Plain(plain_raw);
-                *cgi_data = Some(CGIData {data: payload, content_type: content_type.clone()});
+                *cgi_data = Some(CGIData {body: CGIBody::Memory(payload), content_type: content_type.clone()});
             },
-            Some(content_type) => {
-                *cgi_data = Some(CGIData {data: payload.clone(), content_type: content_type.clone()});
-
-                let Some((content_type, boundary_raw)) = content_type.split_once(';') else {
+            #[cfg(feature = "json")]
+            Some(content_type) if content_type.starts_with("application/json") => {
+                let Ok(json_raw) = String::from_utf8(payload.clone()) else {
                     return Err(ServerError::MalformedPayload);
                 };
 
-                if !content_type.trim_end().eq("multipart/form-data") {
-                    return Ok(request);
-                }
-
-                let Some((_, bound)) = boundary_raw.trim_end_matches(';').split_once('=') else {
+                let Ok(json) = serde_json::from_str::<Value>(&json_raw) else {
                     return Err(ServerError::MalformedPayload);
                 };
-                let bound = bound.trim_matches(|y| y == '"');
-
-                let mut body_hm: HashMap<String, FormDataValue> = HashMap::new();
-
-                for field in payload.split_str(&*format!("--{bound}")).skip(1) {
-                    if field.trim_ascii().eq(&[45, 45]) {
-                        break;
-                    }
-
-                    let mut field_lines = field.split_str("\r\n").skip(1);
-                    let mut headers: HashMap<String, String> = HashMap::new();
-
-                    let Some(mut header_bytes) = field_lines.next() else {
-                        return Err(ServerError::MalformedPayload);
-                    };
-
-                    while HEADERS_REGEX.is_match(header_bytes) {
-                        if let Some(h) = field_lines.next() {
-                            let Some((header_name, header_value)) = header_bytes.split_once_str(":") else {
-                                return Err(ServerError::MalformedPayload);
-                            };
 
-                            headers.insert(String::from_utf8_lossy(header_name.trim_ascii()).to_lowercase(), String::from_utf8_lossy(header_value.trim_ascii()).to_string());
-                            header_bytes = h;
-                            continue;
-                        }
-                        return Err(ServerError::MalformedPayload);
-                    }
-
-                    let (Some(content_disp), Some(field_data)) = (headers.get("content-disposition"), field_lines.next()) else {
-                        return Err(ServerError::MalformedPayload);
-                    };
-
-                    let mut content_disp_split = content_disp.split(";");
-                    let (Some(form_data), Some(name)) = (content_disp_split.next(), content_disp_split.next()) else {
-                        return Err(ServerError::MalformedPayload);
-                    };
+                update_request_info(|info| info.json_body = Some(Arc::new(json)));
+                body = Plain(json_raw);
+                *cgi_data = Some(CGIData {body: CGIBody::Memory(payload), content_type: content_type.clone()});
+            },
+            Some(content_type) => {
+                *cgi_data = Some(CGIData {body: CGIBody::Memory(payload.clone()), content_type: content_type.clone()});
 
-                    if !form_data.trim_start().eq("form-data") || !header_bytes.trim_ascii().is_empty() {
-                        return Err(ServerError::MalformedPayload);
+                let Some(boundary) = multipart_boundary(content_type) else {
+                    if let Some(media_type) = CONFIG.load().accepted_media_type(content_type) {
+                        update_request_info(|info| info.body_media_type = Some(media_type));
+                        *data = Some(OctetStream(payload));
                     }
+                    return Ok(request);
+                };
 
-                    let Some((_, name)) = name.split_once("=") else {
-                        return Err(ServerError::MalformedPayload);
-                    };
+                let mut parser = MultipartParser::new(&boundary);
+                parser.feed(&payload)?;
 
-                    body_hm.insert(String::from(name.trim_matches('"')), FormDataValue {
-                        filename: if let Some(filename) = content_disp_split.next() {
-                            let Some((_, filename)) = filename.split_once("=") else {
-                                return Err(ServerError::MalformedPayload);
-                            };
+                let (fields, spooled_files) = parser.finish()?;
+                update_request_info(|info| info.spooled_files.extend(spooled_files));
 
-                            Some(String::from(filename.trim_matches('"')))
-                        } else {
-                            None
-                        },
-                        headers,
-                        value: Vec::from(field_data)
-                    });
-                }
-                body = FormData(body_hm);
+                body = FormData(fields);
             },
             _ => {
                 return Err(ServerError::UnsupportedMediaType);
@@ -540,55 +1358,56 @@ where
     Request::Put {data, headers, ..} |
     Request::Patch {data, headers, ..} |
     Request::Delete {data, headers, ..} = &mut request {
-        let mut buffer = BytesMut::with_capacity(
-            match headers.get("content-length").unwrap_or(&String::from("0")).parse::<usize>() {
-                Ok(l) if l > 0 => {
-                    if l > CONFIG.max_content_length {
-                        return Err(ServerError::BodyTooLarge);
-                    }
-                    l
-                },
-                Ok(l) if l == 0 => {
-                    return Ok(request);
-                },
-                _ => {
-                    return Err(ServerError::InvalidRequest);
+        let body_length = match headers.get("content-length").unwrap_or(&String::from("0")).parse::<usize>() {
+            Ok(l) if l > 0 => {
+                if l > CONFIG.load().max_content_length {
+                    *keep_alive = false;
+                    return Err(ServerError::BodyTooLarge);
                 }
+                l
+            },
+            Ok(l) if l == 0 => {
+                return Ok(request);
+            },
+            _ => {
+                return Err(ServerError::InvalidRequest);
             }
-        );
+        };
 
-        if let Err(e1) = reader.read_buf(&mut buffer).await {
-            eprintln!("[receive_request():{}] An error occurred while reading a request from a client.\n\
-                        Error information:\n{e1}\n\
-                        Attempting to close connection...", line!());
-            if let Err(e2) = stream.shutdown().await {
-                eprintln!("[receive_request():{}] FAILED. Error information:\n{e2}", line!());
+        if let (None, Some(content_type)) = (headers.get("content-encoding"), headers.get("content-type")) {
+            if let Some(boundary) = multipart_boundary(content_type) {
+                let mut parser = MultipartParser::new(&boundary);
+                read_body(stream, body_length, keep_alive, |chunk| parser.feed(chunk)).await?;
+
+                let (fields, spooled_files) = parser.finish()?;
+                update_request_info(|info| info.spooled_files.extend(spooled_files));
+
+                *data = Some(FormData(fields));
+                return Ok(request);
             }
-            panic!("Unrecoverable error occurred while handling connection.");
         }
 
-        let mut payload: Vec<u8> = Vec::new();
+        let mut buffer = BytesMut::with_capacity(body_length);
+        read_body(stream, body_length, keep_alive, |chunk| {
+            buffer.extend_from_slice(chunk);
+            Ok(())
+        }).await?;
+
+        let payload: Vec<u8>;
 
-        match (headers.get("content-encoding"), CONFIG.get_supported_encodings()) {
+        match (headers.get("content-encoding"), CONFIG.load().get_supported_encodings()) {
             (Some(content_encoding), Some(supported_encodings))
             if supported_encodings.contains(content_encoding) => {
-                if content_encoding.eq("gzip") {
-                    if let Err(e) = GzDecoder::new(&*buffer).read_to_end(&mut payload) {
-                        eprintln!("[receive_request():{}] An error occurred while decompressing the request body using GZIP:\n{e}\n\
-                                    Sending 406 status to the client...", line!());
-
-                        return Err(ServerError::DecompressionError(e));
-                    }
-                } else if content_encoding.eq("br") {
-                    if let Err(e) = BrotliDecompress(&mut &*buffer, &mut payload) {
-                        eprintln!("[receive_request():{}] An error occurred while decompressing the request body using Brotli:\n{e}\n\
-                                    Sending 406 status to the client...", line!());
+                payload = match decompress_bounded(content_encoding, &*buffer) {
+                    Ok(payload) => payload,
+                    Err(ServerError::DecompressionError(e)) => {
+                        error!("An error occurred while decompressing the request body using {content_encoding}:\n{e}\n\
+                                Sending 406 status to the client...");
 
                         return Err(ServerError::DecompressionError(e));
-                    }
-                } else {
-                    return Err(ServerError::UnsupportedEncoding);
-                }
+                    },
+                    Err(e) => return Err(e)
+                };
             }
             (Some(content_encoding), Some(supported_encodings))
             if !supported_encodings.contains(content_encoding) => {
@@ -630,79 +1449,37 @@ where
                 let plain_raw = String::from(String::from_utf8_lossy(&payload));
                 body = Plain(plain_raw);
             },
-            Some(content_type) => {
-                let Some((content_type, boundary_raw)) = content_type.split_once(';') else {
+            #[cfg(feature = "json")]
+            Some(content_type) if content_type.starts_with("application/json") => {
+                let Ok(json_raw) = String::from_utf8(payload) else {
                     return Err(ServerError::MalformedPayload);
                 };
 
-                if !content_type.trim_end().eq("multipart/form-data") {
-                    return Err(ServerError::UnsupportedMediaType);
-                }
-
-                let Some((_, bound)) = boundary_raw.trim_end_matches(';').split_once('=') else {
+                let Ok(json) = serde_json::from_str::<Value>(&json_raw) else {
                     return Err(ServerError::MalformedPayload);
                 };
-                let bound = bound.trim_matches(|y| y == '"');
-
-                let mut body_hm: HashMap<String, FormDataValue> = HashMap::new();
-
-                for field in payload.split_str(&*format!("--{bound}")).skip(1) {
-                    if field.trim_ascii().eq(&[45, 45]) {
-                        break;
-                    }
-
-                    let mut field_lines = field.split_str("\r\n").skip(1);
-                    let mut headers: HashMap<String, String> = HashMap::new();
-
-                    let Some(mut header_bytes) = field_lines.next() else {
-                        return Err(ServerError::MalformedPayload);
-                    };
-
-                    while HEADERS_REGEX.is_match(header_bytes) {
-                        if let Some(h) = field_lines.next() {
-                            let Some((header_name, header_value)) = header_bytes.split_once_str(":") else {
-                                return Err(ServerError::MalformedPayload);
-                            };
-
-                            headers.insert(String::from_utf8_lossy(header_name.trim_ascii()).to_lowercase(), String::from_utf8_lossy(header_value.trim_ascii()).to_string());
-                            header_bytes = h;
-                            continue;
-                        }
-                        return Err(ServerError::MalformedPayload);
-                    }
-
-                    let (Some(content_disp), Some(field_data)) = (headers.get("content-disposition"), field_lines.next()) else {
-                        return Err(ServerError::MalformedPayload);
-                    };
 
-                    let mut content_disp_split = content_disp.split(";");
-                    let (Some(form_data), Some(name)) = (content_disp_split.next(), content_disp_split.next()) else {
-                        return Err(ServerError::MalformedPayload);
+                update_request_info(|info| info.json_body = Some(Arc::new(json)));
+                body = Plain(json_raw);
+            },
+            Some(content_type) => {
+                let Some(boundary) = multipart_boundary(content_type) else {
+                    let Some(media_type) = CONFIG.load().accepted_media_type(content_type) else {
+                        return Err(ServerError::UnsupportedMediaType);
                     };
 
-                    if !form_data.trim_start().eq("form-data") || !header_bytes.trim_ascii().is_empty() {
-                        return Err(ServerError::MalformedPayload);
-                    }
+                    update_request_info(|info| info.body_media_type = Some(media_type));
+                    *data = Some(OctetStream(payload));
+                    return Ok(request);
+                };
 
-                    let Some((_, name)) = name.split_once("=") else {
-                        return Err(ServerError::MalformedPayload);
-                    };
+                let mut parser = MultipartParser::new(&boundary);
+                parser.feed(&payload)?;
 
-                    body_hm.insert(String::from(name.trim_matches('"')), FormDataValue {
-                        filename: if let Some(filename) = content_disp_split.next() {
-                            let Some((_, filename)) = filename.split_once("=") else {
-                                return Err(ServerError::MalformedPayload);
-                            };
+                let (fields, spooled_files) = parser.finish()?;
+                update_request_info(|info| info.spooled_files.extend(spooled_files));
 
-                            Some(String::from(filename.trim_matches('"')))
-                        } else {
-                            None
-                        },
-                        headers,
-                        value: Vec::from(field_data)
-                    });
-                }
-                body = FormData(body_hm);
+                body = FormData(fields);
             },
             _ => {
                 return Err(ServerError::UnsupportedMediaType);
@@ -714,27 +1491,292 @@ where
     Ok(request)
 }
 
+pub fn decode_bounded<W: Write>(content_encoding: &str, data: &[u8], output: &mut W) -> Result<(), ServerError> {
+    let limit = (data.len() as u64)
+        .saturating_mul(CONFIG.load().max_decompression_ratio)
+        .min(CONFIG.load().max_content_length as u64);
+
+    let result = if content_encoding.eq("gzip") {
+        io::copy(&mut GzDecoder::new(data).take(limit + 1), output)
+    } else if content_encoding.eq("br") {
+        io::copy(&mut Decompressor::new(data, 4096).take(limit + 1), output)
+    } else {
+        return Err(ServerError::UnsupportedEncoding);
+    };
+
+    match result {
+        Ok(length) if length > limit => Err(ServerError::DecompressionLimitExceeded),
+        Ok(_) => Ok(()),
+        Err(e) => Err(ServerError::DecompressionError(e))
+    }
+}
+
+pub fn decompress_bounded(content_encoding: &str, data: &[u8]) -> Result<Vec<u8>, ServerError> {
+    let mut payload: Vec<u8> = Vec::new();
+    decode_bounded(content_encoding, data, &mut payload)?;
+    Ok(payload)
+}
+
+pub fn compress(content_encoding: &str, data: &[u8]) -> io::Result<Vec<u8>> {
+    if content_encoding.eq("gzip") {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data)?;
+        return encoder.finish();
+    }
+
+    if content_encoding.eq("br") {
+        let params = BrotliEncoderParams::default();
+        let mut compressed = Vec::new();
+        {
+            let mut encoder = CompressorWriter::new(&mut compressed, BODY_CHUNK_LENGTH, params.quality as u32, params.lgwin as u32);
+            encoder.write_all(data)?;
+        }
+        return Ok(compressed);
+    }
+
+    Err(io::Error::other(format!("unsupported content encoding: {content_encoding}")))
+}
+
 pub async fn rte_wrapper<T>(f: &mut File, buf: &mut Vec<u8>, stream: &mut T)
 where
     T: AsyncRead + AsyncWrite + Unpin
 {
     if let Err(e1) = f.read_to_end(buf).await {
-        eprintln!("[rte_wrapper():{}] An error occurred after an attempt to read from a file: {:?}.\n\
-                   Error information:\n{e1}\n\
-                   Attempting to send Internal Server Error page to the client...", line!(), f);
+        error!("An error occurred after an attempt to read from a file: {:?}.\n\
+                Error information:\n{e1}\n\
+                Attempting to send Internal Server Error page to the client...", f);
         if let Err(e2) = internal_server_error(stream).await {
-            eprintln!("[rte_wrapper():{}] FAILED. Error information:\n{e2}", line!());
+            error!("FAILED. Error information:\n{e2}");
         }
-        eprintln!("Attempting to close connection...");
+        error!("Attempting to close connection...");
         if let Err(e2) = stream.shutdown().await {
-            eprintln!("[rte_wrapper():{}] FAILED. Error information:\n{e2}", line!());
+            error!("FAILED. Error information:\n{e2}");
         }
         panic!("Unrecoverable error occurred while handling connection.");
     }
 }
 
-pub fn get_current_date() -> String {
-    let dt = Utc::now();
-    let dt_formatted = dt.format("%a, %e %b %Y %T GMT");
-    dt_formatted.to_string()
-}
\ No newline at end of file
+fn format_date(timestamp: i64) -> String {
+    DateTime::<Utc>::from_timestamp(timestamp, 0).unwrap_or_default().format("%a, %d %b %Y %T GMT").to_string()
+}
+
+fn push_current_date(response: &mut String) {
+    let now = Utc::now().timestamp();
+
+    if let Ok(date) = CURRENT_DATE.read() {
+        if date.0 == now {
+            response.push_str(&date.1);
+            return;
+        }
+    }
+
+    let date = format_date(now);
+    response.push_str(&date);
+    if let Ok(mut current_date) = CURRENT_DATE.write() {
+        *current_date = (now, date);
+    }
+}
+
+pub async fn refresh_date() {
+    loop {
+        let now = Utc::now();
+        let date = format_date(now.timestamp());
+        if let Ok(mut current_date) = CURRENT_DATE.write() {
+            *current_date = (now.timestamp(), date);
+        }
+
+        let until_next_second = 1_000_000_000 - now.timestamp_subsec_nanos().min(999_999_999);
+        tokio::time::sleep(Duration::from_nanos(until_next_second as u64)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{duplex, BufStream};
+    use tokio::spawn;
+    use tokio::time::sleep;
+
+    #[tokio::test]
+    async fn body_split_across_reads() {
+        let (mut client, server) = duplex(8);
+        let mut server = BufStream::new(server);
+
+        let writer = spawn(async move {
+            for part in [&b"hello, "[..], b"split ", b"body"] {
+                client.write_all(part).await.unwrap();
+                sleep(Duration::from_millis(10)).await;
+            }
+            client
+        });
+
+        let (mut body, mut reads, mut keep_alive) = (Vec::new(), 0, true);
+        let result = read_chunks(&mut server, 17, Duration::from_secs(1), &mut keep_alive, |chunk| {
+            reads += 1;
+            body.extend_from_slice(chunk);
+            Ok(())
+        }).await;
+
+        assert!(result.is_ok());
+        assert_eq!(body, b"hello, split body");
+        assert!(reads > 1);
+        assert!(keep_alive);
+        writer.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn body_stops_at_content_length() {
+        let (mut client, server) = duplex(64);
+        let mut server = BufStream::new(server);
+        client.write_all(b"bodyGET / HTTP/1.1\r\n").await.unwrap();
+
+        let (mut body, mut keep_alive) = (Vec::new(), true);
+        let result = read_chunks(&mut server, 4, Duration::from_secs(1), &mut keep_alive, |chunk| {
+            body.extend_from_slice(chunk);
+            Ok(())
+        }).await;
+
+        assert!(result.is_ok());
+        assert_eq!(body, b"body");
+
+        let mut next = [0u8; 5];
+        server.read_exact(&mut next).await.unwrap();
+        assert_eq!(&next, b"GET /");
+    }
+
+    #[tokio::test]
+    async fn slow_body_within_inactivity_timeout() {
+        let (mut client, server) = duplex(64);
+        let mut server = BufStream::new(server);
+
+        let writer = spawn(async move {
+            for _ in 0..5 {
+                sleep(Duration::from_millis(40)).await;
+                client.write_all(b"ab").await.unwrap();
+            }
+            client
+        });
+
+        let mut keep_alive = true;
+        let result = read_chunks(&mut server, 10, Duration::from_millis(150), &mut keep_alive, |_| Ok(())).await;
+
+        assert!(result.is_ok());
+        assert!(keep_alive);
+        writer.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn stalled_body_times_out() {
+        let (mut client, server) = duplex(64);
+        let mut server = BufStream::new(server);
+        client.write_all(b"part").await.unwrap();
+
+        let mut keep_alive = true;
+        let result = read_chunks(&mut server, 10, Duration::from_millis(50), &mut keep_alive, |_| Ok(())).await;
+
+        assert!(matches!(result, Err(ServerError::RequestTimeout)));
+        assert!(!keep_alive);
+        drop(client);
+    }
+
+    #[tokio::test]
+    async fn truncated_body_is_invalid() {
+        let (mut client, server) = duplex(64);
+        let mut server = BufStream::new(server);
+        client.write_all(b"part").await.unwrap();
+        drop(client);
+
+        let mut keep_alive = true;
+        let result = read_chunks(&mut server, 10, Duration::from_secs(1), &mut keep_alive, |_| Ok(())).await;
+
+        assert!(matches!(result, Err(ServerError::InvalidRequest)));
+        assert!(!keep_alive);
+    }
+
+    #[tokio::test]
+    async fn chunked_body_is_not_read_as_next_request() {
+        let (mut client, server) = duplex(256);
+        let mut server = BufStream::new(server);
+        client.write_all(b"POST /form HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n\
+                           20\r\nGET /admin HTTP/1.1\r\nHost: x\r\n\r\n0\r\n\r\n\
+                           GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").await.unwrap();
+
+        let mut keep_alive = true;
+        let result = receive_request(&mut server, &mut keep_alive).await;
+
+        assert!(matches!(result, Err(ServerError::NotImplemented)));
+        assert!(!keep_alive);
+    }
+
+    #[test]
+    fn transfer_encoding_framing() {
+        let request = |headers: &str| format!("POST / HTTP/1.1\r\nHost: localhost\r\n{headers}\r\n");
+
+        assert!(validate_framing(&request("Content-Length: 4\r\n")).is_ok());
+        assert!(matches!(validate_framing(&request("Transfer-Encoding: chunked\r\n")), Err(ServerError::NotImplemented)));
+        assert!(matches!(validate_framing(&request("Transfer-Encoding: gzip, chunked\r\n")), Err(ServerError::NotImplemented)));
+        assert!(matches!(validate_framing(&request("Transfer-Encoding: chunked\r\nContent-Length: 4\r\n")), Err(ServerError::InvalidRequest)));
+        assert!(matches!(validate_framing(&request("Transfer-Encoding: chunked\r\nTransfer-Encoding: chunked\r\n")), Err(ServerError::InvalidRequest)));
+        assert!(matches!(validate_framing(&request("Transfer-Encoding: \"chunked\"\r\n")), Err(ServerError::InvalidRequest)));
+        assert!(matches!(validate_framing(&request("Transfer-Encoding:\r\n")), Err(ServerError::InvalidRequest)));
+    }
+
+    #[test]
+    fn validator_etag_is_a_quoted_weak_tag() {
+        let path = std::env::temp_dir().join(format!("drain-validator-{}", std::process::id()));
+        std::fs::write(&path, "content").unwrap();
+        let etag = validator_etag(&std::fs::metadata(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(etag.starts_with("W/\"") && etag.ends_with('"'));
+        assert!(etag[3..etag.len() - 1].split('-').all(|part| u128::from_str_radix(part, 16).is_ok()));
+    }
+
+    #[tokio::test]
+    async fn compressed_body_is_read_in_chunks_and_counted_after_compression() {
+        let content = "drain ".repeat(50000);
+        let mut output = Vec::new();
+        let written = StreamingEncoder::new("gzip").unwrap().write_chunked(&mut output, content.as_bytes()).await.unwrap();
+
+        let (mut body, mut rest) = (Vec::new(), output.as_slice());
+        loop {
+            let (size, after) = rest.split_once_str("\r\n").unwrap();
+            let size = usize::from_str_radix(size.to_str().unwrap(), 16).unwrap();
+            if size == 0 {
+                break;
+            }
+            body.extend_from_slice(&after[..size]);
+            rest = &after[size + 2..];
+        }
+
+        let mut decompressed = String::new();
+        GzDecoder::new(body.as_slice()).read_to_string(&mut decompressed).unwrap();
+
+        assert_eq!(written, body.len() as u64);
+        assert!(written < content.len() as u64);
+        assert_eq!(decompressed, content);
+    }
+
+    #[test]
+    fn empty_body_is_raw() {
+        assert_eq!(raw_body(&None), Some(Vec::new()));
+        assert_eq!(raw_body(&Some(Plain(String::from("text")))), Some(Vec::from(b"text")));
+        assert_eq!(raw_body(&Some(XWWWFormUrlEncoded(HashMap::new()))), None);
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn json_body_is_parsed_once_for_endpoints() {
+        let (mut client, server) = duplex(1024);
+        let mut server = BufStream::new(server);
+        let body = r#"{"name":"drain","tags":["a","b"]}"#;
+        client.write_all(format!("POST /users HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}", body.len()).as_bytes()).await.unwrap();
+
+        let json_body = REQUEST_INFO.scope(RefCell::new(RequestInfo::default()), async {
+            receive_request(&mut server, &mut true).await.ok().unwrap();
+            REQUEST_INFO.with(|info| info.borrow().json_body.clone())
+        }).await;
+
+        assert_eq!(json_body.as_deref(), Some(&serde_json::json!({"name": "drain", "tags": ["a", "b"]})));
+    }
+}
@@ -0,0 +1,73 @@
+use std::io;
+use tokio::fs::File;
+use tokio::io::{copy_buf, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_openssl::SslStream;
+
+const COPY_BUFFER_LENGTH: usize = 262144;
+
+pub trait ZeroCopy {
+    fn tcp_stream(&self) -> Option<&TcpStream> {
+        None
+    }
+}
+
+impl ZeroCopy for BufReader<TcpStream> {
+    fn tcp_stream(&self) -> Option<&TcpStream> {
+        Some(self.get_ref())
+    }
+}
+
+impl ZeroCopy for BufReader<SslStream<TcpStream>> {}
+
+#[cfg(target_os = "linux")]
+async fn sendfile(tcp_stream: &TcpStream, file: &File, length: u64) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+    use tokio::io::Interest;
+
+    let mut offset: libc::off_t = 0;
+    let mut remaining = length;
+
+    while remaining > 0 {
+        tcp_stream.writable().await?;
+
+        let result = tcp_stream.try_io(Interest::WRITABLE, || {
+            let count = remaining.min(1073741824) as usize;
+            let sent = unsafe {libc::sendfile(tcp_stream.as_raw_fd(), file.as_raw_fd(), &mut offset, count)};
+
+            if sent < 0 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(sent as u64)
+            }
+        });
+
+        match result {
+            Ok(0) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "file was truncated while it was being sent")),
+            Ok(sent) => remaining -= sent,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+            Err(e) => return Err(e)
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn transmit<T>(stream: &mut T, file: &mut File, length: u64) -> io::Result<()>
+where
+    T: AsyncWrite + Unpin + ZeroCopy
+{
+    stream.flush().await?;
+
+    #[cfg(target_os = "linux")]
+    if let Some(tcp_stream) = stream.tcp_stream() {
+        return sendfile(tcp_stream, file, length).await;
+    }
+
+    let copied = copy_buf(&mut BufReader::with_capacity(COPY_BUFFER_LENGTH, file.take(length)), stream).await?;
+    if copied != length {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "file was truncated while it was being sent"));
+    }
+
+    Ok(())
+}